@@ -0,0 +1,287 @@
+//! Compares banderole executables with Node.js SEA and pkg builds of the same fixture apps.
+//!
+//! For every fixture in `benches/fixtures` and every available tool it measures the bundle
+//! time, the first (cold) start and the following (warm) starts with criterion, and prints the
+//! artifact size. Criterion keeps the results in `target/criterion` and compares each run with
+//! the previous one.
+//!
+//! ```sh
+//! cargo bench --bench bundle_comparison
+//! ```
+//!
+//! SEA needs `node` 20+ and `npx` (for postject), pkg runs through `npx @yao-pkg/pkg`. Tools
+//! that are not installed are reported as skipped. SEA only embeds a single CommonJS file, so
+//! fixtures are self-contained scripts that only use builtins.
+
+use anyhow::{Context, Result};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Bundling and cold starts take seconds, so fewer samples than criterion's default 100
+const SAMPLE_SIZE: usize = 10;
+
+/// Fuse postject flips so node looks for the injected blob
+const SEA_FUSE: &str = "NODE_SEA_FUSE_fce680ab2cc467b6e072b8b5df1996b2";
+
+#[derive(Debug, Clone, Copy)]
+enum Tool {
+    Banderole,
+    Sea,
+    Pkg,
+}
+
+impl Tool {
+    const ALL: [Tool; 3] = [Tool::Banderole, Tool::Sea, Tool::Pkg];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Banderole => "banderole",
+            Tool::Sea => "node-sea",
+            Tool::Pkg => "pkg",
+        }
+    }
+
+    /// Why the tool cannot be benchmarked on this machine, if it cannot
+    fn unavailable(self) -> Option<String> {
+        match self {
+            Tool::Banderole => None,
+            Tool::Sea => {
+                let major =
+                    command_stdout(Command::new("node").args(["-p", "process.versions.node"]))
+                        .ok()
+                        .and_then(|version| version.split('.').next()?.parse::<u32>().ok());
+                match major {
+                    None => Some("node is not on the PATH".to_string()),
+                    Some(major) if major < 20 => {
+                        Some(format!("node {major} has no stable SEA support"))
+                    }
+                    Some(_) => command_stdout(npx().args(["--yes", "postject", "--help"]))
+                        .err()
+                        .map(|e| format!("postject is unavailable: {e:#}")),
+                }
+            }
+            Tool::Pkg => command_stdout(npx().args(["--yes", "@yao-pkg/pkg", "--version"]))
+                .err()
+                .map(|e| format!("pkg is unavailable: {e:#}")),
+        }
+    }
+
+    /// Build `fixture` into an executable inside `out_dir`
+    fn bundle(self, fixture: &Path, out_dir: &Path) -> Result<PathBuf> {
+        let executable = out_dir.join(format!(
+            "{}-app{}",
+            self.name(),
+            std::env::consts::EXE_SUFFIX
+        ));
+        match self {
+            Tool::Banderole => {
+                command_stdout(
+                    Command::new(env!("CARGO_BIN_EXE_banderole"))
+                        .arg("--quiet")
+                        .arg("bundle")
+                        .arg(fixture)
+                        .arg("--output")
+                        .arg(&executable),
+                )?;
+            }
+            Tool::Sea => {
+                let blob = out_dir.join("sea-prep.blob");
+                let config = out_dir.join("sea-config.json");
+                std::fs::write(
+                    &config,
+                    serde_json::to_string(&serde_json::json!({
+                        "main": fixture.join("index.js"),
+                        "output": blob,
+                        "disableExperimentalSEAWarning": true,
+                    }))?,
+                )?;
+                command_stdout(
+                    Command::new("node")
+                        .arg("--experimental-sea-config")
+                        .arg(&config),
+                )?;
+                let node = command_stdout(Command::new("node").args(["-p", "process.execPath"]))?;
+                std::fs::copy(node.trim(), &executable)
+                    .context("Failed to copy the node binary")?;
+                if cfg!(target_os = "macos") {
+                    command_stdout(
+                        Command::new("codesign")
+                            .arg("--remove-signature")
+                            .arg(&executable),
+                    )?;
+                }
+                let mut postject = npx();
+                postject
+                    .args(["--yes", "postject"])
+                    .arg(&executable)
+                    .arg("NODE_SEA_BLOB")
+                    .arg(&blob)
+                    .args(["--sentinel-fuse", SEA_FUSE]);
+                if cfg!(target_os = "macos") {
+                    postject.args(["--macho-segment-name", "NODE_SEA"]);
+                }
+                command_stdout(&mut postject)?;
+                if cfg!(target_os = "macos") {
+                    command_stdout(
+                        Command::new("codesign")
+                            .args(["--sign", "-"])
+                            .arg(&executable),
+                    )?;
+                }
+            }
+            Tool::Pkg => {
+                command_stdout(
+                    npx()
+                        .args(["--yes", "@yao-pkg/pkg"])
+                        .arg(fixture.join("index.js"))
+                        .args(["--targets", "host", "--output"])
+                        .arg(&executable),
+                )?;
+            }
+        }
+        Ok(executable)
+    }
+
+    /// Start `executable` once; banderole extracts into `cache` when it is not filled yet
+    fn start(self, executable: &Path, cache: &Path) -> Result<()> {
+        let mut command = Command::new(executable);
+        if let Tool::Banderole = self {
+            command.arg("--banderole-cache-dir").arg(cache);
+        }
+        command_stdout(&mut command).map(drop)
+    }
+}
+
+fn bundle_comparison(c: &mut Criterion) {
+    let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/fixtures");
+    let mut fixtures: Vec<PathBuf> = std::fs::read_dir(&fixtures_dir)
+        .expect("Failed to read benches/fixtures")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.join("package.json").is_file())
+        .collect();
+    fixtures.sort();
+
+    let mut tools = Vec::new();
+    for tool in Tool::ALL {
+        match tool.unavailable() {
+            Some(reason) => println!("{:<10} skipped: {reason}", tool.name()),
+            None => tools.push(tool),
+        }
+    }
+
+    for fixture in &fixtures {
+        let fixture_name = fixture.file_name().unwrap().to_string_lossy().into_owned();
+        // Bundle a copy so nothing is written into the repository
+        let work = TempDir::new().expect("Failed to create a work directory");
+        let project = work.path().join("app");
+        copy_dir(fixture, &project).expect("Failed to copy the fixture");
+
+        // One build per tool up front, for the start timings and to leave out tools that fail
+        let mut built = Vec::new();
+        for &tool in &tools {
+            let out_dir = work.path().join(tool.name());
+            let executable = std::fs::create_dir_all(&out_dir)
+                .map_err(anyhow::Error::from)
+                .and_then(|()| tool.bundle(&project, &out_dir));
+            match executable.and_then(|executable| {
+                let size = std::fs::metadata(&executable)?.len();
+                Ok((executable, size))
+            }) {
+                Ok((executable, size)) => {
+                    println!(
+                        "{fixture_name:<10} {:<10} size {:>6.1} MiB",
+                        tool.name(),
+                        size as f64 / (1024.0 * 1024.0)
+                    );
+                    built.push((tool, executable));
+                }
+                Err(e) => println!(
+                    "{fixture_name:<10} {:<10} skipped: failed: {e:#}",
+                    tool.name()
+                ),
+            }
+        }
+
+        let mut group = c.benchmark_group(format!("bundle/{fixture_name}"));
+        group.sample_size(SAMPLE_SIZE);
+        for (tool, _) in &built {
+            group.bench_function(tool.name(), |b| {
+                b.iter_batched(
+                    || TempDir::new().unwrap(),
+                    |out_dir| tool.bundle(&project, out_dir.path()).unwrap(),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+        group.finish();
+
+        // For banderole, the first start extracts into an empty cache
+        let mut group = c.benchmark_group(format!("cold_start/{fixture_name}"));
+        group.sample_size(SAMPLE_SIZE);
+        for (tool, executable) in &built {
+            group.bench_function(tool.name(), |b| {
+                b.iter_batched(
+                    || TempDir::new().unwrap(),
+                    |cache| tool.start(executable, cache.path()).unwrap(),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("warm_start/{fixture_name}"));
+        group.sample_size(SAMPLE_SIZE);
+        for (tool, executable) in &built {
+            let cache = work.path().join(format!("{}-cache", tool.name()));
+            tool.start(executable, &cache).unwrap();
+            group.bench_function(tool.name(), |b| {
+                b.iter(|| tool.start(executable, &cache).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(30));
+    targets = bundle_comparison
+}
+criterion_main!(benches);
+
+/// npx is a batch-file shim on Windows
+fn npx() -> Command {
+    Command::new(if cfg!(windows) { "npx.cmd" } else { "npx" })
+}
+
+/// Run `command` to completion and return its stdout, failing with its stderr
+fn command_stdout(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{:?} exited with {}: {}",
+        command.get_program(),
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+// Loads a typical set of builtins and does a little work, like a CLI parsing its config
+const crypto = require("crypto");
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const zlib = require("zlib");
+
+const config = { name: "bench-startup", entries: [] };
+for (let i = 0; i < 2000; i++) {
+  config.entries.push({ id: i, hash: crypto.createHash("sha256").update(String(i)).digest("hex") });
+}
+const packed = zlib.gzipSync(JSON.stringify(config));
+const scratch = path.join(os.tmpdir(), `bench-startup-${process.pid}.gz`);
+fs.writeFileSync(scratch, packed);
+fs.unlinkSync(scratch);
+console.log(`bench-startup ${config.entries.length} entries, ${packed.length} bytes`);
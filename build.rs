@@ -1,3 +1,4 @@
+use std::process::{Command, Stdio};
 use std::{env, fs, path::Path};
 
 const NODE_VERSION: &str = "22.17.1";
@@ -11,16 +12,64 @@ fn main() {
 
     println!("cargo:rustc-env=NODE_VERSION={NODE_VERSION}");
     println!("cargo:rerun-if-changed=build.rs");
+
+    if env::var_os("CARGO_FEATURE_VENDORED_RUNNER").is_some() {
+        vendor_runner_crates(Path::new(&out_dir));
+    }
+}
+
+/// Resolve the runner template's dependencies and pack the lockfile and their sources
+/// (`cargo vendor`) into `runner-vendor.tar.gz`, which banderole unpacks next to the runner crate
+fn vendor_runner_crates(out_dir: &Path) {
+    println!("cargo:rerun-if-changed=src/template/crg.toml");
+    let work = out_dir.join("runner-vendor");
+    let _ = fs::remove_dir_all(&work);
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::copy("src/template/crg.toml", work.join("Cargo.toml")).unwrap();
+    fs::write(work.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    for args in [
+        &["generate-lockfile"][..],
+        &["vendor", "--locked", "vendor"],
+    ] {
+        // stdout is reserved for build script directives
+        let status = Command::new(&cargo)
+            .current_dir(&work)
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .expect("Failed to run cargo");
+        assert!(
+            status.success(),
+            "cargo {} failed for the runner template",
+            args.join(" ")
+        );
+    }
+
+    let archive = fs::File::create(out_dir.join("runner-vendor.tar.gz")).unwrap();
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        archive,
+        flate2::Compression::best(),
+    ));
+    tar.append_path_with_name(work.join("Cargo.lock"), "Cargo.lock")
+        .unwrap();
+    tar.append_dir_all("vendor", work.join("vendor")).unwrap();
+    tar.into_inner().unwrap().finish().unwrap();
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 enum Platform {
     LinuxX64,
     LinuxArm64,
+    LinuxArmv7,
     MacosX64,
     MacosArm64,
     WindowsX64,
     WindowsArm64,
+    /// A host outside the built-in list, such as FreeBSD. It is only
+    /// reachable at runtime through a custom `platforms` entry.
+    Other(String),
 }
 
 impl std::fmt::Display for Platform {
@@ -28,10 +77,12 @@ impl std::fmt::Display for Platform {
         match self {
             Self::LinuxX64 => write!(f, "linux-x64"),
             Self::LinuxArm64 => write!(f, "linux-arm64"),
+            Self::LinuxArmv7 => write!(f, "linux-armv7l"),
             Self::MacosX64 => write!(f, "darwin-x64"),
             Self::MacosArm64 => write!(f, "darwin-arm64"),
             Self::WindowsX64 => write!(f, "win32-x64"),
             Self::WindowsArm64 => write!(f, "win32-arm64"),
+            Self::Other(name) => write!(f, "{name}"),
         }
     }
 }
@@ -43,10 +94,11 @@ fn get_platform() -> Platform {
     match (os.as_str(), arch.as_str()) {
         ("linux", "x86_64") => Platform::LinuxX64,
         ("linux", "aarch64") => Platform::LinuxArm64,
+        ("linux", "arm") => Platform::LinuxArmv7,
         ("macos", "x86_64") => Platform::MacosX64,
         ("macos", "aarch64") => Platform::MacosArm64,
         ("windows", "x86_64") => Platform::WindowsX64,
         ("windows", "aarch64") => Platform::WindowsArm64,
-        _ => panic!("Unsupported platform: {os}-{arch}"),
+        _ => Platform::Other(format!("{os}-{arch}")),
     }
 }
@@ -1,36 +1,264 @@
-use crate::executable;
-use crate::node_downloader::NodeDownloader;
+mod blob_cache;
+mod dedup;
+mod hooks;
+pub mod layers;
+mod local_packages;
+mod packlist;
+pub mod plan;
+mod protect;
+mod prune;
+mod smoke_test;
+mod suite;
+mod trim;
+mod universal;
+pub mod workspace;
+
+use crate::config::{BundleConfig, ExtractionLayout, HooksConfig, SmokeTestConfig};
+use crate::executable::{self, RunnerBuildOptions};
+use crate::manifest::{self, BundleManifest};
+use crate::node_downloader::{NodeBuild, NodeSource};
 use crate::node_version_manager::NodeVersionManager;
-use crate::platform::Platform;
+use crate::output::{self, emit, Event};
+use crate::package_entry;
+use crate::platform::{self, Platform};
+use crate::runtime_config::{
+    RuntimeConfig, RuntimeEntrypoint, RuntimeKind, RuntimeNotice, RuntimeService,
+};
+use crate::runtime_provider::{self, RuntimeSpec};
+use crate::size_report::{AnalyzeFormat, SizeReport};
 use anyhow::{Context, Result};
 use console::{style, Emoji};
-use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use local_packages::LocalPackages;
 use zip::ZipWriter;
 
+/// Options for a single `banderole bundle` invocation.
+#[derive(Debug, Clone, Default)]
+pub struct BundleOptions {
+    /// Path that contains a `package.json`.
+    pub project_path: PathBuf,
+    /// Optional path to the produced bundle file. If omitted, an automatically-generated name is used.
+    pub output_path: Option<PathBuf>,
+    /// Optional custom name for the executable.
+    pub custom_name: Option<String>,
+    /// Disable compression for faster bundling (useful for testing).
+    pub no_compression: bool,
+    /// Deflate every file again instead of reusing compressed data cached by earlier bundles.
+    pub no_archive_cache: bool,
+    /// Ignore cached version resolution results.
+    pub ignore_cached_versions: bool,
+    /// Runtime to bundle instead of the project's Node.js version.
+    pub runtime: Option<RuntimeSpec>,
+    /// ICU variant of Node.js, overriding the `nodeBuild` configuration.
+    pub node_build: Option<NodeBuild>,
+    /// Node.js distribution mirror, overriding `nodeMirror` and `NODE_MIRROR`.
+    pub node_mirror: Option<String>,
+    /// Pre-downloaded Node.js archive to bundle instead of downloading one.
+    pub node_archive: Option<PathBuf>,
+    /// Verify the GPG signature of the Node.js checksums, in addition to `verifyNodeSignature`.
+    pub verify_node_signature: bool,
+    /// Name of the `bin` entry to launch when package.json declares more than one.
+    pub bin_entry: Option<String>,
+    /// Keep caches and build leftovers (`.turbo`, `coverage/`, ...) in the app directory.
+    pub no_default_excludes: bool,
+    /// Keep docs, tests, source maps and TypeScript sources of installed packages.
+    pub no_prune_junk: bool,
+    /// Bundle the Node.js release in full, including headers, docs, npm and corepack.
+    pub no_trim_runtime: bool,
+    /// Paths in the Node.js release kept despite trimming, in addition to `keepRuntimeFiles`.
+    pub keep: Vec<String>,
+    /// Fail instead of warning when the compiled output looks older than its sources.
+    pub strict_freshness: bool,
+    /// How pnpm's `node_modules` is bundled, overriding the `nodeModulesLayout` configuration.
+    pub node_modules_layout: Option<NodeModulesLayout>,
+    /// How the runner binary itself is compiled.
+    pub runner: RunnerBuildOptions,
+    /// Report where the executable's bytes go once it is built.
+    pub analyze: Option<AnalyzeFormat>,
+    /// Leave the runtime out of the executable, in addition to `externalRuntime`.
+    pub external_runtime: bool,
+    /// Keep the host's Node.js variables from the app, in addition to `isolateEnv`.
+    pub isolate_env: bool,
+    /// Bundle Node.js's npm and corepack in working order, in addition to `includeNpm`.
+    pub include_npm: bool,
+    /// Platform to bundle for instead of the host.
+    pub target: Option<BundleTarget>,
+    /// Run the executable once it is built, in addition to `smokeTest`.
+    pub smoke_test: bool,
+    /// Print what would be bundled instead of bundling it.
+    pub dry_run: bool,
+}
+
+/// Platforms `--target` bundles for; each needs a macOS host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BundleTarget {
+    /// Intel Macs
+    MacosX64,
+    /// Apple silicon Macs
+    MacosArm64,
+    /// One executable for both, joined from the two with `lipo`
+    MacosUniversal,
+}
+
+impl BundleTarget {
+    /// The single platform bundled for; a universal bundle is built from two
+    fn platform(self) -> Option<Platform> {
+        match self {
+            BundleTarget::MacosX64 => Some(Platform::MacosX64),
+            BundleTarget::MacosArm64 => Some(Platform::MacosArm64),
+            BundleTarget::MacosUniversal => None,
+        }
+    }
+}
+
+/// How installed dependencies are laid out in the bundle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeModulesLayout {
+    /// One copy of every package directly in `node_modules/`, as npm installs them
+    #[default]
+    Flat,
+    /// pnpm's `.pnpm` virtual store and its symlinks, so packages keep their own dependency versions
+    Pnpm,
+}
+
+/// What a successful bundle produced, reported as the final `--json` event.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleSummary {
+    pub output: PathBuf,
+    /// Executable size in bytes
+    pub size: u64,
+    pub runtime: String,
+    pub runtime_version: String,
+    pub duration_ms: u64,
+    /// Warnings logged while bundling; filled in by the caller
+    pub warnings: Vec<String>,
+    /// `--dry-run`: nothing was written, `size` is the estimated uncompressed contents
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
 /// Public entry-point used by `main.rs`.
 ///
-/// * `project_path` – path that contains a `package.json`.
-/// * `output_path`  – optional path to the produced bundle file. If omitted, an
-///   automatically-generated name is used.
-/// * `custom_name` – optional custom name for the executable.
-/// * `no_compression` – disable compression for faster bundling (useful for testing).
-///
 /// The implementation uses a simpler, more reliable approach based on Playwright's bundling strategy.
 pub async fn bundle_project(
+    options: BundleOptions,
+    multi: &MultiProgress,
+) -> Result<BundleSummary> {
+    if let Some(target) = options.target {
+        anyhow::ensure!(
+            std::env::consts::OS == "macos",
+            "--target bundles for macOS and needs a macOS host to build the runner on"
+        );
+        if target.platform().is_none() {
+            anyhow::ensure!(
+                !options.dry_run,
+                "--dry-run plans one architecture; pass --target macos-arm64 or --target macos-x64"
+            );
+            return Box::pin(universal::bundle(options, multi)).await;
+        }
+    }
+    let (summary, _) = bundle_platform(options, multi, None).await?;
+    Ok(summary)
+}
+
+/// One of the executables a universal executable is joined from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slice {
+    /// Runs the `scripts` and `preBundle` hooks for the whole bundle
+    First,
+    Later,
+}
+
+/// What follows writing the executable: the smoke test and the `postBuild` hooks. Universal
+/// executables run it once, on the joined executable.
+struct Finish {
+    hooks: HooksConfig,
     project_path: PathBuf,
-    output_path: Option<PathBuf>,
-    custom_name: Option<String>,
-    no_compression: bool,
-    ignore_cached_versions: bool,
+    app_name: String,
+    app_version: String,
+    runtime: String,
+    runtime_version: String,
+    manifest: BundleManifest,
+    smoke_test: Option<SmokeTestConfig>,
+}
+
+impl Finish {
+    /// Smoke-test `output`, built for `platform`, unless this machine cannot run it, then hand
+    /// it to the `postBuild` hooks
+    fn run(&self, output: &Path, platform: &str, runnable: bool) -> Result<()> {
+        if let Some(test) = &self.smoke_test {
+            if runnable {
+                smoke_test::run(output, test)?;
+            } else {
+                warn!("Skipping the smoke test: {platform} executables cannot run on this machine");
+            }
+        }
+        hooks::run(
+            &self.hooks,
+            &hooks::HookContext {
+                event: hooks::HookEvent::PostBuild,
+                project: &self.project_path,
+                output,
+                app_name: &self.app_name,
+                app_version: &self.app_version,
+                platform: platform.to_string(),
+                runtime: &self.runtime,
+                runtime_version: &self.runtime_version,
+                archive: None,
+                manifest: Some(&self.manifest),
+                size: Some(fs::metadata(output)?.len()),
+            },
+        )
+    }
+}
+
+/// Bundle for the one platform `options` target, or the host. A universal executable's `slice`
+/// leaves the smoke test and `postBuild` hooks to the joined executable, returning them as
+/// [`Finish`] (`None` after `--dry-run`).
+async fn bundle_platform(
+    options: BundleOptions,
     multi: &MultiProgress,
-) -> Result<()> {
+    slice: Option<Slice>,
+) -> Result<(BundleSummary, Option<Finish>)> {
+    let BundleOptions {
+        project_path,
+        output_path,
+        custom_name,
+        no_compression,
+        no_archive_cache,
+        ignore_cached_versions,
+        runtime,
+        node_build,
+        node_mirror,
+        node_archive,
+        verify_node_signature,
+        bin_entry,
+        no_default_excludes,
+        no_prune_junk,
+        no_trim_runtime,
+        keep,
+        strict_freshness,
+        node_modules_layout,
+        mut runner,
+        analyze,
+        external_runtime,
+        isolate_env,
+        include_npm,
+        target,
+        smoke_test,
+        dry_run,
+    } = options;
     let project_path = project_path
         .canonicalize()
         .context("Failed to resolve project path")?;
@@ -53,26 +281,276 @@ pub async fn bundle_project(
             .to_string(),
     );
 
-    let source_dir = determine_source_directory(&project_path, &package_value)?;
+    let config = BundleConfig::load(&project_path, &package_value)?;
+    platform::register_custom(&config.platforms)?;
+    let platform = match target.and_then(BundleTarget::platform) {
+        Some(platform) => platform,
+        None => Platform::detect()?,
+    };
+    let config = config.for_platform(platform)?;
+    if !dry_run {
+        run_slice_scripts(slice, &project_path, &package_value, &config.scripts).await?;
+    }
+    if let Some(build) = &config.runner_build {
+        runner.rustc_wrapper = runner.rustc_wrapper.or(build.rustc_wrapper.clone());
+        runner.cargo_home = runner
+            .cargo_home
+            .or(build.cargo_home.as_ref().map(|dir| project_path.join(dir)));
+        runner.target_dir = runner
+            .target_dir
+            .or(build.target_dir.as_ref().map(|dir| project_path.join(dir)));
+        runner.template = runner
+            .template
+            .or(build.template.as_ref().map(|dir| project_path.join(dir)));
+        runner.profile = build.profile.clone();
+        // Reject invalid settings before anything is downloaded
+        runner.profile.cargo_env()?;
+    }
+    // An app suite bundles its member projects instead of the project itself
+    let suite_apps = if config.apps.is_empty() {
+        None
+    } else {
+        anyhow::ensure!(
+            bin_entry.is_none(),
+            "--entry does not apply to app suites; set \"entry\" on the app instead"
+        );
+        Some(suite::resolve_apps(&project_path, &config, platform)?)
+    };
+    let entry_script = match &suite_apps {
+        Some(_) => None,
+        None => resolve_entry_script(&project_path, &package_value, bin_entry.as_deref())?,
+    };
+    let source_dir = determine_source_directory(&project_path, entry_script.as_deref())?;
+    if let Some(stale) = stale_build_source(&project_path, &source_dir) {
+        let message = format!(
+            "{} is newer than everything in {}; the compiled output looks stale, rebuild before bundling",
+            stale.strip_prefix(&project_path).unwrap_or(&stale).display(),
+            source_dir.strip_prefix(&project_path).unwrap_or(&source_dir).display(),
+        );
+        anyhow::ensure!(!strict_freshness, "{message}");
+        warn!("{message}");
+    }
+    let node_modules_layout = node_modules_layout
+        .or(config.node_modules_layout)
+        .unwrap_or_default();
+    let mut runtime_config = build_runtime_config(
+        &config,
+        &project_path,
+        &source_dir,
+        &app_name,
+        &app_version,
+        platform,
+    )?;
+    if let Some(apps) = &suite_apps {
+        runtime_config.entrypoints = suite::entrypoints(apps);
+    }
+    if let Some(build) = node_build {
+        runtime_config.node_build = build;
+    }
+    anyhow::ensure!(
+        !runtime_config.lazy_icu || runtime_config.node_build == NodeBuild::SmallIcu,
+        "lazyIcu needs the small-icu Node build, not {:?}",
+        runtime_config.node_build
+    );
+    if let Some(script) = &entry_script {
+        runtime_config.main_script = Some(
+            relative_to_source_dir(&project_path, &source_dir, script).with_context(|| {
+                format!(
+                    "Entry script {script} is outside the bundled source directory {}",
+                    source_dir.display()
+                )
+            })?,
+        );
+    }
 
-    let node_version =
-        detect_node_version_with_workspace_support(&project_path, ignore_cached_versions)
-            .await
-            .unwrap_or_else(|_| "22.17.1".into());
+    let provider = runtime_provider::select(
+        runtime.as_ref(),
+        platform,
+        &project_path,
+        &package_value,
+        ignore_cached_versions,
+        NodeSource {
+            build: runtime_config.node_build,
+            mirror: node_mirror.or_else(|| config.node_mirror.clone()),
+            archive: node_archive,
+            verify_signature: verify_node_signature || config.verify_node_signature,
+        },
+    )
+    .await?;
+    runtime_config.runtime = provider.kind();
+
+    // Entries are deflated once the payload is complete, see `blob_cache`
+    let opts: zip::write::FileOptions<'static, ()> =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    // The app and its dependencies, collected alike for the payload and for `--dry-run`
+    // `dereferenced` counts the symlinks bundled as copies of their targets
+    let collect_app = |zip: &mut ZipWriter<std::io::Cursor<&mut Vec<u8>>>,
+                       dereferenced: &mut usize,
+                       progress: Option<&ProgressBar>|
+     -> Result<String> {
+        if let Some(apps) = &suite_apps {
+            suite::add_apps_to_zip(
+                zip,
+                apps,
+                !no_default_excludes,
+                node_modules_layout,
+                opts,
+                dereferenced,
+                progress,
+            )?;
+            return Ok(format!(
+                "{} suite apps, each with its own dependencies",
+                apps.len()
+            ));
+        }
+        // Pre-count app files
+        if let Some(pb) = progress {
+            pb.set_length(count_files_in_dir(&source_dir, true, true));
+        }
+        add_dir_to_zip_excluding_node_modules(
+            zip,
+            &source_dir,
+            Path::new("app"),
+            config.default_excludes && !no_default_excludes,
+            opts,
+            progress,
+        )?;
+        add_included_paths(
+            zip,
+            &project_path,
+            &source_dir,
+            &config.include,
+            opts,
+            progress,
+        )?;
+        // Dependencies will extend the total as we discover them
+        bundle_dependencies(
+            zip,
+            &project_path,
+            &source_dir,
+            node_modules_layout,
+            opts,
+            dereferenced,
+            progress,
+        )
+    };
+
+    if dry_run {
+        let started = Instant::now();
+        let mut zip_data = Vec::new();
+        let dependencies = {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut zip_data));
+            let dependencies = collect_app(&mut zip, &mut 0, None)?;
+            zip.finish()?;
+            dependencies
+        };
+        let (zip_data, pruned) = if config.prune_junk && !no_prune_junk {
+            prune::prune(zip_data, &prune::JunkFilter::new(&config.junk_patterns))?
+        } else {
+            (zip_data, prune::PruneStats::default())
+        };
+        let contents = plan::Contents::tally(zip_data)?;
+        let external_runtime = external_runtime || config.external_runtime;
+        let runtime_size = provider
+            .cached_runtime()
+            .filter(|_| !external_runtime)
+            .map(|dir| plan::dir_size(&dir));
+        let output = resolve_output_path(output_path, &app_name, custom_name.as_deref())?;
+        let plan = plan::BundlePlan {
+            estimated_size: contents.size() + runtime_size.unwrap_or_default(),
+            app_name,
+            app_version,
+            platform: platform.to_string(),
+            runtime: provider.display_name().to_string(),
+            runtime_version: provider.version().to_string(),
+            runtime_size,
+            external_runtime,
+            source_dir,
+            entry: runtime_config.main_script,
+            dependencies,
+            scripts: config.scripts.clone(),
+            not_applied: [
+                ("--analyze", analyze.is_some()),
+                ("--encrypt", runner.encryption_key.is_some()),
+                (
+                    "trimRuntime",
+                    config.trim_runtime
+                        && !no_trim_runtime
+                        && provider.kind() == RuntimeKind::Node
+                        && !external_runtime,
+                ),
+                ("protect", config.protect.is_some()),
+            ]
+            .into_iter()
+            .filter(|(_, configured)| *configured)
+            .map(|(step, _)| step.to_string())
+            .collect(),
+            output,
+            app_files: contents.app_files,
+            app_source: contents.app_source,
+            packages: contents.packages,
+            pruned_files: pruned.files,
+            pruned_bytes: pruned.bytes,
+        };
+        if output::json() {
+            emit(&Event::Plan { plan: &plan });
+        } else {
+            print!("{}", plan.render_text());
+        }
+        let summary = BundleSummary {
+            output: plan.output,
+            size: plan.estimated_size,
+            runtime: plan.runtime,
+            runtime_version: plan.runtime_version,
+            duration_ms: started.elapsed().as_millis() as u64,
+            warnings: Vec::new(),
+            dry_run: true,
+        };
+        return Ok((summary, None));
+    }
+
+    if external_runtime || config.external_runtime {
+        runtime_config.external_runtime = Some(provider.remote_archive().await?);
+    }
+    runtime_config.isolate_env |= isolate_env;
+    let (runtime_name, runtime_version) = (provider.display_name(), provider.version());
 
     info!(
-        "Preparing build for {app_name} v{app_version} (Node {node_version}, {plat})",
-        plat = Platform::current()
+        "Preparing build for {app_name} v{app_version} ({runtime_name} {runtime_version}, {platform})"
     );
 
-    // Emit a plain stdout line so tests (and users without verbose logging) can detect the exact Node.js version
-    println!("Node.js v{node_version}");
+    // Emit a plain stdout line so tests (and users without verbose logging) can detect the exact runtime version
+    if output::json() {
+        emit(&Event::Runtime {
+            name: runtime_name,
+            version: runtime_version,
+        });
+    } else if !output::quiet() {
+        println!("{runtime_name} v{runtime_version}");
+    }
 
     if source_dir != project_path {
         debug!("Using source directory: {}", source_dir.display());
     }
 
     let output_path = resolve_output_path(output_path, &app_name, custom_name.as_deref())?;
+    let hooks = config.hooks.clone().unwrap_or_default();
+    let hook_context = |event| hooks::HookContext {
+        event,
+        project: &project_path,
+        output: &output_path,
+        app_name: &app_name,
+        app_version: &app_version,
+        platform: platform.to_string(),
+        runtime: runtime_name,
+        runtime_version,
+        archive: None,
+        manifest: None,
+        size: None,
+    };
+    if slice != Some(Slice::Later) {
+        hooks::run(&hooks, &hook_context(hooks::HookEvent::PreBundle))?;
+    }
 
     // Styles
     let spinner_style =
@@ -91,118 +569,517 @@ pub async fn bundle_project(
     let started = Instant::now();
 
     // Stage 1: Prepare environment (resolve version + Node ready)
-    println!(
-        "{} {} Preparing environment...",
-        style("[1/3]").bold().dim(),
-        emoji_prepare
-    );
+    stage(1, emoji_prepare, "prepare", "Preparing environment...");
     let pb_prepare = multi.add(ProgressBar::new_spinner());
     pb_prepare.set_style(spinner_style.clone());
 
-    let node_downloader = NodeDownloader::new_with_persistent_cache(&node_version).await?;
-    let node_executable = node_downloader
-        .ensure_node_binary_with_progress(Some(&pb_prepare))
-        .await?;
-    let node_root_buf = {
-        // The extraction target_dir is what we passed to NodeDownloader::download_and_extract_node
-        // which is cache_dir/node/<version>/<platform> on all platforms. We want to bundle that
-        // entire directory under "node/" so the runtime can find binaries consistently.
-        // Derive the root by walking up from the executable until we find the directory named
-        // the platform triplet (win32-*, darwin-*, linux-*).
-        let mut cur = node_executable
-            .parent()
-            .expect("node executable must have a parent");
-        // If on Unix and we are at .../<platform>/bin, step up to <platform>
-        if cur.file_name().is_some_and(|n| n == "bin") {
-            cur = cur.parent().unwrap_or(cur);
-        }
-        cur.to_path_buf()
-    };
-    let node_root: &Path = &node_root_buf;
+    let runtime_root = provider.ensure_runtime(Some(&pb_prepare)).await?;
     pb_prepare.finish_and_clear();
+    let include_npm = include_npm || config.include_npm;
+    if include_npm {
+        anyhow::ensure!(
+            provider.kind() == RuntimeKind::Node,
+            "--include-npm needs the Node.js runtime, not {runtime_name}"
+        );
+        runtime_config.npm_cli = Some(npm_cli(&runtime_root)?);
+    }
+    // npm's `bin/npm`, `bin/npx` and `bin/corepack` only work as links into `lib/node_modules`
+    let keep_runtime_links = provider.preserves_symlinks() || include_npm;
 
     // Stage 2: Bundle application into archive
-    println!(
-        "{} {} Bundling application...",
-        style("[2/3]").bold().dim(),
-        emoji_bundle
-    );
+    stage(2, emoji_bundle, "bundle", "Bundling application...");
     let pb_bundle = multi.add(ProgressBar::new(0));
     pb_bundle.set_style(bar_style.clone());
 
     let mut zip_data: Vec<u8> = Vec::new();
+    let mut dereferenced = 0;
     {
         let mut zip = ZipWriter::new(std::io::Cursor::new(&mut zip_data));
-        let opts: zip::write::FileOptions<'static, ()> = if no_compression {
-            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored)
+        collect_app(&mut zip, &mut dereferenced, Some(&pb_bundle))?;
+
+        // Count runtime files and extend length
+        let runtime_files = if runtime_config.external_runtime.is_some() {
+            0
         } else {
-            zip::write::FileOptions::default()
-                .compression_method(zip::CompressionMethod::Deflated)
-                .compression_level(Some(8))
+            count_files_in_dir(&runtime_root, false, !keep_runtime_links)
         };
-
-        // Pre-count app files
-        let app_files = count_files_in_dir(&source_dir, true, true);
-        pb_bundle.set_length(app_files);
-        add_dir_to_zip_excluding_node_modules(
-            &mut zip,
-            &source_dir,
-            Path::new("app"),
-            opts,
-            Some(&pb_bundle),
-        )?;
-
-        // Dependencies will extend the total as we discover them
-        bundle_dependencies(
-            &mut zip,
-            &project_path,
-            &source_dir,
-            &package_value,
-            opts,
-            Some(&pb_bundle),
-        )?;
-
-        // Count node runtime files and extend length
-        let node_files = count_files_in_dir(node_root, false, true);
-        let new_len = pb_bundle.length().unwrap_or(0) + node_files;
+        let new_len = pb_bundle.length().unwrap_or(0) + runtime_files;
         pb_bundle.set_length(new_len);
-        add_dir_to_zip(
-            &mut zip,
-            node_root,
-            Path::new("node"),
-            opts,
-            Some(&pb_bundle),
-        )?;
+        let runtime_prefix = Path::new(provider.kind().dir_name());
+        if let Some(external) = &runtime_config.external_runtime {
+            info!(
+                "Leaving {runtime_name} out of the executable; it is downloaded from {} on first run",
+                external.url
+            );
+        } else if keep_runtime_links {
+            // Electron's macOS framework bundle is built from symlinks; keep them as links
+            add_dir_to_zip_with_symlinks(
+                &mut zip,
+                &runtime_root,
+                runtime_prefix,
+                opts,
+                Some(&pb_bundle),
+            )?;
+        } else {
+            add_dir_to_zip(
+                &mut zip,
+                &runtime_root,
+                runtime_prefix,
+                opts,
+                Some(&pb_bundle),
+            )?;
+        }
         zip.finish()?;
     }
+    let zip_data = if config.prune_junk && !no_prune_junk {
+        let (zip_data, pruned) =
+            prune::prune(zip_data, &prune::JunkFilter::new(&config.junk_patterns))?;
+        debug!(
+            "Pruned {} junk files ({}) from node_modules",
+            pruned.files,
+            HumanBytes(pruned.bytes)
+        );
+        zip_data
+    } else {
+        zip_data
+    };
+    let trim_runtime = config.trim_runtime && !no_trim_runtime;
+    let zip_data = if trim_runtime
+        && provider.kind() == RuntimeKind::Node
+        && runtime_config.external_runtime.is_none()
+    {
+        let keep = [config.keep_runtime_files.as_slice(), keep.as_slice()].concat();
+        let (zip_data, trimmed) = trim::trim(
+            zip_data,
+            provider.kind().dir_name(),
+            &trim::RuntimeTrim::new(include_npm, &keep),
+        )?;
+        debug!(
+            "Trimmed {} files ({}) from {runtime_name}",
+            trimmed.files,
+            HumanBytes(trimmed.bytes)
+        );
+        zip_data
+    } else {
+        zip_data
+    };
+    let zip_data = match &config.protect {
+        Some(protection) => {
+            // Bytecode is only valid for the V8 of the node that compiled it: the bundled one,
+            // which only runs on a host of the target platform
+            let node = (provider.kind() == RuntimeKind::Node)
+                .then(|| runtime_root.join(platform.node_executable_path()));
+            if node.is_some()
+                && !protection.bytecode.is_empty()
+                && Platform::host().ok() != Some(platform)
+            {
+                anyhow::bail!(
+                    "protect.bytecode is compiled by the bundled {platform} Node.js, which cannot run on this machine; bundle on a {platform} host or use protect.obfuscate"
+                );
+            }
+            let (zip_data, protected) = protect::protect(
+                zip_data,
+                &protect::Protection::new(protection),
+                &project_path,
+                node.as_deref(),
+                &config.node_args,
+            )?;
+            info!(
+                "Obfuscated {} and compiled {} files to bytecode",
+                protected.obfuscated, protected.compiled
+            );
+            zip_data
+        }
+        None => zip_data,
+    };
+    let zip_data = if no_compression {
+        zip_data
+    } else {
+        let cache = if no_archive_cache {
+            None
+        } else {
+            blob_cache::BlobCache::open()
+                .map_err(|e| warn!("Not caching compressed files: {e:#}"))
+                .ok()
+        };
+        let (zip_data, compressed) = blob_cache::compress(zip_data, cache.as_ref())?;
+        debug!(
+            "Compressed {} files, reused {} from the archive cache",
+            compressed.compressed, compressed.reused
+        );
+        if let Some(cache) = &cache {
+            cache.expire();
+        }
+        zip_data
+    };
+    let (zip_data, deduplicated) = dedup::deduplicate(zip_data, &config.mutable_paths)?;
+    if deduplicated.files > 0 {
+        debug!(
+            "Stored {} duplicate files once, saving {}",
+            deduplicated.files,
+            HumanBytes(deduplicated.bytes)
+        );
+    }
+    let zip_data = if hooks.handles(hooks::HookEvent::PostArchive) {
+        let dir = tempfile::TempDir::new().context("Failed to create temporary directory")?;
+        let archive = dir.path().join("payload.zip");
+        fs::write(&archive, &zip_data).context("Failed to write the payload for hooks")?;
+        hooks::run(
+            &hooks,
+            &hooks::HookContext {
+                archive: Some(&archive),
+                ..hook_context(hooks::HookEvent::PostArchive)
+            },
+        )?;
+        fs::read(&archive).context("Failed to read the payload back from the hooks")?
+    } else {
+        zip_data
+    };
     pb_bundle.finish_and_clear();
+    if dereferenced > 0 {
+        warn!(
+            "{dereferenced} symlinks could not be kept as links and were bundled as copies of their targets{}; run with --verbose to list them",
+            if !platform.is_windows() {
+                " (absolute or pointing outside the bundle)"
+            } else {
+                " (symlinks are not recreated on this platform)"
+            }
+        );
+    }
 
     // Stage 3: Create executable
-    println!(
-        "{} {} Building native binary...",
-        style("[3/3]").bold().dim(),
-        emoji_build
-    );
+    stage(3, emoji_build, "build", "Building native binary...");
     let pb_build = multi.add(ProgressBar::new(0));
     // Do not show a determinate bar yet; use a spinner until total is known
     pb_build.set_style(spinner_style.clone());
 
-    executable::create_self_extracting_executable_with_progress(
-        &output_path,
-        zip_data,
+    let manifest = BundleManifest::from_payload(
+        &zip_data,
         &app_name,
+        &app_version,
+        provider.kind(),
+        runtime_version,
+    )?;
+    runtime_config.runtime_digest = match &runtime_config.external_runtime {
+        Some(external) => Some(external.sha256.clone()),
+        None => manifest.content_digest(&format!("{}/", runtime_config.runtime.dir_name())),
+    };
+    let layers = layers::split(
+        zip_data,
+        runtime_config.runtime.dir_name(),
+        &config.mutable_paths,
+    )?;
+    if layers.deps.is_some() {
+        runtime_config.deps_digest = manifest.content_digest(&format!("{}/", layers::DEPS_DIR));
+    }
+    hooks::run(
+        &hooks,
+        &hooks::HookContext {
+            manifest: Some(&manifest),
+            ..hook_context(hooks::HookEvent::PreBuild)
+        },
+    )?;
+    let payload_size = executable::create_self_extracting_executable_with_progress(
+        &output_path,
+        layers,
+        &runtime_config,
+        &manifest,
+        &runner,
+        platform,
         Some(&pb_build),
     )?;
     pb_build.finish_and_clear();
+    let finish = Finish {
+        hooks,
+        project_path: project_path.clone(),
+        app_name: app_name.clone(),
+        app_version: app_version.clone(),
+        runtime: runtime_name.to_string(),
+        runtime_version: runtime_version.to_string(),
+        manifest: manifest.clone(),
+        smoke_test: config
+            .smoke_test
+            .clone()
+            .or_else(|| smoke_test.then(Default::default)),
+    };
+    if slice.is_none() {
+        finish.run(
+            &output_path,
+            &platform.to_string(),
+            smoke_test::can_run(platform),
+        )?;
+    }
 
-    println!(
-        "{} Done in {}",
-        emoji_done,
-        HumanDuration(started.elapsed())
-    );
+    let duration = started.elapsed();
+    if !output::json() && !output::quiet() {
+        if output::plain() {
+            println!("Done in {}", HumanDuration(duration));
+        } else {
+            println!("{} Done in {}", emoji_done, HumanDuration(duration));
+        }
+    }
 
     info!("Bundle created at {}", output_path.display());
-    Ok(())
+
+    let executable_size = fs::metadata(&output_path)
+        .context("Failed to read the executable size")?
+        .len();
+    if let Some(format) = analyze {
+        let report = SizeReport::new(&manifest, executable_size, payload_size);
+        let write_report = |extension: &str, contents: String| -> Result<()> {
+            let mut path = output_path.clone().into_os_string();
+            path.push(extension);
+            let path = PathBuf::from(path);
+            fs::write(&path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            if output::json() {
+                emit(&Event::ReportWritten { path: &path });
+            } else {
+                println!("Size report written to {}", path.display());
+            }
+            Ok(())
+        };
+        match format {
+            AnalyzeFormat::Text if output::json() => emit(&Event::SizeReport { report: &report }),
+            AnalyzeFormat::Text => print!("{}", report.render_text()),
+            AnalyzeFormat::Json => {
+                write_report(".size.json", serde_json::to_string_pretty(&report)?)?
+            }
+            AnalyzeFormat::Html => write_report(".size.html", report.render_html())?,
+        }
+    }
+    let summary = BundleSummary {
+        output: output_path,
+        size: executable_size,
+        runtime: runtime_name.to_string(),
+        runtime_version: runtime_version.to_string(),
+        duration_ms: duration.as_millis() as u64,
+        warnings: Vec::new(),
+        dry_run: false,
+    };
+    Ok((summary, Some(finish)))
+}
+
+/// Announce bundling stage `step` of 3, as a styled or plain line or a `stage` event
+fn stage(step: u8, emoji: Emoji<'_, '_>, name: &str, label: &str) {
+    if output::json() {
+        emit(&Event::Stage {
+            step,
+            total: 3,
+            name,
+        });
+    } else if output::plain() && !output::quiet() {
+        println!("[{step}/3] {label}");
+    } else if !output::quiet() {
+        println!(
+            "{} {} {label}",
+            style(format!("[{step}/3]")).bold().dim(),
+            emoji
+        );
+    }
+}
+
+/// Translate bundle configuration into the settings embedded into the runner
+fn build_runtime_config(
+    config: &BundleConfig,
+    project_path: &Path,
+    source_dir: &Path,
+    app_name: &str,
+    app_version: &str,
+    platform: Platform,
+) -> Result<RuntimeConfig> {
+    let notice = match &config.notice {
+        Some(notice) => Some(RuntimeNotice {
+            text: notice.resolve_text(project_path)?,
+            require_acceptance: notice.require_acceptance,
+        }),
+        None => None,
+    };
+
+    let mut entrypoints = Vec::new();
+    for (name, entry) in &config.entrypoints {
+        let script_path = project_path.join(entry.script());
+        anyhow::ensure!(
+            script_path.is_file(),
+            "Entrypoint '{name}' script not found: {}",
+            script_path.display()
+        );
+        let script = relative_to_source_dir(project_path, source_dir, entry.script())
+            .with_context(|| {
+                format!(
+                    "Entrypoint '{name}' ({}) is outside the bundled source directory {}",
+                    entry.script(),
+                    source_dir.display()
+                )
+            })?;
+        entrypoints.push(RuntimeEntrypoint {
+            name: name.clone(),
+            script,
+            description: entry.description().map(str::to_string),
+        });
+    }
+    if let Some(default) = &config.default_entrypoint {
+        anyhow::ensure!(
+            config.entrypoints.contains_key(default) || config.apps.contains_key(default),
+            "defaultEntrypoint '{default}' is not one of the configured entrypoints"
+        );
+    }
+
+    let mut completion_scripts = BTreeMap::new();
+    for (shell, path) in &config.completion_scripts {
+        let path = project_path.join(path);
+        let script = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Failed to read the {shell} completion script {}",
+                path.display()
+            )
+        })?;
+        completion_scripts.insert(shell.clone(), script);
+    }
+
+    let service = match &config.service {
+        Some(service) => {
+            let name = service
+                .name
+                .clone()
+                .unwrap_or_else(|| crate::package::package_name(app_name));
+            anyhow::ensure!(
+                !name.is_empty()
+                    && !name.starts_with('.')
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+                "service name '{name}' must consist of letters, digits, '-', '_' and '.'"
+            );
+            Some(RuntimeService {
+                name,
+                description: service
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| app_name.to_string()),
+                args: service.args.clone(),
+                user: service.user.clone(),
+                restart: service.restart,
+            })
+        }
+        None => None,
+    };
+
+    for pattern in &config.mutable_paths {
+        anyhow::ensure!(
+            !pattern.is_empty()
+                && !pattern.starts_with('/')
+                && !pattern.contains('\\')
+                && pattern.split('/').all(|segment| segment != ".."),
+            "mutablePaths entry '{pattern}' must be a relative '/'-separated path inside the app"
+        );
+    }
+
+    Ok(RuntimeConfig {
+        app_name: app_name.to_string(),
+        app_version: app_version.to_string(),
+        platform: platform.to_string(),
+        runtime: RuntimeKind::Node,
+        node_build: config.node_build.unwrap_or(if config.lazy_icu {
+            NodeBuild::SmallIcu
+        } else {
+            NodeBuild::Full
+        }),
+        lazy_icu: config.lazy_icu,
+        icu_data_url: config.icu_data_url.clone(),
+        main_script: None,
+        notice,
+        entrypoints,
+        default_entrypoint: config.default_entrypoint.clone(),
+        entry_override_disabled: !config.allow_entry_override,
+        shell_disabled: !config.allow_shell,
+        exec_path_shim: config.exec_path_shim,
+        pnp: is_yarn_pnp(project_path),
+        env: config.env.clone(),
+        npm_cli: None,
+        isolate_env: config.isolate_env,
+        node_args: config.node_args.clone(),
+        mutable_paths: config.mutable_paths.clone(),
+        verify_on_start: config.verify_on_start,
+        completion_args: config.completions.clone(),
+        completion_scripts,
+        runtime_digest: None,
+        deps_digest: None,
+        external_runtime: None,
+        metrics_file: config.metrics.as_ref().and_then(|m| m.file.clone()),
+        metrics_command: config.metrics.as_ref().and_then(|m| m.command.clone()),
+        post_extract: config.post_extract.clone(),
+        cache_scope: config.cache_scope,
+        extraction_dir: match config.extraction_layout {
+            ExtractionLayout::Named => Some(extraction_dir(app_name, app_version)?),
+            ExtractionLayout::Flat => None,
+        },
+        encryption_key_file: config.encryption_key_file.clone(),
+        service,
+    })
+}
+
+/// npm's entry script below the Node.js runtime directory: `lib/node_modules` in the Unix
+/// archives, `node_modules` next to `node.exe` in the Windows ones
+fn npm_cli(runtime_root: &Path) -> Result<String> {
+    [
+        "lib/node_modules/npm/bin/npm-cli.js",
+        "node_modules/npm/bin/npm-cli.js",
+    ]
+    .into_iter()
+    .find(|candidate| runtime_root.join(candidate).is_file())
+    .map(str::to_string)
+    .with_context(|| {
+        format!(
+            "--include-npm: the Node.js runtime in {} contains no npm",
+            runtime_root.display()
+        )
+    })
+}
+
+/// `<name>/<version>` of the named extraction layout, named like packages and services
+fn extraction_dir(app_name: &str, app_version: &str) -> Result<String> {
+    let component = |text: &str, fallback: &str| {
+        let component = crate::package::package_name(text);
+        if component.is_empty() {
+            fallback.to_string()
+        } else {
+            component
+        }
+    };
+    let name = component(app_name, "app");
+    // Beside the extractions, the cache holds shared data and per-user subdirectories
+    if ["runtimes", "deps", "icu"].contains(&name.as_str()) || name.starts_with("user-") {
+        anyhow::bail!(
+            "App name '{app_name}' would be extracted to '{name}/' in the cache, which banderole uses for shared data; rename the app or set extractionLayout to \"flat\""
+        );
+    }
+    Ok(format!("{name}/{}", component(app_version, "unversioned")))
+}
+
+/// Express a project-relative path relative to the bundled source directory (`app/` at runtime).
+/// `.` and `..` are resolved first, so a path leaving the source directory yields `None`.
+fn relative_to_source_dir(project_path: &Path, source_dir: &Path, path: &str) -> Option<String> {
+    let mut full_path = PathBuf::new();
+    for component in project_path.join(path).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                if !full_path.pop() {
+                    return None;
+                }
+            }
+            component => full_path.push(component),
+        }
+    }
+    let relative = full_path.strip_prefix(source_dir).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
 }
 
 // Count files (and symlinks) in a directory. Optionally exclude top-level node_modules.
@@ -233,15 +1110,16 @@ fn count_files_in_dir(dir: &Path, exclude_node_modules: bool, follow_links: bool
     count
 }
 
-/// Bundle dependencies with improved package manager support
+/// Bundle dependencies with improved package manager support, describing where they came from
 fn bundle_dependencies<W>(
     zip: &mut ZipWriter<W>,
     project_path: &Path,
     source_dir: &Path,
-    _package_value: &Value,
+    layout: NodeModulesLayout,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
-) -> Result<()>
+) -> Result<String>
 where
     W: Write + Read + std::io::Seek,
 {
@@ -256,10 +1134,10 @@ where
                 serde_json::from_str(&content).context("Failed to parse root package.json")?;
 
             if let Some(main) = package_value["main"].as_str() {
-                let main_path = project_path.join(main);
-                if let Ok(relative_to_source) = main_path.strip_prefix(source_dir) {
-                    package_value["main"] =
-                        Value::String(relative_to_source.to_string_lossy().to_string());
+                if let Some(relative_to_source) =
+                    relative_to_source_dir(project_path, source_dir, main)
+                {
+                    package_value["main"] = Value::String(relative_to_source);
                 }
             }
 
@@ -269,7 +1147,15 @@ where
         }
     }
 
-    let deps_result = find_and_bundle_dependencies(zip, project_path, opts, progress)?;
+    let deps_result = find_and_bundle_dependencies(
+        zip,
+        project_path,
+        source_dir,
+        layout,
+        opts,
+        dereferenced,
+        progress,
+    )?;
 
     if deps_result.dependencies_found {
         debug!("Bundled dependencies: {}", deps_result.source_description);
@@ -281,7 +1167,7 @@ where
         debug!("{warning}");
     }
 
-    Ok(())
+    Ok(deps_result.source_description)
 }
 
 struct DependenciesResult {
@@ -294,7 +1180,10 @@ struct DependenciesResult {
 fn find_and_bundle_dependencies<W>(
     zip: &mut ZipWriter<W>,
     project_path: &Path,
+    source_dir: &Path,
+    layout: NodeModulesLayout,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<DependenciesResult>
 where
@@ -302,6 +1191,17 @@ where
 {
     let mut warnings = Vec::new();
 
+    // Yarn Plug'n'Play keeps packages in zip archives resolved by .pnp.cjs, without node_modules
+    if is_yarn_pnp(project_path) {
+        bundle_yarn_pnp(zip, project_path, source_dir, opts, dereferenced, progress)?;
+        return Ok(DependenciesResult {
+            dependencies_found: true,
+            source_description: "yarn Plug'n'Play dependencies (.pnp.cjs + .yarn/cache)"
+                .to_string(),
+            warnings,
+        });
+    }
+
     // Strategy 1: Check for node_modules in the project directory
     let project_node_modules = project_path.join("node_modules");
     if project_node_modules.exists() {
@@ -329,9 +1229,23 @@ where
         };
 
         if !is_pnpm_workspace {
+            if layout == NodeModulesLayout::Pnpm && !project_node_modules.join(".pnpm").is_dir() {
+                warn!("--node-modules-layout pnpm needs a pnpm install (node_modules/.pnpm); bundling node_modules as it is");
+            }
             match package_manager {
+                PackageManager::Pnpm
+                    if layout == NodeModulesLayout::Pnpm
+                        && project_node_modules.join(".pnpm").is_dir() =>
+                {
+                    bundle_pnpm_virtual_store(zip, project_path, opts, dereferenced, progress)?;
+                    return Ok(DependenciesResult {
+                        dependencies_found: true,
+                        source_description: "pnpm dependencies (.pnpm virtual store)".to_string(),
+                        warnings,
+                    });
+                }
                 PackageManager::Pnpm => {
-                    bundle_pnpm_dependencies(zip, project_path, opts, progress)?;
+                    bundle_pnpm_dependencies(zip, project_path, opts, dereferenced, progress)?;
                     return Ok(DependenciesResult {
                         dependencies_found: true,
                         source_description: "pnpm dependencies (node_modules + .pnpm)".to_string(),
@@ -344,6 +1258,7 @@ where
                         &project_node_modules,
                         project_path,
                         opts,
+                        dereferenced,
                         progress,
                     )?;
                     return Ok(DependenciesResult {
@@ -358,6 +1273,7 @@ where
                         &project_node_modules,
                         project_path,
                         opts,
+                        dereferenced,
                         progress,
                     )?;
                     return Ok(DependenciesResult {
@@ -400,6 +1316,13 @@ where
 
                 let package_manager = detect_package_manager(&parent_node_modules, parent_path);
 
+                if layout == NodeModulesLayout::Pnpm {
+                    warn!(
+                        "--node-modules-layout pnpm needs the project's own node_modules/.pnpm; flattening the workspace dependencies from {}",
+                        parent_path.display()
+                    );
+                }
+
                 match package_manager {
                     PackageManager::Pnpm => {
                         bundle_pnpm_workspace_dependencies(
@@ -407,6 +1330,7 @@ where
                             parent_path,
                             project_path,
                             opts,
+                            dereferenced,
                             progress,
                         )?;
                         return Ok(DependenciesResult {
@@ -425,6 +1349,7 @@ where
                             parent_path,
                             project_path,
                             opts,
+                            dereferenced,
                             progress,
                         )?;
                         return Ok(DependenciesResult {
@@ -454,6 +1379,64 @@ where
     })
 }
 
+/// Files a Yarn Plug'n'Play install needs at runtime, relative to the project
+const YARN_PNP_FILES: &[&str] = &[
+    ".pnp.cjs",
+    ".pnp.loader.mjs",
+    ".pnp.data.json",
+    ".yarnrc.yml",
+    ".yarn/cache",
+    ".yarn/unplugged",
+];
+
+/// Whether the project was installed by Yarn with Plug'n'Play (`.pnp.cjs` instead of node_modules)
+fn is_yarn_pnp(project_path: &Path) -> bool {
+    project_path.join(".pnp.cjs").is_file()
+}
+
+/// Bundle the PnP runtime, the zipped package cache and unplugged packages next to the app, where
+/// `.pnp.cjs` resolves them relative to itself. The runner requires `.pnp.cjs` when node starts.
+fn bundle_yarn_pnp<W>(
+    zip: &mut ZipWriter<W>,
+    project_path: &Path,
+    source_dir: &Path,
+    opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + std::io::Seek,
+{
+    let pnp =
+        fs::read_to_string(project_path.join(".pnp.cjs")).context("Failed to read .pnp.cjs")?;
+    anyhow::ensure!(
+        !pnp.contains("/berry/cache/"),
+        "This Yarn Plug'n'Play install resolves packages from Yarn's global cache, which is not bundled; \
+         set `enableGlobalCache: false` in .yarnrc.yml and run `yarn install` again"
+    );
+    // The source directory is bundled as the app already, PnP files included
+    if source_dir == project_path {
+        return Ok(());
+    }
+    for file in YARN_PNP_FILES {
+        let path = project_path.join(file);
+        let dest = Path::new("app").join(file);
+        if path.is_dir() {
+            if let Some(pb) = progress {
+                pb.inc_length(count_files_in_dir(&path, false, false));
+            }
+            add_dir_to_zip_no_follow(zip, &path, &dest, opts, dereferenced, progress)?;
+        } else if path.is_file() {
+            zip.start_file(
+                dest.to_string_lossy().as_ref(),
+                entry_options(opts, &fs::metadata(&path)?),
+            )?;
+            zip.write_all(&fs::read(&path)?)?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum PackageManager {
     Npm,
@@ -498,11 +1481,74 @@ fn detect_package_manager(node_modules_path: &Path, project_path: &Path) -> Pack
     PackageManager::Unknown
 }
 
+/// [`run_bundle_scripts`] before the first slice of a universal executable, or a single-platform
+/// bundle; later slices reuse what the scripts built
+async fn run_slice_scripts(
+    slice: Option<Slice>,
+    project_path: &Path,
+    package_value: &Value,
+    scripts: &[String],
+) -> Result<()> {
+    if slice != Some(Slice::Later) {
+        run_bundle_scripts(project_path, package_value, scripts).await?;
+    }
+    Ok(())
+}
+
+/// Run the package.json scripts listed in the `scripts` configuration, in order, with the
+/// project's package manager. Anything printed goes straight to the terminal (stderr with `--json`).
+async fn run_bundle_scripts(
+    project_path: &Path,
+    package_value: &Value,
+    scripts: &[String],
+) -> Result<()> {
+    for name in scripts {
+        anyhow::ensure!(
+            package_value["scripts"][name].is_string(),
+            "Script \"{name}\" from the banderole configuration is not defined in package.json"
+        );
+    }
+    if scripts.is_empty() {
+        return Ok(());
+    }
+
+    let program = match detect_package_manager(&project_path.join("node_modules"), project_path) {
+        PackageManager::Pnpm => "pnpm",
+        PackageManager::Yarn => "yarn",
+        PackageManager::Npm | PackageManager::Unknown => "npm",
+    };
+    // npm, pnpm and yarn are batch-file shims on Windows
+    let program = if cfg!(windows) {
+        format!("{program}.cmd")
+    } else {
+        program.to_string()
+    };
+    for name in scripts {
+        info!("Running {program} run {name}");
+        let mut command = tokio::process::Command::new(&program);
+        command.arg("run").arg(name).current_dir(project_path);
+        if output::json() {
+            // Keep stdout free for JSON events
+            command.stdout(std::io::stderr());
+        }
+        let status = command
+            .status()
+            .await
+            .with_context(|| format!("Failed to run {program} (is it installed?)"))?;
+        anyhow::ensure!(
+            status.success(),
+            "Script \"{name}\" failed ({status}); not bundling"
+        );
+    }
+    Ok(())
+}
+
 /// Bundle pnpm dependencies by creating a flattened node_modules structure
 fn bundle_pnpm_dependencies<W>(
     zip: &mut ZipWriter<W>,
     project_path: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
@@ -523,29 +1569,18 @@ where
                 &node_modules_path,
                 Path::new("app/node_modules"),
                 opts,
+                dereferenced,
                 progress,
             )?;
         }
         return Ok(());
     }
 
-    let mut packages_to_bundle = std::collections::HashSet::new();
-
-    let package_json_path = project_path.join("package.json");
-    if let Ok(package_json_content) = fs::read_to_string(&package_json_path) {
-        if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-            if let Some(deps) = package_json["dependencies"].as_object() {
-                for dep_name in deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            // Only include devDependencies if they're actually used in production
-            // For now, skip them to keep the bundle smaller
-        }
-    }
+    // devDependencies are not bundled
+    let locals = LocalPackages::find(project_path)?;
 
     let mut resolved_packages = std::collections::HashSet::new();
-    for package_name in &packages_to_bundle {
+    for package_name in &locals.dependencies {
         resolve_package_dependencies(
             &node_modules_path,
             &pnpm_dir,
@@ -555,6 +1590,7 @@ where
         )?;
     }
 
+    resolved_packages.retain(|name| !locals.contains(name));
     debug!(
         "Bundling {} packages (resolved dependencies) for pnpm project",
         resolved_packages.len()
@@ -569,11 +1605,13 @@ where
             &pnpm_dir,
             package_name,
             opts,
+            dereferenced,
             progress,
         ) {
             warn!("Failed to copy package {package_name}: {e}");
         }
     }
+    locals.add_to_zip(zip, opts, progress)?;
 
     let bin_dir = node_modules_path.join(".bin");
     if bin_dir.exists() {
@@ -585,6 +1623,7 @@ where
             &bin_dir,
             Path::new("app/node_modules/.bin"),
             opts,
+            dereferenced,
             progress,
         )?;
     }
@@ -603,6 +1642,158 @@ where
     Ok(())
 }
 
+/// Bundle pnpm's layout as installed: the top-level links of the production dependencies and
+/// every `.pnpm` store entry reachable from them, with the links between entries kept. Each
+/// package then resolves exactly the dependency versions pnpm linked for it.
+fn bundle_pnpm_virtual_store<W>(
+    zip: &mut ZipWriter<W>,
+    project_path: &Path,
+    opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + std::io::Seek,
+{
+    let node_modules_path = project_path.join("node_modules");
+    let pnpm_dir = node_modules_path
+        .join(".pnpm")
+        .canonicalize()
+        .context("Failed to resolve node_modules/.pnpm")?;
+    let dest = Path::new("app/node_modules");
+
+    let package_json: Value = serde_json::from_str(
+        &fs::read_to_string(project_path.join("package.json"))
+            .context("Failed to read package.json")?,
+    )
+    .context("Failed to parse package.json")?;
+    let direct: Vec<&String> = ["dependencies", "optionalDependencies"]
+        .iter()
+        .filter_map(|field| package_json[field].as_object())
+        .flat_map(|deps| deps.keys())
+        .collect();
+
+    zip.add_directory("app/node_modules/", opts)?;
+    let mut pending = Vec::new();
+    for name in direct {
+        let path = node_modules_path.join(name);
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            // Missing optional dependencies are expected
+            continue;
+        };
+        if metadata.file_type().is_symlink() {
+            add_symlink_to_zip(zip, &path, &dest.join(name), opts, dereferenced, progress)?;
+            pending.extend(pnpm_store_entry(&pnpm_dir, &path));
+        } else {
+            if let Some(pb) = progress {
+                pb.inc_length(count_files_in_dir(&path, false, false));
+            }
+            add_dir_to_zip_no_follow(zip, &path, &dest.join(name), opts, dereferenced, progress)?;
+        }
+    }
+
+    // Walk the links inside each store entry's node_modules to find the entries it depends on
+    let mut entries = std::collections::BTreeSet::new();
+    while let Some(entry) = pending.pop() {
+        if !entries.insert(entry.clone()) {
+            continue;
+        }
+        for package in package_dirs(&pnpm_dir.join(&entry).join("node_modules")) {
+            pending.extend(pnpm_store_entry(&pnpm_dir, &package));
+        }
+    }
+    debug!(
+        "Bundling {} .pnpm store entries with the pnpm layout",
+        entries.len()
+    );
+
+    let store_dest = dest.join(".pnpm");
+    for entry in &entries {
+        let entry_path = pnpm_dir.join(entry);
+        if let Some(pb) = progress {
+            pb.inc_length(count_files_in_dir(&entry_path, false, false));
+        }
+        add_dir_to_zip_no_follow(
+            zip,
+            &entry_path,
+            &store_dest.join(entry),
+            opts,
+            dereferenced,
+            progress,
+        )?;
+    }
+    // pnpm's hoisted links, limited to the bundled entries
+    let hoisted = pnpm_dir.join("node_modules");
+    for link in package_dirs(&hoisted) {
+        let bundled = pnpm_store_entry(&pnpm_dir, &link).is_some_and(|e| entries.contains(&e));
+        if bundled && link.is_symlink() {
+            let relative = link.strip_prefix(&hoisted).unwrap_or(&link);
+            add_symlink_to_zip(
+                zip,
+                &link,
+                &store_dest.join("node_modules").join(relative),
+                opts,
+                dereferenced,
+                progress,
+            )?;
+        }
+    }
+
+    let bin_dir = node_modules_path.join(".bin");
+    if bin_dir.exists() {
+        if let Some(pb) = progress {
+            pb.inc_length(count_files_in_dir(&bin_dir, false, false));
+        }
+        add_dir_to_zip_no_follow(
+            zip,
+            &bin_dir,
+            &dest.join(".bin"),
+            opts,
+            dereferenced,
+            progress,
+        )?;
+    }
+    let modules_yaml = node_modules_path.join(".modules.yaml");
+    if modules_yaml.is_file() {
+        zip.start_file("app/node_modules/.modules.yaml", opts)?;
+        zip.write_all(&fs::read(&modules_yaml)?)?;
+    }
+    Ok(())
+}
+
+/// Name of the `.pnpm` entry (e.g. `lodash@4.17.21`) that the package at `path` resolves into
+fn pnpm_store_entry(pnpm_dir: &Path, path: &Path) -> Option<std::ffi::OsString> {
+    let resolved = path.canonicalize().ok()?;
+    let relative = resolved.strip_prefix(pnpm_dir).ok()?;
+    match relative.components().next()? {
+        std::path::Component::Normal(entry) if entry != "node_modules" => Some(entry.to_owned()),
+        _ => None,
+    }
+}
+
+/// Package directories or links directly in `node_modules`, looking into `@scope` directories
+fn package_dirs(node_modules: &Path) -> Vec<PathBuf> {
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(node_modules).into_iter().flatten().flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if name.to_string_lossy().starts_with('@') && !entry.path().is_symlink() {
+            packages.extend(
+                fs::read_dir(entry.path())
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|scoped| scoped.path()),
+            );
+        } else {
+            packages.push(entry.path());
+        }
+    }
+    packages
+}
+
 /// Recursively resolve dependencies for a package
 fn resolve_package_dependencies(
     node_modules_path: &Path,
@@ -622,51 +1813,25 @@ fn resolve_package_dependencies(
 
     resolved.insert(package_name.to_string());
 
-    let package_json_content =
-        match find_package_json_content(node_modules_path, pnpm_dir, package_name) {
-            Ok(content) => content,
-            Err(_) => return Ok(()), // Skip packages we can't find
-        };
-
-    if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-        if let Some(deps) = package_json["dependencies"].as_object() {
-            for dep_name in deps.keys() {
-                resolve_package_dependencies(
-                    node_modules_path,
-                    pnpm_dir,
-                    dep_name,
-                    resolved,
-                    depth + 1,
-                )?;
-            }
-        }
-
-        if let Some(peer_deps) = package_json["peerDependencies"].as_object() {
-            for dep_name in peer_deps.keys() {
-                if package_exists_in_pnpm(node_modules_path, pnpm_dir, dep_name) {
-                    resolve_package_dependencies(
-                        node_modules_path,
-                        pnpm_dir,
-                        dep_name,
-                        resolved,
-                        depth + 1,
-                    )?;
-                }
-            }
-        }
-
-        if let Some(optional_deps) = package_json["optionalDependencies"].as_object() {
-            for dep_name in optional_deps.keys() {
-                if package_exists_in_pnpm(node_modules_path, pnpm_dir, dep_name) {
-                    resolve_package_dependencies(
-                        node_modules_path,
-                        pnpm_dir,
-                        dep_name,
-                        resolved,
-                        depth + 1,
-                    )?;
-                }
-            }
+    let names = workspace::dependency_names(pnpm_dir.join(package_name), || {
+        // Skip packages we can't find
+        Ok(find_package_json_content(node_modules_path, pnpm_dir, package_name).ok())
+    })?;
+    let Some(names) = names else {
+        return Ok(());
+    };
+    for dep_name in &names.required {
+        resolve_package_dependencies(node_modules_path, pnpm_dir, dep_name, resolved, depth + 1)?;
+    }
+    for dep_name in &names.if_installed {
+        if package_exists_in_pnpm(node_modules_path, pnpm_dir, dep_name) {
+            resolve_package_dependencies(
+                node_modules_path,
+                pnpm_dir,
+                dep_name,
+                resolved,
+                depth + 1,
+            )?;
         }
     }
 
@@ -766,6 +1931,7 @@ fn copy_pnpm_package_comprehensive<W>(
     pnpm_dir: &Path,
     package_name: &str,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
@@ -796,7 +1962,14 @@ where
                     pb.length().unwrap_or(0) + count_files_in_dir(&target_path, false, false),
                 );
             }
-            add_dir_to_zip_no_follow_skip_parents(zip, &target_path, &dest_path, opts, progress)?;
+            add_dir_to_zip_no_follow_skip_parents(
+                zip,
+                &target_path,
+                &dest_path,
+                opts,
+                dereferenced,
+                progress,
+            )?;
             return Ok(());
         }
     }
@@ -818,6 +1991,7 @@ where
                         &pnpm_package_path,
                         &dest_path,
                         opts,
+                        dereferenced,
                         progress,
                     )?;
                     return Ok(());
@@ -835,38 +2009,18 @@ fn bundle_node_modules_comprehensive<W>(
     node_modules_path: &Path,
     project_path: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
     W: Write + Read + std::io::Seek,
 {
-    let mut packages_to_bundle = std::collections::HashSet::new();
-
-    let package_json_path = project_path.join("package.json");
-    if let Ok(package_json_content) = fs::read_to_string(&package_json_path) {
-        if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-            if let Some(deps) = package_json["dependencies"].as_object() {
-                for dep_name in deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(peer_deps) = package_json["peerDependencies"].as_object() {
-                for dep_name in peer_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(optional_deps) = package_json["optionalDependencies"].as_object() {
-                for dep_name in optional_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-        }
-    }
+    let locals = LocalPackages::find(project_path)?;
 
     let pnpm_dir = node_modules_path.join(".pnpm");
     if pnpm_dir.exists() {
         let mut resolved_packages = std::collections::HashSet::new();
-        for package_name in &packages_to_bundle {
+        for package_name in &locals.dependencies {
             resolve_package_dependencies(
                 node_modules_path,
                 &pnpm_dir,
@@ -876,6 +2030,7 @@ where
             )?;
         }
 
+        resolved_packages.retain(|name| !locals.contains(name));
         debug!(
             "Bundling {} packages (resolved dependencies) for pnpm node_modules",
             resolved_packages.len()
@@ -890,6 +2045,7 @@ where
                 &pnpm_dir,
                 package_name,
                 opts,
+                dereferenced,
                 progress,
             ) {
                 warn!("Failed to copy package {package_name}: {e}");
@@ -897,7 +2053,7 @@ where
         }
     } else {
         let mut resolved_packages = std::collections::HashSet::new();
-        for package_name in &packages_to_bundle {
+        for package_name in &locals.dependencies {
             resolve_workspace_dependencies(
                 node_modules_path,
                 package_name,
@@ -906,6 +2062,7 @@ where
             )?;
         }
 
+        resolved_packages.retain(|name| !locals.contains(name));
         debug!(
             "Bundling {} packages (resolved dependencies) for regular node_modules",
             resolved_packages.len()
@@ -914,13 +2071,19 @@ where
         zip.add_directory("app/node_modules/", opts)?;
 
         for package_name in &resolved_packages {
-            if let Err(e) =
-                copy_workspace_package(zip, node_modules_path, package_name, opts, progress)
-            {
+            if let Err(e) = copy_workspace_package(
+                zip,
+                node_modules_path,
+                package_name,
+                opts,
+                dereferenced,
+                progress,
+            ) {
                 warn!("Failed to copy package {package_name}: {e}");
             }
         }
     }
+    locals.add_to_zip(zip, opts, progress)?;
 
     let bin_dir = node_modules_path.join(".bin");
     if bin_dir.exists() {
@@ -932,6 +2095,7 @@ where
             &bin_dir,
             Path::new("app/node_modules/.bin"),
             opts,
+            dereferenced,
             progress,
         )?;
     }
@@ -960,36 +2124,16 @@ fn bundle_workspace_dependencies<W>(
     _parent_path: &Path,
     project_path: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
     W: Write + Read + std::io::Seek,
 {
-    let mut packages_to_bundle = std::collections::HashSet::new();
-
-    let package_json_path = project_path.join("package.json");
-    if let Ok(package_json_content) = fs::read_to_string(&package_json_path) {
-        if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-            if let Some(deps) = package_json["dependencies"].as_object() {
-                for dep_name in deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(peer_deps) = package_json["peerDependencies"].as_object() {
-                for dep_name in peer_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(optional_deps) = package_json["optionalDependencies"].as_object() {
-                for dep_name in optional_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-        }
-    }
+    let locals = LocalPackages::find(project_path)?;
 
     let mut resolved_packages = std::collections::HashSet::new();
-    for package_name in &packages_to_bundle {
+    for package_name in &locals.dependencies {
         resolve_workspace_dependencies(
             node_modules_path,
             package_name,
@@ -998,6 +2142,7 @@ where
         )?;
     }
 
+    resolved_packages.retain(|name| !locals.contains(name));
     debug!(
         "Bundling {} packages (resolved dependencies) for workspace node_modules",
         resolved_packages.len()
@@ -1006,11 +2151,18 @@ where
     zip.add_directory("app/node_modules/", opts)?;
 
     for package_name in &resolved_packages {
-        if let Err(e) = copy_workspace_package(zip, node_modules_path, package_name, opts, progress)
-        {
+        if let Err(e) = copy_workspace_package(
+            zip,
+            node_modules_path,
+            package_name,
+            opts,
+            dereferenced,
+            progress,
+        ) {
             warn!("Failed to copy package {package_name}: {e}");
         }
     }
+    locals.add_to_zip(zip, opts, progress)?;
 
     let bin_dir = node_modules_path.join(".bin");
     if bin_dir.exists() {
@@ -1022,6 +2174,7 @@ where
             &bin_dir,
             Path::new("app/node_modules/.bin"),
             opts,
+            dereferenced,
             progress,
         )?;
     }
@@ -1049,36 +2202,16 @@ fn bundle_pnpm_workspace_dependencies<W>(
     parent_path: &Path,
     project_path: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
     W: Write + Read + std::io::Seek,
 {
-    let mut packages_to_bundle = std::collections::HashSet::new();
-
-    let package_json_path = project_path.join("package.json");
-    if let Ok(package_json_content) = fs::read_to_string(&package_json_path) {
-        if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-            if let Some(deps) = package_json["dependencies"].as_object() {
-                for dep_name in deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(peer_deps) = package_json["peerDependencies"].as_object() {
-                for dep_name in peer_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-            if let Some(optional_deps) = package_json["optionalDependencies"].as_object() {
-                for dep_name in optional_deps.keys() {
-                    packages_to_bundle.insert(dep_name.clone());
-                }
-            }
-        }
-    }
+    let locals = LocalPackages::find(project_path)?;
 
     let mut resolved_packages = std::collections::HashSet::new();
-    for package_name in &packages_to_bundle {
+    for package_name in &locals.dependencies {
         resolve_package_dependencies(
             &parent_path.join("node_modules"),
             &parent_path.join("node_modules").join(".pnpm"),
@@ -1088,6 +2221,7 @@ where
         )?;
     }
 
+    resolved_packages.retain(|name| !locals.contains(name));
     debug!(
         "Bundling {} packages (resolved dependencies) for workspace pnpm node_modules",
         resolved_packages.len()
@@ -1103,12 +2237,15 @@ where
             &parent_path.join("node_modules").join(".pnpm"),
             package_name,
             opts,
+            dereferenced,
             progress,
         ) {
             warn!("Failed to copy package {package_name}: {e}");
         }
     }
 
+    locals.add_to_zip(zip, opts, progress)?;
+
     let bin_dir = parent_path.join("node_modules").join(".bin");
     if bin_dir.exists() {
         if let Some(pb) = progress {
@@ -1119,6 +2256,7 @@ where
             &bin_dir,
             Path::new("app/node_modules/.bin"),
             opts,
+            dereferenced,
             progress,
         )?;
     }
@@ -1141,11 +2279,12 @@ where
 }
 
 /// Enhanced Node version detection with workspace support and version resolution.
-async fn detect_node_version_with_workspace_support(
+pub(crate) async fn detect_node_version_with_workspace_support(
     project_path: &Path,
     ignore_cached_versions: bool,
+    mirror: &str,
 ) -> Result<String> {
-    let version_manager = NodeVersionManager::new();
+    let version_manager = NodeVersionManager::with_mirror(mirror);
     let version_spec = find_node_version_spec(project_path)?;
 
     version_manager
@@ -1153,8 +2292,9 @@ async fn detect_node_version_with_workspace_support(
         .await
 }
 
-/// Find Node version specification from .nvmrc or .node-version files,
-/// supporting workspace packages (parent/package, parent/packages/package patterns)
+/// Find Node version specification from .nvmrc or .node-version files, falling back to
+/// `engines.node` in package.json, supporting workspace packages (parent/package,
+/// parent/packages/package patterns)
 fn find_node_version_spec(project_path: &Path) -> Result<String> {
     let mut current_path = project_path;
 
@@ -1178,6 +2318,26 @@ fn find_node_version_spec(project_path: &Path) -> Result<String> {
         current_path = current_path.parent().unwrap();
     }
 
+    // Without version files, use the range the package (or its workspace root) declares
+    let mut current_path = project_path;
+    loop {
+        if let Ok(content) = fs::read_to_string(current_path.join("package.json")) {
+            if let Ok(package_json) = serde_json::from_str::<Value>(&content) {
+                if let Some(range) = package_json["engines"]["node"].as_str() {
+                    if !range.trim().is_empty() {
+                        return Ok(range.trim().to_owned());
+                    }
+                }
+            }
+        }
+
+        if is_workspace_root(current_path) || current_path.parent().is_none() {
+            break;
+        }
+
+        current_path = current_path.parent().unwrap();
+    }
+
     anyhow::bail!("Node version specification not found in project or workspace hierarchy")
 }
 
@@ -1207,10 +2367,102 @@ fn normalize_node_version_spec(raw: &str) -> String {
     raw.trim().trim_start_matches('v').to_owned()
 }
 
+/// Choose the project-relative script the executable launches.
+///
+/// The package entry (`exports`, `main`, or `index.*`, see [`package_entry::resolve`]) wins
+/// unless it is missing or looks like a library (no shebang) while the package
+/// declares `bin` targets; CLI-only packages then run their `bin`. `bin_entry` selects one of
+/// several bins and defaults to the bin named after the package.
+fn resolve_entry_script(
+    project_path: &Path,
+    package_json: &Value,
+    bin_entry: Option<&str>,
+) -> Result<Option<String>> {
+    let bins = package_bins(package_json);
+    if let Some(name) = bin_entry {
+        anyhow::ensure!(
+            !bins.is_empty(),
+            "--entry {name} was given but package.json declares no \"bin\" field"
+        );
+        return bins
+            .iter()
+            .find(|(bin, _)| bin == name)
+            .map(|(_, script)| Some(script.clone()))
+            .with_context(|| {
+                format!(
+                    "package.json has no bin named '{name}' (available: {})",
+                    bin_names(&bins)
+                )
+            });
+    }
+
+    let main = package_entry::resolve(project_path, package_json);
+    let main_is_app = main.as_deref().is_some_and(|main| {
+        let main_path = project_path.join(main);
+        has_shebang(&main_path)
+            || bins
+                .iter()
+                .any(|(_, script)| project_path.join(script) == main_path)
+    });
+    if main_is_app || bins.is_empty() {
+        return Ok(main);
+    }
+
+    let script = match bins.as_slice() {
+        [(_, script)] => script.clone(),
+        _ => {
+            let package_name = package_json["name"].as_str().unwrap_or_default();
+            let default_name = package_name.rsplit('/').next().unwrap_or(package_name);
+            bins.iter()
+                .find(|(bin, _)| bin == default_name)
+                .map(|(_, script)| script.clone())
+                .with_context(|| {
+                    format!(
+                        "package.json declares multiple bins ({}); pass --entry <name> to choose one",
+                        bin_names(&bins)
+                    )
+                })?
+        }
+    };
+    debug!("Using bin script {script} as entry point");
+    Ok(Some(script))
+}
+
+/// `bin` targets as (name, script) pairs; the string form is named after the unscoped package name
+fn package_bins(package_json: &Value) -> Vec<(String, String)> {
+    match &package_json["bin"] {
+        Value::String(script) => {
+            let name = package_json["name"].as_str().unwrap_or("app");
+            let name = name.rsplit('/').next().unwrap_or(name);
+            vec![(name.to_string(), script.clone())]
+        }
+        Value::Object(map) => map
+            .iter()
+            .filter_map(|(name, script)| Some((name.clone(), script.as_str()?.to_string())))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn bin_names(bins: &[(String, String)]) -> String {
+    bins.iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn has_shebang(path: &Path) -> bool {
+    let mut prefix = [0u8; 2];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut prefix))
+        .is_ok()
+        && &prefix == b"#!"
+}
+
 /// Determine the correct source directory to bundle for the project.
 /// This handles TypeScript projects and other build configurations.
-fn determine_source_directory(project_path: &Path, package_json: &Value) -> Result<PathBuf> {
-    if let Some(main) = package_json["main"].as_str() {
+fn determine_source_directory(project_path: &Path, entry_script: Option<&str>) -> Result<PathBuf> {
+    if let Some(main) = entry_script {
         let main_path = project_path.join(main);
         if let Some(parent) = main_path.parent() {
             let parent_name = parent
@@ -1249,6 +2501,46 @@ fn determine_source_directory(project_path: &Path, package_json: &Value) -> Resu
     Ok(project_path.to_path_buf())
 }
 
+/// The newest source file when it was modified after every file of the build output in `source_dir`.
+///
+/// Sources are tsconfig's `rootDir`, or `src/`. Only applies when a build directory is bundled.
+fn stale_build_source(project_path: &Path, source_dir: &Path) -> Option<PathBuf> {
+    if source_dir == project_path {
+        return None;
+    }
+    let root_dir = read_tsconfig(&project_path.join("tsconfig.json"))
+        .ok()
+        .and_then(|tsconfig| {
+            tsconfig["compilerOptions"]["rootDir"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "src".to_string());
+    let sources = project_path.join(root_dir);
+    if !sources.is_dir() || source_dir.starts_with(&sources) || sources.starts_with(source_dir) {
+        return None;
+    }
+
+    let newest = |dir: &Path, is_source: fn(&Path) -> bool| {
+        walkdir::WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != "node_modules")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_source(entry.path()))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.into_path())))
+            .max()
+    };
+    let (built, _) = newest(source_dir, |_| true)?;
+    let (modified, source) = newest(&sources, |path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"].contains(&ext)
+            })
+    })?;
+    (modified > built).then_some(source)
+}
+
 /// Read and parse tsconfig.json, handling extends configuration
 fn read_tsconfig(tsconfig_path: &Path) -> Result<Value> {
     let content = fs::read_to_string(tsconfig_path).context("Failed to read tsconfig.json")?;
@@ -1380,7 +2672,10 @@ where
         let zip_path = dest_dir.join(rel_path);
 
         if entry.file_type().is_dir() {
-            zip.add_directory(zip_path.to_string_lossy().as_ref(), opts)?;
+            zip.add_directory(
+                zip_path.to_string_lossy().as_ref(),
+                entry_options(opts, &entry.metadata()?),
+            )?;
             continue;
         }
 
@@ -1388,20 +2683,7 @@ where
             continue;
         }
 
-        let file_opts = {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let metadata = fs::metadata(path)?;
-                let permissions = metadata.permissions();
-                let mode = permissions.mode();
-                opts.unix_permissions(mode)
-            }
-            #[cfg(not(unix))]
-            {
-                opts
-            }
-        };
+        let file_opts = entry_options(opts, &fs::metadata(path)?);
 
         zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
         let data = fs::read(path).context("Failed to read file while zipping")?;
@@ -1419,6 +2701,7 @@ fn add_dir_to_zip_no_follow<W>(
     src_dir: &Path,
     dest_dir: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
@@ -1431,39 +2714,224 @@ where
         let zip_path = dest_dir.join(rel_path);
 
         if entry.file_type().is_dir() {
-            zip.add_directory(zip_path.to_string_lossy().as_ref(), opts)?;
+            zip.add_directory(
+                zip_path.to_string_lossy().as_ref(),
+                entry_options(opts, &entry.metadata()?),
+            )?;
             continue;
         }
 
-        if !entry.file_type().is_file() && !entry.file_type().is_symlink() {
+        if entry.file_type().is_symlink() {
+            add_symlink_to_zip(zip, path, &zip_path, opts, dereferenced, progress)?;
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
             continue;
         }
 
-        let file_opts = {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let metadata = entry.metadata()?;
-                let permissions = metadata.permissions();
-                let mode = permissions.mode();
-                opts.unix_permissions(mode)
-            }
-            #[cfg(not(unix))]
-            {
-                opts
-            }
-        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let file_opts = entry_options(opts, &entry.metadata()?);
 
         zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
+        let data = fs::read(path).context("Failed to read file while zipping")?;
+        zip.write_all(&data)?;
+        if let Some(pb) = progress {
+            pb.inc(1);
+        }
+    }
+    Ok(())
+}
 
-        if entry.file_type().is_symlink() {
-            if let Ok(target) = fs::read_link(path) {
-                let target_str = target.to_string_lossy();
-                zip.write_all(target_str.as_bytes())?;
+/// `opts` carrying the permission bits and modification time of the file or directory described
+/// by `metadata`. Zip timestamps have two-second resolution and are stored as UTC.
+fn entry_options(
+    opts: zip::write::FileOptions<'static, ()>,
+    metadata: &fs::Metadata,
+) -> zip::write::FileOptions<'static, ()> {
+    let opts = match metadata.modified().ok().and_then(zip_time) {
+        Some(time) => opts.last_modified_time(time),
+        None => opts,
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        opts.unix_permissions(metadata.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        opts
+    }
+}
+
+/// `time` as a zip timestamp; `None` outside the representable 1980-2107 range
+fn zip_time(time: std::time::SystemTime) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+    let utc = chrono::DateTime::<chrono::Utc>::from(time);
+    zip::DateTime::from_date_and_time(
+        u16::try_from(utc.year()).ok()?,
+        utc.month() as u8,
+        utc.day() as u8,
+        utc.hour() as u8,
+        utc.minute() as u8,
+        // Rounded down to the two-second resolution zip can store
+        (utc.second() & !1) as u8,
+    )
+    .ok()
+}
+
+/// Store the symlink at `path` as a symlink entry the runner recreates on extraction.
+///
+/// Links the runner would refuse (absolute, or escaping the extraction directory) and links for
+/// targets without usable symlinks (Windows) are dereferenced: the target's contents are bundled
+/// instead, counted in `dereferenced`.
+fn add_symlink_to_zip<W>(
+    zip: &mut ZipWriter<W>,
+    path: &Path,
+    zip_path: &Path,
+    opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + std::io::Seek,
+{
+    let target = fs::read_link(path).context("Failed to read symlink while zipping")?;
+    if !Platform::current().is_windows() && link_stays_in_bundle(zip_path, &target) {
+        zip.add_symlink(
+            zip_path.to_string_lossy().as_ref(),
+            target.to_string_lossy().as_ref(),
+            opts,
+        )?;
+        return Ok(());
+    }
+
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            debug!(
+                "Bundling a copy of {} -> {} instead of the symlink",
+                path.display(),
+                target.display()
+            );
+            *dereferenced += 1;
+            if metadata.is_dir() {
+                add_dir_to_zip(zip, path, zip_path, opts, progress)?;
+            } else {
+                zip.start_file(
+                    zip_path.to_string_lossy().as_ref(),
+                    entry_options(opts, &metadata),
+                )?;
+                zip.write_all(&fs::read(path).context("Failed to read symlink target")?)?;
+            }
+        }
+        Err(_) => warn!(
+            "Skipping dangling symlink {} -> {}",
+            path.display(),
+            target.display()
+        ),
+    }
+    Ok(())
+}
+
+/// Whether a relative `target` of a link stored at `zip_path` resolves inside the archive,
+/// which is what the runner checks before recreating it
+fn link_stays_in_bundle(zip_path: &Path, target: &Path) -> bool {
+    use std::path::Component;
+    let mut depth = zip_path.components().count().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Copy `include` entries from the project into `app/`, keeping their project-relative paths.
+/// Entries inside the source directory are already part of the app and are skipped.
+fn add_included_paths<W>(
+    zip: &mut ZipWriter<W>,
+    project_path: &Path,
+    source_dir: &Path,
+    include: &[PathBuf],
+    opts: zip::write::FileOptions<'static, ()>,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + std::io::Seek,
+{
+    for relative in include {
+        anyhow::ensure!(
+            relative.is_relative()
+                && relative
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_))),
+            "include entry {} must be a relative path inside the project",
+            relative.display()
+        );
+        let source = project_path.join(relative);
+        if source.starts_with(source_dir) {
+            debug!(
+                "include {} is already bundled from the source directory",
+                relative.display()
+            );
+            continue;
+        }
+        let dest = Path::new("app").join(relative);
+        if source.is_dir() {
+            if let Some(pb) = progress {
+                pb.set_length(pb.length().unwrap_or(0) + count_files_in_dir(&source, false, true));
             }
+            add_dir_to_zip(zip, &source, &dest, opts, progress)?;
+        } else if source.is_file() {
+            zip.start_file(
+                dest.to_string_lossy().replace('\\', "/"),
+                entry_options(opts, &fs::metadata(&source)?),
+            )?;
+            zip.write_all(&fs::read(&source).context("Failed to read included file")?)?;
         } else {
+            anyhow::bail!("include entry not found: {}", source.display());
+        }
+    }
+    Ok(())
+}
+
+/// Add directory to zip, storing symlinks as symlink entries the runner recreates on extraction
+fn add_dir_to_zip_with_symlinks<W>(
+    zip: &mut ZipWriter<W>,
+    src_dir: &Path,
+    dest_dir: &Path,
+    opts: zip::write::FileOptions<'static, ()>,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + std::io::Seek,
+{
+    for entry in walkdir::WalkDir::new(src_dir).follow_links(false) {
+        let entry = entry?;
+        let path = entry.path();
+        let rel_path = path.strip_prefix(src_dir).unwrap();
+        let zip_path = dest_dir.join(rel_path);
+        let zip_name = zip_path.to_string_lossy().replace('\\', "/");
+
+        if entry.file_type().is_dir() {
+            zip.add_directory(zip_name, entry_options(opts, &entry.metadata()?))?;
+            continue;
+        }
+        if entry.file_type().is_symlink() {
+            let target = fs::read_link(path).context("Failed to read symlink while zipping")?;
+            zip.add_symlink(zip_name, target.to_string_lossy().replace('\\', "/"), opts)?;
+        } else if entry.file_type().is_file() {
+            let file_opts = entry_options(opts, &entry.metadata()?);
+            zip.start_file(zip_name, file_opts)?;
             let data = fs::read(path).context("Failed to read file while zipping")?;
             zip.write_all(&data)?;
+        } else {
+            continue;
         }
         if let Some(pb) = progress {
             pb.inc(1);
@@ -1478,6 +2946,7 @@ fn add_dir_to_zip_no_follow_skip_parents<W>(
     src_dir: &Path,
     dest_dir: &Path,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
@@ -1491,41 +2960,31 @@ where
 
         if entry.file_type().is_dir() {
             if !rel_path.as_os_str().is_empty() {
-                zip.add_directory(zip_path.to_string_lossy().as_ref(), opts)?;
+                zip.add_directory(
+                    zip_path.to_string_lossy().as_ref(),
+                    entry_options(opts, &entry.metadata()?),
+                )?;
             }
             continue;
         }
 
-        if !entry.file_type().is_file() && !entry.file_type().is_symlink() {
+        if entry.file_type().is_symlink() {
+            add_symlink_to_zip(zip, path, &zip_path, opts, dereferenced, progress)?;
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
             continue;
         }
 
-        let file_opts = {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let metadata = entry.metadata()?;
-                let permissions = metadata.permissions();
-                let mode = permissions.mode();
-                opts.unix_permissions(mode)
-            }
-            #[cfg(not(unix))]
-            {
-                opts
-            }
-        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
 
-        zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
+        let file_opts = entry_options(opts, &entry.metadata()?);
 
-        if entry.file_type().is_symlink() {
-            if let Ok(target) = fs::read_link(path) {
-                let target_str = target.to_string_lossy();
-                zip.write_all(target_str.as_bytes())?;
-            }
-        } else {
-            let data = fs::read(path).context("Failed to read file while zipping")?;
-            zip.write_all(&data)?;
-        }
+        zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
+        let data = fs::read(path).context("Failed to read file while zipping")?;
+        zip.write_all(&data)?;
         if let Some(pb) = progress {
             pb.inc(1);
         }
@@ -1533,18 +2992,56 @@ where
     Ok(())
 }
 
+/// Directories that only hold tool caches, skipped anywhere in the app directory
+const JUNK_DIRS: &[&str] = &[".turbo", ".nyc_output", ".parcel-cache"];
+
+/// Caches and build leftovers that routinely bloat payloads without being needed at runtime:
+/// `node_modules/.cache`, `.next/cache`, `.turbo`, a top-level `coverage/`, `*.tsbuildinfo`, ...
+fn is_build_junk(rel_path: &Path, is_dir: bool) -> bool {
+    let components: Vec<&std::ffi::OsStr> = rel_path.iter().collect();
+    let Some(last) = components.last() else {
+        return false;
+    };
+    if is_dir {
+        let name = last.to_string_lossy();
+        let parent = components
+            .len()
+            .checked_sub(2)
+            .map(|i| components[i].to_string_lossy());
+        JUNK_DIRS.contains(&name.as_ref())
+            || (name == "coverage" && components.len() == 1)
+            || (name == "cache" && parent.as_deref() == Some(".next"))
+            || (name == ".cache" && parent.as_deref() == Some("node_modules"))
+    } else {
+        let name = last.to_string_lossy();
+        name.ends_with(".tsbuildinfo") || name == ".eslintcache"
+    }
+}
+
 /// Add directory to zip, excluding node_modules from the source directory
 fn add_dir_to_zip_excluding_node_modules<W>(
     zip: &mut ZipWriter<W>,
     src_dir: &Path,
     dest_dir: &Path,
+    skip_junk: bool,
     opts: zip::write::FileOptions<'static, ()>,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
     W: Write + Read + std::io::Seek,
 {
-    for entry in walkdir::WalkDir::new(src_dir).follow_links(true) {
+    let walker = walkdir::WalkDir::new(src_dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|entry| {
+            let rel_path = entry.path().strip_prefix(src_dir).unwrap_or(entry.path());
+            let junk = skip_junk && is_build_junk(rel_path, entry.file_type().is_dir());
+            if junk {
+                debug!("Skipping build leftover {}", entry.path().display());
+            }
+            !junk
+        });
+    for entry in walker {
         let entry = entry?;
         let path = entry.path();
         let rel_path = path.strip_prefix(src_dir).unwrap();
@@ -1555,7 +3052,10 @@ where
         }
 
         if entry.file_type().is_dir() {
-            zip.add_directory(zip_path.to_string_lossy().as_ref(), opts)?;
+            zip.add_directory(
+                zip_path.to_string_lossy().as_ref(),
+                entry_options(opts, &entry.metadata()?),
+            )?;
             continue;
         }
 
@@ -1563,23 +3063,20 @@ where
             continue;
         }
 
-        let file_opts = {
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let metadata = fs::metadata(path)?;
-                let permissions = metadata.permissions();
-                let mode = permissions.mode();
-                opts.unix_permissions(mode)
-            }
-            #[cfg(not(unix))]
-            {
-                opts
-            }
-        };
+        let file_opts = entry_options(opts, &fs::metadata(path)?);
 
-        zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
         let data = fs::read(path).context("Failed to read file while zipping")?;
+        if manifest::is_bundle_output(&data) {
+            warn!(
+                "Skipping {}: it is an executable built by banderole (a previous output?)",
+                path.display()
+            );
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
+            continue;
+        }
+        zip.start_file(zip_path.to_string_lossy().as_ref(), file_opts)?;
         zip.write_all(&data)?;
         if let Some(pb) = progress {
             pb.inc(1);
@@ -1594,13 +3091,14 @@ fn copy_workspace_package<W>(
     node_modules_path: &Path,
     package_name: &str,
     opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
     progress: Option<&ProgressBar>,
 ) -> Result<()>
 where
     W: Write + Read + std::io::Seek,
 {
     let dest_path = Path::new("app/node_modules").join(package_name);
-    let package_path = node_modules_path.join(package_name);
+    let package_path = installed_package_path(node_modules_path, package_name);
 
     if package_path.exists() {
         let target_path = if package_path.is_symlink() {
@@ -1621,7 +3119,14 @@ where
                     pb.length().unwrap_or(0) + count_files_in_dir(&target_path, false, false),
                 );
             }
-            add_dir_to_zip_no_follow_skip_parents(zip, &target_path, &dest_path, opts, progress)?;
+            add_dir_to_zip_no_follow_skip_parents(
+                zip,
+                &target_path,
+                &dest_path,
+                opts,
+                dereferenced,
+                progress,
+            )?;
             return Ok(());
         }
     }
@@ -1632,6 +3137,23 @@ where
     )
 }
 
+/// Where Node finds `package_name` from `node_modules_path`: there, or hoisted into the
+/// `node_modules` of an ancestor directory (e.g. a workspace member's dependency installed at the root)
+fn installed_package_path(node_modules_path: &Path, package_name: &str) -> PathBuf {
+    let own = node_modules_path.join(package_name);
+    if own.exists() {
+        return own;
+    }
+    node_modules_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::ancestors)
+        .skip(1)
+        .map(|dir| dir.join("node_modules").join(package_name))
+        .find(|candidate| candidate.exists())
+        .unwrap_or(own)
+}
+
 /// Resolve dependencies for regular workspaces (non-pnpm)
 fn resolve_workspace_dependencies(
     node_modules_path: &Path,
@@ -1650,61 +3172,446 @@ fn resolve_workspace_dependencies(
 
     resolved.insert(package_name.to_string());
 
-    let package_path = node_modules_path.join(package_name);
-    let package_json_path = if package_path.is_symlink() {
-        let target = fs::read_link(&package_path)?;
-        let target_path = if target.is_absolute() {
-            target
+    let package_path = installed_package_path(node_modules_path, package_name);
+    let names = workspace::dependency_names(package_path.clone(), || {
+        let package_json_path = if package_path.is_symlink() {
+            let target = fs::read_link(&package_path)?;
+            let target_path = if target.is_absolute() {
+                target
+            } else {
+                package_path.parent().unwrap().join(target).canonicalize()?
+            };
+            target_path.join("package.json")
         } else {
-            package_path.parent().unwrap().join(target).canonicalize()?
+            package_path.join("package.json")
         };
-        target_path.join("package.json")
-    } else {
-        package_path.join("package.json")
+        if !package_json_path.exists() {
+            return Ok(None); // Skip packages we can't find
+        }
+        fs::read_to_string(&package_json_path)
+            .context("Failed to read package.json")
+            .map(Some)
+    })?;
+    let Some(names) = names else {
+        return Ok(());
     };
-
-    if !package_json_path.exists() {
-        return Ok(()); // Skip packages we can't find
+    for dep_name in &names.required {
+        resolve_workspace_dependencies(node_modules_path, dep_name, resolved, depth + 1)?;
+    }
+    for dep_name in &names.if_installed {
+        if installed_package_path(node_modules_path, dep_name).exists() {
+            resolve_workspace_dependencies(node_modules_path, dep_name, resolved, depth + 1)?;
+        }
     }
 
-    let package_json_content =
-        fs::read_to_string(&package_json_path).context("Failed to read package.json")?;
+    Ok(())
+}
 
-    if let Ok(package_json) = serde_json::from_str::<Value>(&package_json_content) {
-        if let Some(deps) = package_json["dependencies"].as_object() {
-            for dep_name in deps.keys() {
-                resolve_workspace_dependencies(node_modules_path, dep_name, resolved, depth + 1)?;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if let Some(peer_deps) = package_json["peerDependencies"].as_object() {
-            for dep_name in peer_deps.keys() {
-                let dep_path = node_modules_path.join(dep_name);
-                if dep_path.exists() {
-                    resolve_workspace_dependencies(
-                        node_modules_path,
-                        dep_name,
-                        resolved,
-                        depth + 1,
-                    )?;
-                }
-            }
+    fn project_with(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
         }
+        dir
+    }
 
-        if let Some(optional_deps) = package_json["optionalDependencies"].as_object() {
-            for dep_name in optional_deps.keys() {
-                let dep_path = node_modules_path.join(dep_name);
-                if dep_path.exists() {
-                    resolve_workspace_dependencies(
-                        node_modules_path,
-                        dep_name,
-                        resolved,
-                        depth + 1,
-                    )?;
-                }
-            }
-        }
+    #[tokio::test]
+    async fn test_undefined_bundle_script() {
+        let package: Value = serde_json::json!({ "scripts": { "build": "tsc" } });
+        let err = run_bundle_scripts(Path::new("."), &package, &["prebundle".to_string()])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("\"prebundle\""));
+        run_bundle_scripts(Path::new("."), &package, &[])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_universal_scripts_run_once() {
+        let script = "echo run>> runs.txt";
+        let project = project_with(&[(
+            "package.json",
+            &serde_json::json!({ "scripts": { "prebundle": script } }).to_string(),
+        )]);
+        let package: Value =
+            serde_json::from_str(&fs::read_to_string(project.path().join("package.json")).unwrap())
+                .unwrap();
+        let scripts = ["prebundle".to_string()];
+        for slice in [Some(Slice::First), Some(Slice::Later)] {
+            run_slice_scripts(slice, project.path(), &package, &scripts)
+                .await
+                .unwrap();
+        }
+        let runs = fs::read_to_string(project.path().join("runs.txt")).unwrap();
+        assert_eq!(runs.lines().count(), 1);
+
+        run_slice_scripts(None, project.path(), &package, &scripts)
+            .await
+            .unwrap();
+        let runs = fs::read_to_string(project.path().join("runs.txt")).unwrap();
+        assert_eq!(runs.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_stale_build_source() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let project = dir.path();
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::create_dir_all(project.join("dist")).unwrap();
+        fs::write(project.join("src/index.ts"), "export {};").unwrap();
+        fs::write(project.join("dist/index.js"), "export {};").unwrap();
+        let set_modified = |path: &str, secs: u64| {
+            fs::File::options()
+                .write(true)
+                .open(project.join(path))
+                .unwrap()
+                .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                .unwrap();
+        };
+
+        set_modified("src/index.ts", 1_000);
+        set_modified("dist/index.js", 2_000);
+        assert_eq!(stale_build_source(project, &project.join("dist")), None);
+
+        set_modified("src/index.ts", 3_000);
+        assert_eq!(
+            stale_build_source(project, &project.join("dist")),
+            Some(project.join("src/index.ts"))
+        );
+        assert_eq!(stale_build_source(project, project), None);
+    }
+
+    #[test]
+    fn test_zip_time() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_001);
+        let zip = zip_time(time).unwrap();
+        assert_eq!((zip.year(), zip.month(), zip.day()), (2023, 11, 14));
+        assert_eq!((zip.hour(), zip.minute(), zip.second()), (22, 13, 20));
+        assert!(zip_time(std::time::UNIX_EPOCH).is_none());
+    }
+
+    #[test]
+    fn test_relative_to_source_dir() {
+        let project = Path::new("/work/app");
+        let source = Path::new("/work/app/dist");
+        let relative = |path| relative_to_source_dir(project, source, path);
+        assert_eq!(relative("dist/cli.js").as_deref(), Some("cli.js"));
+        assert_eq!(relative("./dist/bin/../cli.js").as_deref(), Some("cli.js"));
+        assert_eq!(relative("dist/../../other/cli.js"), None);
+        assert_eq!(relative("dist/../../../../../etc/passwd"), None);
+        assert_eq!(relative("src/cli.js"), None);
+    }
+
+    #[test]
+    fn test_link_stays_in_bundle() {
+        let link = Path::new("app/node_modules/.bin/tool");
+        assert!(link_stays_in_bundle(link, Path::new("../tool/cli.js")));
+        assert!(link_stays_in_bundle(
+            link,
+            Path::new("../../../node/bin/node")
+        ));
+        assert!(!link_stays_in_bundle(
+            link,
+            Path::new("../../../../etc/passwd")
+        ));
+        assert!(!link_stays_in_bundle(link, Path::new("/usr/bin/node")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_stored_as_links() {
+        let dir = project_with(&[("pkg/cli.js", "#!/usr/bin/env node\n")]);
+        std::os::unix::fs::symlink("../pkg/cli.js", dir.path().join("tool")).unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", dir.path().join("outside")).unwrap();
+
+        let mut data = Vec::new();
+        let mut dereferenced = 0;
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut data));
+            add_dir_to_zip_no_follow(
+                &mut zip,
+                dir.path(),
+                Path::new("app/bin"),
+                zip::write::FileOptions::default(),
+                &mut dereferenced,
+                None,
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let mut tool = archive.by_name("app/bin/tool").unwrap();
+        assert!(tool.is_symlink());
+        let mut target = String::new();
+        tool.read_to_string(&mut target).unwrap();
+        assert_eq!(target, "../pkg/cli.js");
+        drop(tool);
+        if Path::new("/etc/hostname").is_file() {
+            assert!(!archive.by_name("app/bin/outside").unwrap().is_symlink());
+            assert_eq!(dereferenced, 1);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pnpm_layout_keeps_store_links() {
+        use std::os::unix::fs::symlink;
+        let dir = project_with(&[
+            (
+                "package.json",
+                r#"{"dependencies":{"lodash":"^4","a":"1.0.0"},"devDependencies":{"jest":"29"}}"#,
+            ),
+            (
+                "node_modules/.pnpm/lodash@4.17.21/node_modules/lodash/package.json",
+                r#"{"version":"4.17.21"}"#,
+            ),
+            (
+                "node_modules/.pnpm/lodash@3.10.1/node_modules/lodash/package.json",
+                r#"{"version":"3.10.1"}"#,
+            ),
+            ("node_modules/.pnpm/a@1.0.0/node_modules/a/index.js", ""),
+            (
+                "node_modules/.pnpm/jest@29.0.0/node_modules/jest/index.js",
+                "",
+            ),
+        ]);
+        let modules = dir.path().join("node_modules");
+        for (target, link) in [
+            (
+                "../../lodash@3.10.1/node_modules/lodash",
+                ".pnpm/a@1.0.0/node_modules/lodash",
+            ),
+            (
+                "../lodash@4.17.21/node_modules/lodash",
+                ".pnpm/node_modules/lodash",
+            ),
+            (
+                "../jest@29.0.0/node_modules/jest",
+                ".pnpm/node_modules/jest",
+            ),
+            (".pnpm/lodash@4.17.21/node_modules/lodash", "lodash"),
+            (".pnpm/a@1.0.0/node_modules/a", "a"),
+            (".pnpm/jest@29.0.0/node_modules/jest", "jest"),
+        ] {
+            fs::create_dir_all(modules.join(link).parent().unwrap()).unwrap();
+            symlink(target, modules.join(link)).unwrap();
+        }
+
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut data));
+            bundle_pnpm_virtual_store(&mut zip, dir.path(), Default::default(), &mut 0, None)
+                .unwrap();
+            zip.finish().unwrap();
+        }
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let names: Vec<String> = archive.file_names().map(String::from).collect();
+        let has = |name: &str| names.iter().any(|n| n == name);
+        assert!(has(
+            "app/node_modules/.pnpm/lodash@4.17.21/node_modules/lodash/package.json"
+        ));
+        assert!(has(
+            "app/node_modules/.pnpm/lodash@3.10.1/node_modules/lodash/package.json"
+        ));
+        assert!(has("app/node_modules/.pnpm/node_modules/lodash"));
+        assert!(!names.iter().any(|n| n.contains("jest")));
+
+        let mut link = archive
+            .by_name("app/node_modules/.pnpm/a@1.0.0/node_modules/lodash")
+            .unwrap();
+        assert!(link.is_symlink());
+        let mut target = String::new();
+        link.read_to_string(&mut target).unwrap();
+        assert_eq!(target, "../../lodash@3.10.1/node_modules/lodash");
+        drop(link);
+        assert!(archive.by_name("app/node_modules/a").unwrap().is_symlink());
+    }
+
+    #[test]
+    fn test_yarn_pnp_files_are_bundled_next_to_the_app() {
+        let dir = project_with(&[
+            ("package.json", "{}"),
+            (".pnp.cjs", "// ./.yarn/cache/is-odd-npm-3.0.1.zip"),
+            (".yarnrc.yml", "nodeLinker: pnp\n"),
+            (".yarn/cache/is-odd-npm-3.0.1.zip", "zip"),
+            ("dist/index.js", ""),
+        ]);
+        assert!(is_yarn_pnp(dir.path()));
+
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut data));
+            let source_dir = dir.path().join("dist");
+            bundle_yarn_pnp(
+                &mut zip,
+                dir.path(),
+                &source_dir,
+                Default::default(),
+                &mut 0,
+                None,
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+        let archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert!(names.contains(&"app/.pnp.cjs"));
+        assert!(names.contains(&"app/.yarnrc.yml"));
+        assert!(names.contains(&"app/.yarn/cache/is-odd-npm-3.0.1.zip"));
+
+        fs::write(
+            dir.path().join(".pnp.cjs"),
+            "// ../../.yarn/berry/cache/is-odd-npm-3.0.1-10c0.zip",
+        )
+        .unwrap();
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let err = bundle_yarn_pnp(
+            &mut zip,
+            dir.path(),
+            dir.path(),
+            Default::default(),
+            &mut 0,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("enableGlobalCache"));
+    }
+
+    #[test]
+    fn test_build_junk() {
+        assert!(is_build_junk(Path::new(".turbo"), true));
+        assert!(is_build_junk(Path::new("coverage"), true));
+        assert!(!is_build_junk(Path::new("src/coverage"), true));
+        assert!(is_build_junk(Path::new("web/.next/cache"), true));
+        assert!(!is_build_junk(Path::new("web/.next/server"), true));
+        assert!(is_build_junk(Path::new("lib/node_modules/.cache"), true));
+        assert!(is_build_junk(Path::new("tsconfig.tsbuildinfo"), false));
+        assert!(!is_build_junk(Path::new("coverage"), false));
+        assert!(!is_build_junk(Path::new("dist/index.js"), false));
+    }
+
+    #[test]
+    fn test_npm_cli_in_unix_and_windows_archives() {
+        let runtime = tempfile::TempDir::new().unwrap();
+        assert!(npm_cli(runtime.path()).is_err());
+        let windows = runtime.path().join("node_modules/npm/bin");
+        fs::create_dir_all(&windows).unwrap();
+        fs::write(windows.join("npm-cli.js"), "").unwrap();
+        assert_eq!(
+            npm_cli(runtime.path()).unwrap(),
+            "node_modules/npm/bin/npm-cli.js"
+        );
+        let unix = runtime.path().join("lib/node_modules/npm/bin");
+        fs::create_dir_all(&unix).unwrap();
+        fs::write(unix.join("npm-cli.js"), "").unwrap();
+        assert_eq!(
+            npm_cli(runtime.path()).unwrap(),
+            "lib/node_modules/npm/bin/npm-cli.js"
+        );
     }
 
-    Ok(())
+    #[test]
+    fn test_extraction_dir() {
+        assert_eq!(
+            extraction_dir("@acme/server", "1.2.0+build.7").unwrap(),
+            "acme-server/1.2.0+build.7"
+        );
+        assert_eq!(extraction_dir("!!!", "").unwrap(), "app/unversioned");
+        assert_eq!(
+            extraction_dir("@acme/deps", "1.0.0").unwrap(),
+            "acme-deps/1.0.0"
+        );
+        for reserved in ["runtimes", "deps", "ICU", "user-1000"] {
+            assert!(extraction_dir(reserved, "1.0.0").is_err(), "{reserved}");
+        }
+    }
+
+    #[test]
+    fn test_node_version_spec_falls_back_to_engines() {
+        let project = project_with(&[
+            (
+                "package.json",
+                r#"{"workspaces":["packages/*"],"engines":{"node":">=18 <21"}}"#,
+            ),
+            ("packages/app/package.json", r#"{"name":"app"}"#),
+        ]);
+        let app = project.path().join("packages/app");
+        assert_eq!(find_node_version_spec(&app).unwrap(), ">=18 <21");
+
+        fs::write(project.path().join(".nvmrc"), "v20.11.1\n").unwrap();
+        assert_eq!(find_node_version_spec(&app).unwrap(), "20.11.1");
+    }
+
+    #[test]
+    fn test_entry_script_prefers_bin_over_library_main() {
+        let dir = project_with(&[
+            ("lib/index.js", "module.exports = {};"),
+            ("bin/cli.js", "#!/usr/bin/env node\nrequire('../lib');"),
+        ]);
+        let package_json = serde_json::json!({
+            "name": "@scope/tool",
+            "main": "lib/index.js",
+            "bin": "./bin/cli.js"
+        });
+
+        let entry = resolve_entry_script(dir.path(), &package_json, None).unwrap();
+        assert_eq!(entry.as_deref(), Some("./bin/cli.js"));
+    }
+
+    #[test]
+    fn test_entry_script_keeps_main_without_bin() {
+        let dir = project_with(&[("server.js", "console.log('hi');")]);
+        let package_json = serde_json::json!({ "name": "srv", "main": "server.js" });
+
+        let entry = resolve_entry_script(dir.path(), &package_json, None).unwrap();
+        assert_eq!(entry.as_deref(), Some("server.js"));
+    }
+
+    #[test]
+    fn test_entry_script_multiple_bins() {
+        let dir = project_with(&[("a.js", ""), ("b.js", "")]);
+        let package_json = serde_json::json!({
+            "name": "tool",
+            "bin": { "tool-a": "a.js", "tool-b": "b.js" }
+        });
+
+        assert!(resolve_entry_script(dir.path(), &package_json, None).is_err());
+        let entry = resolve_entry_script(dir.path(), &package_json, Some("tool-b")).unwrap();
+        assert_eq!(entry.as_deref(), Some("b.js"));
+        assert!(resolve_entry_script(dir.path(), &package_json, Some("missing")).is_err());
+
+        let package_json = serde_json::json!({
+            "name": "tool",
+            "bin": { "tool": "a.js", "tool-b": "b.js" }
+        });
+        let entry = resolve_entry_script(dir.path(), &package_json, None).unwrap();
+        assert_eq!(entry.as_deref(), Some("a.js"));
+    }
+
+    #[test]
+    fn test_entry_script_esm_exports_and_probing() {
+        let dir = project_with(&[
+            ("dist/index.mjs", "export {};"),
+            ("dist/index.cjs", "module.exports = {};"),
+            ("src/cli.mjs", "console.log('cli');"),
+        ]);
+        let package_json = serde_json::json!({
+            "name": "esm-app",
+            "type": "module",
+            "exports": { ".": { "import": "./dist/index.mjs", "require": "./dist/index.cjs" } }
+        });
+        let entry = resolve_entry_script(dir.path(), &package_json, None).unwrap();
+        assert_eq!(entry.as_deref(), Some("dist/index.mjs"));
+
+        let package_json = serde_json::json!({ "name": "esm-app", "main": "src/cli" });
+        let entry = resolve_entry_script(dir.path(), &package_json, None).unwrap();
+        assert_eq!(entry.as_deref(), Some("src/cli.mjs"));
+    }
 }
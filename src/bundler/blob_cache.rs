@@ -0,0 +1,361 @@
+//! Compress the payload's entries, reusing deflated data from earlier bundles.
+//!
+//! The payload is assembled uncompressed and deflated here in one pass. Each file's deflate
+//! stream is stored in banderole's cache under the SHA-256 of its contents, so re-bundling a
+//! project where only a few files changed compresses just those; everything else is copied from
+//! the cache once it inflates to the expected size and CRC-32. Blobs unused for [`MAX_AGE`] are
+//! removed after each bundle. The uncompressed payload is spooled to a temporary file first, so
+//! only the compressed one is held in memory.
+
+use anyhow::{Context, Result};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, CrcWriter};
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, BufReader, Cursor, Read, Seek, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use zip::{ZipArchive, ZipWriter};
+
+/// Deflate level of payload entries
+pub const LEVEL: u32 = 8;
+
+/// Files smaller than this compress faster than a cache lookup
+const MIN_CACHED_SIZE: u64 = 4096;
+
+/// Blobs not used by any bundle for this long are deleted
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Outcome of [`compress`]
+#[derive(Debug, Default, PartialEq)]
+pub struct CompressStats {
+    /// Entries whose deflated data came from the cache
+    pub reused: usize,
+    /// Entries deflated by this bundle
+    pub compressed: usize,
+}
+
+/// Persistent store of deflate streams keyed by content hash
+pub struct BlobCache {
+    dir: PathBuf,
+}
+
+impl BlobCache {
+    /// The cache in banderole's persistent cache directory
+    pub fn open() -> Result<Self> {
+        let dir = crate::cache::persistent_cache_dir()?.join("archive-blobs");
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, digest: &str) -> PathBuf {
+        self.dir
+            .join(&digest[..2])
+            .join(format!("{digest}-{LEVEL}.deflate"))
+    }
+
+    /// The blob stored for `digest`, unless it does not inflate to `size` bytes with `crc32`
+    fn get(&self, digest: &str, size: u64, crc32: u32) -> Option<Vec<u8>> {
+        let path = self.path(digest);
+        let data = fs::read(&path).ok()?;
+        let mut check = CrcWriter::new(io::sink());
+        let inflated = io::copy(&mut DeflateDecoder::new(data.as_slice()), &mut check);
+        if inflated.ok() != Some(size) || check.crc().sum() != crc32 {
+            debug!("Ignoring damaged compressed blob {}", path.display());
+            return None;
+        }
+        // Mark it as used, for expiry
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(data)
+    }
+
+    /// Store `blob` atomically, so concurrent bundles never read a partial one
+    fn put(&self, digest: &str, blob: &[u8]) {
+        let path = self.path(digest);
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+                fs::write(&temp, blob).and_then(|()| fs::rename(&temp, &path))
+            });
+        if let Err(e) = stored {
+            debug!("Could not cache compressed blob {}: {e}", path.display());
+        }
+    }
+
+    /// Delete blobs no bundle used for [`MAX_AGE`]
+    pub fn expire(&self) {
+        let Some(cutoff) = SystemTime::now().checked_sub(MAX_AGE) else {
+            return;
+        };
+        for entry in walkdir::WalkDir::new(&self.dir)
+            .min_depth(2)
+            .into_iter()
+            .flatten()
+        {
+            let expired = entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|modified| modified < cutoff);
+            if expired && entry.file_type().is_file() {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Deflate every stored file of the payload, taking the compressed data from `cache` when an
+/// earlier bundle already compressed the same contents
+pub fn compress(zip_data: Vec<u8>, cache: Option<&BlobCache>) -> Result<(Vec<u8>, CompressStats)> {
+    let mut spool = tempfile::tempfile().context("Failed to create a temporary payload file")?;
+    spool
+        .write_all(&zip_data)
+        .and_then(|()| spool.rewind())
+        .context("Failed to write the payload to a temporary file")?;
+    drop(zip_data);
+    let mut archive =
+        ZipArchive::new(BufReader::new(spool)).context("Failed to read bundle payload")?;
+    let mut stats = CompressStats::default();
+    let mut contents = Vec::new();
+    let mut output = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut output));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_file()
+                || file.is_symlink()
+                || file.size() == 0
+                || file.compression() != zip::CompressionMethod::Stored
+            {
+                zip.raw_copy_file(file)?;
+                continue;
+            }
+            let entry = Entry {
+                name: file.name().to_string(),
+                crc32: file.crc32(),
+                size: file.size(),
+                modified: file.last_modified(),
+                unix_mode: file.unix_mode(),
+            };
+            drop(file);
+            contents.clear();
+            archive
+                .by_index(i)?
+                .read_to_end(&mut contents)
+                .with_context(|| format!("Failed to read {} from the payload", entry.name))?;
+
+            let cache = cache.filter(|_| entry.size >= MIN_CACHED_SIZE);
+            let digest = cache.map(|_| format!("{:x}", Sha256::digest(&contents)));
+            let cached = cache
+                .zip(digest.as_deref())
+                .and_then(|(c, d)| c.get(d, entry.size, entry.crc32));
+            let deflated = match cached {
+                Some(blob) => {
+                    stats.reused += 1;
+                    blob
+                }
+                None => {
+                    stats.compressed += 1;
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(LEVEL));
+                    encoder.write_all(&contents)?;
+                    let blob = encoder.finish()?;
+                    if let Some((cache, digest)) = cache.zip(digest.as_deref()) {
+                        cache.put(digest, &blob);
+                    }
+                    blob
+                }
+            };
+            match entry.single_file_zip(&deflated) {
+                Some(single) => {
+                    let mut single = ZipArchive::new(Cursor::new(single))
+                        .context("Failed to read a compressed payload entry")?;
+                    zip.raw_copy_file(single.by_index_raw(0)?)?;
+                }
+                // Beyond what a plain zip header holds: let the zip writer use zip64
+                None => {
+                    let mut options = zip::write::SimpleFileOptions::default()
+                        .compression_method(zip::CompressionMethod::Deflated)
+                        .compression_level(Some(LEVEL as i64))
+                        .large_file(true);
+                    if let Some(modified) = entry.modified {
+                        options = options.last_modified_time(modified);
+                    }
+                    if let Some(mode) = entry.unix_mode {
+                        options = options.unix_permissions(mode);
+                    }
+                    zip.start_file(&entry.name, options)?;
+                    zip.write_all(&contents)?;
+                }
+            }
+        }
+        zip.finish()?;
+    }
+    Ok((output, stats))
+}
+
+/// Metadata of a stored payload file
+struct Entry {
+    name: String,
+    crc32: u32,
+    size: u64,
+    modified: Option<zip::DateTime>,
+    unix_mode: Option<u32>,
+}
+
+impl Entry {
+    /// A zip archive holding just this entry with `deflated` as its data, for the zip writer to
+    /// copy without compressing again; `None` when the sizes need zip64
+    fn single_file_zip(&self, deflated: &[u8]) -> Option<Vec<u8>> {
+        let compressed_size = u32::try_from(deflated.len()).ok()?;
+        let size = u32::try_from(self.size).ok()?;
+        let name = self.name.as_bytes();
+        let name_len = u16::try_from(name.len()).ok()?;
+        // UTF-8 names
+        let flags: u16 = if self.name.is_ascii() { 0 } else { 0x0800 };
+        let (time, date) = self.modified.map_or((0, 0x21), |modified| {
+            (modified.timepart(), modified.datepart())
+        });
+
+        let mut zip = Vec::with_capacity(deflated.len() + 2 * name.len() + 98);
+        let common = |zip: &mut Vec<u8>| {
+            zip.extend_from_slice(&20u16.to_le_bytes());
+            zip.extend_from_slice(&flags.to_le_bytes());
+            zip.extend_from_slice(&8u16.to_le_bytes());
+            zip.extend_from_slice(&time.to_le_bytes());
+            zip.extend_from_slice(&date.to_le_bytes());
+            zip.extend_from_slice(&self.crc32.to_le_bytes());
+            zip.extend_from_slice(&compressed_size.to_le_bytes());
+            zip.extend_from_slice(&size.to_le_bytes());
+            zip.extend_from_slice(&name_len.to_le_bytes());
+            zip.extend_from_slice(&0u16.to_le_bytes());
+        };
+        zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        common(&mut zip);
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(deflated);
+
+        let central_offset = zip.len();
+        zip.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        // Made by Unix when a mode is recorded, so readers pick up the permissions
+        let made_by: u16 = if self.unix_mode.is_some() { 0x0314 } else { 20 };
+        zip.extend_from_slice(&made_by.to_le_bytes());
+        common(&mut zip);
+        zip.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        zip.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        zip.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        zip.extend_from_slice(&(self.unix_mode.unwrap_or(0) << 16).to_le_bytes());
+        zip.extend_from_slice(&0u32.to_le_bytes()); // local header offset
+        zip.extend_from_slice(name);
+        let central_size = zip.len() - central_offset;
+
+        zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]); // disk numbers
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&(central_size as u32).to_le_bytes());
+        zip.extend_from_slice(&u32::try_from(central_offset).ok()?.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        Some(zip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored_payload() -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored)
+                .last_modified_time(
+                    zip::DateTime::from_date_and_time(2024, 5, 1, 12, 0, 0).unwrap(),
+                );
+            zip.add_directory("app/", opts).unwrap();
+            zip.start_file("app/index.js", opts.unix_permissions(0o755))
+                .unwrap();
+            zip.write_all(&b"console.log('hello');\n".repeat(500))
+                .unwrap();
+            zip.start_file("app/ünïcode.txt", opts.unix_permissions(0o644))
+                .unwrap();
+            zip.write_all(b"small").unwrap();
+            zip.finish().unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_reuses_cached_blobs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = BlobCache {
+            dir: dir.path().to_path_buf(),
+        };
+        let (first, stats) = compress(stored_payload(), Some(&cache)).unwrap();
+        assert_eq!(
+            stats,
+            CompressStats {
+                reused: 0,
+                compressed: 2
+            }
+        );
+        let (second, stats) = compress(stored_payload(), Some(&cache)).unwrap();
+        assert_eq!(
+            stats,
+            CompressStats {
+                reused: 1,
+                compressed: 1
+            }
+        );
+        assert_eq!(first, second);
+
+        let mut archive = ZipArchive::new(Cursor::new(second)).unwrap();
+        let mut file = archive.by_name("app/index.js").unwrap();
+        assert_eq!(file.compression(), zip::CompressionMethod::Deflated);
+        assert_eq!(file.unix_mode().unwrap() & 0o777, 0o755);
+        assert_eq!(file.last_modified().unwrap().year(), 2024);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "console.log('hello');\n".repeat(500));
+        drop(file);
+        let mut file = archive.by_name("app/ünïcode.txt").unwrap();
+        contents.clear();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "small");
+    }
+
+    #[test]
+    fn test_damaged_blobs_are_compressed_again() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = BlobCache {
+            dir: dir.path().to_path_buf(),
+        };
+        let (first, _) = compress(stored_payload(), Some(&cache)).unwrap();
+        let digest = format!(
+            "{:x}",
+            Sha256::digest("console.log('hello');\n".repeat(500))
+        );
+        let mut other = DeflateEncoder::new(Vec::new(), Compression::new(LEVEL));
+        other
+            .write_all(&b"console.log('HELLO');\n".repeat(500))
+            .unwrap();
+        fs::write(cache.path(&digest), other.finish().unwrap()).unwrap();
+
+        let (second, stats) = compress(stored_payload(), Some(&cache)).unwrap();
+        assert_eq!(
+            stats,
+            CompressStats {
+                reused: 0,
+                compressed: 2
+            }
+        );
+        assert_eq!(first, second);
+        assert!(cache.get(&digest, 11000, 0).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! Store identical files once.
+//!
+//! pnpm's `.pnpm` directory and flattened monorepos ship many byte-identical files (licenses,
+//! duplicated package versions, hard links into the store). Every duplicate after the first
+//! becomes a second central-directory entry pointing at the first one's compressed data, so
+//! ordinary zip readers still see every file while the data is stored once. The runner
+//! recognizes entries sharing their data and recreates them as hard links. Files the app may
+//! write to (`mutablePaths`) keep their own copy, since writing one hard link changes them all.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use zip::{ZipArchive, ZipWriter};
+
+/// Outcome of [`deduplicate`]
+#[derive(Debug, Default, PartialEq)]
+pub struct DedupStats {
+    /// Entries that now reference an earlier entry's data
+    pub files: usize,
+    /// Compressed bytes no longer stored
+    pub bytes: u64,
+}
+
+/// Rewrite the payload so files with identical contents and permissions share one copy of their
+/// data. Duplicates keep the modification time of the first copy.
+pub fn deduplicate(zip_data: Vec<u8>, mutable_paths: &[String]) -> Result<(Vec<u8>, DedupStats)> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+    let mut stats = DedupStats::default();
+    let mut first_copy: HashMap<([u8; 32], Option<u32>), String> = HashMap::new();
+    let mut buffer = Vec::new();
+    let mut output = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut output));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_file()
+                || file.is_symlink()
+                || file.size() == 0
+                || is_mutable(mutable_paths, file.name())
+            {
+                zip.raw_copy_file(file)?;
+                continue;
+            }
+            let (name, mode, compressed_size) = (
+                file.name().to_string(),
+                file.unix_mode(),
+                file.compressed_size(),
+            );
+            drop(file);
+            buffer.clear();
+            archive
+                .by_index(i)?
+                .read_to_end(&mut buffer)
+                .with_context(|| format!("Failed to read {name} from the payload"))?;
+            let key = (Sha256::digest(&buffer).into(), mode);
+            match first_copy.get(&key) {
+                Some(original) => {
+                    stats.files += 1;
+                    stats.bytes += compressed_size;
+                    zip.shallow_copy_file(original, &name)?;
+                }
+                None => {
+                    zip.raw_copy_file(archive.by_index_raw(i)?)?;
+                    first_copy.insert(key, name);
+                }
+            }
+        }
+        zip.finish()?;
+    }
+    Ok((output, stats))
+}
+
+/// Whether the payload entry `name` falls under a `mutablePaths` glob (relative to `app/`)
+fn is_mutable(mutable_paths: &[String], name: &str) -> bool {
+    name.strip_prefix("app/").is_some_and(|path| {
+        mutable_paths
+            .iter()
+            .any(|pattern| crate::glob::covers(pattern, path))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_identical_files_share_data() {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default().unix_permissions(0o644);
+            let content = "module.exports = 42;\n".repeat(100);
+            for (name, content, opts) in [
+                (
+                    "app/node_modules/.pnpm/a@1/index.js",
+                    content.as_str(),
+                    opts,
+                ),
+                (
+                    "app/node_modules/.pnpm/b@1/index.js",
+                    content.as_str(),
+                    opts,
+                ),
+                ("app/node_modules/.pnpm/c@1/other.js", "other", opts),
+                (
+                    "app/node_modules/.pnpm/d@1/cli.js",
+                    content.as_str(),
+                    opts.unix_permissions(0o755),
+                ),
+                ("app/empty-a", "", opts),
+                ("app/empty-b", "", opts),
+                ("app/data/state.json", content.as_str(), opts),
+            ] {
+                zip.start_file(name, opts).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let original_len = data.len();
+
+        let (deduplicated, stats) = deduplicate(data, &["data".to_string()]).unwrap();
+        assert_eq!(stats.files, 1);
+        assert!(deduplicated.len() < original_len);
+
+        let mut archive = ZipArchive::new(Cursor::new(deduplicated)).unwrap();
+        assert_eq!(archive.len(), 7);
+        let data_start =
+            |archive: &mut ZipArchive<_>, name: &str| archive.by_name(name).unwrap().data_start();
+        assert_eq!(
+            data_start(&mut archive, "app/node_modules/.pnpm/a@1/index.js"),
+            data_start(&mut archive, "app/node_modules/.pnpm/b@1/index.js")
+        );
+        // Mutable files are never shared
+        assert_ne!(
+            data_start(&mut archive, "app/node_modules/.pnpm/a@1/index.js"),
+            data_start(&mut archive, "app/data/state.json")
+        );
+        // Different permissions keep their own copy
+        assert_ne!(
+            data_start(&mut archive, "app/node_modules/.pnpm/a@1/index.js"),
+            data_start(&mut archive, "app/node_modules/.pnpm/d@1/cli.js")
+        );
+        let mut content = String::new();
+        archive
+            .by_name("app/node_modules/.pnpm/b@1/index.js")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "module.exports = 42;\n".repeat(100));
+    }
+}
@@ -0,0 +1,188 @@
+//! Bundle lifecycle hooks (`hooks` configuration).
+//!
+//! Commands listed for an event run through the shell from the project directory; plugins are
+//! programs run for every event with its name as the only argument. Both get the bundle context
+//! as JSON on stdin and the event in `BANDEROLE_HOOK`. A hook exiting with an error aborts the
+//! bundle. `postArchive` hooks may rewrite the archive in place (`BANDEROLE_ARCHIVE`), and
+//! `postBuild` hooks the executable (`BANDEROLE_OUTPUT`), e.g. to sign it.
+
+use crate::config::HooksConfig;
+use crate::manifest::BundleManifest;
+use crate::output;
+use anyhow::{bail, Context, Result};
+use log::info;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Points of the bundle the hooks run at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HookEvent {
+    /// The runtime is resolved; nothing has been collected yet
+    PreBundle,
+    /// The payload archive is complete, before the manifest is computed from it
+    PostArchive,
+    /// The manifest is known; the runner is about to be compiled
+    PreBuild,
+    /// The executable has been written
+    PostBuild,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreBundle => "preBundle",
+            HookEvent::PostArchive => "postArchive",
+            HookEvent::PreBuild => "preBuild",
+            HookEvent::PostBuild => "postBuild",
+        }
+    }
+}
+
+/// What a hook learns about the bundle, as JSON on its stdin
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookContext<'a> {
+    pub event: HookEvent,
+    pub project: &'a Path,
+    /// Path the executable is (or will be) written to
+    pub output: &'a Path,
+    pub app_name: &'a str,
+    pub app_version: &'a str,
+    pub platform: String,
+    pub runtime: &'a str,
+    pub runtime_version: &'a str,
+    /// The payload zip (`postArchive` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<&'a Path>,
+    /// Every bundled file with its SHA-256 (`preBuild` and `postBuild`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<&'a BundleManifest>,
+    /// Executable size in bytes (`postBuild` only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl HooksConfig {
+    /// Whether anything runs for `event`
+    pub fn handles(&self, event: HookEvent) -> bool {
+        !self.plugins.is_empty() || !self.commands(event).is_empty()
+    }
+
+    fn commands(&self, event: HookEvent) -> &[String] {
+        match event {
+            HookEvent::PreBundle => &self.pre_bundle,
+            HookEvent::PostArchive => &self.post_archive,
+            HookEvent::PreBuild => &self.pre_build,
+            HookEvent::PostBuild => &self.post_build,
+        }
+    }
+}
+
+/// Run the hook commands and plugins configured for `context.event`, in order
+pub fn run(hooks: &HooksConfig, context: &HookContext) -> Result<()> {
+    if !hooks.handles(context.event) {
+        return Ok(());
+    }
+    let event = context.event.name();
+    let json = serde_json::to_vec(context).context("Failed to serialize the hook context")?;
+    for command in hooks.commands(context.event) {
+        info!("Running {event} hook `{command}`");
+        let shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C").arg(command);
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c").arg(command);
+            shell
+        };
+        run_one(shell, command, context, &json)?;
+    }
+    for plugin in &hooks.plugins {
+        let name = plugin.display().to_string();
+        info!("Running {event} hook of plugin {name}");
+        let mut program = Command::new(context.project.join(plugin));
+        program.arg(event);
+        run_one(program, &name, context, &json)?;
+    }
+    Ok(())
+}
+
+fn run_one(mut command: Command, name: &str, context: &HookContext, json: &[u8]) -> Result<()> {
+    command
+        .current_dir(context.project)
+        .env("BANDEROLE_HOOK", context.event.name())
+        .env("BANDEROLE_OUTPUT", context.output)
+        .stdin(Stdio::piped());
+    if let Some(archive) = context.archive {
+        command.env("BANDEROLE_ARCHIVE", archive);
+    }
+    if output::json() {
+        // Keep stdout free for JSON events
+        command.stdout(std::io::stderr());
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run {} hook `{name}`", context.event.name()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks that ignore the context may exit without reading it
+        let _ = stdin.write_all(json);
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{} hook `{name}` failed ({status})", context.event.name());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_hooks_receive_context() {
+        let project = tempfile::TempDir::new().unwrap();
+        let plugin = project.path().join("plugin.sh");
+        fs::write(&plugin, "#!/bin/sh\necho \"$1\" >> plugin.log\n").unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+        let hooks = HooksConfig {
+            pre_build: vec!["cat > context.json && test \"$BANDEROLE_HOOK\" = preBuild".into()],
+            post_build: vec!["exit 3".into()],
+            plugins: vec!["plugin.sh".into()],
+            ..Default::default()
+        };
+        let context = |event| HookContext {
+            event,
+            project: project.path(),
+            output: Path::new("/tmp/app"),
+            app_name: "app",
+            app_version: "1.0.0",
+            platform: "linux-x64".into(),
+            runtime: "Node.js",
+            runtime_version: "22.17.1",
+            archive: None,
+            manifest: None,
+            size: None,
+        };
+
+        run(&hooks, &context(HookEvent::PreBuild)).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(project.path().join("context.json")).unwrap())
+                .unwrap();
+        assert_eq!(json["event"], "preBuild");
+        assert_eq!(json["appName"], "app");
+        assert!(json.get("archive").is_none());
+
+        let err = run(&hooks, &context(HookEvent::PostBuild)).unwrap_err();
+        assert!(err.to_string().contains("postBuild hook `exit 3` failed"));
+        assert_eq!(
+            fs::read_to_string(project.path().join("plugin.log")).unwrap(),
+            "preBuild\n"
+        );
+    }
+}
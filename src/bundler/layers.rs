@@ -0,0 +1,187 @@
+//! Split the finished payload into separately embedded archives: the app, its installed
+//! dependencies (`app/node_modules`) and the runtime.
+//!
+//! The runner extracts the dependency and runtime layers once per machine under their content
+//! digests and links them into every extraction using them, so an update that only changes app
+//! code unpacks just the app layer. Each layer is deduplicated on its own.
+
+use super::dedup;
+use anyhow::{Context, Result};
+use log::debug;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path};
+use zip::{ZipArchive, ZipWriter};
+
+/// Directory of the dependency layer inside the payload
+pub const DEPS_DIR: &str = "app/node_modules";
+
+/// The payload zips embedded into the runner
+#[derive(Debug)]
+pub struct Layers {
+    /// Everything not in another layer; always embedded
+    pub app: Vec<u8>,
+    /// `app/node_modules`, unless it is empty or links out of itself
+    pub deps: Option<Vec<u8>>,
+    /// The runtime directory, unless the runtime is downloaded by the runner
+    pub runtime: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layer {
+    App,
+    Deps,
+    Runtime,
+}
+
+/// Split `zip_data` by entry location; the runtime lives below `runtime_dir`. Files under
+/// `mutable_paths` are kept out of deduplication, as in the payload
+pub fn split(zip_data: Vec<u8>, runtime_dir: &str, mutable_paths: &[String]) -> Result<Layers> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+    let share_deps = deps_self_contained(&mut archive)?;
+    let layer_of = |name: &str| {
+        let name = name.trim_end_matches('/');
+        if in_dir(name, runtime_dir) {
+            Layer::Runtime
+        } else if share_deps && in_dir(name, DEPS_DIR) {
+            Layer::Deps
+        } else {
+            Layer::App
+        }
+    };
+
+    let mut outputs = [Vec::new(), Vec::new(), Vec::new()];
+    let mut counts = [0usize; 3];
+    {
+        let [app, deps, runtime] = &mut outputs;
+        let mut writers = [
+            ZipWriter::new(Cursor::new(app)),
+            ZipWriter::new(Cursor::new(deps)),
+            ZipWriter::new(Cursor::new(runtime)),
+        ];
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let index = layer_of(file.name()) as usize;
+            counts[index] += 1;
+            writers[index].raw_copy_file(file)?;
+        }
+        for writer in writers {
+            writer.finish()?;
+        }
+    }
+
+    let [app, deps, runtime] = outputs;
+    let finish = |zip: Vec<u8>, layer: Layer| -> Result<Option<Vec<u8>>> {
+        if counts[layer as usize] == 0 && layer != Layer::App {
+            return Ok(None);
+        }
+        // Copying entries one by one stored duplicates twice again
+        let (zip, _) = dedup::deduplicate(zip, mutable_paths)?;
+        Ok(Some(zip))
+    };
+    debug!(
+        "Payload layers: {} app, {} dependency and {} runtime entries",
+        counts[0], counts[1], counts[2]
+    );
+    Ok(Layers {
+        app: finish(app, Layer::App)?.unwrap_or_default(),
+        deps: finish(deps, Layer::Deps)?,
+        runtime: finish(runtime, Layer::Runtime)?,
+    })
+}
+
+fn in_dir(name: &str, dir: &str) -> bool {
+    name.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether every symlink in `app/node_modules` points inside it, so the directory still works
+/// when it is shared between extractions
+fn deps_self_contained(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Result<bool> {
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_symlink() || !in_dir(file.name(), DEPS_DIR) {
+            continue;
+        }
+        let name = file.name().to_string();
+        let mut target = String::new();
+        file.read_to_string(&mut target)
+            .with_context(|| format!("Failed to read the symlink {name}"))?;
+        let mut depth = Path::new(&name).components().count() - 1;
+        let escapes = Path::new(&target)
+            .components()
+            .any(|component| match component {
+                Component::Normal(_) => {
+                    depth += 1;
+                    false
+                }
+                Component::CurDir => false,
+                Component::ParentDir if depth > DEPS_DIR.split('/').count() => {
+                    depth -= 1;
+                    false
+                }
+                _ => true,
+            });
+        if escapes {
+            debug!("Keeping dependencies in the app layer: {name} links to {target}");
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn payload(link_target: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            for (name, contents) in [
+                ("app/index.js", "require('dep')"),
+                ("app/node_modules/dep/index.js", "module.exports = 1"),
+                ("app/node_modules/dep/LICENSE", "MIT"),
+                ("app/LICENSE", "MIT"),
+                ("node/bin/node", "ELF"),
+            ] {
+                zip.start_file(name, opts).unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            zip.add_symlink("app/node_modules/alias", link_target, opts)
+                .unwrap();
+            zip.finish().unwrap();
+        }
+        data
+    }
+
+    fn names(zip: &[u8]) -> Vec<String> {
+        let archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        archive.file_names().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_splits_app_dependencies_and_runtime() {
+        let layers = split(payload("dep"), "node", &[]).unwrap();
+        assert_eq!(names(&layers.app), ["app/index.js", "app/LICENSE"]);
+        assert_eq!(
+            names(layers.deps.as_deref().unwrap()),
+            [
+                "app/node_modules/dep/index.js",
+                "app/node_modules/dep/LICENSE",
+                "app/node_modules/alias"
+            ]
+        );
+        assert_eq!(names(layers.runtime.as_deref().unwrap()), ["node/bin/node"]);
+
+        // A link out of node_modules would dangle in the shared copy
+        let layers = split(payload("../lib"), "node", &[]).unwrap();
+        assert!(layers.deps.is_none());
+        assert_eq!(names(&layers.app).len(), 5);
+        let layers = split(payload("dep"), "bun", &[]).unwrap();
+        assert!(layers.runtime.is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//! Local packages: sibling workspace packages (`workspace:*`) and `file:`/`link:` dependencies.
+//!
+//! Package managers install them as symlinks to their source directory, outside of any
+//! `node_modules`. Bundling the link target as-is would drag in the package's own
+//! `node_modules` (full of links leaving the project), so local packages are copied the way
+//! `npm pack` would publish them and their dependencies are bundled next to the app's like any
+//! other package.
+
+use super::{entry_options, packlist};
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use log::{debug, info};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipWriter;
+
+/// Dependency fields whose packages must be present at runtime
+const RUNTIME_DEPENDENCY_FIELDS: &[&str] =
+    &["dependencies", "optionalDependencies", "peerDependencies"];
+
+/// Local packages the project depends on, directly or through other local packages
+#[derive(Debug, Default)]
+pub(super) struct LocalPackages {
+    /// Package name -> resolved source directory
+    pub packages: BTreeMap<String, PathBuf>,
+    /// Installed (non-local) packages the project or its local packages depend on
+    pub dependencies: HashSet<String>,
+}
+
+impl LocalPackages {
+    /// Collect the local packages reachable from the project's runtime dependencies
+    pub fn find(project_path: &Path) -> Result<Self> {
+        let mut found = Self::default();
+        let mut pending = vec![project_path.to_path_buf()];
+        let mut visited = BTreeSet::new();
+        // Installed packages only declared as peer or optional dependencies, and by whom
+        let mut peer_or_optional: BTreeMap<String, String> = BTreeMap::new();
+        let mut required = HashSet::new();
+        while let Some(dir) = pending.pop() {
+            if !visited.insert(dir.clone()) {
+                continue;
+            }
+            let manifest = read_manifest(&dir)?;
+            let declared_by = manifest["name"]
+                .as_str()
+                .unwrap_or("the project")
+                .to_string();
+            for (field, name, spec) in runtime_dependencies(&manifest) {
+                if field == "dependencies" {
+                    required.insert(name.clone());
+                } else {
+                    peer_or_optional
+                        .entry(name.clone())
+                        .or_insert_with(|| format!("{field} of {declared_by}"));
+                }
+                if found.packages.contains_key(&name) {
+                    continue;
+                }
+                match local_package_dir(&dir, &name, &spec) {
+                    Some(local) => {
+                        debug!("Local package {name} at {}", local.display());
+                        found.packages.insert(name, local.clone());
+                        pending.push(local);
+                    }
+                    None => {
+                        found.dependencies.insert(name);
+                    }
+                }
+            }
+        }
+        // A name can be both when an installed package was seen before the local one
+        let packages = &found.packages;
+        found
+            .dependencies
+            .retain(|name| !packages.contains_key(name));
+
+        // Bundled when installed, like Node would find them; say so, since they are easy to miss
+        let extra: Vec<String> = peer_or_optional
+            .into_iter()
+            .filter(|(name, _)| found.dependencies.contains(name) && !required.contains(name))
+            .map(|(name, declared)| format!("{name} ({declared})"))
+            .collect();
+        if !extra.is_empty() {
+            info!(
+                "Also bundling peer and optional dependencies, where installed: {}",
+                extra.join(", ")
+            );
+        }
+        Ok(found)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    /// Copy the published files of every local package into `app/node_modules/<name>`
+    pub fn add_to_zip<W>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        opts: zip::write::FileOptions<'static, ()>,
+        progress: Option<&ProgressBar>,
+    ) -> Result<()>
+    where
+        W: Write + Read + Seek,
+    {
+        for (name, dir) in &self.packages {
+            let files = packlist::packed_files(dir)
+                .with_context(|| format!("Failed to list the files of local package {name}"))?;
+            debug!("Bundling {} files of local package {name}", files.len());
+            if let Some(pb) = progress {
+                pb.inc_length(files.len() as u64);
+            }
+            let dest = Path::new("app/node_modules").join(name);
+            for relative in files {
+                let source = dir.join(&relative);
+                zip.start_file(
+                    dest.join(&relative).to_string_lossy().replace('\\', "/"),
+                    entry_options(opts, &fs::metadata(&source)?),
+                )?;
+                zip.write_all(
+                    &fs::read(&source)
+                        .with_context(|| format!("Failed to read {}", source.display()))?,
+                )?;
+                if let Some(pb) = progress {
+                    pb.inc(1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_manifest(dir: &Path) -> Result<Value> {
+    let path = dir.join("package.json");
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// `(field, name, version spec)` of every runtime dependency declared in `manifest`
+fn runtime_dependencies(manifest: &Value) -> Vec<(&'static str, String, String)> {
+    RUNTIME_DEPENDENCY_FIELDS
+        .iter()
+        .filter_map(|&field| Some((field, manifest[field].as_object()?)))
+        .flat_map(|(field, deps)| {
+            deps.iter().map(move |(name, spec)| {
+                (
+                    field,
+                    name.clone(),
+                    spec.as_str().unwrap_or_default().to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Source directory of dependency `name` of the package in `from`, when it is a local package.
+///
+/// Path specs (`file:`, `link:`, `portal:` or a bare relative path) point at it directly. Otherwise the package is looked
+/// up like Node does, in `node_modules` of `from` and its ancestors; it is local when the
+/// installed entry resolves to a directory outside of any `node_modules` (a workspace link).
+pub(super) fn local_package_dir(from: &Path, name: &str, spec: &str) -> Option<PathBuf> {
+    let path_spec = ["file:", "link:", "portal:"]
+        .iter()
+        .find_map(|protocol| spec.strip_prefix(protocol))
+        .or_else(|| {
+            ["./", "../", "/"]
+                .iter()
+                .any(|p| spec.starts_with(p))
+                .then_some(spec)
+        });
+    if let Some(relative) = path_spec {
+        let dir = from.join(relative).canonicalize().ok()?;
+        return dir.join("package.json").is_file().then_some(dir);
+    }
+
+    let installed = from
+        .ancestors()
+        .map(|dir| dir.join("node_modules").join(name))
+        .find(|candidate| fs::symlink_metadata(candidate).is_ok())?;
+    let dir = installed.canonicalize().ok()?;
+    let inside_node_modules = dir
+        .components()
+        .any(|component| component == Component::Normal("node_modules".as_ref()));
+    (!inside_node_modules && dir.join("package.json").is_file()).then_some(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_file_dependencies_are_followed_transitively() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "app/package.json",
+            r#"{"dependencies":{"@acme/shared":"file:../shared","lodash":"^4"}}"#,
+        );
+        write(
+            root,
+            "shared/package.json",
+            r#"{"dependencies":{"@acme/utils":"../utils","chalk":"^5"}}"#,
+        );
+        write(
+            root,
+            "utils/package.json",
+            r#"{"peerDependencies":{"react":"*"}}"#,
+        );
+
+        let locals = LocalPackages::find(&root.join("app")).unwrap();
+        assert_eq!(
+            locals.packages.keys().collect::<Vec<_>>(),
+            ["@acme/shared", "@acme/utils"]
+        );
+        assert_eq!(
+            locals.packages["@acme/utils"],
+            root.join("utils").canonicalize().unwrap()
+        );
+        let mut dependencies: Vec<_> = locals.dependencies.iter().cloned().collect();
+        dependencies.sort();
+        assert_eq!(dependencies, ["chalk", "lodash", "react"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_links_are_local() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "packages/app/package.json",
+            r#"{"dependencies":{"@acme/shared":"workspace:*","lodash":"^4"}}"#,
+        );
+        write(root, "packages/shared/package.json", "{}");
+        write(root, "node_modules/lodash/package.json", "{}");
+        fs::create_dir_all(root.join("node_modules/@acme")).unwrap();
+        std::os::unix::fs::symlink(
+            "../../packages/shared",
+            root.join("node_modules/@acme/shared"),
+        )
+        .unwrap();
+
+        let app = root.join("packages/app");
+        assert_eq!(
+            local_package_dir(&app, "@acme/shared", "workspace:*"),
+            Some(root.join("packages/shared").canonicalize().unwrap())
+        );
+        assert_eq!(local_package_dir(&app, "lodash", "^4"), None);
+        assert_eq!(local_package_dir(&app, "missing", "^1"), None);
+    }
+}
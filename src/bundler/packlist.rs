@@ -0,0 +1,390 @@
+//! The files `npm pack` would publish for a local package.
+//!
+//! Mirrors npm's rules: the `files` whitelist when present (nested `.npmignore` files still
+//! apply inside it), otherwise `.npmignore` or, without one, `.gitignore` in each directory.
+//! `package.json`, the readme, the license, `main` and `bin` are always published, and VCS
+//! metadata, lockfiles and editor leftovers never are.
+
+use super::package_bins;
+use crate::glob::glob_match;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Never published, whatever the package's own rules say
+const ALWAYS_IGNORED: &[&str] = &[
+    ".npmignore",
+    ".gitignore",
+    ".git",
+    ".svn",
+    ".hg",
+    "CVS",
+    ".npmrc",
+    ".DS_Store",
+    "._*",
+    ".*.swp",
+    "*.orig",
+    "npm-debug.log",
+    "/node_modules",
+    "/.lock-wscript",
+    "/.wafpickle-*",
+    "/build/config.gypi",
+    "/package-lock.json",
+    "/yarn.lock",
+    "/pnpm-lock.yaml",
+    "/archived-packages",
+];
+
+/// Root files published regardless of `files` and ignore rules (any extension, any case)
+const ALWAYS_INCLUDED: &[&str] = &["readme", "license", "licence", "copying"];
+
+/// One line of a gitignore-style file
+#[derive(Debug)]
+pub(super) struct Rule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path instead of the file name
+    anchored: bool,
+}
+
+impl Rule {
+    /// Parse a line; `None` for blank lines and comments
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let line = line.strip_prefix("./").unwrap_or(line);
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+        (!pattern.is_empty()).then(|| Self {
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Whether a match re-includes the path (`!pattern`)
+    pub fn negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Whether the rule applies to `path`, `/`-separated and relative to the rule's directory
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match(&self.pattern, path)
+        } else {
+            glob_match(&self.pattern, path.rsplit('/').next().unwrap_or(path))
+        }
+    }
+}
+
+fn read_rules(path: &Path) -> Result<Vec<Rule>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(content.lines().filter_map(Rule::parse).collect())
+}
+
+/// `path` relative to a package, as the `/`-separated string ignore rules match against
+fn slash_path(path: &Path) -> String {
+    path.iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Strip the `./` and `/` npm tolerates around paths in `main`, `bin` and `files`
+fn normalize(path: &str) -> &str {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    path.trim_end_matches('/')
+}
+
+/// What a package publishes
+struct PackRules {
+    /// [`ALWAYS_IGNORED`], parsed
+    defaults: Vec<Rule>,
+    /// `files` entries as whitelist rules; `None` publishes everything not ignored
+    files: Option<Vec<Rule>>,
+    /// `main` and `bin` targets, published even when ignored
+    required: BTreeSet<String>,
+    /// Ignore rules by the directory (relative to the package) whose file declared them
+    ignores: HashMap<String, Vec<Rule>>,
+}
+
+impl PackRules {
+    fn always_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.defaults.iter().any(|rule| rule.matches(path, is_dir))
+    }
+
+    fn always_included(&self, path: &str) -> bool {
+        if self.required.contains(path) || path == "package.json" {
+            return true;
+        }
+        !path.contains('/') && {
+            let name = path.to_ascii_lowercase();
+            let stem = name.split('.').next().unwrap_or(&name);
+            ALWAYS_INCLUDED.contains(&stem)
+        }
+    }
+
+    /// Whether a directory holds a file that must be published no matter the ignore rules
+    fn holds_required(&self, dir: &str) -> bool {
+        self.required
+            .iter()
+            .any(|path| path.starts_with(dir) && path[dir.len()..].starts_with('/'))
+    }
+
+    fn whitelisted(&self, path: &str) -> bool {
+        let Some(files) = &self.files else {
+            return true;
+        };
+        // An entry naming a directory publishes everything below it
+        let prefixes: Vec<&str> =
+            std::iter::successors(Some(path), |p| p.rfind('/').map(|i| &p[..i])).collect();
+        let mut listed = false;
+        for rule in files {
+            if prefixes
+                .iter()
+                .any(|prefix| glob_match(&rule.pattern, prefix))
+            {
+                listed = !rule.negated;
+            }
+        }
+        listed
+    }
+
+    fn ignored(&self, path: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        // Deeper ignore files take precedence, so they are applied last
+        let mut dirs: Vec<&String> = self.ignores.keys().collect();
+        dirs.sort_by_key(|dir| {
+            if dir.is_empty() {
+                0
+            } else {
+                dir.matches('/').count() + 1
+            }
+        });
+        for dir in dirs {
+            let relative = if dir.is_empty() {
+                path
+            } else {
+                match path
+                    .strip_prefix(dir.as_str())
+                    .and_then(|p| p.strip_prefix('/'))
+                {
+                    Some(relative) => relative,
+                    None => continue,
+                }
+            };
+            for rule in &self.ignores[dir] {
+                if rule.matches(relative, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+/// Files of the package in `package_dir` that `npm pack` would publish, relative to it
+pub(super) fn packed_files(package_dir: &Path) -> Result<Vec<PathBuf>> {
+    let manifest_path = package_dir.join("package.json");
+    let manifest: Value = serde_json::from_str(
+        &fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+
+    let files = manifest["files"].as_array().map(|entries| {
+        entries
+            .iter()
+            .filter_map(Value::as_str)
+            .filter_map(|entry| {
+                let (negated, entry) = match entry.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, entry),
+                };
+                let entry = normalize(entry);
+                (!entry.is_empty()).then(|| Rule {
+                    pattern: entry.to_string(),
+                    negated,
+                    dir_only: false,
+                    anchored: true,
+                })
+            })
+            .collect()
+    });
+    let mut required = BTreeSet::new();
+    if let Some(main) = manifest["main"].as_str() {
+        let main = normalize(main);
+        required.insert(main.to_string());
+        required.insert(format!("{main}.js"));
+        required.insert(format!("{main}/index.js"));
+    }
+    for (_, script) in package_bins(&manifest) {
+        required.insert(normalize(&script).to_string());
+    }
+    let mut rules = PackRules {
+        defaults: ALWAYS_IGNORED
+            .iter()
+            .filter_map(|line| Rule::parse(line))
+            .collect(),
+        files,
+        required,
+        ignores: HashMap::new(),
+    };
+
+    let mut packed = Vec::new();
+    let mut walker = walkdir::WalkDir::new(package_dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry?;
+        let relative = entry
+            .path()
+            .strip_prefix(package_dir)
+            .unwrap_or(entry.path());
+        let path = slash_path(relative);
+        let is_dir = entry.file_type().is_dir();
+        if !path.is_empty() {
+            if rules.always_ignored(&path, is_dir) {
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+            if !is_dir && rules.always_included(&path) {
+                packed.push(relative.to_path_buf());
+                continue;
+            }
+            if rules.ignored(&path, is_dir) && !(is_dir && rules.holds_required(&path)) {
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+        }
+        if is_dir {
+            // The root's ignore files give way to `files`; nested ones always apply
+            if !(path.is_empty() && rules.files.is_some()) {
+                let ignore_file = [".npmignore", ".gitignore"]
+                    .iter()
+                    .map(|name| entry.path().join(name))
+                    .find(|file| file.is_file());
+                if let Some(ignore_file) = ignore_file {
+                    rules.ignores.insert(path, read_rules(&ignore_file)?);
+                }
+            }
+            continue;
+        }
+        if rules.whitelisted(&path) {
+            packed.push(relative.to_path_buf());
+        }
+    }
+    Ok(packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_with(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::TempDir::new().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn packed(dir: &tempfile::TempDir) -> Vec<String> {
+        packed_files(dir.path())
+            .unwrap()
+            .iter()
+            .map(|path| slash_path(path))
+            .collect()
+    }
+
+    #[test]
+    fn test_files_whitelist() {
+        let dir = package_with(&[
+            (
+                "package.json",
+                r#"{"main":"dist/index.js","bin":{"tool":"./bin/tool.js"},"files":["dist","!dist/**/*.map","types/*.d.ts"]}"#,
+            ),
+            ("README.md", ""),
+            ("LICENSE", ""),
+            (".npmignore", "dist\n"),
+            ("dist/index.js", ""),
+            ("dist/index.js.map", ""),
+            ("dist/util/helper.js", ""),
+            ("dist/fixtures/.npmignore", "*.json\n"),
+            ("dist/fixtures/data.json", ""),
+            ("bin/tool.js", ""),
+            ("types/index.d.ts", ""),
+            ("types/nested/other.d.ts", ""),
+            ("src/index.ts", ""),
+            ("test/index.test.js", ""),
+            (".git/HEAD", ""),
+        ]);
+        assert_eq!(
+            packed(&dir),
+            [
+                "LICENSE",
+                "README.md",
+                "bin/tool.js",
+                "dist/index.js",
+                "dist/util/helper.js",
+                "package.json",
+                "types/index.d.ts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_npmignore_then_gitignore() {
+        let dir = package_with(&[
+            ("package.json", r#"{"main":"lib/index.js"}"#),
+            (
+                ".npmignore",
+                "# published sources only\ntest/\n*.map\n/docs\n!keep.map\n",
+            ),
+            (".gitignore", "lib/\n"),
+            ("lib/index.js", ""),
+            ("lib/index.js.map", ""),
+            ("lib/keep.map", ""),
+            ("lib/test/unit.js", ""),
+            ("docs/guide.md", ""),
+            ("examples/docs/guide.md", ""),
+            ("examples/.gitignore", "*.md\n"),
+            ("examples/run.js", ""),
+            ("package-lock.json", ""),
+        ]);
+        assert_eq!(
+            packed(&dir),
+            [
+                "examples/run.js",
+                "lib/index.js",
+                "lib/keep.map",
+                "package.json",
+            ]
+        );
+    }
+}
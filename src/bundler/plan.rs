@@ -0,0 +1,276 @@
+//! `--dry-run`: what a bundle would contain, without downloading the runtime, compressing
+//! anything or building the runner.
+//!
+//! The app and its dependencies are collected by the same code as for a real bundle, into an
+//! uncompressed archive kept in memory, so a package missing from the plan is missing from the
+//! executable too.
+
+use crate::size_report::{owning_package, PackageSize};
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// Packages listed by the text plan; the `plan` JSON event includes all of them
+const TOP_PACKAGES: usize = 15;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundlePlan {
+    pub app_name: String,
+    pub app_version: String,
+    pub platform: String,
+    pub runtime: String,
+    pub runtime_version: String,
+    /// Size of the runtime before trimming, when it is in the cache already
+    pub runtime_size: Option<u64>,
+    /// The runner downloads the runtime on first run instead of carrying it
+    pub external_runtime: bool,
+    pub source_dir: PathBuf,
+    /// Script the runtime starts, relative to the source directory
+    pub entry: Option<String>,
+    /// Where dependencies are collected from, naming the package manager that installed them
+    pub dependencies: String,
+    /// `scripts` a real bundle runs first, which the dry run skips
+    pub scripts: Vec<String>,
+    /// Configured steps the estimate leaves out: `--analyze`, `--encrypt`, `trimRuntime`, `protect`
+    pub not_applied: Vec<String>,
+    pub output: PathBuf,
+    /// Files outside `node_modules`, including `include` paths
+    pub app_files: u64,
+    pub app_source: u64,
+    /// Installed packages, largest first
+    pub packages: Vec<PackageSize>,
+    /// Junk files `pruneJunk` leaves out of `node_modules`
+    pub pruned_files: usize,
+    pub pruned_bytes: u64,
+    /// Uncompressed contents, with the runtime when its size is known
+    pub estimated_size: u64,
+}
+
+/// What the collected payload holds
+pub struct Contents {
+    pub app_files: u64,
+    pub app_source: u64,
+    pub packages: Vec<PackageSize>,
+}
+
+impl Contents {
+    pub fn tally(zip_data: Vec<u8>) -> Result<Self> {
+        let mut archive =
+            ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+        let mut app_files = 0;
+        let mut app_source = 0;
+        let mut packages: BTreeMap<String, u64> = BTreeMap::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let path = file.name().strip_prefix("app/").unwrap_or(file.name());
+            match owning_package(path) {
+                Some(name) => *packages.entry(name).or_default() += file.size(),
+                None => {
+                    app_files += 1;
+                    app_source += file.size();
+                }
+            }
+        }
+        let mut packages: Vec<PackageSize> = packages
+            .into_iter()
+            .map(|(name, size)| PackageSize { name, size })
+            .collect();
+        packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        Ok(Self {
+            app_files,
+            app_source,
+            packages,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.app_source
+            + self
+                .packages
+                .iter()
+                .map(|package| package.size)
+                .sum::<u64>()
+    }
+}
+
+/// Bytes of the files below `dir`, without following links
+pub fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+impl BundlePlan {
+    pub fn render_text(&self) -> String {
+        let size = |bytes: u64| HumanBytes(bytes).to_string();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Dry run for {} v{} ({}): nothing was downloaded, compressed or written",
+            self.app_name, self.app_version, self.platform
+        );
+        let runtime = match (self.external_runtime, self.runtime_size) {
+            (true, _) => "downloaded by the executable on first run".to_string(),
+            (false, Some(bytes)) => format!("{} in the cache, before trimming", size(bytes)),
+            (false, None) => "not downloaded yet".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "  {:<14} {} {} ({runtime})",
+            "Runtime", self.runtime, self.runtime_version
+        );
+        let _ = writeln!(out, "  {:<14} {}", "Source", self.source_dir.display());
+        if let Some(entry) = &self.entry {
+            let _ = writeln!(out, "  {:<14} {entry}", "Entry");
+        }
+        let _ = writeln!(out, "  {:<14} {}", "Dependencies", self.dependencies);
+        if !self.scripts.is_empty() {
+            let _ = writeln!(
+                out,
+                "  {:<14} {} (not run by --dry-run)",
+                "Scripts",
+                self.scripts.join(", ")
+            );
+        }
+        if !self.not_applied.is_empty() {
+            let _ = writeln!(
+                out,
+                "  {:<14} {} (not applied by --dry-run)",
+                "Skipped",
+                self.not_applied.join(", ")
+            );
+        }
+        let _ = writeln!(out, "  {:<14} {}", "Output", self.output.display());
+        let _ = writeln!(out, "Unpacked contents:");
+        let _ = writeln!(
+            out,
+            "  {:<40} {:>12}",
+            format!("App source ({} files)", self.app_files),
+            size(self.app_source)
+        );
+        let package_total: u64 = self.packages.iter().map(|package| package.size).sum();
+        let _ = writeln!(
+            out,
+            "  {:<40} {:>12}",
+            format!("node_modules ({} packages)", self.packages.len()),
+            size(package_total)
+        );
+        for package in self.packages.iter().take(TOP_PACKAGES) {
+            let _ = writeln!(out, "    {:<38} {:>12}", package.name, size(package.size));
+        }
+        if self.packages.len() > TOP_PACKAGES {
+            let _ = writeln!(
+                out,
+                "    ... {} more (--json lists all)",
+                self.packages.len() - TOP_PACKAGES
+            );
+        }
+        if self.pruned_files > 0 {
+            let _ = writeln!(
+                out,
+                "  {:<40} {:>12}",
+                format!("Pruned junk ({} files)", self.pruned_files),
+                format!("-{}", size(self.pruned_bytes))
+            );
+        }
+        let _ = writeln!(
+            out,
+            "  {:<40} {:>12}",
+            "Estimated total, uncompressed",
+            size(self.estimated_size)
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_contents_are_tallied_by_package() {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default();
+            zip.add_directory("app/node_modules/", opts).unwrap();
+            for (name, size) in [
+                ("app/index.js", 10),
+                ("app/package.json", 5),
+                ("app/node_modules/a/index.js", 100),
+                ("app/node_modules/@s/b/index.js", 40),
+                ("app/node_modules/@s/b/lib/util.js", 20),
+            ] {
+                zip.start_file(name, opts).unwrap();
+                zip.write_all(&vec![b'x'; size]).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let contents = Contents::tally(data).unwrap();
+        assert_eq!((contents.app_files, contents.app_source), (2, 15));
+        assert_eq!(
+            contents.packages,
+            [
+                PackageSize {
+                    name: "a".to_string(),
+                    size: 100
+                },
+                PackageSize {
+                    name: "@s/b".to_string(),
+                    size: 60
+                },
+            ]
+        );
+        assert_eq!(contents.size(), 175);
+    }
+
+    #[test]
+    fn test_render_text_names_skipped_steps() {
+        let plan = BundlePlan {
+            app_name: "app".to_string(),
+            app_version: "1.0.0".to_string(),
+            platform: "linux-x64".to_string(),
+            runtime: "Node.js".to_string(),
+            runtime_version: "22.1.0".to_string(),
+            runtime_size: Some(50_000_000),
+            external_runtime: false,
+            source_dir: PathBuf::from("/project"),
+            entry: Some("index.js".to_string()),
+            dependencies: "npm".to_string(),
+            scripts: vec!["build".to_string()],
+            not_applied: vec!["--encrypt".to_string(), "trimRuntime".to_string()],
+            output: PathBuf::from("app"),
+            app_files: 2,
+            app_source: 15,
+            packages: Vec::new(),
+            pruned_files: 0,
+            pruned_bytes: 0,
+            estimated_size: 50_000_015,
+        };
+        let text = plan.render_text();
+        assert!(
+            text.contains("Scripts        build (not run by --dry-run)"),
+            "{text}"
+        );
+        assert!(
+            text.contains("Skipped        --encrypt, trimRuntime (not applied by --dry-run)"),
+            "{text}"
+        );
+        assert!(text.contains("in the cache, before trimming"), "{text}");
+    }
+}
@@ -0,0 +1,381 @@
+//! Keep the app's JavaScript source out of the bundle (`protect` configuration).
+//!
+//! Files matching the `obfuscate` globs are rewritten by an obfuscator command, and files
+//! matching the `bytecode` globs are compiled to V8 bytecode by the bundled node itself, so the
+//! bytecode matches the V8 version that runs it. A compiled `x.js` is replaced by a small loader
+//! that runs `x.jsc` (bytenode-style); its source text is not bundled. Like pruning, this runs
+//! over the finished payload and only touches regular files.
+//!
+//! V8 only accepts bytecode compiled under the same flags, so the compiler runs with the bundle's
+//! `nodeArgs` and both it and the loader set [`V8_FLAGS`]; the bytecode itself is left untouched.
+
+use super::packlist::Rule;
+use crate::config::ProtectConfig;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::process::Command;
+use zip::{ZipArchive, ZipWriter};
+
+/// Used when `protect.obfuscator` is not set; the project installs it as a dev dependency
+const DEFAULT_OBFUSCATOR: &str = "javascript-obfuscator {input} --output {output}";
+
+/// Set while compiling and loading: eagerly compiled functions, since the loader has no source to
+/// compile lazy ones from, whose bytecode V8 must never flush
+const V8_FLAGS: &str = "--no-lazy --no-flush-bytecode";
+
+/// Run by the bundled node: compiles each listed `<id>.js` in a directory to `<id>.jsc`
+const COMPILE_SCRIPT: &str = r#"const fs = require("fs");
+const path = require("path");
+const v8 = require("v8");
+const vm = require("vm");
+const { wrap } = require("module");
+v8.setFlagsFromString("{flags}");
+const [dir, list] = process.argv.slice(2);
+for (const line of fs.readFileSync(list, "utf8").split("\n").filter(Boolean)) {
+  const [id, name] = line.split("\t");
+  const source = fs.readFileSync(path.join(dir, `${id}.js`), "utf8").replace(/^#!.*/, "");
+  try {
+    const script = new vm.Script(wrap(source), { filename: name });
+    fs.writeFileSync(path.join(dir, `${id}.jsc`), script.createCachedData());
+  } catch (error) {
+    console.error(`${name}: ${error.message}`);
+    process.exit(1);
+  }
+}
+"#;
+
+/// Replaces every compiled file; runs the bytecode stored next to it as `<file>c`
+const LOADER: &str = r#"// Compiled to V8 bytecode by banderole; the source is not bundled
+const fs = require("fs");
+const v8 = require("v8");
+const vm = require("vm");
+const bytecode = fs.readFileSync(`${__filename}c`);
+// V8 accepts the bytecode under the flags it was compiled with and a source of the original length
+v8.setFlagsFromString("{flags}");
+const length = bytecode.readUInt32LE(8) & 0x7fffffff;
+const source = length > 1 ? `"${"\u200b".repeat(length - 2)}"` : "";
+const script = new vm.Script(source, { cachedData: bytecode, filename: __filename });
+// The app's own code compiles lazily again
+v8.setFlagsFromString("--lazy");
+if (script.cachedDataRejected) {
+  throw new Error(
+    `${__filename} was compiled to bytecode for another Node.js version or other V8 flags (NODE_OPTIONS)`
+  );
+}
+script.runInThisContext()(exports, require, module, __filename, __dirname);
+"#;
+
+/// Outcome of [`protect`]
+#[derive(Debug, Default, PartialEq)]
+pub struct ProtectStats {
+    /// Files run through the obfuscator
+    pub obfuscated: usize,
+    /// Files replaced by bytecode
+    pub compiled: usize,
+}
+
+/// The `protect` globs, matched against paths relative to the bundled `app/` directory; the last
+/// matching pattern wins, so `!dist/vendor/**` exempts files again
+pub struct Protection {
+    bytecode: Vec<Rule>,
+    obfuscate: Vec<Rule>,
+    obfuscator: String,
+}
+
+impl Protection {
+    pub fn new(config: &ProtectConfig) -> Self {
+        let parse = |patterns: &[String]| patterns.iter().filter_map(|p| Rule::parse(p)).collect();
+        Self {
+            bytecode: parse(&config.bytecode),
+            obfuscate: parse(&config.obfuscate),
+            obfuscator: config
+                .obfuscator
+                .clone()
+                .unwrap_or_else(|| DEFAULT_OBFUSCATOR.to_string()),
+        }
+    }
+
+    /// Whether the payload entry `name` is (obfuscated, compiled to bytecode)
+    fn modes(&self, name: &str) -> Result<(bool, bool)> {
+        let Some(path) = name.strip_prefix("app/") else {
+            return Ok((false, false));
+        };
+        let selected = |rules: &[Rule]| {
+            rules
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(path, false))
+                .is_some_and(|rule| !rule.negated())
+        };
+        let javascript = [".js", ".cjs", ".mjs"]
+            .iter()
+            .any(|ext| path.ends_with(ext));
+        let obfuscate = javascript && selected(&self.obfuscate);
+        let compile = javascript && selected(&self.bytecode);
+        if compile && path.ends_with(".mjs") {
+            bail!("{path}: ES modules cannot be compiled to bytecode, only CommonJS files");
+        }
+        Ok((obfuscate, compile))
+    }
+}
+
+struct Selected {
+    index: usize,
+    name: String,
+    obfuscate: bool,
+    compile: bool,
+}
+
+/// Rewrite the payload with the selected app files obfuscated or compiled. `node` is the bundled
+/// node executable, needed only when something is compiled to bytecode, and runs with the
+/// bundle's `node_args` so its V8 flags match the app's.
+pub fn protect(
+    zip_data: Vec<u8>,
+    protection: &Protection,
+    project_path: &Path,
+    node: Option<&Path>,
+    node_args: &[String],
+) -> Result<(Vec<u8>, ProtectStats)> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+    let mut selected = Vec::new();
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if !file.is_file() || file.is_symlink() {
+            continue;
+        }
+        let (obfuscate, compile) = protection.modes(file.name())?;
+        if obfuscate || compile {
+            selected.push(Selected {
+                index,
+                name: file.name().to_string(),
+                obfuscate,
+                compile,
+            });
+        }
+    }
+    let mut stats = ProtectStats::default();
+    if selected.is_empty() {
+        return Ok((archive.into_inner().into_inner(), stats));
+    }
+
+    let work = tempfile::TempDir::new().context("Failed to create a temporary directory")?;
+    let source = |id: usize| work.path().join(format!("{id}.js"));
+    for (id, entry) in selected.iter().enumerate() {
+        let mut contents = Vec::new();
+        archive
+            .by_index(entry.index)?
+            .read_to_end(&mut contents)
+            .with_context(|| format!("Failed to read {} from the payload", entry.name))?;
+        fs::write(source(id), contents)?;
+    }
+
+    for (id, entry) in selected.iter().enumerate().filter(|(_, e)| e.obfuscate) {
+        let output = work.path().join(format!("{id}.obfuscated.js"));
+        run_obfuscator(&protection.obfuscator, project_path, &source(id), &output)
+            .with_context(|| format!("Failed to obfuscate {}", entry.name))?;
+        fs::rename(&output, source(id))?;
+        stats.obfuscated += 1;
+    }
+
+    let compiled: Vec<_> = selected
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.compile)
+        .collect();
+    if !compiled.is_empty() {
+        let Some(node) = node else {
+            bail!("Bytecode compilation needs the Node.js runtime; use \"obfuscate\" instead");
+        };
+        let list: String = compiled
+            .iter()
+            .map(|(id, entry)| format!("{id}\t{}\n", entry.name))
+            .collect();
+        fs::write(work.path().join("files.txt"), list)?;
+        fs::write(
+            work.path().join("compile.js"),
+            COMPILE_SCRIPT.replace("{flags}", V8_FLAGS),
+        )?;
+        let output = Command::new(node)
+            .args(node_args)
+            .arg(work.path().join("compile.js"))
+            .arg(work.path())
+            .arg(work.path().join("files.txt"))
+            .output()
+            .with_context(|| format!("Failed to run {}", node.display()))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to compile the app to V8 bytecode: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        stats.compiled = compiled.len();
+    }
+
+    let mut output = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut output));
+        let mut next = selected.iter().enumerate().peekable();
+        for index in 0..archive.len() {
+            let Some((id, entry)) = next.next_if(|(_, entry)| entry.index == index) else {
+                zip.raw_copy_file(archive.by_index_raw(index)?)?;
+                continue;
+            };
+            let file = archive.by_index_raw(index)?;
+            let mut opts = zip::write::SimpleFileOptions::default()
+                .compression_method(file.compression())
+                .last_modified_time(file.last_modified().unwrap_or_default());
+            if let Some(mode) = file.unix_mode() {
+                opts = opts.unix_permissions(mode);
+            }
+            drop(file);
+            if entry.compile {
+                zip.start_file(&entry.name, opts)?;
+                zip.write_all(LOADER.replace("{flags}", V8_FLAGS).as_bytes())?;
+                zip.start_file(format!("{}c", entry.name), opts)?;
+                zip.write_all(&fs::read(work.path().join(format!("{id}.jsc")))?)?;
+            } else {
+                zip.start_file(&entry.name, opts)?;
+                zip.write_all(&fs::read(source(id))?)?;
+            }
+        }
+        zip.finish()?;
+    }
+    Ok((output, stats))
+}
+
+/// Run the obfuscator command through the shell from the project directory, with the project's
+/// `node_modules/.bin` first on `PATH`
+fn run_obfuscator(command: &str, project_path: &Path, input: &Path, output: &Path) -> Result<()> {
+    let command = command
+        .replace("{input}", &format!("\"{}\"", input.display()))
+        .replace("{output}", &format!("\"{}\"", output.display()));
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut path = vec![project_path.join("node_modules").join(".bin")];
+    path.extend(
+        std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths),
+    );
+    let result = shell
+        .arg(&command)
+        .current_dir(project_path)
+        .env("PATH", std::env::join_paths(path)?)
+        .output()
+        .with_context(|| format!("Failed to run `{command}`"))?;
+    if !result.status.success() {
+        bail!(
+            "`{command}` failed ({}): {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    if !output.is_file() {
+        bail!("`{command}` did not write {}", output.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protection(bytecode: &[&str], obfuscate: &[&str], obfuscator: Option<&str>) -> Protection {
+        Protection::new(&ProtectConfig {
+            bytecode: bytecode.iter().map(|p| p.to_string()).collect(),
+            obfuscate: obfuscate.iter().map(|p| p.to_string()).collect(),
+            obfuscator: obfuscator.map(String::from),
+        })
+    }
+
+    #[test]
+    fn test_modes_follow_globs() {
+        let protection = protection(&["dist/**", "!dist/vendor/**"], &["*.cjs"], None);
+        assert_eq!(
+            protection.modes("app/dist/index.js").unwrap(),
+            (false, true)
+        );
+        assert_eq!(
+            protection.modes("app/dist/vendor/x.js").unwrap(),
+            (false, false)
+        );
+        assert_eq!(
+            protection.modes("app/dist/data.json").unwrap(),
+            (false, false)
+        );
+        assert_eq!(protection.modes("app/lib/util.cjs").unwrap(), (true, false));
+        assert_eq!(protection.modes("app/dist/cli.cjs").unwrap(), (true, true));
+        assert_eq!(
+            protection.modes("node/lib/index.js").unwrap(),
+            (false, false)
+        );
+        assert!(protection.modes("app/dist/module.mjs").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_obfuscated_files_are_replaced() {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default();
+            for name in [
+                "app/index.js",
+                "app/README.md",
+                "app/node_modules/a/index.js",
+            ] {
+                zip.start_file(name, opts).unwrap();
+                zip.write_all(b"module.exports = 1;").unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let project = tempfile::TempDir::new().unwrap();
+        let protection = protection(&[], &["/*.js"], Some("tr a-z A-Z < {input} > {output}"));
+
+        let (data, stats) = protect(data, &protection, project.path(), None, &[]).unwrap();
+        assert_eq!(
+            stats,
+            ProtectStats {
+                obfuscated: 1,
+                compiled: 0
+            }
+        );
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut read = |name: &str| {
+            let mut contents = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        };
+        assert_eq!(read("app/index.js"), "MODULE.EXPORTS = 1;");
+        assert_eq!(read("app/node_modules/a/index.js"), "module.exports = 1;");
+    }
+
+    #[test]
+    fn test_bytecode_needs_node() {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            zip.start_file("app/index.js", zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(b"module.exports = 1;").unwrap();
+            zip.finish().unwrap();
+        }
+        let project = tempfile::TempDir::new().unwrap();
+        let protection = protection(&["*.js"], &[], None);
+        assert!(protect(data, &protection, project.path(), None, &[]).is_err());
+    }
+}
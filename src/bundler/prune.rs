@@ -0,0 +1,228 @@
+//! Drop files of installed packages that are never loaded at runtime.
+//!
+//! Packages routinely publish their docs, source maps, tests and TypeScript sources next to the
+//! compiled JavaScript. Pruning runs over the finished payload, so it covers every way
+//! dependencies get bundled (flat, pnpm store, workspaces, local packages) in one place.
+
+use super::packlist::Rule;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Cursor;
+use zip::{ZipArchive, ZipWriter};
+
+/// Skipped inside every package unless `--no-prune-junk`; gitignore syntax
+const DEFAULT_JUNK: &[&str] = &[
+    "*.md",
+    "*.markdown",
+    "*.map",
+    "test/",
+    "tests/",
+    "__tests__/",
+    "example/",
+    "examples/",
+    "coverage/",
+    ".github/",
+    ".nyc_output/",
+    ".travis.yml",
+    ".editorconfig",
+    ".gitattributes",
+    ".npmignore",
+    ".eslintrc",
+    ".eslintrc.*",
+    ".prettierrc",
+    ".prettierrc.*",
+    "*.tsbuildinfo",
+];
+
+/// TypeScript sources and declarations, by the extension of the JavaScript compiled from them
+const TYPESCRIPT_EXTENSIONS: &[(&str, &str)] = &[
+    (".d.ts", ".js"),
+    (".ts", ".js"),
+    (".d.mts", ".mjs"),
+    (".mts", ".mjs"),
+    (".d.cts", ".cjs"),
+    (".cts", ".cjs"),
+];
+
+/// Outcome of [`prune`]
+#[derive(Debug, Default, PartialEq)]
+pub struct PruneStats {
+    /// Files dropped from the payload
+    pub files: usize,
+    /// Their uncompressed size
+    pub bytes: u64,
+}
+
+/// The default junk patterns followed by the configured `junkPatterns`; later patterns win, so
+/// `!test/` keeps test directories again
+pub struct JunkFilter {
+    rules: Vec<Rule>,
+}
+
+impl JunkFilter {
+    pub fn new(extra_patterns: &[String]) -> Self {
+        let rules = DEFAULT_JUNK
+            .iter()
+            .copied()
+            .chain(extra_patterns.iter().map(String::as_str))
+            .filter_map(Rule::parse)
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether `path`, relative to its package, is junk. Licenses are always kept.
+    fn is_junk(&self, path: &str, is_dir: bool) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path).to_ascii_lowercase();
+        if name.starts_with("license") || name.starts_with("licence") {
+            return false;
+        }
+        let excluded = |path: &str, is_dir: bool| {
+            self.rules
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(path, is_dir))
+                .is_some_and(|rule| !rule.negated())
+        };
+        // Nothing below an excluded directory survives, as with .gitignore
+        let mut parents = path.match_indices('/').map(|(i, _)| &path[..i]);
+        parents.any(|dir| excluded(dir, true)) || excluded(path, is_dir)
+    }
+}
+
+/// Path of a payload entry relative to the installed package containing it, if any: the part
+/// after the last `node_modules/<name>` (or `node_modules/@scope/name`) of an entry under `app/`
+fn package_relative(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix("app/")?;
+    let start = match rest.rfind("/node_modules/") {
+        Some(i) => i + "/node_modules/".len(),
+        None => rest
+            .strip_prefix("node_modules/")
+            .map(|_| "node_modules/".len())?,
+    };
+    let in_modules = &rest[start..];
+    let package_len = if in_modules.starts_with('@') { 2 } else { 1 };
+    let relative = in_modules.splitn(package_len + 1, '/').nth(package_len)?;
+    (!relative.is_empty()).then_some(relative)
+}
+
+/// TypeScript source or declaration file whose compiled JavaScript is bundled next to it
+fn compiled_typescript(name: &str, names: &HashSet<String>) -> bool {
+    TYPESCRIPT_EXTENSIONS
+        .iter()
+        .any(|(typescript, javascript)| {
+            name.strip_suffix(typescript)
+                .is_some_and(|stem| names.contains(&format!("{stem}{javascript}")))
+        })
+}
+
+/// Rewrite the payload without the junk files of the packages in `app/**/node_modules`
+pub fn prune(zip_data: Vec<u8>, filter: &JunkFilter) -> Result<(Vec<u8>, PruneStats)> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+    let names: HashSet<String> = archive.file_names().map(String::from).collect();
+    let mut stats = PruneStats::default();
+    let mut output = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut output));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let junk = !file.is_symlink()
+                && package_relative(file.name()).is_some_and(|relative| {
+                    let relative = relative.trim_end_matches('/');
+                    filter.is_junk(relative, file.is_dir())
+                        || (file.is_file() && compiled_typescript(file.name(), &names))
+                });
+            if junk {
+                if file.is_file() {
+                    stats.files += 1;
+                    stats.bytes += file.size();
+                }
+                continue;
+            }
+            zip.raw_copy_file(file)?;
+        }
+        zip.finish()?;
+    }
+    Ok((output, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_package_relative() {
+        assert_eq!(
+            package_relative("app/node_modules/lodash/README.md"),
+            Some("README.md")
+        );
+        assert_eq!(
+            package_relative("app/node_modules/@types/node/test/a.ts"),
+            Some("test/a.ts")
+        );
+        assert_eq!(
+            package_relative("app/node_modules/.pnpm/a@1/node_modules/a/docs/x.md"),
+            Some("docs/x.md")
+        );
+        assert_eq!(package_relative("app/node_modules/test/"), None);
+        assert_eq!(package_relative("app/README.md"), None);
+        assert_eq!(
+            package_relative("node/lib/node_modules/npm/README.md"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_junk_is_pruned_from_packages() {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default();
+            for name in [
+                "app/README.md",
+                "app/test/app.test.js",
+                "app/node_modules/test/index.js",
+                "app/node_modules/lib/README.md",
+                "app/node_modules/lib/LICENSE.md",
+                "app/node_modules/lib/index.js",
+                "app/node_modules/lib/index.js.map",
+                "app/node_modules/lib/index.d.ts",
+                "app/node_modules/lib/types.d.ts",
+                "app/node_modules/lib/src/util.ts",
+                "app/node_modules/lib/test/index.test.js",
+                "app/node_modules/lib/fixtures/data.json",
+                "app/node_modules/lib/docs/guide.txt",
+            ] {
+                zip.start_file(name, opts).unwrap();
+                zip.write_all(b"content").unwrap();
+            }
+            zip.add_directory("app/node_modules/lib/test/", opts)
+                .unwrap();
+            zip.add_symlink("app/node_modules/.bin/lib.md", "../lib/index.js", opts)
+                .unwrap();
+            zip.finish().unwrap();
+        }
+
+        let filter = JunkFilter::new(&["fixtures/".to_string(), "!*.test.js".to_string()]);
+        let (pruned, stats) = prune(data, &filter).unwrap();
+        assert_eq!(stats.files, 5);
+        let archive = ZipArchive::new(Cursor::new(pruned)).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "app/README.md",
+                "app/node_modules/.bin/lib.md",
+                "app/node_modules/lib/LICENSE.md",
+                "app/node_modules/lib/docs/guide.txt",
+                "app/node_modules/lib/index.js",
+                "app/node_modules/lib/src/util.ts",
+                "app/node_modules/lib/types.d.ts",
+                "app/node_modules/test/index.js",
+                "app/test/app.test.js",
+            ]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! `--smoke-test`: run the executable that was just built and fail the bundle unless it exits
+//! as configured and prints the expected text.
+//!
+//! The run extracts into a throwaway cache directory, so it exercises the same first start a
+//! user gets and leaves the real cache alone. Notices are accepted, stdin is empty, and a run
+//! exceeding the timeout is killed along with everything it started.
+
+use crate::config::SmokeTestConfig;
+use crate::platform::Platform;
+use anyhow::{Context, Result};
+use indicatif::HumanDuration;
+use log::info;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// How long output is still read once the process is gone, in case something it started keeps
+/// the pipes open
+const OUTPUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Lines of output quoted when the run fails
+const QUOTED_LINES: usize = 20;
+
+/// Whether this machine can start executables built for `platform`: its own architecture, or
+/// Intel builds on Apple silicon through Rosetta, and its libc, which the bundled runtime links
+/// against
+pub fn can_run(platform: Platform) -> bool {
+    let host = std::env::consts::ARCH;
+    let arch = platform.arch() == host || (platform.os() == "macos" && host == "aarch64");
+    arch && Platform::host().is_ok_and(|host| host.is_musl() == platform.is_musl())
+}
+
+pub fn run(executable: &Path, test: &SmokeTestConfig) -> Result<()> {
+    let executable = executable
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", executable.display()))?;
+    let cache_dir = tempfile::TempDir::new().context("Failed to create a smoke test cache")?;
+    let timeout = Duration::from_secs(test.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let command_line = std::iter::once(executable.display().to_string())
+        .chain(test.args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    info!("Smoke testing `{command_line}`");
+
+    let started = Instant::now();
+    let mut command = Command::new(&executable);
+    command
+        .arg("--banderole-cache-dir")
+        .arg(cache_dir.path())
+        .args(&test.args)
+        .env("BANDEROLE_ACCEPT_NOTICE", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Its own process group, so the runtime and whatever the app started can be killed with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to start {}", executable.display()))?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            break None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    // Nothing the test started outlives it, whether it finished or timed out
+    kill_tree(&mut child);
+    let stdout = stdout.recv_timeout(OUTPUT_GRACE).unwrap_or_default();
+    let stderr = stderr.recv_timeout(OUTPUT_GRACE).unwrap_or_default();
+
+    let mut problems = Vec::new();
+    match status.map(|status| status.code()) {
+        None => problems.push(format!("did not finish within {}", HumanDuration(timeout))),
+        Some(None) => problems.push("was killed by a signal".to_string()),
+        Some(Some(code)) if code != test.exit_code => {
+            problems.push(format!("exited with {code} instead of {}", test.exit_code))
+        }
+        Some(Some(_)) => {}
+    }
+    for (name, output, expected) in [
+        ("stdout", &stdout, &test.stdout_contains),
+        ("stderr", &stderr, &test.stderr_contains),
+    ] {
+        for text in expected
+            .iter()
+            .filter(|text| !output.contains(text.as_str()))
+        {
+            problems.push(format!("printed no {text:?} on {name}"));
+        }
+    }
+    anyhow::ensure!(
+        problems.is_empty(),
+        "Smoke test failed: `{command_line}` {}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+        problems.join(", "),
+        tail(&stdout),
+        tail(&stderr)
+    );
+    info!("Smoke test passed in {}", HumanDuration(started.elapsed()));
+    Ok(())
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut bytes).ok();
+        }
+        sender
+            .send(String::from_utf8_lossy(&bytes).into_owned())
+            .ok();
+    });
+    receiver
+}
+
+/// Kill `child` and the processes it started: its process group on Unix, its process tree on
+/// Windows
+fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    Command::new("kill")
+        .args(["-9", "--", &format!("-{}", child.id())])
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+    #[cfg(windows)]
+    Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .output()
+        .ok();
+    child.kill().ok();
+    child.wait().ok();
+}
+
+/// The last [`QUOTED_LINES`] lines of `output`
+fn tail(output: &str) -> String {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(QUOTED_LINES)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_smoke_test_checks_exit_code_and_output() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("app");
+        std::fs::write(&script, "#!/bin/sh\necho \"version 1.2.3 $3\"\nexit 0\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let test = SmokeTestConfig {
+            args: vec!["--version".to_string()],
+            stdout_contains: vec!["1.2.3 --version".to_string()],
+            ..Default::default()
+        };
+        run(&script, &test).unwrap();
+
+        let test = SmokeTestConfig {
+            exit_code: 2,
+            stderr_contains: vec!["ready".to_string()],
+            ..test
+        };
+        let error = run(&script, &test).unwrap_err().to_string();
+        assert!(error.contains("exited with 0 instead of 2"), "{error}");
+        assert!(error.contains("printed no \"ready\" on stderr"), "{error}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_smoke_test_kills_what_the_app_started() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("app");
+        // The background sleep keeps stdout open after the app exits
+        std::fs::write(&script, "#!/bin/sh\nsleep 60 &\necho started\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let started = Instant::now();
+        let test = SmokeTestConfig {
+            stdout_contains: vec!["started".to_string()],
+            ..Default::default()
+        };
+        run(&script, &test).unwrap();
+
+        std::fs::write(&script, "#!/bin/sh\nsleep 60 &\nwait\n").unwrap();
+        let test = SmokeTestConfig {
+            timeout: Some(1),
+            ..Default::default()
+        };
+        let error = run(&script, &test).unwrap_err().to_string();
+        assert!(error.contains("did not finish"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_can_run() {
+        let host = Platform::host().unwrap();
+        assert!(can_run(host));
+        match host {
+            Platform::LinuxX64 => assert!(!can_run(Platform::LinuxX64Musl)),
+            Platform::LinuxX64Musl => assert!(!can_run(Platform::LinuxX64)),
+            _ => {}
+        }
+        assert!(!can_run(if host.arch() == "x86_64" {
+            Platform::LinuxArm64
+        } else {
+            Platform::LinuxX64
+        }));
+    }
+}
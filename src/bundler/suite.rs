@@ -0,0 +1,486 @@
+//! App suites: several projects in one executable, sharing a single runtime.
+//!
+//! Each app is laid out under `app/<name>/` and registered as an entrypoint, so the first
+//! argument picks the app. Packages that are byte-identical in every app that ships them are
+//! stored once in `app/node_modules/`, where Node's parent-directory lookup finds them.
+
+use super::{
+    add_dir_to_zip_excluding_node_modules, add_included_paths, bundle_dependencies,
+    determine_source_directory, relative_to_source_dir, resolve_entry_script, NodeModulesLayout,
+};
+use crate::config::BundleConfig;
+use crate::platform::Platform;
+use crate::runtime_config::RuntimeEntrypoint;
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::{ZipArchive, ZipWriter};
+
+/// Largest `.bin` entry read as a flattened symlink when pinning packages
+const MAX_BIN_LINK_LEN: u64 = 1024;
+
+/// One member of the suite, resolved at bundle time
+pub struct SuiteApp {
+    name: String,
+    project_path: PathBuf,
+    source_dir: PathBuf,
+    include: Vec<PathBuf>,
+    default_excludes: bool,
+    /// Entry script relative to the app's own directory
+    main_script: String,
+    description: Option<String>,
+}
+
+/// Resolve every configured app for `platform`: its package.json, entry script and source directory
+pub fn resolve_apps(
+    suite_path: &Path,
+    config: &BundleConfig,
+    platform: Platform,
+) -> Result<Vec<SuiteApp>> {
+    anyhow::ensure!(
+        config.entrypoints.is_empty(),
+        "\"apps\" and \"entrypoints\" cannot be combined; every app becomes a command"
+    );
+    let mut apps = Vec::new();
+    for (name, app) in &config.apps {
+        anyhow::ensure!(
+            !name.is_empty()
+                && !name.starts_with('.')
+                && name != "node_modules"
+                && !name.contains(['/', '\\']),
+            "App name '{name}' must be a plain directory name"
+        );
+        let project_path = suite_path
+            .join(app.path())
+            .canonicalize()
+            .with_context(|| format!("App '{name}' not found at {}", app.path().display()))?;
+        let pkg_json = project_path.join("package.json");
+        let content = fs::read_to_string(&pkg_json)
+            .with_context(|| format!("Failed to read {}", pkg_json.display()))?;
+        let package_value: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", pkg_json.display()))?;
+
+        let app_config =
+            BundleConfig::load(&project_path, &package_value)?.for_platform(platform)?;
+        // The suite starts every app with one node, environment and manifest
+        for (field, set) in [
+            ("nodeArgs", !app_config.node_args.is_empty()),
+            ("env", !app_config.env.is_empty()),
+            ("mutablePaths", !app_config.mutable_paths.is_empty()),
+        ] {
+            anyhow::ensure!(
+                !set,
+                "App '{name}' sets \"{field}\", which suite apps do not support; set it on the suite instead (mutablePaths as '{name}/<path>')"
+            );
+        }
+        let entry_script = resolve_entry_script(&project_path, &package_value, app.entry())?
+            .with_context(|| format!("App '{name}' has no entry script"))?;
+        let source_dir = determine_source_directory(&project_path, Some(&entry_script))?;
+        let main_script = relative_to_source_dir(&project_path, &source_dir, &entry_script)
+            .with_context(|| {
+                format!("Entry script of app '{name}' is outside its source directory")
+            })?;
+        apps.push(SuiteApp {
+            name: name.clone(),
+            project_path,
+            source_dir,
+            include: app_config.include,
+            default_excludes: app_config.default_excludes,
+            main_script,
+            description: app.description().map(str::to_string),
+        });
+    }
+    Ok(apps)
+}
+
+/// Commands dispatching to each app's entry script
+pub fn entrypoints(apps: &[SuiteApp]) -> Vec<RuntimeEntrypoint> {
+    apps.iter()
+        .map(|app| RuntimeEntrypoint {
+            name: app.name.clone(),
+            script: format!("{}/{}", app.name, app.main_script),
+            description: app.description.clone(),
+        })
+        .collect()
+}
+
+/// Bundle every app under `app/<name>/`, storing shared packages once in `app/node_modules/`
+pub fn add_apps_to_zip<W>(
+    zip: &mut ZipWriter<W>,
+    apps: &[SuiteApp],
+    skip_junk: bool,
+    layout: NodeModulesLayout,
+    opts: zip::write::FileOptions<'static, ()>,
+    dereferenced: &mut usize,
+    progress: Option<&ProgressBar>,
+) -> Result<()>
+where
+    W: Write + Read + Seek,
+{
+    // Each app is first bundled on its own exactly like a single-app build
+    let mut archives = Vec::new();
+    for app in apps {
+        let mut data = Vec::new();
+        {
+            let mut app_zip = ZipWriter::new(Cursor::new(&mut data));
+            add_dir_to_zip_excluding_node_modules(
+                &mut app_zip,
+                &app.source_dir,
+                Path::new("app"),
+                skip_junk && app.default_excludes,
+                opts,
+                None,
+            )?;
+            add_included_paths(
+                &mut app_zip,
+                &app.project_path,
+                &app.source_dir,
+                &app.include,
+                opts,
+                None,
+            )?;
+            bundle_dependencies(
+                &mut app_zip,
+                &app.project_path,
+                &app.source_dir,
+                layout,
+                opts,
+                dereferenced,
+                None,
+            )?;
+            app_zip.finish()?;
+        }
+        let archive = ZipArchive::new(Cursor::new(data))
+            .with_context(|| format!("Failed to read bundle of app '{}'", app.name))?;
+        if let Some(pb) = progress {
+            pb.inc_length(archive.len() as u64);
+        }
+        archives.push(archive);
+    }
+
+    let mut indexes = Vec::new();
+    for archive in &mut archives {
+        indexes.push(PackageIndex::read(archive)?);
+    }
+    let shared = shared_packages(&indexes);
+    log::debug!("Packages shared by the suite: {shared:?}");
+
+    let mut written = HashSet::new();
+    for (app, archive) in apps.iter().zip(archives.iter_mut()) {
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let name = file.name().to_string();
+            let target = match package_of(&name) {
+                Some((package, rest)) if shared.contains(package) => {
+                    format!("app/node_modules/{rest}")
+                }
+                _ => match name.strip_prefix("app/") {
+                    Some(rest) => format!("app/{}/{rest}", app.name),
+                    None => continue,
+                },
+            };
+            if written.insert(target.clone()) {
+                zip.raw_copy_file_rename(file, target)?;
+            }
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What an app ships in its top-level `node_modules/`
+#[derive(Default)]
+struct PackageIndex {
+    /// Package name -> (path, crc32, size) of every entry below it
+    contents: BTreeMap<String, Vec<(String, u32, u64)>>,
+    /// Package name -> names of its dependencies and peer dependencies
+    dependencies: BTreeMap<String, Vec<String>>,
+    /// Packages that must stay in the app (referenced from `.bin`, or a pnpm layout)
+    pinned: BTreeSet<String>,
+}
+
+impl PackageIndex {
+    fn read<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Self> {
+        let mut index = Self::default();
+        let mut pnpm = false;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let name = file.name().to_string();
+            if name.starts_with("app/node_modules/.pnpm/") {
+                pnpm = true;
+            }
+            if let Some(link) = name.strip_prefix("app/node_modules/.bin/") {
+                if !link.is_empty() && file.is_file() && file.size() <= MAX_BIN_LINK_LEN {
+                    let mut content = String::new();
+                    if file.read_to_string(&mut content).is_ok() {
+                        if let Some((package, _)) = content
+                            .strip_prefix("../")
+                            .and_then(|target| split_package(target))
+                        {
+                            index.pinned.insert(package.to_string());
+                        }
+                    }
+                }
+                continue;
+            }
+            let Some((package, rest)) = package_of(&name) else {
+                continue;
+            };
+            let package = package.to_string();
+            if rest == format!("{package}/package.json") {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                let manifest: Value = serde_json::from_str(&content).unwrap_or_default();
+                let deps = ["dependencies", "optionalDependencies", "peerDependencies"]
+                    .iter()
+                    .filter_map(|field| manifest[field].as_object())
+                    .flat_map(|deps| deps.keys().cloned())
+                    .collect();
+                index.dependencies.insert(package.clone(), deps);
+            }
+            index.contents.entry(package).or_default().push((
+                rest.to_string(),
+                file.crc32(),
+                file.size(),
+            ));
+        }
+        if pnpm {
+            index.pinned.extend(index.contents.keys().cloned());
+        }
+        Ok(index)
+    }
+}
+
+/// Packages identical in every app shipping them (and in at least two), whose dependencies
+/// also resolve from the shared directory
+fn shared_packages(indexes: &[PackageIndex]) -> BTreeSet<String> {
+    let mut candidates = BTreeSet::new();
+    let all: BTreeSet<&String> = indexes.iter().flat_map(|i| i.contents.keys()).collect();
+    for package in &all {
+        if indexes.iter().any(|index| index.pinned.contains(*package)) {
+            continue;
+        }
+        let copies: Vec<_> = indexes
+            .iter()
+            .filter_map(|index| index.contents.get(*package))
+            .collect();
+        if copies.len() >= 2 && copies.windows(2).all(|pair| pair[0] == pair[1]) {
+            candidates.insert((*package).clone());
+        }
+    }
+
+    // A shared package resolves its dependencies from app/node_modules too, so drop it when one
+    // of them stays inside the apps (unless it ships its own nested copy)
+    loop {
+        let unresolved: Vec<String> = candidates
+            .iter()
+            .filter(|package| {
+                let contents = &indexes
+                    .iter()
+                    .find_map(|index| index.contents.get(*package))
+                    .expect("candidate packages exist in an app");
+                let deps = indexes
+                    .iter()
+                    .find_map(|index| index.dependencies.get(*package));
+                deps.into_iter().flatten().any(|dep| {
+                    let nested = format!("{package}/node_modules/{dep}/");
+                    all.contains(dep)
+                        && !candidates.contains(dep)
+                        && !contents
+                            .iter()
+                            .any(|(path, _, _)| path.starts_with(&nested))
+                })
+            })
+            .cloned()
+            .collect();
+        if unresolved.is_empty() {
+            return candidates;
+        }
+        for package in unresolved {
+            candidates.remove(&package);
+        }
+    }
+}
+
+/// Split `app/node_modules/<package>/...` into the package name and the path below `node_modules/`
+fn package_of(name: &str) -> Option<(&str, &str)> {
+    let rest = name.strip_prefix("app/node_modules/")?;
+    let (package, _) = split_package(rest)?;
+    Some((package, rest))
+}
+
+/// Leading package name of a `node_modules`-relative path (scoped names include their scope)
+fn split_package(path: &str) -> Option<(&str, &str)> {
+    let mut parts = path.splitn(3, '/');
+    let first = parts.next()?;
+    if first.is_empty() || first.starts_with('.') {
+        return None;
+    }
+    let len = if first.starts_with('@') {
+        let second = parts.next().filter(|second| !second.is_empty())?;
+        first.len() + 1 + second.len()
+    } else {
+        first.len()
+    };
+    // The package directory itself needs to be complete (`x/` or `x/file`)
+    if path.len() <= len || path.as_bytes()[len] != b'/' {
+        return None;
+    }
+    Some((&path[..len], &path[len + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(packages: &[(&str, u32, &[&str])]) -> PackageIndex {
+        let mut index = PackageIndex::default();
+        for (name, crc, deps) in packages {
+            index.contents.insert(
+                name.to_string(),
+                vec![(format!("{name}/index.js"), *crc, 10)],
+            );
+            index.dependencies.insert(
+                name.to_string(),
+                deps.iter().map(|dep| dep.to_string()).collect(),
+            );
+        }
+        index
+    }
+
+    #[test]
+    fn test_split_package() {
+        assert_eq!(
+            package_of("app/node_modules/lodash/"),
+            Some(("lodash", "lodash/"))
+        );
+        assert_eq!(
+            package_of("app/node_modules/@scope/pkg/lib/a.js"),
+            Some(("@scope/pkg", "@scope/pkg/lib/a.js"))
+        );
+        assert_eq!(package_of("app/node_modules/@scope/"), None);
+        assert_eq!(package_of("app/node_modules/.bin/tool"), None);
+        assert_eq!(package_of("app/index.js"), None);
+    }
+
+    #[test]
+    fn test_shared_packages() {
+        let a = index(&[
+            ("same", 1, &[]),
+            ("differs", 2, &[]),
+            ("uses-differs", 3, &["differs"]),
+        ]);
+        let b = index(&[
+            ("same", 1, &[]),
+            ("differs", 4, &[]),
+            ("uses-differs", 3, &["differs"]),
+        ]);
+        let mut c = index(&[("same", 1, &[]), ("only-c", 5, &[])]);
+        c.pinned.insert("only-c".to_string());
+
+        let shared = shared_packages(&[a, b, c]);
+        assert_eq!(shared, BTreeSet::from(["same".to_string()]));
+    }
+
+    #[test]
+    fn test_suite_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let files = [
+            (
+                "package.json",
+                r#"{"name":"suite","banderole":{"apps":{"api":"api","cli":"cli"}}}"#,
+            ),
+            (
+                "api/package.json",
+                r#"{"name":"api","main":"server.js","dependencies":{"shared":"1","own":"1"}}"#,
+            ),
+            ("api/server.js", "require('shared');"),
+            (
+                "api/node_modules/shared/package.json",
+                r#"{"name":"shared"}"#,
+            ),
+            ("api/node_modules/shared/index.js", "module.exports = 1;"),
+            ("api/node_modules/own/package.json", r#"{"name":"own"}"#),
+            ("api/node_modules/own/index.js", "module.exports = 'api';"),
+            (
+                "cli/package.json",
+                r#"{"name":"cli","main":"cli.js","dependencies":{"shared":"1","own":"1"}}"#,
+            ),
+            ("cli/cli.js", "require('shared');"),
+            (
+                "cli/node_modules/shared/package.json",
+                r#"{"name":"shared"}"#,
+            ),
+            ("cli/node_modules/shared/index.js", "module.exports = 1;"),
+            ("cli/node_modules/own/package.json", r#"{"name":"own"}"#),
+            ("cli/node_modules/own/index.js", "module.exports = 'cli';"),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let package_json: Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join("package.json")).unwrap())
+                .unwrap();
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+        let apps = resolve_apps(dir.path(), &config, Platform::LinuxX64).unwrap();
+        let scripts: Vec<String> = entrypoints(&apps).into_iter().map(|e| e.script).collect();
+        assert_eq!(scripts, ["api/server.js", "cli/cli.js"]);
+
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            add_apps_to_zip(
+                &mut zip,
+                &apps,
+                true,
+                NodeModulesLayout::Flat,
+                Default::default(),
+                &mut 0,
+                None,
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+        let archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let names: BTreeSet<&str> = archive.file_names().collect();
+        assert!(names.contains("app/api/server.js"));
+        assert!(names.contains("app/cli/cli.js"));
+        assert!(names.contains("app/node_modules/shared/index.js"));
+        assert!(!names.contains("app/api/node_modules/shared/index.js"));
+        assert!(names.contains("app/api/node_modules/own/index.js"));
+        assert!(names.contains("app/cli/node_modules/own/index.js"));
+    }
+
+    #[test]
+    fn test_unsupported_app_settings_are_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("api")).unwrap();
+        fs::write(
+            dir.path().join("api/package.json"),
+            r#"{"name":"api","main":"server.js","banderole":{"nodeArgs":["--inspect"]}}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("api/server.js"), "").unwrap();
+
+        let suite = |apps: &str| {
+            let package_json: Value =
+                serde_json::from_str(&format!(r#"{{"banderole":{{"apps":{apps}}}}}"#)).unwrap();
+            BundleConfig::load(dir.path(), &package_json)
+        };
+        let config = suite(r#"{"api":"api"}"#).unwrap();
+        let err = resolve_apps(dir.path(), &config, Platform::LinuxX64)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("nodeArgs"), "{err}");
+        assert!(suite(r#"{"api":{"path":"api","env":{"A":"1"}}}"#).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Drop the parts of a Node.js release that bundled apps never use.
+//!
+//! The official archives carry C headers, man pages, docs, npm and corepack next to the node
+//! executable, together about 30 MB. Trimming runs over the finished payload like pruning, and
+//! only touches entries below the runtime directory.
+
+use super::prune::PruneStats;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Cursor;
+use zip::{ZipArchive, ZipWriter};
+
+/// Headers and documentation, relative to the runtime directory
+const DOCS: &[&str] = &["include", "share", "CHANGELOG.md", "README.md"];
+
+/// npm and corepack in the Unix (`lib/node_modules`, `bin/`) and Windows (top-level) layouts
+const PACKAGE_MANAGERS: &[&str] = &[
+    "lib/node_modules/npm",
+    "lib/node_modules/corepack",
+    "bin/npm",
+    "bin/npx",
+    "bin/corepack",
+    "node_modules/npm",
+    "node_modules/corepack",
+    "npm",
+    "npm.cmd",
+    "npm.ps1",
+    "npx",
+    "npx.cmd",
+    "npx.ps1",
+    "corepack",
+    "corepack.cmd",
+    "install_tools.bat",
+];
+
+/// What is left out of the runtime, and the `keepRuntimeFiles`/`--keep` paths overriding it
+pub struct RuntimeTrim {
+    trimmed: Vec<&'static str>,
+    keep: Vec<String>,
+}
+
+impl RuntimeTrim {
+    /// `include_npm` keeps npm and corepack in full, since `--banderole-npm` needs them
+    pub fn new(include_npm: bool, keep: &[String]) -> Self {
+        let mut trimmed = DOCS.to_vec();
+        if !include_npm {
+            trimmed.extend_from_slice(PACKAGE_MANAGERS);
+        }
+        let keep = keep
+            .iter()
+            .map(|path| path.replace('\\', "/").trim_matches('/').to_string())
+            .filter(|path| !path.is_empty())
+            .collect();
+        Self { trimmed, keep }
+    }
+
+    /// Whether `path`, relative to the runtime directory, is left out. A keep path matches
+    /// whole components anywhere in it, so `node_modules/npm` keeps npm in both layouts.
+    fn is_trimmed(&self, path: &str) -> bool {
+        let in_dir = |dir: &str| {
+            path.strip_prefix(dir)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        if !self.trimmed.iter().any(|dir| in_dir(dir)) {
+            return false;
+        }
+        let components = format!("/{path}/");
+        !self
+            .keep
+            .iter()
+            .any(|keep| components.contains(&format!("/{keep}/")))
+    }
+}
+
+/// Rewrite the payload without the trimmed entries below `runtime_dir`. Directories stay when
+/// something kept lies inside them.
+pub fn trim(
+    zip_data: Vec<u8>,
+    runtime_dir: &str,
+    trim: &RuntimeTrim,
+) -> Result<(Vec<u8>, PruneStats)> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+    let prefix = format!("{runtime_dir}/");
+    let relative = |name: &str| -> Option<String> {
+        name.strip_prefix(&prefix)
+            .map(|rest| rest.trim_end_matches('/').to_string())
+            .filter(|rest| !rest.is_empty())
+    };
+    let mut needed_dirs = HashSet::new();
+    for name in archive.file_names() {
+        if let Some(path) = relative(name).filter(|path| !trim.is_trimmed(path)) {
+            needed_dirs.extend(path.match_indices('/').map(|(i, _)| path[..i].to_string()));
+        }
+    }
+    let mut stats = PruneStats::default();
+    let mut output = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut output));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let trimmed = relative(file.name()).is_some_and(|path| {
+                trim.is_trimmed(&path) && !(file.is_dir() && needed_dirs.contains(&path))
+            });
+            if trimmed {
+                if !file.is_dir() {
+                    stats.files += 1;
+                    stats.bytes += file.size();
+                }
+                continue;
+            }
+            zip.raw_copy_file(file)?;
+        }
+        zip.finish()?;
+    }
+    Ok((output, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn payload(names: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut data));
+            let opts = zip::write::SimpleFileOptions::default();
+            for name in names {
+                if name.ends_with('/') {
+                    zip.add_directory(*name, opts).unwrap();
+                } else {
+                    zip.start_file(*name, opts).unwrap();
+                    zip.write_all(b"content").unwrap();
+                }
+            }
+            zip.finish().unwrap();
+        }
+        data
+    }
+
+    fn names(data: Vec<u8>) -> Vec<String> {
+        let archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        archive.file_names().map(String::from).collect()
+    }
+
+    const RUNTIME: &[&str] = &[
+        "app/share/data.json",
+        "app/node_modules/npm/index.js",
+        "node/bin/node",
+        "node/bin/npm",
+        "node/LICENSE",
+        "node/README.md",
+        "node/include/node/v8.h",
+        "node/share/man/man1/node.1",
+        "node/lib/node_modules/npm/bin/npm-cli.js",
+        "node/lib/node_modules/corepack/dist/corepack.js",
+        "node/node_modules/npm/bin/npm-cli.js",
+        "node/npm.cmd",
+        "node/node.exe",
+    ];
+
+    #[test]
+    fn test_runtime_is_trimmed_by_default() {
+        let (data, stats) = trim(payload(RUNTIME), "node", &RuntimeTrim::new(false, &[])).unwrap();
+        assert_eq!(
+            names(data),
+            [
+                "app/share/data.json",
+                "app/node_modules/npm/index.js",
+                "node/bin/node",
+                "node/LICENSE",
+                "node/node.exe",
+            ]
+        );
+        assert_eq!(stats.files, 8);
+    }
+
+    #[test]
+    fn test_npm_and_keep_paths_survive() {
+        let (data, _) = trim(payload(RUNTIME), "node", &RuntimeTrim::new(true, &[])).unwrap();
+        let kept = names(data);
+        assert!(kept.contains(&"node/bin/npm".to_string()));
+        assert!(kept.contains(&"node/lib/node_modules/corepack/dist/corepack.js".to_string()));
+        assert!(!kept.contains(&"node/include/node/v8.h".to_string()));
+
+        let keep = ["node_modules/npm/".to_string(), "include\\node".to_string()];
+        let data = payload(&[
+            "node/include/",
+            "node/include/node/",
+            "node/include/node/v8.h",
+            "node/include/other.h",
+            "node/lib/node_modules/npm/bin/npm-cli.js",
+            "node/node_modules/npm/bin/npm-cli.js",
+            "node/bin/npm",
+        ]);
+        let (data, _) = trim(data, "node", &RuntimeTrim::new(false, &keep)).unwrap();
+        assert_eq!(
+            names(data),
+            [
+                "node/include/",
+                "node/include/node/",
+                "node/include/node/v8.h",
+                "node/lib/node_modules/npm/bin/npm-cli.js",
+                "node/node_modules/npm/bin/npm-cli.js",
+            ]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! `--target macos-universal`: one executable for Intel and Apple silicon Macs.
+//!
+//! The project is bundled once per architecture, each runner embedding the runtime built for its
+//! CPU, and `lipo` joins the two executables into a universal Mach-O binary. macOS starts the
+//! slice matching the machine, so neither runs under Rosetta, at the cost of carrying both
+//! runtimes. The smoke test and the `postBuild` hooks run once, on the joined executable, and
+//! the `scripts` and `preBundle` hooks only before the first slice.
+
+use super::{bundle_platform, BundleOptions, BundleSummary, BundleTarget, Finish, Slice};
+use anyhow::{Context, Result};
+use indicatif::MultiProgress;
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+/// Architectures of the slices, by the suffix their single-architecture build is kept under
+const SLICES: [(BundleTarget, &str); 2] = [
+    (BundleTarget::MacosArm64, "arm64"),
+    (BundleTarget::MacosX64, "x64"),
+];
+
+pub async fn bundle(options: BundleOptions, multi: &MultiProgress) -> Result<BundleSummary> {
+    let started = Instant::now();
+    let mut slices = Vec::new();
+    let joined = match bundle_slices(&options, multi, &mut slices).await {
+        Ok((summary, finish)) => lipo(&slices, &summary.output).map(|()| (summary, finish)),
+        Err(e) => Err(e),
+    };
+    for slice in &slices {
+        fs::remove_file(slice).ok();
+    }
+    let (mut summary, finish) = joined?;
+    // The slice for the host's architecture runs
+    finish.run(&summary.output, "macos-universal", true)?;
+    summary.size = fs::metadata(&summary.output)
+        .with_context(|| format!("Failed to read {}", summary.output.display()))?
+        .len();
+    summary.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "Joined the arm64 and x64 executables into {}",
+        summary.output.display()
+    );
+    Ok(summary)
+}
+
+/// Bundle every architecture, moving each executable aside into `slices`
+async fn bundle_slices(
+    options: &BundleOptions,
+    multi: &MultiProgress,
+    slices: &mut Vec<PathBuf>,
+) -> Result<(BundleSummary, Finish)> {
+    let mut output = options.output_path.clone();
+    let mut last = None;
+    for (target, arch) in SLICES {
+        info!("Bundling the {arch} slice of the universal executable");
+        let part = if slices.is_empty() {
+            Slice::First
+        } else {
+            Slice::Later
+        };
+        let (summary, finish) = bundle_platform(
+            BundleOptions {
+                output_path: output.clone(),
+                target: Some(target),
+                ..options.clone()
+            },
+            multi,
+            Some(part),
+        )
+        .await?;
+        // Later slices are written to the path the first one picked
+        let slice = slice_path(&summary.output, arch);
+        fs::rename(&summary.output, &slice)
+            .with_context(|| format!("Failed to move {} aside", summary.output.display()))?;
+        slices.push(slice);
+        output = Some(summary.output.clone());
+        last = Some((summary, finish.context("A slice was planned, not bundled")?));
+    }
+    last.context("No architecture was bundled")
+}
+
+/// `my-app` becomes `my-app-arm64` next to it
+fn slice_path(output: &Path, arch: &str) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(format!("-{arch}"));
+    output.with_file_name(name)
+}
+
+fn lipo(slices: &[PathBuf], output: &Path) -> Result<()> {
+    let result = Command::new("lipo")
+        .arg("-create")
+        .args(slices)
+        .arg("-output")
+        .arg(output)
+        .output()
+        .context("Universal executables need lipo: install the Xcode command line tools")?;
+    anyhow::ensure!(
+        result.status.success(),
+        "lipo failed to join the executables: {}",
+        String::from_utf8_lossy(&result.stderr).trim()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_path() {
+        assert_eq!(
+            slice_path(Path::new("dist/my-app"), "arm64"),
+            PathBuf::from("dist/my-app-arm64")
+        );
+    }
+}
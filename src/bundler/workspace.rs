@@ -0,0 +1,333 @@
+//! Apps of a monorepo for `banderole bundle --all`.
+//!
+//! Workspace packages come from the `workspaces` field of the root package.json (npm, yarn) or
+//! from `pnpm-workspace.yaml`; every package declaring a `bin` or `main` is bundled. The bundles
+//! run one after another in the same process, so they share the downloaded runtime, the resolved
+//! Node.js versions, the compiled runner and the dependency names read from the workspace's
+//! installed packages.
+
+use crate::glob::glob_match;
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    /// Installed packages' dependency names by package location, `None` when not installed; the
+    /// apps of a workspace resolve from the same node_modules, so each package.json is read once
+    static ref DEPENDENCY_NAMES: Mutex<HashMap<PathBuf, Option<Arc<DependencyNames>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Directories never searched for workspace packages
+const SKIPPED_DIRS: &[&str] = &["node_modules", ".git"];
+
+/// A workspace package that can be bundled
+#[derive(Debug, PartialEq)]
+pub struct WorkspaceApp {
+    pub dir: PathBuf,
+    /// Executable file name: the package name without its scope
+    pub file_name: String,
+}
+
+/// What an installed package depends on
+#[derive(Debug, Default, PartialEq)]
+pub struct DependencyNames {
+    /// `dependencies`
+    pub required: Vec<String>,
+    /// `peerDependencies` and `optionalDependencies`, bundled only when installed
+    pub if_installed: Vec<String>,
+}
+
+impl DependencyNames {
+    fn parse(package_json: &str) -> Self {
+        let Ok(package) = serde_json::from_str::<Value>(package_json) else {
+            return Self::default();
+        };
+        let names = |field: &str| -> Vec<String> {
+            package[field]
+                .as_object()
+                .map(|deps| deps.keys().cloned().collect())
+                .unwrap_or_default()
+        };
+        Self {
+            required: names("dependencies"),
+            if_installed: [names("peerDependencies"), names("optionalDependencies")].concat(),
+        }
+    }
+}
+
+/// The dependency names of the package at `key`, whose package.json `read` returns (`None` when
+/// it is not installed); remembered for the rest of the run
+pub fn dependency_names(
+    key: PathBuf,
+    read: impl FnOnce() -> Result<Option<String>>,
+) -> Result<Option<Arc<DependencyNames>>> {
+    if let Some(names) = DEPENDENCY_NAMES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+    {
+        return Ok(names.clone());
+    }
+    let names = read()?.map(|content| Arc::new(DependencyNames::parse(&content)));
+    DEPENDENCY_NAMES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, names.clone());
+    Ok(names)
+}
+
+/// The bundleable packages of the workspace rooted at `root`, sorted by path
+pub fn find_apps(root: &Path) -> Result<Vec<WorkspaceApp>> {
+    let patterns = workspace_patterns(root)?;
+    anyhow::ensure!(
+        !patterns.is_empty(),
+        "{} is not a workspace root: package.json has no \"workspaces\" and there is no pnpm-workspace.yaml",
+        root.display()
+    );
+    let (excluded, included): (Vec<_>, Vec<_>) = patterns
+        .iter()
+        .partition(|pattern| pattern.starts_with('!'));
+
+    let mut apps = Vec::new();
+    let walker = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.file_type().is_dir()
+                && !SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref())
+        });
+    for entry in walker {
+        let entry = entry.with_context(|| format!("Failed to search {}", root.display()))?;
+        let relative = entry
+            .path()
+            .strip_prefix(root)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let matches = |patterns: &[&String]| {
+            patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.trim_start_matches('!'), &relative))
+        };
+        if !matches(&included) || matches(&excluded) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(entry.path().join("package.json")) else {
+            continue;
+        };
+        let package: Value = serde_json::from_str(&content).with_context(|| {
+            format!(
+                "Failed to parse {}",
+                entry.path().join("package.json").display()
+            )
+        })?;
+        if package.get("bin").is_none() && package.get("main").is_none() {
+            continue;
+        }
+        let name = package["name"].as_str().unwrap_or(&relative);
+        apps.push((name.to_string(), entry.path().to_path_buf()));
+    }
+    file_names(apps)
+}
+
+/// Name each app's executable after its package without the scope, or with the scope as a prefix
+/// (`@a/cli` becomes `a-cli`) when apps of several scopes share a name
+fn file_names(apps: Vec<(String, PathBuf)>) -> Result<Vec<WorkspaceApp>> {
+    let unscoped = |name: &str| name.rsplit('/').next().unwrap_or(name).to_string();
+    let mut counts = HashMap::new();
+    for (name, _) in &apps {
+        *counts.entry(unscoped(name)).or_insert(0) += 1;
+    }
+    let apps: Vec<WorkspaceApp> = apps
+        .into_iter()
+        .map(|(name, dir)| {
+            let file_name = match counts[&unscoped(&name)] {
+                1 => unscoped(&name),
+                _ => crate::package::package_name(&name),
+            };
+            WorkspaceApp { dir, file_name }
+        })
+        .collect();
+    for (i, app) in apps.iter().enumerate() {
+        if let Some(other) = apps[..i]
+            .iter()
+            .find(|other| other.file_name == app.file_name)
+        {
+            anyhow::bail!(
+                "{} and {} would both be bundled as {}; give the packages distinct names",
+                other.dir.display(),
+                app.dir.display(),
+                app.file_name
+            );
+        }
+    }
+    Ok(apps)
+}
+
+/// Package globs of the workspace, normalized to `dir/*` form; `!` excludes
+fn workspace_patterns(root: &Path) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    if let Ok(content) = fs::read_to_string(root.join("package.json")) {
+        let package: Value =
+            serde_json::from_str(&content).context("Failed to parse package.json")?;
+        // Yarn classic also accepts `{ "packages": [...], "nohoist": [...] }`
+        let workspaces = match &package["workspaces"] {
+            Value::Object(fields) => fields.get("packages").cloned().unwrap_or_default(),
+            other => other.clone(),
+        };
+        patterns.extend(
+            workspaces
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string),
+        );
+    }
+    if let Ok(content) = fs::read_to_string(root.join("pnpm-workspace.yaml")) {
+        patterns.extend(pnpm_packages(&content));
+    }
+    Ok(patterns
+        .into_iter()
+        .map(|pattern| {
+            let (negated, glob) = match pattern.strip_prefix('!') {
+                Some(glob) => ("!", glob),
+                None => ("", pattern.as_str()),
+            };
+            let glob = glob.trim_start_matches("./").trim_end_matches('/');
+            format!("{negated}{glob}")
+        })
+        .collect())
+}
+
+/// Entries of the `packages:` list in pnpm-workspace.yaml
+fn pnpm_packages(yaml: &str) -> Vec<String> {
+    let mut packages = Vec::new();
+    let mut in_packages = false;
+    for line in yaml.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = trimmed == "packages:";
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix('-').filter(|_| in_packages) {
+            let item = item.split(" #").next().unwrap_or(item).trim();
+            packages.push(item.trim_matches(['"', '\'']).to_string());
+        }
+    }
+    packages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(root: &Path, dir: &str, json: &str) {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("package.json"), json).unwrap();
+    }
+
+    #[test]
+    fn test_finds_apps_of_npm_and_pnpm_workspaces() {
+        let root = tempfile::TempDir::new().unwrap();
+        let root = root.path();
+        package(
+            root,
+            ".",
+            r#"{ "private": true, "workspaces": ["packages/*", "!packages/ignored", "./tools/cli/"] }"#,
+        );
+        package(
+            root,
+            "packages/server",
+            r#"{ "name": "@acme/server", "main": "index.js" }"#,
+        );
+        package(root, "packages/types", r#"{ "name": "@acme/types" }"#);
+        package(
+            root,
+            "packages/ignored",
+            r#"{ "name": "ignored", "bin": "x.js" }"#,
+        );
+        package(
+            root,
+            "packages/server/node_modules/dep",
+            r#"{ "main": "dep.js" }"#,
+        );
+        package(
+            root,
+            "tools/cli",
+            r#"{ "name": "acme", "bin": { "acme": "cli.js" } }"#,
+        );
+
+        let apps = find_apps(root).unwrap();
+        assert_eq!(
+            apps,
+            vec![
+                WorkspaceApp {
+                    dir: root.join("packages/server"),
+                    file_name: "server".to_string(),
+                },
+                WorkspaceApp {
+                    dir: root.join("tools/cli"),
+                    file_name: "acme".to_string(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            pnpm_packages(
+                "packages:\n  - 'apps/*'\n  - \"tools/**\" # tooling\n  - '!**/test/**'\ncatalog:\n  - nope\n"
+            ),
+            vec!["apps/*", "tools/**", "!**/test/**"]
+        );
+        let plain = tempfile::TempDir::new().unwrap();
+        package(plain.path(), ".", r#"{ "name": "app" }"#);
+        assert!(find_apps(plain.path()).is_err());
+    }
+
+    #[test]
+    fn test_file_names_keep_scopes_apart() {
+        let apps = file_names(vec![
+            ("@a/cli".to_string(), PathBuf::from("a")),
+            ("@b/cli".to_string(), PathBuf::from("b")),
+            ("@a/server".to_string(), PathBuf::from("server")),
+        ])
+        .unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.file_name.as_str()).collect();
+        assert_eq!(names, ["a-cli", "b-cli", "server"]);
+
+        assert!(file_names(vec![
+            ("cli".to_string(), PathBuf::from("a")),
+            ("cli".to_string(), PathBuf::from("b")),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_dependency_names_are_read_once() {
+        let key = PathBuf::from("/nonexistent/node_modules/dependency-names-test");
+        let read = || {
+            Ok(Some(
+                r#"{ "dependencies": { "a": "1" }, "peerDependencies": { "b": "1" }, "optionalDependencies": { "c": "1" } }"#
+                    .to_string(),
+            ))
+        };
+        let names = dependency_names(key.clone(), read).unwrap().unwrap();
+        assert_eq!(
+            *names,
+            DependencyNames {
+                required: vec!["a".to_string()],
+                if_installed: vec!["b".to_string(), "c".to_string()],
+            }
+        );
+        let cached = dependency_names(key, || panic!("read again")).unwrap();
+        assert!(Arc::ptr_eq(&cached.unwrap(), &names));
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Root of banderole's persistent cache on the build machine (Node runtimes, runner builds)
+pub fn persistent_cache_dir() -> Result<PathBuf> {
+    let cache_dir = if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
+        PathBuf::from(cache_home).join("banderole")
+    } else if let Some(home) = std::env::var_os("HOME") {
+        PathBuf::from(home).join(".cache").join("banderole")
+    } else if let Some(appdata) = std::env::var_os("APPDATA") {
+        PathBuf::from(appdata).join("banderole").join("cache")
+    } else {
+        std::env::temp_dir().join("banderole-cache")
+    };
+
+    std::fs::create_dir_all(&cache_dir).context("Failed to create persistent cache directory")?;
+
+    Ok(cache_dir)
+}
+
+/// Directory of the runner crate inside [`runner_target_dir`]
+pub const RUNNER_CRATE_DIR: &str = "crate";
+
+/// Shared cargo target directory for runner builds, under `root` when given (a target cache
+/// shared by CI jobs) and in banderole's cache otherwise.
+///
+/// Keyed by banderole version, a checksum of the embedded template and the target triple so
+/// that every project on the machine reuses the compiled runner, while a template change never
+/// links against stale artifacts. Interrupted builds simply resume on the next run. The runner
+/// crate itself is written to [`RUNNER_CRATE_DIR`] inside it, so its path (and cargo's
+/// fingerprints) stay the same from one bundle to the next.
+pub fn runner_target_dir(
+    root: Option<&Path>,
+    template_checksum: &str,
+    target_triple: &str,
+) -> Result<PathBuf> {
+    let key = format!(
+        "{}-{}",
+        env!("CARGO_PKG_VERSION"),
+        &template_checksum[..template_checksum.len().min(16)]
+    );
+    let root = match root {
+        Some(root) => root.to_path_buf(),
+        None => persistent_cache_dir()?.join("runner-target"),
+    };
+    let dir = root.join(key).join(target_triple);
+    std::fs::create_dir_all(&dir).context("Failed to create runner target directory")?;
+    Ok(dir)
+}
+
+/// Hold an exclusive OS lock on `path` (created if missing) until the returned file is dropped.
+///
+/// Serializes processes filling the same cache entry; the lock disappears with the process,
+/// so a crashed bundle never blocks later ones.
+pub async fn lock_exclusive(path: &Path) -> Result<File> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || lock_exclusive_blocking(&path)).await?
+}
+
+/// Blocking variant of [`lock_exclusive`] for code outside the async runtime
+pub fn lock_exclusive_blocking(path: &Path) -> Result<File> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to create lock file {}", path.display()))?;
+    if file.try_lock().is_err() {
+        info!("Waiting for another banderole process ({})", path.display());
+        file.lock()
+            .with_context(|| format!("Failed to lock {}", path.display()))?;
+    }
+    Ok(file)
+}
@@ -0,0 +1,706 @@
+use crate::bundler::NodeModulesLayout;
+use crate::node_downloader::NodeBuild;
+use crate::platform::{CustomPlatform, Platform};
+use crate::runtime_config::CacheScope;
+use crate::target_cfg;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the standalone bundle configuration
+pub const CONFIG_FILE_NAME: &str = "banderole.json";
+
+/// File name of the standalone bundle configuration in TOML, with the same keys
+pub const TOML_CONFIG_FILE_NAME: &str = "banderole.toml";
+
+/// Bundle configuration, read from `banderole.toml` or `banderole.json` in the project directory
+/// or from the `"banderole"` key of package.json (a standalone file wins when both exist).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BundleConfig {
+    /// Notice (EULA, telemetry disclosure) shown by the runner on first run
+    pub notice: Option<NoticeConfig>,
+    /// Named entry scripts dispatched by the first argument (`mybin serve ...`)
+    #[serde(default)]
+    pub entrypoints: BTreeMap<String, EntrypointConfig>,
+    /// Entrypoint used when the first argument is not a known command
+    pub default_entrypoint: Option<String>,
+    /// Separate projects bundled into one executable as subcommands, sharing the runtime
+    #[serde(default)]
+    pub apps: BTreeMap<String, SuiteAppConfig>,
+    /// Allow `--banderole-entry <path>` to launch another script from the bundle
+    #[serde(default = "default_true")]
+    pub allow_entry_override: bool,
+    /// Allow `--banderole-shell` to open a Node REPL inside the extracted app
+    #[serde(default = "default_true")]
+    pub allow_shell: bool,
+    /// Preload a shim pinning `process.execPath` to the bundled node for fork/worker libraries
+    #[serde(default)]
+    pub exec_path_shim: bool,
+    /// Paths (globs relative to the bundled app) the app writes to at runtime, skipped by verification
+    #[serde(default)]
+    pub mutable_paths: Vec<String>,
+    /// Check the extracted files against the embedded manifest before every launch
+    #[serde(default)]
+    pub verify_on_start: bool,
+    /// Skip caches and build leftovers (`node_modules/.cache`, `.turbo`, `coverage/`, ...) in the app directory
+    #[serde(default = "default_true")]
+    pub default_excludes: bool,
+    /// Skip docs, tests, source maps and compiled-over TypeScript sources of installed packages
+    #[serde(default = "default_true")]
+    pub prune_junk: bool,
+    /// Extra gitignore-style patterns pruned inside installed packages (`!pattern` keeps files)
+    #[serde(default)]
+    pub junk_patterns: Vec<String>,
+    /// Leave the headers, docs, npm and corepack of the Node.js release out of the bundle
+    #[serde(default = "default_true")]
+    pub trim_runtime: bool,
+    /// Paths in the Node.js release kept despite `trimRuntime`, e.g. `node_modules/npm`
+    #[serde(default)]
+    pub keep_runtime_files: Vec<String>,
+    /// App files obfuscated or compiled to V8 bytecode before bundling
+    pub protect: Option<ProtectConfig>,
+    /// `flat` (default) or `pnpm` to keep pnpm's `.pnpm` virtual store and symlinks
+    pub node_modules_layout: Option<NodeModulesLayout>,
+    /// package.json scripts run in order before bundling (e.g. `prebundle`); no other script runs
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Extra files or directories (relative to the project) copied into the bundled app
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// Environment variables set for the app unless already defined by the caller
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Arguments passed to node before the entry script
+    #[serde(default)]
+    pub node_args: Vec<String>,
+    /// App arguments printing its completion script for `--banderole-completions <shell>` (`{shell}` substituted)
+    #[serde(default)]
+    pub completions: Vec<String>,
+    /// Completion scripts (relative to the project) embedded by shell, e.g. `{"bash": "completions/app.bash"}`
+    #[serde(default)]
+    pub completion_scripts: BTreeMap<String, PathBuf>,
+    /// Build of Node.js to bundle: `full` (default), `small-icu`, `without-intl`,
+    /// `pointer-compression` or `debug`
+    pub node_build: Option<NodeBuild>,
+    /// Base URL of a nodejs.org/dist compatible mirror (Artifactory, Nexus, ...)
+    pub node_mirror: Option<String>,
+    /// Verify the GPG signature of Node.js checksums against the local keyring
+    #[serde(default)]
+    pub verify_node_signature: bool,
+    /// Leave Node.js out of the executable: the runner downloads the pinned release on first run
+    /// and shares it with every other app using the same one
+    #[serde(default)]
+    pub external_runtime: bool,
+    /// Keep Node.js's npm and corepack usable in the bundle, for `--banderole-npm`
+    #[serde(default)]
+    pub include_npm: bool,
+    /// Start the app without the host's `NODE_PATH`, `NODE_OPTIONS` and similar variables
+    #[serde(default)]
+    pub isolate_env: bool,
+    /// Ship a small-icu node and download full ICU data on the first run that needs it
+    #[serde(default)]
+    pub lazy_icu: bool,
+    /// URL template for the ICU data zip (`{version}`, `{major}`, `{minor}`), defaults to the ICU releases
+    pub icu_data_url: Option<String>,
+    /// Where the runner of an encrypted bundle looks for its key file (`~/` expanded)
+    pub encryption_key_file: Option<String>,
+    /// Extract into the per-user cache (default) or the machine-wide one
+    #[serde(default)]
+    pub cache_scope: CacheScope,
+    /// How extraction directories below the cache are named
+    #[serde(default)]
+    pub extraction_layout: ExtractionLayout,
+    /// Shell command the runner runs from the app directory once after each extraction
+    pub post_extract: Option<String>,
+    /// Opt-in startup timing metrics recorded by the runner on every launch
+    pub metrics: Option<MetricsConfig>,
+    /// Run the built executable once and fail the bundle unless it behaves as expected
+    pub smoke_test: Option<SmokeTestConfig>,
+    /// Long-running service registered on the user's machine by `--banderole-install-service`
+    pub service: Option<ServiceConfig>,
+    /// Compiler wrapper and caches the runner build uses, e.g. a CI fleet's sccache
+    pub runner_build: Option<RunnerBuildConfig>,
+    /// Commands and plugins run at points of the bundle (signing, scanning, uploading)
+    pub hooks: Option<HooksConfig>,
+    /// Per-platform additions keyed by `cfg(...)` expressions, e.g. `"cfg(windows)"`
+    #[serde(default)]
+    pub target: BTreeMap<String, TargetConfig>,
+    /// Platforms without built-in support, bundled with a Node.js build from their own URL. An
+    /// entry only takes effect when bundling on a host of its `os` and `arch`.
+    #[serde(default)]
+    pub platforms: Vec<CustomPlatform>,
+}
+
+/// Settings merged into the base configuration when the bundle's platform matches
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TargetConfig {
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub node_args: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// An entry script, either as a bare path or with a description for the generated help
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum EntrypointConfig {
+    Script(String),
+    Detailed {
+        script: String,
+        description: Option<String>,
+    },
+}
+
+impl EntrypointConfig {
+    /// Script path relative to the project directory
+    pub fn script(&self) -> &str {
+        match self {
+            Self::Script(script) | Self::Detailed { script, .. } => script,
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Self::Script(_) => None,
+            Self::Detailed { description, .. } => description.as_deref(),
+        }
+    }
+}
+
+/// A member of an app suite, either as a bare project path or with details
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum SuiteAppConfig {
+    Path(PathBuf),
+    Detailed {
+        path: PathBuf,
+        description: Option<String>,
+        /// `bin` entry to launch when the app's package.json declares several
+        entry: Option<String>,
+    },
+}
+
+impl SuiteAppConfig {
+    /// Project directory relative to the suite
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Path(path) | Self::Detailed { path, .. } => path,
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Self::Path(_) => None,
+            Self::Detailed { description, .. } => description.as_deref(),
+        }
+    }
+
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Self::Path(_) => None,
+            Self::Detailed { entry, .. } => entry.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NoticeConfig {
+    /// Path to a text file with the notice, relative to the project directory
+    pub file: Option<PathBuf>,
+    /// Inline notice text, used when `file` is not set
+    pub text: Option<String>,
+    /// Require the user to accept the notice before the app starts
+    #[serde(default)]
+    pub require_acceptance: bool,
+}
+
+/// Globs (gitignore syntax, relative to the bundled app) selecting the JavaScript files whose
+/// source is not shipped as is
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProtectConfig {
+    /// CommonJS files replaced by V8 bytecode compiled with the bundled node
+    #[serde(default)]
+    pub bytecode: Vec<String>,
+    /// Files rewritten by the obfuscator command
+    #[serde(default)]
+    pub obfuscate: Vec<String>,
+    /// Obfuscator run per file with `{input}` and `{output}` substituted, defaults to
+    /// `javascript-obfuscator {input} --output {output}`
+    pub obfuscator: Option<String>,
+}
+
+/// Where the runner reports startup timings; `BANDEROLE_METRICS_FILE` overrides the file on the
+/// machine running the bundle
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// JSON Lines file appended to on every launch (`~/` is the user's home directory)
+    pub file: Option<String>,
+    /// Shell command started on every launch with the timings as JSON in `BANDEROLE_METRICS`
+    pub command: Option<String>,
+}
+
+/// Run of the built executable checked before the bundle counts as done (`--smoke-test`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SmokeTestConfig {
+    /// Arguments the executable is started with, e.g. `["--version"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Seconds the run may take, including the first extraction (default: 60)
+    pub timeout: Option<u64>,
+    /// Exit code the run must end with (default: 0)
+    #[serde(default)]
+    pub exit_code: i32,
+    /// Text that must appear in the executable's stdout
+    #[serde(default)]
+    pub stdout_contains: Vec<String>,
+    /// Text that must appear in the executable's stderr
+    #[serde(default)]
+    pub stderr_contains: Vec<String>,
+}
+
+/// Service definition embedded into the runner; it runs the executable with `args`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ServiceConfig {
+    /// systemd unit, launchd label or scheduled task name (default: derived from the app name)
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// App arguments the service runs with, e.g. `["serve", "--port", "8080"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Account a system-wide service runs as
+    pub user: Option<String>,
+    #[serde(default)]
+    pub restart: ServiceRestart,
+}
+
+/// Shell commands run at each bundle lifecycle event, and plugins run at all of them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Before the app is collected
+    #[serde(default)]
+    pub pre_bundle: Vec<String>,
+    /// Once the payload archive is complete; it may be modified in place
+    #[serde(default)]
+    pub post_archive: Vec<String>,
+    /// Before the runner is compiled
+    #[serde(default)]
+    pub pre_build: Vec<String>,
+    /// Once the executable is written; it may be modified in place (e.g. signed)
+    #[serde(default)]
+    pub post_build: Vec<String>,
+    /// Programs (relative to the project) run for every event with its name as argument
+    #[serde(default)]
+    pub plugins: Vec<PathBuf>,
+}
+
+/// Caches for compiling the runner, shared between machines or CI jobs; the matching
+/// `--runner-*` flags take precedence
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunnerBuildConfig {
+    /// Compiler wrapper cargo runs rustc through, e.g. `sccache`
+    pub rustc_wrapper: Option<String>,
+    /// Cargo home with a pre-fetched registry (relative to the project)
+    pub cargo_home: Option<PathBuf>,
+    /// Pre-warmed cargo target directory (relative to the project)
+    pub target_dir: Option<PathBuf>,
+    /// Cargo package built instead of the embedded runner template (relative to the project)
+    pub template: Option<PathBuf>,
+    /// LTO, codegen units, opt-level, strip and panic strategy of the runner
+    #[serde(default)]
+    pub profile: RunnerProfile,
+}
+
+/// Overrides of the template's `[profile.release]`, trading build time against startup speed and
+/// size. Unset fields keep the template's defaults: fat LTO, one codegen unit, `opt-level = "z"`,
+/// stripped symbols and `panic = "abort"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunnerProfile {
+    pub lto: Option<RunnerLto>,
+    pub codegen_units: Option<u32>,
+    /// `0` to `3`, `"s"` or `"z"`
+    pub opt_level: Option<OptLevel>,
+    pub strip: Option<bool>,
+    pub panic: Option<PanicStrategy>,
+}
+
+/// Link-time optimization of the runner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunnerLto {
+    /// Whole-program optimization: smallest and fastest runner, slowest link
+    Fat,
+    /// Parallel LTO, most of the gain at a fraction of the link time
+    Thin,
+    Off,
+}
+
+/// Optimization level, as a number or a size preset
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum OptLevel {
+    Level(u8),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanicStrategy {
+    Abort,
+    Unwind,
+}
+
+impl RunnerProfile {
+    /// The `CARGO_PROFILE_RELEASE_*` variables applying these overrides
+    pub fn cargo_env(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut env = Vec::new();
+        if let Some(lto) = self.lto {
+            let value = match lto {
+                RunnerLto::Fat => "fat",
+                RunnerLto::Thin => "thin",
+                RunnerLto::Off => "off",
+            };
+            env.push(("CARGO_PROFILE_RELEASE_LTO", value.to_string()));
+        }
+        if let Some(units) = self.codegen_units {
+            anyhow::ensure!(
+                units > 0,
+                "runnerBuild.profile.codegenUnits must be at least 1"
+            );
+            env.push(("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", units.to_string()));
+        }
+        match &self.opt_level {
+            Some(OptLevel::Level(level @ 0..=3)) => {
+                env.push(("CARGO_PROFILE_RELEASE_OPT_LEVEL", level.to_string()));
+            }
+            Some(OptLevel::Name(name)) if name == "s" || name == "z" => {
+                env.push(("CARGO_PROFILE_RELEASE_OPT_LEVEL", name.clone()));
+            }
+            Some(other) => anyhow::bail!(
+                "runnerBuild.profile.optLevel must be 0, 1, 2, 3, \"s\" or \"z\", got {other:?}"
+            ),
+            None => {}
+        }
+        if let Some(strip) = self.strip {
+            env.push(("CARGO_PROFILE_RELEASE_STRIP", strip.to_string()));
+        }
+        if let Some(panic) = self.panic {
+            let value = match panic {
+                PanicStrategy::Abort => "abort",
+                PanicStrategy::Unwind => "unwind",
+            };
+            env.push(("CARGO_PROFILE_RELEASE_PANIC", value.to_string()));
+        }
+        Ok(env)
+    }
+}
+
+/// Path of a build's extraction below the cache directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractionLayout {
+    /// `<name>/<version>/<build ID>`, recognizable on servers
+    #[default]
+    Named,
+    /// `<build ID>` alone, the shortest paths
+    Flat,
+}
+
+/// When the service manager restarts the app
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceRestart {
+    Always,
+    #[default]
+    OnFailure,
+    No,
+}
+
+impl BundleConfig {
+    /// Load the configuration for a project, falling back to defaults when none is present
+    pub fn load(project_path: &Path, package_json: &Value) -> Result<Self> {
+        let toml_file = project_path.join(TOML_CONFIG_FILE_NAME);
+        let config_file = project_path.join(CONFIG_FILE_NAME);
+        if toml_file.exists() {
+            anyhow::ensure!(
+                !config_file.exists(),
+                "Both {TOML_CONFIG_FILE_NAME} and {CONFIG_FILE_NAME} exist in {}; keep one of them",
+                project_path.display()
+            );
+            let content = fs::read_to_string(&toml_file)
+                .with_context(|| format!("Failed to read {}", toml_file.display()))?;
+            return toml::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", toml_file.display()));
+        }
+        if config_file.exists() {
+            let content = fs::read_to_string(&config_file)
+                .with_context(|| format!("Failed to read {}", config_file.display()))?;
+            return serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", config_file.display()));
+        }
+
+        match package_json.get("banderole") {
+            Some(value) => serde_json::from_value(value.clone())
+                .context("Failed to parse \"banderole\" configuration in package.json"),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+impl BundleConfig {
+    /// Merge the `target` sections matching `platform`: lists are appended, env keys override
+    pub fn for_platform(mut self, platform: Platform) -> Result<Self> {
+        for (expr, section) in std::mem::take(&mut self.target) {
+            if !target_cfg::matches(&expr, platform)? {
+                continue;
+            }
+            self.include.extend(section.include);
+            self.env.extend(section.env);
+            self.node_args.extend(section.node_args);
+        }
+        Ok(self)
+    }
+}
+
+impl Default for BundleConfig {
+    fn default() -> Self {
+        // Defaults live in the serde attributes; an empty object yields all of them
+        serde_json::from_value(Value::Object(Default::default()))
+            .expect("empty bundle configuration is valid")
+    }
+}
+
+impl NoticeConfig {
+    /// Resolve the notice text, reading `file` relative to the project directory
+    pub fn resolve_text(&self, project_path: &Path) -> Result<String> {
+        if let Some(file) = &self.file {
+            let path = project_path.join(file);
+            return fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read notice file {}", path.display()));
+        }
+        self.text.clone().ok_or_else(|| {
+            anyhow::anyhow!("Notice configuration needs either \"file\" or \"text\"")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_from_package_json() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let package_json = serde_json::json!({
+            "name": "app",
+            "banderole": { "notice": { "text": "Terms", "requireAcceptance": true } }
+        });
+
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+        let notice = config.notice.unwrap();
+        assert!(notice.require_acceptance);
+        assert_eq!(notice.resolve_text(dir.path()).unwrap(), "Terms");
+        assert!(config.allow_entry_override);
+    }
+
+    #[test]
+    fn test_config_file_takes_precedence() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("EULA.txt"), "From file").unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE_NAME),
+            r#"{ "notice": { "file": "EULA.txt" } }"#,
+        )
+        .unwrap();
+        let package_json = serde_json::json!({ "banderole": { "notice": { "text": "Inline" } } });
+
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+        let notice = config.notice.unwrap();
+        assert!(!notice.require_acceptance);
+        assert_eq!(notice.resolve_text(dir.path()).unwrap(), "From file");
+    }
+
+    #[test]
+    fn test_load_from_toml() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join(TOML_CONFIG_FILE_NAME),
+            r#"
+nodeArgs = ["--max-old-space-size=4096"]
+
+[entrypoints]
+serve = "dist/serve.js"
+
+[target.'cfg(windows)']
+include = ["assets/win"]
+env = { APP_SHELL = "cmd" }
+"#,
+        )
+        .unwrap();
+        let config = BundleConfig::load(dir.path(), &serde_json::json!({})).unwrap();
+        assert_eq!(config.entrypoints["serve"].script(), "dist/serve.js");
+        let windows = config.clone().for_platform(Platform::WindowsX64).unwrap();
+        assert_eq!(windows.include, [PathBuf::from("assets/win")]);
+        assert_eq!(windows.env["APP_SHELL"], "cmd");
+        assert!(config
+            .for_platform(Platform::LinuxX64)
+            .unwrap()
+            .include
+            .is_empty());
+
+        fs::write(dir.path().join(CONFIG_FILE_NAME), "{}").unwrap();
+        let error = BundleConfig::load(dir.path(), &serde_json::json!({})).unwrap_err();
+        assert!(error.to_string().contains("keep one of them"), "{error}");
+    }
+
+    #[test]
+    fn test_entrypoints_accept_both_forms() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let package_json = serde_json::json!({
+            "banderole": {
+                "entrypoints": {
+                    "serve": "dist/serve.js",
+                    "migrate": { "script": "dist/migrate.js", "description": "Run migrations" }
+                },
+                "defaultEntrypoint": "serve"
+            }
+        });
+
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+        assert_eq!(config.entrypoints["serve"].script(), "dist/serve.js");
+        assert_eq!(config.entrypoints["serve"].description(), None);
+        assert_eq!(
+            config.entrypoints["migrate"].description(),
+            Some("Run migrations")
+        );
+        assert_eq!(config.default_entrypoint.as_deref(), Some("serve"));
+
+        let package_json = serde_json::json!({
+            "banderole": {
+                "entrypoints": {
+                    "migrate": { "script": "dist/migrate.js", "descripton": "Run migrations" }
+                }
+            }
+        });
+        assert!(BundleConfig::load(dir.path(), &package_json).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let package_json = serde_json::json!({ "banderole": { "notcie": {} } });
+        assert!(BundleConfig::load(dir.path(), &package_json).is_err());
+    }
+
+    #[test]
+    fn test_runner_profile_overrides() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let load = |profile: Value| {
+            let package_json =
+                serde_json::json!({ "banderole": { "runnerBuild": { "profile": profile } } });
+            BundleConfig::load(dir.path(), &package_json)
+                .unwrap()
+                .runner_build
+                .unwrap()
+                .profile
+        };
+
+        let profile = load(serde_json::json!({ "lto": "thin", "codegenUnits": 16, "optLevel": 3 }));
+        assert_eq!(
+            profile.cargo_env().unwrap(),
+            vec![
+                ("CARGO_PROFILE_RELEASE_LTO", "thin".to_string()),
+                ("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", "16".to_string()),
+                ("CARGO_PROFILE_RELEASE_OPT_LEVEL", "3".to_string()),
+            ]
+        );
+        let profile = load(serde_json::json!({ "optLevel": "s", "panic": "unwind" }));
+        assert_eq!(profile.cargo_env().unwrap().len(), 2);
+        assert!(load(serde_json::json!({ "optLevel": 4 }))
+            .cargo_env()
+            .is_err());
+        assert!(load(serde_json::json!({ "optLevel": "fast" }))
+            .cargo_env()
+            .is_err());
+    }
+
+    #[test]
+    fn test_target_sections_merge_for_platform() {
+        let package_json = serde_json::json!({
+            "banderole": {
+                "env": { "LOG_LEVEL": "info", "APP_MODE": "cli" },
+                "nodeArgs": ["--enable-source-maps"],
+                "target": {
+                    "cfg(windows)": { "env": { "APP_MODE": "win" }, "include": ["win-assets"] },
+                    "cfg(unix)": { "nodeArgs": ["--max-old-space-size=512"] }
+                }
+            }
+        });
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+
+        let windows = config.clone().for_platform(Platform::WindowsX64).unwrap();
+        assert_eq!(windows.env["APP_MODE"], "win");
+        assert_eq!(windows.env["LOG_LEVEL"], "info");
+        assert_eq!(windows.include, vec![PathBuf::from("win-assets")]);
+        assert_eq!(windows.node_args, vec!["--enable-source-maps"]);
+
+        let linux = config.for_platform(Platform::LinuxX64).unwrap();
+        assert_eq!(linux.env["APP_MODE"], "cli");
+        assert!(linux.include.is_empty());
+        assert_eq!(
+            linux.node_args,
+            vec!["--enable-source-maps", "--max-old-space-size=512"]
+        );
+    }
+
+    #[test]
+    fn test_custom_platforms() {
+        let package_json = serde_json::json!({
+            "banderole": {
+                "platforms": [{
+                    "name": "freebsd-x64",
+                    "os": "freebsd",
+                    "arch": "x86_64",
+                    "nodeUrl": "https://builds.example.com/v{version}/node-v{version}-freebsd-x64.tar.xz",
+                    "target": "x86_64-unknown-freebsd"
+                }]
+            }
+        });
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = BundleConfig::load(dir.path(), &package_json).unwrap();
+        let freebsd = &config.platforms[0];
+        assert_eq!(
+            freebsd.node_url("22.1.0"),
+            "https://builds.example.com/v22.1.0/node-v22.1.0-freebsd-x64.tar.xz"
+        );
+        assert_eq!(freebsd.checksums_url("22.1.0"), None);
+        // Even entries for other hosts must be verifiable or say they are not
+        let error = crate::platform::register_custom(&config.platforms).unwrap_err();
+        assert!(error.to_string().contains("checksumsUrl"), "{error}");
+
+        let mut unverified = config.platforms.clone();
+        unverified[0].insecure_skip_checksum = true;
+        // Entries for other hosts are skipped
+        crate::platform::register_custom(&unverified).unwrap();
+        assert_ne!(Platform::current(), Platform::Custom);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::manifest::BundleManifest;
+use crate::runtime_config::RuntimeKind;
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+/// Differences between the manifests of two bundles
+#[derive(Debug, Default, PartialEq)]
+pub struct BundleDiff {
+    pub app_name: String,
+    pub app_version: Option<(String, String)>,
+    pub runtime: Option<(String, String)>,
+    pub packages_added: Vec<(String, String)>,
+    pub packages_removed: Vec<(String, String)>,
+    /// Package, old version, new version
+    pub packages_changed: Vec<(String, String, String)>,
+    pub files_added: Vec<String>,
+    pub files_removed: Vec<String>,
+    pub files_modified: Vec<String>,
+}
+
+impl BundleDiff {
+    pub fn compare(old: &BundleManifest, new: &BundleManifest) -> Self {
+        let mut diff = BundleDiff {
+            app_name: new.app_name.clone(),
+            ..Default::default()
+        };
+        if old.app_version != new.app_version {
+            diff.app_version = Some((old.app_version.clone(), new.app_version.clone()));
+        }
+        if old.runtime != new.runtime || old.runtime_version != new.runtime_version {
+            diff.runtime = Some((
+                format!("{} {}", runtime_name(old.runtime), old.runtime_version),
+                format!("{} {}", runtime_name(new.runtime), new.runtime_version),
+            ));
+        }
+
+        for (location, version) in &new.packages {
+            match old.packages.get(location) {
+                None => diff
+                    .packages_added
+                    .push((package_name(location), version.clone())),
+                Some(previous) if previous != version => diff.packages_changed.push((
+                    package_name(location),
+                    previous.clone(),
+                    version.clone(),
+                )),
+                Some(_) => {}
+            }
+        }
+        for (location, version) in &old.packages {
+            if !new.packages.contains_key(location) {
+                diff.packages_removed
+                    .push((package_name(location), version.clone()));
+            }
+        }
+
+        // Runtime and dependency files are summarised above; list only the app's own files
+        let app_file = |path: &&String| {
+            path.starts_with("app/") && !path.split('/').any(|segment| segment == "node_modules")
+        };
+        for (path, file) in new.files.iter().filter(|(path, _)| app_file(path)) {
+            match old.files.get(path) {
+                None => diff.files_added.push(path["app/".len()..].to_string()),
+                Some(previous) if previous != file => {
+                    diff.files_modified.push(path["app/".len()..].to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        for path in old.files.keys().filter(app_file) {
+            if !new.files.contains_key(path) {
+                diff.files_removed.push(path["app/".len()..].to_string());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.app_version.is_none()
+            && self.runtime.is_none()
+            && self.packages_added.is_empty()
+            && self.packages_removed.is_empty()
+            && self.packages_changed.is_empty()
+            && self.files_added.is_empty()
+            && self.files_removed.is_empty()
+            && self.files_modified.is_empty()
+    }
+
+    /// One line per change, prefixed with `+`, `-` or `~`
+    pub fn render_plain(&self) -> String {
+        let mut out = String::new();
+        if let Some((old, new)) = &self.app_version {
+            let _ = writeln!(out, "~ version {old} -> {new}");
+        }
+        if let Some((old, new)) = &self.runtime {
+            let _ = writeln!(out, "~ runtime {old} -> {new}");
+        }
+        for (name, old, new) in &self.packages_changed {
+            let _ = writeln!(out, "~ {name} {old} -> {new}");
+        }
+        for (name, version) in &self.packages_added {
+            let _ = writeln!(out, "+ {name} {version}");
+        }
+        for (name, version) in &self.packages_removed {
+            let _ = writeln!(out, "- {name} {version}");
+        }
+        for path in &self.files_modified {
+            let _ = writeln!(out, "~ {path}");
+        }
+        for path in &self.files_added {
+            let _ = writeln!(out, "+ {path}");
+        }
+        for path in &self.files_removed {
+            let _ = writeln!(out, "- {path}");
+        }
+        if out.is_empty() {
+            out.push_str("No changes\n");
+        }
+        out
+    }
+
+    /// Markdown release notes describing the changes
+    pub fn render_changelog(&self) -> String {
+        let mut out = String::new();
+        match &self.app_version {
+            Some((old, new)) => {
+                let _ = writeln!(out, "## {} {old} → {new}", self.app_name);
+            }
+            None => {
+                let _ = writeln!(out, "## {}", self.app_name);
+            }
+        }
+        if self.is_empty() {
+            out.push_str("\nNo changes to the bundle contents.\n");
+            return out;
+        }
+
+        if let Some((old, new)) = &self.runtime {
+            let _ = writeln!(out, "\n### Runtime\n\n- Updated from {old} to {new}");
+        }
+
+        if !self.packages_changed.is_empty()
+            || !self.packages_added.is_empty()
+            || !self.packages_removed.is_empty()
+        {
+            out.push_str("\n### Dependencies\n\n");
+            for (name, old, new) in &self.packages_changed {
+                let verb = match compare_versions(old, new) {
+                    Ordering::Greater => "Downgraded",
+                    _ => "Upgraded",
+                };
+                let _ = writeln!(out, "- {verb} `{name}` from {old} to {new}");
+            }
+            for (name, version) in &self.packages_added {
+                let _ = writeln!(out, "- Added `{name}` {version}");
+            }
+            for (name, version) in &self.packages_removed {
+                let _ = writeln!(out, "- Removed `{name}` {version}");
+            }
+        }
+
+        if !self.files_added.is_empty()
+            || !self.files_removed.is_empty()
+            || !self.files_modified.is_empty()
+        {
+            out.push_str("\n### Files\n\n");
+            for path in &self.files_added {
+                let _ = writeln!(out, "- Added `{path}`");
+            }
+            for path in &self.files_removed {
+                let _ = writeln!(out, "- Removed `{path}`");
+            }
+            for path in &self.files_modified {
+                let _ = writeln!(out, "- Modified `{path}`");
+            }
+        }
+        out
+    }
+}
+
+pub(crate) fn runtime_name(kind: RuntimeKind) -> &'static str {
+    match kind {
+        RuntimeKind::Node => "Node.js",
+        RuntimeKind::Electron => "Electron",
+        RuntimeKind::Bun => "Bun",
+        RuntimeKind::Deno => "Deno",
+    }
+}
+
+/// `node_modules/a/node_modules/b` reads better as `a/node_modules/b`
+fn package_name(location: &str) -> String {
+    location
+        .strip_prefix("node_modules/")
+        .unwrap_or(location)
+        .to_string()
+}
+
+/// Compare dotted versions numerically where possible (`1.10.0` > `1.9.2`), textually otherwise
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |version: &str| -> Vec<String> {
+        version.split(['.', '-', '+']).map(str::to_string).collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(&b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestFile;
+    use std::collections::BTreeMap;
+
+    fn manifest(
+        version: &str,
+        node: &str,
+        packages: &[(&str, &str)],
+        files: &[(&str, &str)],
+    ) -> BundleManifest {
+        BundleManifest {
+            banderole_version: "0.0.0".into(),
+            runner_schema: crate::schema::RUNNER_SCHEMA,
+            app_name: "demo".into(),
+            app_version: version.into(),
+            runtime: RuntimeKind::Node,
+            runtime_version: node.into(),
+            bundled_at: None,
+            packages: packages
+                .iter()
+                .map(|(name, version)| (format!("node_modules/{name}"), version.to_string()))
+                .collect(),
+            files: files
+                .iter()
+                .map(|(path, hash)| {
+                    (
+                        path.to_string(),
+                        ManifestFile {
+                            size: 1,
+                            sha256: hash.to_string(),
+                        },
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+            payload: None,
+            layers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("4.17.20", "4.17.21"), Ordering::Less);
+        assert_eq!(compare_versions("2.0.0", "2.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_changelog() {
+        let old = manifest(
+            "1.0.0",
+            "20.11.0",
+            &[
+                ("lodash", "4.17.20"),
+                ("left-pad", "1.3.0"),
+                ("debug", "4.3.4"),
+            ],
+            &[
+                ("app/index.js", "a"),
+                ("app/old.js", "b"),
+                ("app/node_modules/lodash/index.js", "c"),
+                ("node/bin/node", "d"),
+            ],
+        );
+        let new = manifest(
+            "1.1.0",
+            "22.17.1",
+            &[
+                ("lodash", "4.17.21"),
+                ("debug", "4.3.1"),
+                ("chalk", "5.3.0"),
+            ],
+            &[
+                ("app/index.js", "changed"),
+                ("app/new.js", "e"),
+                ("app/node_modules/lodash/index.js", "changed"),
+                ("node/bin/node", "changed"),
+            ],
+        );
+        let diff = BundleDiff::compare(&old, &new);
+        assert_eq!(
+            diff.render_changelog(),
+            "## demo 1.0.0 → 1.1.0\n\
+             \n### Runtime\n\n- Updated from Node.js 20.11.0 to Node.js 22.17.1\n\
+             \n### Dependencies\n\n\
+             - Downgraded `debug` from 4.3.4 to 4.3.1\n\
+             - Upgraded `lodash` from 4.17.20 to 4.17.21\n\
+             - Added `chalk` 5.3.0\n\
+             - Removed `left-pad` 1.3.0\n\
+             \n### Files\n\n\
+             - Added `new.js`\n\
+             - Removed `old.js`\n\
+             - Modified `index.js`\n"
+        );
+
+        let unchanged = BundleDiff::compare(&old, &old);
+        assert!(unchanged.is_empty());
+        assert_eq!(unchanged.render_plain(), "No changes\n");
+    }
+}
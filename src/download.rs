@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::warn;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Network settings shared by every download (Node.js, runtimes, version queries)
+#[derive(Debug, Clone, Default)]
+pub struct HttpSettings {
+    /// Proxy for all requests; without it `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY` apply
+    pub proxy: Option<String>,
+    /// Extra PEM root certificates, e.g. the CA of a TLS-intercepting proxy
+    pub ca_certs: Vec<PathBuf>,
+    /// How often a failed runtime download is retried
+    pub retries: u32,
+    /// Never touch the network; only cached runtimes and version indexes are used
+    pub offline: bool,
+}
+
+/// Retries used when [`configure`] was not called
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Upper bound for the exponential backoff between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A response status that ended a request; only server-side and throttling statuses are retried
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub url: String,
+    pub status: reqwest::StatusCode,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {} for {}", self.status, self.url)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Downloaded or supplied data does not have the expected SHA-256
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub what: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Checksum mismatch for {}: expected {}, got {}",
+            self.what, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+static SETTINGS: OnceLock<HttpSettings> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Set the network settings; must happen before the first request
+pub fn configure(settings: HttpSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+/// Whether `--offline` forbids network access
+pub fn offline() -> bool {
+    SETTINGS.get().is_some_and(|settings| settings.offline)
+}
+
+/// Something that would have to be downloaded is missing from the cache in offline mode
+#[derive(Debug)]
+pub struct NotCached(pub String);
+
+impl std::fmt::Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not cached and --offline forbids downloading it; run once with network access to fill the cache",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+/// Fail with [`NotCached`] when `what` would have to be downloaded in offline mode
+pub fn ensure_online(what: &str) -> Result<()> {
+    if offline() {
+        return Err(NotCached(what.to_string()).into());
+    }
+    Ok(())
+}
+
+/// The shared HTTP client, honouring the configured proxy and certificates.
+///
+/// `NODE_EXTRA_CA_CERTS` is trusted too, since environments that need it for node usually
+/// need it for downloading node as well. Fails in offline mode, so no request slips through.
+pub fn client() -> Result<reqwest::Client> {
+    anyhow::ensure!(!offline(), "Network access is disabled by --offline");
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let settings = SETTINGS.get().cloned().unwrap_or_default();
+    let mut builder =
+        reqwest::Client::builder().user_agent(concat!("banderole/", env!("CARGO_PKG_VERSION")));
+    if let Some(proxy) = &settings.proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .with_context(|| format!("Invalid proxy URL '{proxy}'"))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+    let mut ca_certs = settings.ca_certs;
+    ca_certs.extend(
+        std::env::var_os("NODE_EXTRA_CA_CERTS")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from),
+    );
+    for path in &ca_certs {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid PEM certificate bundle {}", path.display()))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    let client = builder.build().context("Failed to create HTTP client")?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// Run `attempt` until it succeeds, waiting 1s, 2s, 4s, ... between tries.
+///
+/// Client errors (404, 403, ...) fail immediately since repeating the request cannot help.
+pub async fn with_retries<T, F, Fut>(what: &str, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let retries = SETTINGS
+        .get()
+        .map_or(DEFAULT_RETRIES, |settings| settings.retries);
+    let mut failures = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) if failures < retries && is_retryable(&e) => {
+                let delay = Duration::from_secs(1 << failures.min(5)).min(MAX_BACKOFF);
+                failures += 1;
+                warn!("{what} failed ({e:#}), retrying in {delay:?} ({failures}/{retries})");
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn is_retryable(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<HttpStatusError>() {
+        Some(e) => {
+            e.status.is_server_error()
+                || e.status == reqwest::StatusCode::REQUEST_TIMEOUT
+                || e.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        // Connection resets, timeouts and truncated bodies
+        None => error.chain().any(|cause| cause.is::<reqwest::Error>()),
+    }
+}
+
+/// Stream `url` into `path`, reporting bytes to the progress bar when the size is known
+pub async fn download_to(url: &str, path: &Path, progress: Option<&ProgressBar>) -> Result<()> {
+    let response = client()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to request {url}"))?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {} for {url}", response.status());
+    }
+
+    if let (Some(pb), Some(total)) = (progress, response.content_length()) {
+        pb.set_style(
+            ProgressStyle::with_template(
+                "[ {wide_bar} ] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        );
+        pb.set_length(total);
+    }
+
+    let mut file = fs::File::create(path)
+        .await
+        .context("Failed to create archive file")?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("Failed to read download chunk")?;
+        file.write_all(&chunk)
+            .await
+            .context("Failed to write archive chunk")?;
+        if let Some(pb) = progress {
+            pb.inc(chunk.len() as u64);
+        }
+    }
+    file.flush().await.context("Failed to flush archive file")?;
+    Ok(())
+}
+
+/// Releases requested per page of the GitHub API, its maximum
+const GITHUB_RELEASES_PER_PAGE: usize = 100;
+
+/// Newest tag among a GitHub repository's stable releases that `matches` accepts, for resolving
+/// partial versions. Older pages of releases are read until one matches or none are left.
+pub async fn find_github_release_tag(
+    repo: &str,
+    matches: impl Fn(&str) -> bool,
+) -> Result<Option<String>> {
+    let mut page = 1;
+    loop {
+        let url = format!(
+            "https://api.github.com/repos/{repo}/releases?per_page={GITHUB_RELEASES_PER_PAGE}&page={page}"
+        );
+        let releases: Vec<serde_json::Value> = client()?
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request {url}"))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse GitHub releases")?;
+        let found = releases
+            .iter()
+            .filter(|release| !release["prerelease"].as_bool().unwrap_or(false))
+            .filter_map(|release| release["tag_name"].as_str())
+            .find(|tag| matches(tag));
+        if let Some(tag) = found {
+            return Ok(Some(tag.to_string()));
+        }
+        if releases.len() < GITHUB_RELEASES_PER_PAGE {
+            return Ok(None);
+        }
+        page += 1;
+    }
+}
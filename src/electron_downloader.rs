@@ -0,0 +1,138 @@
+use crate::cache;
+use crate::download;
+use crate::platform::Platform;
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use log::info;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Downloads Electron release archives into the persistent cache, one directory per version and platform.
+pub struct ElectronDownloader {
+    platform: Platform,
+    cache_dir: PathBuf,
+    version: String,
+}
+
+impl ElectronDownloader {
+    pub fn new_with_persistent_cache(version: &str, platform: Platform) -> Result<Self> {
+        anyhow::ensure!(
+            !platform.is_musl(),
+            "Electron has no musl build; bundle Electron apps for glibc Linux"
+        );
+        anyhow::ensure!(
+            platform.custom().is_none(),
+            "Electron has no build for {platform}; only Node.js can be bundled for custom platforms"
+        );
+        Ok(Self {
+            platform,
+            cache_dir: cache::persistent_cache_dir()?,
+            version: version.to_string(),
+        })
+    }
+
+    /// Return the directory holding the unpacked Electron distribution, downloading it if needed
+    /// Extraction directory in the persistent cache: `electron/<version>/<platform>`
+    fn dist_dir(&self) -> PathBuf {
+        self.cache_dir
+            .join("electron")
+            .join(&self.version)
+            .join(self.platform.to_string())
+    }
+
+    /// The cached distribution directory, when this version was downloaded before
+    pub fn cached_dir(&self) -> Option<PathBuf> {
+        let dist_dir = self.dist_dir();
+        dist_dir
+            .join(self.platform.electron_executable_path())
+            .exists()
+            .then_some(dist_dir)
+    }
+
+    pub async fn ensure_electron_dist_with_progress(
+        &self,
+        progress: Option<&ProgressBar>,
+    ) -> Result<PathBuf> {
+        let dist_dir = self.dist_dir();
+        let executable = dist_dir.join(self.platform.electron_executable_path());
+        if executable.exists() {
+            return Ok(dist_dir);
+        }
+
+        info!("Fetching Electron {} for {}", self.version, self.platform);
+        if dist_dir.exists() {
+            fs::remove_dir_all(&dist_dir)
+                .await
+                .context("Failed to remove incomplete Electron download")?;
+        }
+        fs::create_dir_all(&dist_dir)
+            .await
+            .context("Failed to create electron cache directory")?;
+
+        let archive_name = self.platform.electron_archive_name(&self.version);
+        let url = format!(
+            "https://github.com/electron/electron/releases/download/v{}/{}",
+            self.version, archive_name
+        );
+        let archive_path = dist_dir.join(&archive_name);
+        download::download_to(&url, &archive_path, progress)
+            .await
+            .context("Failed to download Electron")?;
+
+        if let Some(pb) = progress {
+            pb.set_message("Extracting Electron");
+        }
+        let extract_dir = dist_dir.clone();
+        let archive = archive_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let file = std::fs::File::open(&archive).context("Failed to open Electron archive")?;
+            let mut zip = zip::ZipArchive::new(file).context("Failed to read Electron archive")?;
+            // `extract` recreates symlinks, which the macOS framework bundle relies on
+            zip.extract(&extract_dir)
+                .context("Failed to extract Electron archive")?;
+            Ok(())
+        })
+        .await??;
+        fs::remove_file(&archive_path)
+            .await
+            .context("Failed to remove archive file")?;
+
+        anyhow::ensure!(
+            executable.exists(),
+            "Electron executable not found after extraction: {}",
+            executable.display()
+        );
+        Ok(dist_dir)
+    }
+}
+
+/// Electron version for the project: the installed `node_modules/electron`, else an exact
+/// (or `^`/`~`-prefixed) version in `dependencies`/`devDependencies`.
+pub fn detect_electron_version(project_path: &Path, package_json: &Value) -> Result<String> {
+    let installed = project_path
+        .join("node_modules")
+        .join("electron")
+        .join("package.json");
+    if let Ok(content) = std::fs::read_to_string(&installed) {
+        if let Ok(value) = serde_json::from_str::<Value>(&content) {
+            if let Some(version) = value["version"].as_str() {
+                return Ok(version.to_string());
+            }
+        }
+    }
+
+    let spec = ["dependencies", "devDependencies"]
+        .iter()
+        .find_map(|field| package_json[field]["electron"].as_str())
+        .context("--electron requires an `electron` dependency in package.json")?;
+    let version = spec.trim().trim_start_matches(['^', '~', '=', 'v']);
+    anyhow::ensure!(
+        version.split('.').count() == 3
+            && version
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())),
+        "Cannot determine an exact Electron version from '{spec}'; install dependencies first"
+    );
+    Ok(version.to_string())
+}
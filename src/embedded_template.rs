@@ -1,12 +1,101 @@
 use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Runner sources written under `src/`, as (file name, contents)
+const TEMPLATE_SOURCES: &[(&str, &str)] = &[
+    ("main.rs", include_str!("template/src/main.rs")),
+    ("app_env.rs", include_str!("template/src/app_env.rs")),
+    ("arch_check.rs", include_str!("template/src/arch_check.rs")),
+    ("args.rs", include_str!("template/src/args.rs")),
+    ("bin_shims.rs", include_str!("template/src/bin_shims.rs")),
+    (
+        "cache_location.rs",
+        include_str!("template/src/cache_location.rs"),
+    ),
+    ("clean.rs", include_str!("template/src/clean.rs")),
+    (
+        "completions.rs",
+        include_str!("template/src/completions.rs"),
+    ),
+    ("config.rs", include_str!("template/src/config.rs")),
+    ("disk_space.rs", include_str!("template/src/disk_space.rs")),
+    ("entry.rs", include_str!("template/src/entry.rs")),
+    ("exit_code.rs", include_str!("template/src/exit_code.rs")),
+    ("glob.rs", include_str!("template/src/glob.rs")),
+    ("health.rs", include_str!("template/src/health.rs")),
+    ("icu.rs", include_str!("template/src/icu.rs")),
+    ("integrity.rs", include_str!("template/src/integrity.rs")),
+    ("layers.rs", include_str!("template/src/layers.rs")),
+    ("log.rs", include_str!("template/src/log.rs")),
+    ("long_path.rs", include_str!("template/src/long_path.rs")),
+    ("metrics.rs", include_str!("template/src/metrics.rs")),
+    ("notice.rs", include_str!("template/src/notice.rs")),
+    (
+        "package_entry.rs",
+        include_str!("template/src/package_entry.rs"),
+    ),
+    (
+        "payload_crypto.rs",
+        include_str!("template/src/payload_crypto.rs"),
+    ),
+    (
+        "payload_key.rs",
+        include_str!("template/src/payload_key.rs"),
+    ),
+    ("pnp.rs", include_str!("template/src/pnp.rs")),
+    (
+        "post_extract.rs",
+        include_str!("template/src/post_extract.rs"),
+    ),
+    ("preload.rs", include_str!("template/src/preload.rs")),
+    ("progress.rs", include_str!("template/src/progress.rs")),
+    (
+        "runtime_download.rs",
+        include_str!("template/src/runtime_download.rs"),
+    ),
+    ("schema.rs", include_str!("template/src/schema.rs")),
+    ("service.rs", include_str!("template/src/service.rs")),
+    (
+        "shared_deps.rs",
+        include_str!("template/src/shared_deps.rs"),
+    ),
+    (
+        "shared_runtime.rs",
+        include_str!("template/src/shared_runtime.rs"),
+    ),
+    ("signals.rs", include_str!("template/src/signals.rs")),
+    (
+        "system_cache.rs",
+        include_str!("template/src/system_cache.rs"),
+    ),
+];
+
+/// Lockfile and sources of the runner's dependencies (`vendor/`), packed by build.rs
+#[cfg(feature = "vendored-runner")]
+const VENDORED_CRATES: Option<&[u8]> = Some(include_bytes!(concat!(
+    env!("OUT_DIR"),
+    "/runner-vendor.tar.gz"
+)));
+#[cfg(not(feature = "vendored-runner"))]
+const VENDORED_CRATES: Option<&[u8]> = None;
+
+/// Cargo configuration building the runner from `vendor/` instead of crates.io
+const VENDORED_CARGO_CONFIG: &str = r#"[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"#;
 
 /// Embedded template files
 pub struct EmbeddedTemplate {
     pub cargo_toml: &'static str,
     pub build_rs: &'static str,
-    pub main_rs: &'static str,
+    pub sources: &'static [(&'static str, &'static str)],
+    /// tar.gz of `Cargo.lock` and `vendor/` when built with the `vendored-runner` feature
+    pub vendored_crates: Option<&'static [u8]>,
 }
 
 impl EmbeddedTemplate {
@@ -15,28 +104,176 @@ impl EmbeddedTemplate {
         Self {
             cargo_toml: include_str!("template/crg.toml"),
             build_rs: include_str!("template/build.rs"),
-            main_rs: include_str!("template/src/main.rs"),
+            sources: TEMPLATE_SOURCES,
+            vendored_crates: VENDORED_CRATES,
         }
     }
 
-    /// Write the template files to a build directory
+    /// Hex SHA-256 over all template files, used to key shared runner build caches
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        let parts = [self.cargo_toml, self.build_rs].into_iter().chain(
+            self.sources
+                .iter()
+                .flat_map(|(name, content)| [*name, *content]),
+        );
+        for part in parts.map(str::as_bytes).chain(self.vendored_crates) {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Write the template files to a build directory.
+    ///
+    /// Files that already hold the same contents are left untouched, so cargo does not
+    /// recompile a persistent build directory just because it was written again.
     pub fn write_to_dir(&self, build_dir: &Path) -> Result<()> {
         // Create the src directory
         let src_dir = build_dir.join("src");
         fs::create_dir_all(&src_dir).context("Failed to create src directory")?;
 
-        // Write Cargo.toml
-        let cargo_toml_path = build_dir.join("Cargo.toml");
-        fs::write(&cargo_toml_path, self.cargo_toml).context("Failed to write Cargo.toml")?;
+        write_if_changed(&build_dir.join("Cargo.toml"), self.cargo_toml)
+            .context("Failed to write Cargo.toml")?;
+        write_if_changed(&build_dir.join("build.rs"), self.build_rs)
+            .context("Failed to write build.rs")?;
+        for (name, content) in self.sources {
+            write_if_changed(&src_dir.join(name), content)
+                .with_context(|| format!("Failed to write src/{name}"))?;
+        }
+        if let Some(vendored) = self.vendored_crates {
+            write_vendored_crates(build_dir, vendored)?;
+        }
 
-        // Write build.rs
-        let build_rs_path = build_dir.join("build.rs");
-        fs::write(&build_rs_path, self.build_rs).context("Failed to write build.rs")?;
+        Ok(())
+    }
+}
+
+/// Runner template supplied with `--runner-template`: a cargo package whose build script embeds
+/// the data files banderole writes next to its Cargo.toml, like the built-in template's does
+pub struct CustomTemplate {
+    /// Files relative to the template directory, with their contents
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
 
-        // Write src/main.rs
-        let main_rs_path = src_dir.join("main.rs");
-        fs::write(&main_rs_path, self.main_rs).context("Failed to write src/main.rs")?;
+impl CustomTemplate {
+    /// Read every file of the template directory, except cargo's `target/` and `.git/`
+    pub fn load(dir: &Path) -> Result<Self> {
+        anyhow::ensure!(
+            dir.join("Cargo.toml").is_file(),
+            "Runner template {} has no Cargo.toml; `banderole runner-template <dir>` writes the built-in one as a starting point",
+            dir.display()
+        );
+        let mut files = Vec::new();
+        let walker = walkdir::WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() != 1 || !matches!(entry.file_name().to_str(), Some("target" | ".git"))
+            });
+        for entry in walker {
+            let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let contents = fs::read(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+            let relative = entry.path().strip_prefix(dir)?.to_path_buf();
+            files.push((relative, contents));
+        }
+        Ok(Self { files })
+    }
 
+    /// Hex SHA-256 over all template files, used to key shared runner build caches
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (path, contents) in &self.files {
+            let path = path.to_string_lossy();
+            hasher.update((path.len() as u64).to_le_bytes());
+            hasher.update(path.as_bytes());
+            hasher.update((contents.len() as u64).to_le_bytes());
+            hasher.update(contents);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Write the template files to a build directory, leaving unchanged files untouched
+    pub fn write_to_dir(&self, build_dir: &Path) -> Result<()> {
+        for (path, contents) in &self.files {
+            let target = build_dir.join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            write_if_changed(&target, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
         Ok(())
     }
 }
+
+/// Unpack the vendored dependencies into `build_dir` and point cargo at them, so the runner
+/// builds without network access. Skipped when the same archive was unpacked before.
+fn write_vendored_crates(build_dir: &Path, vendored: &[u8]) -> Result<()> {
+    let stamp_path = build_dir.join("vendor").join(".banderole-stamp");
+    let stamp = format!("{:x}", Sha256::digest(vendored));
+    if fs::read_to_string(&stamp_path).is_ok_and(|existing| existing == stamp) {
+        return Ok(());
+    }
+    let _ = fs::remove_dir_all(build_dir.join("vendor"));
+    tar::Archive::new(flate2::read::GzDecoder::new(vendored))
+        .unpack(build_dir)
+        .context("Failed to unpack the vendored runner crates")?;
+    let cargo_dir = build_dir.join(".cargo");
+    fs::create_dir_all(&cargo_dir).context("Failed to create .cargo directory")?;
+    write_if_changed(&cargo_dir.join("config.toml"), VENDORED_CARGO_CONFIG)
+        .context("Failed to write .cargo/config.toml")?;
+    // Written last, so an interrupted unpack is redone
+    fs::write(&stamp_path, stamp).context("Failed to write the vendored crates stamp")?;
+    Ok(())
+}
+
+/// Write `contents` to `path` unless it already holds exactly that, keeping its mtime
+pub(crate) fn write_if_changed(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let contents = contents.as_ref();
+    if fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_template_skips_build_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let template = EmbeddedTemplate {
+            vendored_crates: None,
+            ..EmbeddedTemplate::new()
+        };
+        template.write_to_dir(dir.path()).unwrap();
+        let custom = CustomTemplate::load(dir.path()).unwrap();
+
+        fs::create_dir_all(dir.path().join("target/release")).unwrap();
+        fs::write(dir.path().join("target/release/runner"), "binary").unwrap();
+        assert_eq!(
+            CustomTemplate::load(dir.path()).unwrap().checksum(),
+            custom.checksum()
+        );
+
+        let build = tempfile::TempDir::new().unwrap();
+        custom.write_to_dir(build.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(build.path().join("src/main.rs")).unwrap(),
+            include_str!("template/src/main.rs")
+        );
+        assert!(!build.path().join("target").exists());
+        assert!(CustomTemplate::load(&build.path().join("src")).is_err());
+    }
+}
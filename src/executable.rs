@@ -1,60 +1,269 @@
 use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
-use log::{error, info};
+use log::{debug, error, info, warn};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use tempfile::TempDir;
 use uuid::Uuid;
 
-use crate::embedded_template::EmbeddedTemplate;
+use crate::bundler::layers::Layers;
+use crate::cache;
+use crate::config::RunnerProfile;
+use crate::download;
+use crate::embedded_template::{write_if_changed, CustomTemplate, EmbeddedTemplate};
+use crate::manifest::{BundleManifest, ManifestFile};
 use crate::platform::Platform;
+use crate::runtime_config::RuntimeConfig;
 use crate::rust_toolchain::RustToolchain;
 
-/// Create a cross-platform Rust executable with embedded data while reporting progress to the provided ProgressBar if any0
+/// Windows subsystem the runner is linked for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum WindowsSubsystem {
+    /// Regular console application
+    #[default]
+    Console,
+    /// GUI application: no console window is created for the runner or for node
+    Gui,
+}
+
+/// Settings that affect how the runner binary is compiled
+#[derive(Debug, Clone, Default)]
+pub struct RunnerBuildOptions {
+    pub windows_subsystem: WindowsSubsystem,
+    /// Link the C runtime statically (`+crt-static`), e.g. for a glibc-free Linux runner
+    pub crt_static: bool,
+    /// Oldest glibc the Linux runner must run on (e.g. `2.17`), built with cargo-zigbuild
+    pub glibc: Option<String>,
+    /// Compress the finished runner with UPX (needs `upx` on the PATH)
+    pub upx: bool,
+    /// Passphrase the embedded payload is encrypted with (`--encrypt`)
+    pub encryption_key: Option<String>,
+    /// Compiler wrapper for the runner build, e.g. `sccache` (`RUSTC_WRAPPER`)
+    pub rustc_wrapper: Option<String>,
+    /// Cargo home with the registry the runner's crates come from (`CARGO_HOME`)
+    pub cargo_home: Option<PathBuf>,
+    /// Target directory the runner is built in instead of banderole's cache
+    pub target_dir: Option<PathBuf>,
+    /// Overrides of the runner's release profile
+    pub profile: RunnerProfile,
+    /// Cargo package built instead of the embedded runner template
+    pub template: Option<PathBuf>,
+    /// Give the bundle a random build ID instead of one derived from its contents, so it never
+    /// shares an extraction with an identical bundle
+    pub unique_build_id: bool,
+}
+
+/// Create a cross-platform Rust executable with embedded data while reporting progress to the provided ProgressBar if any0.
+///
+/// Returns the size of the compressed payload layers embedded into the executable.
 pub fn create_self_extracting_executable_with_progress(
     output_path: &Path,
-    zip_data: Vec<u8>,
-    app_name: &str,
+    layers: Layers,
+    runtime_config: &RuntimeConfig,
+    manifest: &BundleManifest,
+    runner: &RunnerBuildOptions,
+    platform: Platform,
     progress: Option<&ProgressBar>,
-) -> Result<()> {
+) -> Result<u64> {
     if let Err(e) = RustToolchain::check_availability() {
         error!("\nError: {e}");
         error!("{}", RustToolchain::get_installation_instructions());
         return Err(e);
     }
 
-    let build_id = Uuid::new_v4().to_string();
-
-    let temp_dir = TempDir::new().context("Failed to create temporary directory")?;
-    let build_dir = temp_dir.path();
+    // The runner crate lives next to its artifacts in the shared target directory, so a bundle
+    // only recompiles the runner crate with the new data and relinks; dependencies and the
+    // template stay fresh. The lock keeps concurrent bundles from swapping each other's data.
+    let custom_template = runner
+        .template
+        .as_deref()
+        .map(CustomTemplate::load)
+        .transpose()?;
+    let template_checksum = match &custom_template {
+        Some(template) => template.checksum(),
+        None => EmbeddedTemplate::new().checksum(),
+    };
+    let target_dir = runner_target_dir(runner, platform, &template_checksum)?;
+    let build_dir = &target_dir.join(cache::RUNNER_CRATE_DIR);
+    fs::create_dir_all(build_dir).context("Failed to create runner build directory")?;
+    let _lock = cache::lock_exclusive_blocking(&target_dir.join("build.lock"))?;
 
-    copy_template_to_build_dir(build_dir)?;
+    match &custom_template {
+        Some(template) => template
+            .write_to_dir(build_dir)
+            .context("Failed to write the runner template to the build directory")?,
+        None => copy_template_to_build_dir(build_dir)?,
+    }
 
-    // For improved compression ratio, store an xz-compressed stream of the zip payload.
-    // The template executable will decompress XZ first, then read the inner zip.
-    let xz_path = build_dir.join("embedded_data.xz");
-    {
+    // For improved compression ratio, store an xz-compressed stream of each payload layer.
+    // The template executable will decompress XZ first, then read the inner zip. Layers the
+    // bundle does not have are written empty, so no earlier bundle's data is embedded.
+    let embed = |zip_data: Option<&[u8]>, file_name: &str| -> Result<Option<ManifestFile>> {
+        use sha2::{Digest, Sha256};
         use std::io::Cursor;
+        let path = build_dir.join(file_name);
+        let Some(zip_data) = zip_data else {
+            write_if_changed(&path, Vec::new())
+                .with_context(|| format!("Failed to write {file_name}"))?;
+            return Ok(None);
+        };
         let mut xz_bytes: Vec<u8> = Vec::new();
-        let mut reader = Cursor::new(&zip_data);
+        let mut reader = Cursor::new(zip_data);
         lzma_rs::xz_compress(&mut reader, &mut xz_bytes)
             .context("Failed to XZ-compress embedded payload")?;
-        fs::write(&xz_path, &xz_bytes).context("Failed to write embedded xz data")?;
-    }
+        if let Some(key) = &runner.encryption_key {
+            xz_bytes = encrypt_payload(&xz_bytes, key)?;
+        }
+        fs::write(&path, &xz_bytes).with_context(|| format!("Failed to write {file_name}"))?;
+        // Lets `banderole verify` find the payload in the executable and check it is untouched
+        Ok(Some(ManifestFile {
+            size: xz_bytes.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&xz_bytes)),
+        }))
+    };
+    let payload = embed(Some(&layers.app), "embedded_data.xz")?;
+    let layer_files: Vec<ManifestFile> = [
+        embed(layers.deps.as_deref(), "embedded_deps.xz")?,
+        embed(layers.runtime.as_deref(), "embedded_runtime.xz")?,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let payload_size = payload
+        .iter()
+        .chain(&layer_files)
+        .map(|file| file.size)
+        .sum();
+    let manifest = BundleManifest {
+        payload,
+        layers: layer_files,
+        ..manifest.clone()
+    };
+
+    let runtime_config_path = build_dir.join("runtime_config.json");
+    let runtime_config_json =
+        serde_json::to_string(runtime_config).context("Failed to serialize runtime config")?;
+
+    // Names the extraction directory on users' machines
+    let build_id = if runner.unique_build_id {
+        Uuid::new_v4().to_string()
+    } else {
+        content_build_id(&layers, &manifest, &runtime_config_json, &template_checksum)?
+    };
+    debug!("Build ID {build_id}");
+    write_if_changed(&build_dir.join("build_id.txt"), build_id)
+        .context("Failed to write build ID")?;
 
-    let build_id_path = build_dir.join("build_id.txt");
-    fs::write(&build_id_path, &build_id).context("Failed to write build ID")?;
+    write_if_changed(&runtime_config_path, runtime_config_json)
+        .context("Failed to write runtime config")?;
 
-    update_cargo_toml(build_dir, app_name)?;
+    // Kept readable in the binary so `banderole diff` can compare bundles without running them
+    write_if_changed(&build_dir.join("manifest.bin"), manifest.to_embedded()?)
+        .context("Failed to write bundle manifest")?;
 
     info!("Building native binary...");
-    build_executable_with_progress(build_dir, output_path, app_name, progress)?;
+    build_executable_with_progress(
+        build_dir,
+        &target_dir,
+        output_path,
+        runner,
+        platform,
+        progress,
+    )?;
+    if runner.profile.strip != Some(false) {
+        strip_runner(output_path, platform);
+    }
+    if let (Some(dir), Some(_)) = (&runner.template, &custom_template) {
+        check_custom_runner(output_path, &manifest, dir)?;
+    }
+    if runner.upx {
+        compress_with_upx(output_path, platform, &manifest)?;
+    }
     info!("Native binary built");
 
+    Ok(payload_size)
+}
+
+/// Fail when a runner built from a custom template lacks part of the payload, which its
+/// `build.rs` must embed like the built-in one does
+fn check_custom_runner(path: &Path, manifest: &BundleManifest, template: &Path) -> Result<()> {
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if let Err((_, problem)) = crate::verify::find_payload(&data, manifest) {
+        anyhow::bail!(
+            "The runner built from {} does not embed the whole payload ({problem}); its build.rs must include embedded_data.xz, embedded_deps.xz and embedded_runtime.xz like the built-in template's",
+            template.display()
+        );
+    }
     Ok(())
 }
 
+/// Digest of everything that decides what an extraction holds: the payload files and their
+/// modes, the runtime config and the runner. Rebuilding an unchanged app gives the same ID, so
+/// users keep their extraction; identical bundles share one.
+fn content_build_id(
+    layers: &Layers,
+    manifest: &BundleManifest,
+    runtime_config_json: &str,
+    template_checksum: &str,
+) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Cursor;
+    let mut hasher = Sha256::new();
+    for part in [template_checksum, runtime_config_json] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    // The manifest hashes contents only; modes are taken from the archives
+    for (path, file) in &manifest.files {
+        hasher.update(format!("{path}\0{}\n", file.sha256));
+    }
+    let zips = [
+        Some(&layers.app),
+        layers.deps.as_ref(),
+        layers.runtime.as_ref(),
+    ];
+    for zip_data in zips.into_iter().flatten() {
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip_data.as_slice()))
+            .context("Failed to read bundle payload")?;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            hasher.update(format!(
+                "{}\0{:o}\n",
+                file.name(),
+                file.unix_mode().unwrap_or(0)
+            ));
+        }
+    }
+    // As long as a UUID, which keeps extraction paths short on Windows
+    Ok(format!("{:x}", hasher.finalize())[..32].to_string())
+}
+
+/// `plain` encrypted for [`payload_crypto::decrypt`](crate::payload_crypto::decrypt) with a
+/// random salt, which gives every bundle its own key, and a random nonce
+fn encrypt_payload(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    use crate::payload_crypto::{derive_key, HEADER_LEN, MAGIC, NONCE_LEN, SALT_LEN};
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+    let mut header = [0u8; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    getrandom::fill(&mut header[MAGIC.len()..])
+        .map_err(|e| anyhow::anyhow!("Failed to generate a salt and nonce: {e}"))?;
+    let (salt, nonce) = header[MAGIC.len()..].split_at(SALT_LEN);
+    debug_assert_eq!(nonce.len(), NONCE_LEN);
+    let key = derive_key(passphrase, salt);
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: plain,
+                aad: &header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt the payload"))?;
+    Ok([header.as_slice(), &ciphertext].concat())
+}
+
 fn copy_template_to_build_dir(build_dir: &Path) -> Result<()> {
     // Use embedded template files instead of filesystem copy
     let template = EmbeddedTemplate::new();
@@ -65,64 +274,106 @@ fn copy_template_to_build_dir(build_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn update_cargo_toml(build_dir: &Path, app_name: &str) -> Result<()> {
-    let cargo_toml_path = build_dir.join("Cargo.toml");
-    let cargo_content =
-        fs::read_to_string(&cargo_toml_path).context("Failed to read Cargo.toml")?;
-
-    // Replace the package name
-    let updated_content = cargo_content.replace(
-        r#"name = "banderole-app""#,
-        &format!(r#"name = "{}""#, sanitize_package_name(app_name)),
-    );
-
-    fs::write(&cargo_toml_path, updated_content).context("Failed to write updated Cargo.toml")?;
-
-    Ok(())
+/// Link settings of `runner` that apply to `platform`: static CRT and minimum glibc
+fn link_variant(runner: &RunnerBuildOptions, platform: Platform) -> (bool, Option<&str>) {
+    let crt_static = runner.crt_static && platform.os() != "macos";
+    let glibc = runner
+        .glibc
+        .as_deref()
+        .filter(|_| platform.os() == "linux" && !platform.is_musl());
+    (crt_static, glibc)
 }
 
-fn sanitize_package_name(name: &str) -> String {
-    // Rust package names must be valid identifiers
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '-'
-            }
-        })
-        .collect::<String>()
-        .trim_start_matches(|c: char| c.is_numeric() || c == '-')
-        .to_string()
+/// Target directory shared across projects so the runner is compiled once and later bundles only
+/// rebuild the crate holding their data; link variants and profiles get their own so they never
+/// invalidate each other's artifacts
+fn runner_target_dir(
+    runner: &RunnerBuildOptions,
+    platform: Platform,
+    template_checksum: &str,
+) -> Result<PathBuf> {
+    let (crt_static, glibc) = link_variant(runner, platform);
+    let mut cache_key = get_target_triple(&platform);
+    if let Some(version) = glibc {
+        cache_key.push_str(&format!(".{version}"));
+    }
+    if crt_static {
+        cache_key.push_str("-crt-static");
+    }
+    let profile_env = runner.profile.cargo_env()?;
+    if !profile_env.is_empty() {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(format!("{profile_env:?}"));
+        cache_key.push_str(&format!("-profile-{:x}", digest)[..17]);
+    }
+    let target_dir =
+        cache::runner_target_dir(runner.target_dir.as_deref(), template_checksum, &cache_key)?;
+    debug!("Using runner target directory {}", target_dir.display());
+    Ok(target_dir)
 }
 
 fn build_executable_with_progress(
     build_dir: &Path,
+    target_dir: &Path,
     output_path: &Path,
-    app_name: &str,
+    runner: &RunnerBuildOptions,
+    platform: Platform,
     progress: Option<&ProgressBar>,
 ) -> Result<()> {
-    let current_platform = Platform::current();
-    let target_triple = get_target_triple(&current_platform);
+    let target_triple = get_target_triple(&platform);
 
     // Ensure we have the target installed
     install_rust_target(&target_triple)?;
 
+    if runner.windows_subsystem == WindowsSubsystem::Gui && !platform.is_windows() {
+        warn!("--windows-subsystem gui only affects Windows targets; ignoring");
+    }
+    let (crt_static, glibc) = link_variant(runner, platform);
+    if runner.crt_static && !crt_static {
+        warn!("--runner-crt-static is not supported for macOS targets; ignoring");
+    }
+    if runner.glibc.is_some() && glibc.is_none() {
+        warn!("--runner-glibc only affects glibc Linux targets; ignoring");
+    }
+    if glibc.is_some() {
+        ensure_zigbuild_available()?;
+    }
+
     // Do not show a determinate bar until we know the total
 
     // Actual build; consume Cargo JSON messages to compute progress without a dry-run
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(build_dir)
+    // cargo-zigbuild links against the glibc named in the target suffix (`<triple>.2.17`)
+    // but keeps artifacts under the plain triple
+    let build_target = match glibc {
+        Some(version) => format!("{target_triple}.{version}"),
+        None => target_triple.clone(),
+    };
+    let mut cmd = cargo_command(build_dir, runner);
+    cmd.envs(runner.profile.cargo_env()?);
+    cmd.env("CARGO_TARGET_DIR", target_dir)
+        .env(
+            "BANDEROLE_WINDOWS_SUBSYSTEM",
+            match runner.windows_subsystem {
+                WindowsSubsystem::Console => "console",
+                WindowsSubsystem::Gui => "windows",
+            },
+        )
         .args([
-            "build",
+            if glibc.is_some() { "zigbuild" } else { "build" },
             "--release",
             "--target",
-            &target_triple,
+            &build_target,
             "--message-format",
             "json",
         ])
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped());
+    if crt_static {
+        // With --target, RUSTFLAGS only reach the runner itself, not build scripts or proc macros
+        let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+        rustflags.push_str(" -C target-feature=+crt-static");
+        cmd.env("RUSTFLAGS", rustflags.trim_start());
+    }
 
     let mut child = cmd.spawn().context("Failed to execute cargo build")?;
 
@@ -139,7 +390,8 @@ fn build_executable_with_progress(
     let compiled_for_stdout = Arc::clone(&compiled_count);
     // Determine total crates using cargo metadata (no dry run, no stderr parsing)
     // Determine total first, before spawning cargo; don't show bar until known
-    let known_total: u64 = compute_total_via_cargo_metadata(build_dir, &target_triple).unwrap_or(0);
+    let known_total: u64 =
+        compute_total_via_cargo_metadata(build_dir, runner, &target_triple).unwrap_or(0);
     // Determine total compile units using cargo metadata; only then show a determinate bar
     if let Some(pb) = progress {
         if known_total > 0 {
@@ -161,6 +413,8 @@ fn build_executable_with_progress(
                 std::collections::HashSet::new();
             let mut compiled_artifacts: std::collections::HashSet<String> =
                 std::collections::HashSet::new();
+            // The runner binary is the last executable artifact
+            let mut executable = None;
             for line in reader.lines() {
                 let line = match line {
                     Ok(l) => l,
@@ -191,9 +445,13 @@ fn build_executable_with_progress(
                             if !is_fresh {
                                 compiled_artifacts.insert(key);
                             }
+                            if let Some(path) = value.get("executable").and_then(|e| e.as_str()) {
+                                executable = Some(PathBuf::from(path));
+                            }
 
-                            // Update compiled counter and progress position
-                            let compiled_now = compiled_artifacts.len() as u64;
+                            // Update compiled counter and progress position; fresh artifacts
+                            // reused from the shared target dir count as done
+                            let compiled_now = total_artifacts.len() as u64;
                             compiled_for_stdout.store(compiled_now, Ordering::SeqCst);
                             if let Some(pb) = &pb_for_stdout {
                                 let total_len = known_total;
@@ -211,6 +469,7 @@ fn build_executable_with_progress(
                     }
                 }
             }
+            executable
         })
     });
 
@@ -314,9 +573,7 @@ fn build_executable_with_progress(
     }
 
     let status = child.wait().context("Failed to wait for cargo build")?;
-    if let Some(h) = stdout_handle {
-        let _ = h.join();
-    }
+    let reported_executable = stdout_handle.and_then(|h| h.join().ok().flatten());
     if let Some(h) = stderr_handle {
         let _ = h.join();
     }
@@ -353,19 +610,19 @@ fn build_executable_with_progress(
         );
     }
 
-    // Get the sanitized package name to find the correct executable
-    let package_name = sanitize_package_name(app_name);
-    let executable_name = if current_platform.is_windows() {
-        format!("{package_name}.exe")
-    } else {
-        package_name
-    };
-
-    let built_executable = build_dir
-        .join("target")
-        .join(&target_triple)
-        .join("release")
-        .join(executable_name);
+    // The runner crate keeps the template's package name for every app, so its artifacts are
+    // reused; the app's name only appears in `output_path`. Custom templates name their own.
+    let built_executable = reported_executable.unwrap_or_else(|| {
+        let executable_name = if platform.is_windows() {
+            "banderole-app.exe"
+        } else {
+            "banderole-app"
+        };
+        target_dir
+            .join(&target_triple)
+            .join("release")
+            .join(executable_name)
+    });
 
     if !built_executable.exists() {
         anyhow::bail!(
@@ -394,13 +651,37 @@ fn build_executable_with_progress(
     Ok(())
 }
 
-fn compute_total_via_cargo_metadata(build_dir: &Path, target_triple: &str) -> Result<u64> {
+/// `cargo` run from `build_dir` with the caches and wrapper the runner build is configured for
+fn cargo_command(build_dir: &Path, runner: &RunnerBuildOptions) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(build_dir);
+    if download::offline() {
+        // Offline bundles only build with crates already in cargo's cache
+        cmd.env("CARGO_NET_OFFLINE", "true");
+    }
+    if let Some(cargo_home) = &runner.cargo_home {
+        cmd.env("CARGO_HOME", cargo_home);
+    }
+    if let Some(wrapper) = &runner.rustc_wrapper {
+        cmd.env("RUSTC_WRAPPER", wrapper);
+    }
+    cmd
+}
+
+fn compute_total_via_cargo_metadata(
+    build_dir: &Path,
+    runner: &RunnerBuildOptions,
+    target_triple: &str,
+) -> Result<u64> {
     // Strategy: union of host + target resolve nodes, then count compile-relevant targets per package
     // Relevant targets: lib, proc-macro, custom-build for all packages; bin only for the root package
 
-    fn run_metadata(build_dir: &Path, args: &[&str]) -> Result<serde_json::Value> {
-        let output = Command::new("cargo")
-            .current_dir(build_dir)
+    fn run_metadata(
+        build_dir: &Path,
+        runner: &RunnerBuildOptions,
+        args: &[&str],
+    ) -> Result<serde_json::Value> {
+        let output = cargo_command(build_dir, runner)
             .args(args)
             .output()
             .with_context(|| format!("Failed to run cargo {}", args.join(" ")))?;
@@ -416,29 +697,10 @@ fn compute_total_via_cargo_metadata(build_dir: &Path, target_triple: &str) -> Re
         Ok(v)
     }
 
-    fn get_host_triple() -> Result<String> {
-        let output = Command::new("rustc")
-            .arg("-vV")
-            .output()
-            .context("Failed to run rustc -vV")?;
-        if !output.status.success() {
-            anyhow::bail!(
-                "rustc -vV failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            if let Some(rest) = line.strip_prefix("host: ") {
-                return Ok(rest.trim().to_string());
-            }
-        }
-        anyhow::bail!("Failed to parse host triple from rustc -vV")
-    }
-
     // Run three metadata queries: target-filtered, host-filtered, and unfiltered for packages map
     let meta_target = run_metadata(
         build_dir,
+        runner,
         &[
             "metadata",
             "--format-version",
@@ -450,6 +712,7 @@ fn compute_total_via_cargo_metadata(build_dir: &Path, target_triple: &str) -> Re
     let host_triple = get_host_triple().unwrap_or_else(|_| target_triple.to_string());
     let meta_host = run_metadata(
         build_dir,
+        runner,
         &[
             "metadata",
             "--format-version",
@@ -458,7 +721,7 @@ fn compute_total_via_cargo_metadata(build_dir: &Path, target_triple: &str) -> Re
             &host_triple,
         ],
     )?;
-    let meta_all = run_metadata(build_dir, &["metadata", "--format-version", "1"])?;
+    let meta_all = run_metadata(build_dir, runner, &["metadata", "--format-version", "1"])?;
 
     // Collect union of package ids to be considered
     let mut pkg_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
@@ -555,11 +818,216 @@ fn get_target_triple(platform: &Platform) -> String {
         Platform::MacosArm64 => "aarch64-apple-darwin".to_string(),
         Platform::LinuxX64 => "x86_64-unknown-linux-gnu".to_string(),
         Platform::LinuxArm64 => "aarch64-unknown-linux-gnu".to_string(),
+        Platform::LinuxArmv7 => "armv7-unknown-linux-gnueabihf".to_string(),
+        // Statically linked, so the runner starts on Alpine without glibc
+        Platform::LinuxX64Musl => "x86_64-unknown-linux-musl".to_string(),
+        Platform::LinuxArm64Musl => "aarch64-unknown-linux-musl".to_string(),
         Platform::WindowsX64 => "x86_64-pc-windows-msvc".to_string(),
         Platform::WindowsArm64 => "aarch64-pc-windows-msvc".to_string(),
+        Platform::Custom => platform
+            .custom()
+            .map(|custom| custom.target.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// Drop symbols the release profile left in ELF runners, e.g. those of statically linked C code.
+///
+/// Best effort: a missing `strip` only costs size. Mach-O runners are left alone since their
+/// linker signature would not survive, and MSVC keeps debug info in separate PDBs anyway.
+fn strip_runner(path: &Path, platform: Platform) {
+    if !matches!(platform.os(), "linux") {
+        return;
+    }
+    match Command::new("strip").arg("--strip-all").arg(path).output() {
+        Ok(output) if output.status.success() => debug!("Stripped {}", path.display()),
+        Ok(output) => warn!(
+            "Failed to strip the runner: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => debug!("strip not available ({e}); keeping the runner as built"),
+    }
+}
+
+/// Pack the runner with UPX, then append the manifest again since UPX compresses the copy
+/// inside the binary and `banderole inspect`/`diff` could no longer find it
+fn compress_with_upx(path: &Path, platform: Platform, manifest: &BundleManifest) -> Result<()> {
+    if platform.os() == "macos" {
+        warn!("--upx is not supported for macOS targets (packed binaries do not start); ignoring");
+        return Ok(());
     }
+    let before = fs::metadata(path)?.len();
+    let output = Command::new("upx")
+        .args(["--best", "-qq"])
+        .arg(path)
+        .output()
+        .context("--upx needs UPX on the PATH: install it from https://upx.github.io or your package manager")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "UPX failed to compress the runner: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .context("Failed to reopen the compressed runner")?;
+    std::io::Write::write_all(&mut file, &manifest.to_embedded()?)
+        .context("Failed to append the bundle manifest")?;
+    info!(
+        "UPX compressed the runner from {} to {}",
+        indicatif::HumanBytes(before),
+        indicatif::HumanBytes(fs::metadata(path)?.len())
+    );
+    Ok(())
+}
+
+/// `--runner-glibc` needs cargo-zigbuild (which in turn needs zig) on the PATH
+fn ensure_zigbuild_available() -> Result<()> {
+    let available = Command::new("cargo-zigbuild")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    anyhow::ensure!(
+        available,
+        "--runner-glibc needs cargo-zigbuild and zig: `cargo install cargo-zigbuild` and install zig (e.g. `pip install ziglang`)"
+    );
+    Ok(())
+}
+
+/// Triple of the Rust toolchain's host, from `rustc -vV`
+fn get_host_triple() -> Result<String> {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .context("Failed to run rustc -vV")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "rustc -vV failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        if let Some(rest) = line.strip_prefix("host: ") {
+            return Ok(rest.trim().to_string());
+        }
+    }
+    anyhow::bail!("Failed to parse host triple from rustc -vV")
 }
 
 fn install_rust_target(target: &str) -> Result<()> {
+    // Toolchains without rustup, like FreeBSD's rust package, always build for their own host
+    if get_host_triple().is_ok_and(|host| host == target) {
+        return Ok(());
+    }
     RustToolchain::ensure_target_installed(target)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload_crypto::{decrypt, is_encrypted, HEADER_LEN};
+
+    #[test]
+    fn test_encrypt_payload_round_trip() {
+        let plain: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let payload = encrypt_payload(&plain, "secret").unwrap();
+        assert!(is_encrypted(&payload));
+        assert!(!payload.windows(64).any(|window| window == &plain[..64]));
+        assert_eq!(decrypt(&payload, "secret").unwrap(), plain);
+        assert!(decrypt(&payload, "wrong").is_err());
+
+        // Fresh salt and nonce every time
+        assert_ne!(
+            encrypt_payload(&plain, "secret").unwrap()[..HEADER_LEN],
+            payload[..HEADER_LEN]
+        );
+
+        for tampered_at in [10, HEADER_LEN + 10, payload.len() - 1] {
+            let mut tampered = payload.clone();
+            tampered[tampered_at] ^= 1;
+            assert!(decrypt(&tampered, "secret").is_err());
+        }
+    }
+
+    #[test]
+    fn test_custom_runner_must_embed_every_layer() {
+        use sha2::{Digest, Sha256};
+        let record = |data: &[u8]| ManifestFile {
+            size: data.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(data)),
+        };
+        let app = b"\xfd7zXZ\0app payload";
+        let deps = b"\xfd7zXZ\0dependency layer";
+        let mut manifest: BundleManifest = serde_json::from_str(
+            r#"{"banderoleVersion":"0.2.1","appName":"app","appVersion":"1.0.0",
+                "runtime":"node","runtimeVersion":"22.1.0","packages":{},"files":{}}"#,
+        )
+        .unwrap();
+        manifest.payload = Some(record(app));
+        manifest.layers = vec![record(deps)];
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let runner = dir.path().join("runner");
+        fs::write(&runner, [&b"runner"[..], app, b"code"].concat()).unwrap();
+        let error = check_custom_runner(&runner, &manifest, dir.path()).unwrap_err();
+        assert!(error.to_string().contains("embedded_deps.xz"));
+
+        fs::write(&runner, [&b"runner"[..], app, deps].concat()).unwrap();
+        check_custom_runner(&runner, &manifest, dir.path()).unwrap();
+    }
+
+    #[test]
+    fn test_content_build_id() {
+        use std::io::Write;
+        let zip = |entries: &[(&str, u32)], time: zip::DateTime| {
+            let mut data = Vec::new();
+            let mut zip = zip::ZipWriter::new(std::io::Cursor::new(&mut data));
+            for (name, mode) in entries {
+                let options = zip::write::SimpleFileOptions::default()
+                    .unix_permissions(*mode)
+                    .last_modified_time(time);
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(name.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+            data
+        };
+        let time = zip::DateTime::from_date_and_time(2024, 5, 1, 12, 0, 0).unwrap();
+        let layers = |app_mode: u32, time: zip::DateTime| Layers {
+            app: zip(&[("app/index.js", app_mode)], time),
+            deps: Some(zip(&[("app/node_modules/a/index.js", 0o644)], time)),
+            runtime: None,
+        };
+        let mut manifest: BundleManifest = serde_json::from_str(
+            r#"{"banderoleVersion":"0.2.1","appName":"app","appVersion":"1.0.0",
+                "runtime":"node","runtimeVersion":"22.1.0","packages":{},
+                "files":{"app/index.js":{"size":1,"sha256":"aa"}}}"#,
+        )
+        .unwrap();
+
+        let id = content_build_id(&layers(0o644, time), &manifest, "{}", "template").unwrap();
+        assert_eq!(id.len(), 32);
+        // Rebuilding the same contents later gives the same ID
+        let later = zip::DateTime::from_date_and_time(2025, 1, 2, 3, 4, 6).unwrap();
+        assert_eq!(
+            content_build_id(&layers(0o644, later), &manifest, "{}", "template").unwrap(),
+            id
+        );
+
+        for changed in [
+            content_build_id(&layers(0o755, time), &manifest, "{}", "template").unwrap(),
+            content_build_id(&layers(0o644, time), &manifest, r#"{"a":1}"#, "template").unwrap(),
+            content_build_id(&layers(0o644, time), &manifest, "{}", "other").unwrap(),
+            // The same bytes split differently between the template checksum and the config
+            content_build_id(&layers(0o644, time), &manifest, "e{}", "templat").unwrap(),
+        ] {
+            assert_ne!(changed, id);
+        }
+        manifest.files.get_mut("app/index.js").unwrap().sha256 = "bb".into();
+        assert_ne!(
+            content_build_id(&layers(0o644, time), &manifest, "{}", "template").unwrap(),
+            id
+        );
+    }
+}
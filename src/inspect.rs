@@ -0,0 +1,82 @@
+use crate::diff::runtime_name;
+use crate::manifest::BundleManifest;
+use crate::schema::RUNNER_SCHEMA;
+use std::fmt::Write;
+
+/// Human-readable summary of a bundle's manifest
+pub fn render(manifest: &BundleManifest) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{} {}", manifest.app_name, manifest.app_version);
+    let schema = match manifest.runner_schema {
+        0 => "unrecorded".to_string(),
+        RUNNER_SCHEMA => format!("{RUNNER_SCHEMA}, current"),
+        schema => format!("{schema}, current {RUNNER_SCHEMA}"),
+    };
+    let _ = writeln!(
+        out,
+        "Built with:  banderole {} (runner schema {schema})",
+        manifest.banderole_version
+    );
+    let _ = writeln!(
+        out,
+        "Runtime:     {} {}",
+        runtime_name(manifest.runtime),
+        manifest.runtime_version
+    );
+    if let Some(bundled_at) = manifest.bundled_at {
+        let _ = writeln!(
+            out,
+            "Bundled at:  {}",
+            bundled_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    }
+    let _ = writeln!(out, "Packages:    {}", manifest.packages.len());
+    let size: u64 = manifest.files.values().map(|file| file.size).sum();
+    let _ = writeln!(
+        out,
+        "Files:       {} ({})",
+        manifest.files.len(),
+        indicatif::HumanBytes(size)
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestFile;
+    use crate::runtime_config::RuntimeKind;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_render() {
+        let manifest = BundleManifest {
+            banderole_version: "0.2.1".into(),
+            runner_schema: 0,
+            app_name: "demo".into(),
+            app_version: "1.2.3".into(),
+            runtime: RuntimeKind::Node,
+            runtime_version: "22.1.0".into(),
+            bundled_at: "2025-06-01T12:30:00Z".parse().ok(),
+            packages: BTreeMap::from([("node_modules/a".into(), "1.0.0".into())]),
+            files: BTreeMap::from([(
+                "app/index.js".into(),
+                ManifestFile {
+                    size: 2048,
+                    sha256: "00".into(),
+                },
+            )]),
+            payload: None,
+            layers: Vec::new(),
+        };
+        assert_eq!(
+            render(&manifest),
+            "demo 1.2.3\n\
+             Built with:  banderole 0.2.1 (runner schema unrecorded)\n\
+             Runtime:     Node.js 22.1.0\n\
+             Bundled at:  2025-06-01 12:30:00 UTC\n\
+             Packages:    1\n\
+             Files:       1 (2.00 KiB)\n"
+        );
+    }
+}
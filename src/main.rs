@@ -1,15 +1,45 @@
 mod bundler;
+mod cache;
+mod config;
+mod diff;
+mod download;
+mod electron_downloader;
 mod embedded_template;
 mod executable;
+#[path = "template/src/exit_code.rs"]
+mod exit_code;
+#[path = "template/src/glob.rs"]
+mod glob;
+mod inspect;
+mod manifest;
 mod node_downloader;
 mod node_version_manager;
+mod output;
+mod package;
+#[path = "template/src/package_entry.rs"]
+mod package_entry;
+#[path = "template/src/payload_crypto.rs"]
+mod payload_crypto;
 mod platform;
+mod project_input;
+mod runtime_config;
+mod runtime_provider;
 mod rust_toolchain;
+#[path = "template/src/schema.rs"]
+mod schema;
+mod size_report;
+mod target_cfg;
+mod verify;
 
-use clap::{Parser, Subcommand};
-use indicatif::MultiProgress;
+use anyhow::Context;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use executable::{RunnerBuildOptions, WindowsSubsystem};
+use indicatif::{MultiProgress, ProgressDrawTarget};
 use indicatif_log_bridge::LogWrapper;
 use log::LevelFilter;
+use node_downloader::NodeBuild;
+use runtime_config::RuntimeKind;
+use runtime_provider::RuntimeSpec;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -23,6 +53,21 @@ struct Cli {
     /// Enable verbose output
     #[arg(short, long, global = true)]
     verbose: bool,
+    /// Only print errors and warnings: no stage messages or progress bars
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Proxy for all downloads (default: HTTP_PROXY/HTTPS_PROXY/ALL_PROXY from the environment)
+    #[arg(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+    /// Extra trusted root certificates (PEM), e.g. for proxies intercepting TLS; repeatable
+    #[arg(long, global = true, value_name = "PATH")]
+    ca_cert: Vec<PathBuf>,
+    /// Retries (with exponential backoff) for failed runtime downloads
+    #[arg(long, global = true, value_name = "N", default_value_t = 3)]
+    retries: u32,
+    /// Never access the network: only cached runtimes, version indexes and runner crates are used
+    #[arg(long, global = true)]
+    offline: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -30,55 +75,603 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Bundle a Node.js project into a self-contained executable
-    Bundle {
-        /// Path to the directory containing package.json
-        path: PathBuf,
-        /// Output path for the bundle (optional)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-        /// Custom name for the executable (optional)
-        #[arg(short, long)]
-        name: Option<String>,
-        /// Disable compression for faster bundling (useful for testing)
+    Bundle(Box<BundleArgs>),
+    /// Show what a bundle built by banderole contains and which banderole built it
+    Inspect {
+        /// Executable produced by `banderole bundle`
+        executable: PathBuf,
+        /// Print the full embedded manifest as JSON
         #[arg(long)]
-        no_compression: bool,
-        /// Ignore cached version resolution results
+        json: bool,
+    },
+    /// Compare the contents of two bundles built by banderole
+    Diff {
+        /// Previous release's executable
+        old: PathBuf,
+        /// New release's executable
+        new: PathBuf,
+        /// Print Markdown release notes instead of one line per change
+        #[arg(long)]
+        changelog: bool,
+    },
+    /// Wrap a Linux bundle into a deb or rpm package
+    Package(Box<PackageArgs>),
+    /// Check a bundle's manifest, payload and signature without running it (exit code 2 if inconsistent)
+    Verify {
+        /// Executable produced by `banderole bundle`
+        executable: PathBuf,
+        /// Also fail when a check cannot run here (unsigned, UPX-compressed, encrypted without a key)
         #[arg(long)]
-        ignore_cached_versions: bool,
+        strict: bool,
+        /// Print the checks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the built-in runner template to a directory, as a starting point for --runner-template
+    RunnerTemplate {
+        /// Directory to create the template in
+        dir: PathBuf,
+    },
+    /// Print the shell completion script for banderole itself
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
     },
 }
 
+#[derive(Args)]
+struct BundleArgs {
+    /// Directory containing package.json, a .tgz/.zip of it, or an http(s) URL to such an archive
+    path: String,
+    /// Expected SHA-256 of the project archive, checked before unpacking
+    #[arg(long, value_name = "HEX")]
+    sha256: Option<String>,
+    /// Output path for the bundle (optional; with --all, the directory the executables are written to)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Custom name for the executable (optional)
+    #[arg(short, long)]
+    name: Option<String>,
+    /// Bundle every package of the workspace at PATH that declares a `bin` or `main`
+    #[arg(long, conflicts_with_all = ["name", "entry"])]
+    all: bool,
+    /// Disable compression for faster bundling (useful for testing)
+    #[arg(long)]
+    no_compression: bool,
+    /// Compress every file again instead of reusing compressed files cached by earlier bundles
+    #[arg(long)]
+    no_archive_cache: bool,
+    /// Ignore cached version resolution results
+    #[arg(long)]
+    ignore_cached_versions: bool,
+    /// Runtime to bundle as `name[@version]`: node, bun, deno or electron (default: the project's Node.js)
+    #[arg(long, value_name = "RUNTIME", conflicts_with = "electron")]
+    runtime: Option<RuntimeSpec>,
+    /// Bundle the Electron runtime (version from the project's `electron` dependency) instead of Node
+    #[arg(long)]
+    electron: bool,
+    /// Node.js build to bundle: a reduced ICU variant (smaller, warns at runtime if the app needs more) or a linux-x64 flavor
+    #[arg(long, value_enum, value_name = "BUILD")]
+    node_build: Option<NodeBuild>,
+    /// Node.js download mirror laid out like https://nodejs.org/dist (default: `nodeMirror` config, then NODE_MIRROR)
+    #[arg(long, value_name = "URL")]
+    node_mirror: Option<String>,
+    /// Pre-downloaded Node.js archive (e.g. node-v22.17.1-linux-x64.tar.xz) to bundle without network access
+    #[arg(long, value_name = "PATH")]
+    node_archive: Option<PathBuf>,
+    /// Verify the GPG signature of SHASUMS256.txt with the local keyring (needs gpg and the Node.js release keys)
+    #[arg(long)]
+    verify_node_signature: bool,
+    /// Embed only the app and the pinned Node.js release; the executable downloads Node.js on first run and shares it with other apps
+    #[arg(long)]
+    external_runtime: bool,
+    /// Start the app without the host's NODE_PATH, NODE_OPTIONS and similar variables, so a machine-wide setup cannot change how it resolves or runs
+    #[arg(long)]
+    isolate_env: bool,
+    /// Keep Node.js's npm and corepack working in the bundle; the executable runs npm in the extracted app with --banderole-npm
+    #[arg(long)]
+    include_npm: bool,
+    /// Name of the package.json `bin` entry to run when the package declares several
+    #[arg(long, value_name = "NAME")]
+    entry: Option<String>,
+    /// Keep caches and build leftovers (node_modules/.cache, .turbo, coverage/, *.tsbuildinfo) in the app directory
+    #[arg(long)]
+    no_default_excludes: bool,
+    /// Keep docs, tests, source maps and TypeScript sources of packages in node_modules
+    #[arg(long)]
+    no_prune_junk: bool,
+    /// Bundle the Node.js release in full instead of leaving out its headers, docs, npm and corepack
+    #[arg(long)]
+    no_trim_runtime: bool,
+    /// Path in the Node.js release to bundle although trimming leaves it out, e.g. node_modules/npm; repeatable
+    #[arg(long, value_name = "PATH")]
+    keep: Vec<String>,
+    /// Fail instead of warning when src/ has files newer than the compiled output being bundled
+    #[arg(long)]
+    strict_freshness: bool,
+    /// Layout of pnpm dependencies: `flat` copies every package into node_modules, `pnpm` keeps the .pnpm store and its symlinks
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    node_modules_layout: Option<bundler::NodeModulesLayout>,
+    /// Bundle for musl-based Linux such as Alpine (default on musl hosts); Linux only
+    #[arg(long)]
+    musl: bool,
+    /// Bundle for another Mac architecture, or for both in one universal executable joined with lipo; macOS only
+    #[arg(long, value_enum, value_name = "TARGET", conflicts_with = "musl")]
+    target: Option<bundler::BundleTarget>,
+    /// Run the built executable and fail unless it exits successfully; configure args and expected output with `smokeTest`
+    #[arg(long)]
+    smoke_test: bool,
+    /// Print the resolved runtime, source directory, dependencies and estimated size without downloading, compressing or building anything (a `plan` event with --json)
+    #[arg(long)]
+    dry_run: bool,
+    /// Windows subsystem of the produced executable; `gui` starts without a console window
+    #[arg(long, value_enum, default_value_t = WindowsSubsystem::Console)]
+    windows_subsystem: WindowsSubsystem,
+    /// Link the runner's C runtime statically (`+crt-static`; Linux and Windows)
+    #[arg(long)]
+    runner_crt_static: bool,
+    /// Oldest glibc the runner must run on, e.g. 2.17 for CentOS 7 (needs cargo-zigbuild and zig)
+    #[arg(long, value_name = "VERSION", value_parser = parse_glibc_version)]
+    runner_glibc: Option<String>,
+    /// Compile the runner through this rustc wrapper, e.g. sccache (overrides `runnerBuild.rustcWrapper`)
+    #[arg(long, value_name = "COMMAND")]
+    runner_rustc_wrapper: Option<String>,
+    /// Cargo home the runner's crates are fetched into and read from (overrides `runnerBuild.cargoHome`)
+    #[arg(long, value_name = "DIR")]
+    runner_cargo_home: Option<PathBuf>,
+    /// Build the runner in this cargo target directory instead of banderole's cache (overrides `runnerBuild.targetDir`)
+    #[arg(long, value_name = "DIR")]
+    runner_target_dir: Option<PathBuf>,
+    /// Build the runner from this cargo package instead of the built-in template (overrides `runnerBuild.template`)
+    #[arg(long, value_name = "DIR")]
+    runner_template: Option<PathBuf>,
+    /// Compress the runner with UPX (needs `upx` on the PATH; not for macOS)
+    #[arg(long)]
+    upx: bool,
+    /// Encrypt the embedded payload with the key from BANDEROLE_ENCRYPTION_KEY or --encryption-key-file
+    #[arg(long)]
+    encrypt: bool,
+    /// File holding the passphrase for --encrypt
+    #[arg(long, value_name = "PATH", requires = "encrypt")]
+    encryption_key_file: Option<PathBuf>,
+    /// Give the executable a random build ID instead of one derived from its contents, so it
+    /// extracts into its own cache directory even when an identical bundle was run before
+    #[arg(long)]
+    unique_build_id: bool,
+    /// Report the size breakdown (runtime, packages, app, runner) after bundling
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "text")]
+    analyze: Option<size_report::AnalyzeFormat>,
+    /// Print JSON lines (stage and warning events, then a final result) instead of progress bars
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct PackageArgs {
+    /// Executable produced by `banderole bundle`
+    executable: PathBuf,
+    /// Package format
+    #[arg(long, value_enum)]
+    format: package::PackageFormat,
+    /// Package file to write (default: <name>_<version>-<release>_<arch>.deb,
+    /// <name>-<version>-<release>.<arch>.rpm or <name>-<version>-<arch>.msi next to the
+    /// executable)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Package name (default: the app name from the bundle)
+    #[arg(long)]
+    name: Option<String>,
+    /// Package version (default: the app version from the bundle)
+    #[arg(long)]
+    version: Option<String>,
+    /// Packaging revision of the same version
+    #[arg(long, default_value = "1")]
+    release: String,
+    /// One-line package description
+    #[arg(long)]
+    description: Option<String>,
+    /// Maintainer, e.g. "Jane Doe <jane@example.com>" (the name is the msi manufacturer)
+    #[arg(long)]
+    maintainer: String,
+    /// License of the app (rpm only)
+    #[arg(long)]
+    license: Option<String>,
+    /// Project homepage
+    #[arg(long)]
+    homepage: Option<String>,
+    /// Directory the executable is installed into (deb and rpm, default: /usr/bin)
+    #[arg(long)]
+    install_dir: Option<PathBuf>,
+    /// systemd unit file installed and enabled with the package (deb and rpm); repeatable
+    #[arg(long = "systemd-unit", value_name = "FILE")]
+    systemd_units: Vec<PathBuf>,
+    /// PKCS#12 (.pfx) certificate to sign the msi and the executable in it with; the
+    /// password is read from BANDEROLE_SIGN_PASSWORD
+    #[arg(long, value_name = "PFX")]
+    certificate: Option<PathBuf>,
+    /// RFC 3161 timestamp server used with --certificate
+    #[arg(long, value_name = "URL", requires = "certificate")]
+    timestamp_url: Option<String>,
+}
+
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Initialize env_logger wrapped by indicatif's log bridge so logs play nice with progress bars
-    let multi_progress = MultiProgress::new();
-    let cli = Cli::parse();
+async fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // --help and --version are printed to stdout and are not errors
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(exit_code::USAGE);
+        }
+    };
+    let code = match run(cli).await {
+        Ok(()) => exit_code::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            let code = error_exit_code(&e);
+            if output::json() {
+                output::emit(&output::Event::Error {
+                    message: &format!("{e:#}"),
+                    exit_code: code,
+                });
+            }
+            code
+        }
+    };
+    std::process::exit(code);
+}
+
+/// `2.17`-style glibc versions, as cargo-zigbuild expects them in the target suffix
+fn parse_glibc_version(value: &str) -> Result<String, String> {
+    let valid = value.split_once('.').is_some_and(|(major, minor)| {
+        [major, minor]
+            .iter()
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("expected a glibc version like 2.17, got '{value}'"))
+    }
+}
+
+/// Passphrase for `--encrypt`: the key file's first line, else BANDEROLE_ENCRYPTION_KEY
+fn read_encryption_key(key_file: Option<&std::path::Path>) -> anyhow::Result<String> {
+    let key = match key_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read encryption key file {}", path.display()))?
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        None => std::env::var("BANDEROLE_ENCRYPTION_KEY").unwrap_or_default(),
+    };
+    anyhow::ensure!(
+        !key.trim().is_empty(),
+        "--encrypt needs a key: set BANDEROLE_ENCRYPTION_KEY or pass --encryption-key-file"
+    );
+    Ok(key)
+}
+
+/// Exit code for files that are not executables produced by banderole (or predate the queried
+/// data); the runner never reports it, so it is not in `exit_code`
+const NOT_A_BUNDLE: i32 = 4;
+
+/// Map failures onto the documented exit-code contract
+fn error_exit_code(error: &anyhow::Error) -> i32 {
+    if error.downcast_ref::<manifest::NotABundle>().is_some() {
+        NOT_A_BUNDLE
+    } else if error.downcast_ref::<download::ChecksumMismatch>().is_some()
+        || error.downcast_ref::<verify::Inconsistent>().is_some()
+    {
+        exit_code::INTEGRITY
+    } else if error
+        .downcast_ref::<platform::UnsupportedPlatform>()
+        .is_some()
+    {
+        exit_code::UNSUPPORTED_PLATFORM
+    } else {
+        exit_code::FAILURE
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    output::configure(cli.quiet);
+    if matches!(&cli.command, Commands::Bundle(args) if args.json) {
+        output::enable_json();
+    }
+
+    // Initialize env_logger wrapped by indicatif's log bridge so logs play nice with progress bars.
+    // Spinner frames only make sense on an interactive terminal.
+    let multi_progress = if output::progress_visible() {
+        MultiProgress::new()
+    } else {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    };
 
     let default_level = if cli.verbose { "debug" } else { "warn" };
     let built_logger =
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level))
             .build();
     let level: LevelFilter = built_logger.filter();
-    LogWrapper::new(multi_progress.clone(), built_logger).try_init()?;
+    if output::json() {
+        LogWrapper::new(multi_progress.clone(), output::RecordWarnings(built_logger)).try_init()?;
+    } else {
+        LogWrapper::new(multi_progress.clone(), built_logger).try_init()?;
+    }
     log::set_max_level(level);
 
+    download::configure(download::HttpSettings {
+        proxy: cli.proxy,
+        ca_certs: cli.ca_cert,
+        retries: cli.retries,
+        offline: cli.offline,
+    });
+
     match cli.command {
-        Commands::Bundle {
-            path,
-            output,
-            name,
-            no_compression,
-            ignore_cached_versions,
-        } => {
-            bundler::bundle_project(
+        Commands::Bundle(args) => {
+            let BundleArgs {
                 path,
+                sha256,
                 output,
                 name,
+                all,
                 no_compression,
+                no_archive_cache,
                 ignore_cached_versions,
-                &multi_progress,
-            )
-            .await?;
+                runtime,
+                electron,
+                node_build,
+                node_mirror,
+                node_archive,
+                verify_node_signature,
+                external_runtime,
+                isolate_env,
+                include_npm,
+                entry,
+                no_default_excludes,
+                no_prune_junk,
+                no_trim_runtime,
+                keep,
+                strict_freshness,
+                node_modules_layout,
+                musl,
+                target,
+                smoke_test,
+                dry_run,
+                windows_subsystem,
+                runner_crt_static,
+                runner_glibc,
+                runner_rustc_wrapper,
+                runner_cargo_home,
+                runner_target_dir,
+                runner_template,
+                upx,
+                encrypt,
+                encryption_key_file,
+                unique_build_id,
+                analyze,
+                json: _,
+            } = *args;
+            if musl {
+                platform::target_musl()?;
+            }
+            anyhow::ensure!(
+                !(cli.offline && ignore_cached_versions),
+                "--ignore-cached-versions needs network access and cannot be combined with --offline"
+            );
+            let runtime = runtime.or_else(|| {
+                electron.then_some(RuntimeSpec {
+                    kind: RuntimeKind::Electron,
+                    version: None,
+                })
+            });
+            let encryption_key = encrypt
+                .then(|| read_encryption_key(encryption_key_file.as_deref()))
+                .transpose()?;
+            // cargo builds the runner from another directory
+            let absolute = |path: Option<PathBuf>| {
+                path.map(|path| {
+                    std::path::absolute(&path)
+                        .with_context(|| format!("Failed to resolve {}", path.display()))
+                })
+                .transpose()
+            };
+            let runner_cargo_home = absolute(runner_cargo_home)?;
+            let runner_target_dir = absolute(runner_target_dir)?;
+            let input = project_input::resolve(&path, sha256.as_deref()).await?;
+            let options = bundler::BundleOptions {
+                project_path: input.path().to_path_buf(),
+                output_path: output,
+                custom_name: name,
+                no_compression,
+                no_archive_cache,
+                ignore_cached_versions,
+                runtime,
+                node_build,
+                node_mirror,
+                node_archive,
+                verify_node_signature,
+                bin_entry: entry,
+                no_default_excludes,
+                no_prune_junk,
+                no_trim_runtime,
+                keep,
+                strict_freshness,
+                node_modules_layout,
+                runner: RunnerBuildOptions {
+                    windows_subsystem,
+                    crt_static: runner_crt_static,
+                    glibc: runner_glibc,
+                    upx,
+                    encryption_key,
+                    rustc_wrapper: runner_rustc_wrapper,
+                    cargo_home: runner_cargo_home,
+                    target_dir: runner_target_dir,
+                    profile: Default::default(),
+                    template: runner_template,
+                    unique_build_id,
+                },
+                analyze,
+                external_runtime,
+                isolate_env,
+                include_npm,
+                target,
+                smoke_test,
+                dry_run,
+            };
+            if !all {
+                let mut summary = bundler::bundle_project(options, &multi_progress).await?;
+                if output::json() {
+                    summary.warnings = output::take_warnings();
+                    output::emit(&output::Event::Result(&summary));
+                }
+                return Ok(());
+            }
+
+            // One bundle after another: later ones reuse the runtime and runner the first built
+            let apps = bundler::workspace::find_apps(input.path())?;
+            anyhow::ensure!(
+                !apps.is_empty(),
+                "No workspace package in {} declares a `bin` or `main`",
+                path
+            );
+            let output_dir = options.output_path.clone().unwrap_or_default();
+            let mut failed = Vec::new();
+            for app in &apps {
+                let options = bundler::BundleOptions {
+                    project_path: app.dir.clone(),
+                    output_path: Some(output_dir.join(&app.file_name)),
+                    ..options.clone()
+                };
+                match bundler::bundle_project(options, &multi_progress).await {
+                    Ok(mut summary) => {
+                        if output::json() {
+                            summary.warnings = output::take_warnings();
+                            output::emit(&output::Event::Result(&summary));
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to bundle {}: {e:#}", app.dir.display());
+                        failed.push(app.file_name.as_str());
+                    }
+                }
+            }
+            anyhow::ensure!(
+                failed.is_empty(),
+                "{} of {} workspace apps failed to bundle: {}",
+                failed.len(),
+                apps.len(),
+                failed.join(", ")
+            );
+        }
+        Commands::Inspect { executable, json } => {
+            let manifest = manifest::BundleManifest::read_from_executable(&executable)?;
+            if let Some(warning) = manifest.compatibility_warning() {
+                log::warn!("{warning}");
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&manifest)?);
+            } else {
+                print!("{}", inspect::render(&manifest));
+            }
+        }
+        Commands::Diff {
+            old,
+            new,
+            changelog,
+        } => {
+            let old = manifest::BundleManifest::read_from_executable(&old)?;
+            let new = manifest::BundleManifest::read_from_executable(&new)?;
+            for warning in [&old, &new]
+                .iter()
+                .filter_map(|m| m.compatibility_warning())
+            {
+                log::warn!("{warning}");
+            }
+            let diff = diff::BundleDiff::compare(&old, &new);
+            if changelog {
+                print!("{}", diff.render_changelog());
+            } else {
+                print!("{}", diff.render_plain());
+            }
+        }
+        Commands::Package(args) => {
+            let PackageArgs {
+                executable,
+                format,
+                output,
+                name,
+                version,
+                release,
+                description,
+                maintainer,
+                license,
+                homepage,
+                install_dir,
+                systemd_units,
+                certificate,
+                timestamp_url,
+            } = *args;
+            let path = package::package(
+                &executable,
+                format,
+                package::PackageOptions {
+                    name,
+                    version,
+                    release,
+                    description,
+                    maintainer,
+                    license,
+                    homepage,
+                    install_dir,
+                    systemd_units,
+                    certificate,
+                    timestamp_url,
+                    output,
+                },
+            )?;
+            println!("Package written to {}", path.display());
+        }
+        Commands::Verify {
+            executable,
+            strict,
+            json,
+        } => {
+            let report = verify::verify(&executable)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report.render());
+            }
+            let failures = report.failures(strict);
+            if failures > 0 {
+                return Err(verify::Inconsistent(failures).into());
+            }
+        }
+        Commands::RunnerTemplate { dir } => {
+            anyhow::ensure!(
+                !dir.join("Cargo.toml").exists(),
+                "{} already contains a Cargo.toml",
+                dir.display()
+            );
+            // Without the vendored crates: a custom template resolves its own dependencies
+            let template = embedded_template::EmbeddedTemplate {
+                vendored_crates: None,
+                ..embedded_template::EmbeddedTemplate::new()
+            };
+            template.write_to_dir(&dir)?;
+            println!("Wrote the runner template to {}", dir.display());
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                "banderole",
+                &mut std::io::stdout(),
+            );
         }
     }
 
@@ -0,0 +1,359 @@
+use crate::runtime_config::RuntimeKind;
+use crate::schema::RUNNER_SCHEMA;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+/// Surrounds the manifest JSON inside produced executables so it can be found without running them
+const MARKER_START: &[u8] = b"\0BANDEROLE_MANIFEST_JSON\0";
+const MARKER_END: &[u8] = b"\0";
+
+/// Summary of a bundle's payload, embedded into the executable next to the payload itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub banderole_version: String,
+    /// [`RUNNER_SCHEMA`] of the banderole that built the bundle; 0 before it was recorded
+    #[serde(default)]
+    pub runner_schema: u32,
+    pub app_name: String,
+    pub app_version: String,
+    pub runtime: RuntimeKind,
+    pub runtime_version: String,
+    /// When the bundle was built; absent in manifests from before it was recorded
+    #[serde(default)]
+    pub bundled_at: Option<DateTime<Utc>>,
+    /// Installed packages keyed by their location below `app/` (e.g. `node_modules/a/node_modules/b`)
+    pub packages: BTreeMap<String, String>,
+    /// Every payload file keyed by its path in the bundle
+    pub files: BTreeMap<String, ManifestFile>,
+    /// The compressed (and with `--encrypt`, encrypted) payload as embedded into the executable;
+    /// absent in manifests from before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<ManifestFile>,
+    /// Further embedded archives holding the dependencies and the runtime, recorded like `payload`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl BundleManifest {
+    /// Describe the finished payload zip
+    pub fn from_payload(
+        zip_data: &[u8],
+        app_name: &str,
+        app_version: &str,
+        runtime: RuntimeKind,
+        runtime_version: &str,
+    ) -> Result<Self> {
+        let mut archive =
+            zip::ZipArchive::new(Cursor::new(zip_data)).context("Failed to read bundle payload")?;
+        let mut packages = BTreeMap::new();
+        let mut files = BTreeMap::new();
+        let mut buffer = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_string();
+            buffer.clear();
+            file.read_to_end(&mut buffer)
+                .with_context(|| format!("Failed to read {name} from the payload"))?;
+
+            if let Some(location) = package_location(&name) {
+                if let Ok(manifest) = serde_json::from_slice::<serde_json::Value>(&buffer) {
+                    if let Some(version) = manifest["version"].as_str() {
+                        packages.insert(location.to_string(), version.to_string());
+                    }
+                }
+            }
+            files.insert(
+                name,
+                ManifestFile {
+                    size: buffer.len() as u64,
+                    sha256: format!("{:x}", Sha256::digest(&buffer)),
+                },
+            );
+        }
+        Ok(Self {
+            banderole_version: env!("CARGO_PKG_VERSION").to_string(),
+            runner_schema: RUNNER_SCHEMA,
+            app_name: app_name.to_string(),
+            app_version: app_version.to_string(),
+            runtime,
+            runtime_version: runtime_version.to_string(),
+            bundled_at: Some(bundle_time()),
+            packages,
+            files,
+            payload: None,
+            layers: Vec::new(),
+        })
+    }
+
+    /// SHA-256 over the paths and hashes of every file below `prefix` (e.g. `node/`), or `None`
+    /// when there are none. Identical runtimes get identical digests across bundles.
+    pub fn content_digest(&self, prefix: &str) -> Option<String> {
+        let mut hasher = Sha256::new();
+        let mut any = false;
+        for (path, file) in self.files.range(prefix.to_string()..) {
+            let Some(relative) = path.strip_prefix(prefix) else {
+                break;
+            };
+            any = true;
+            hasher.update(format!("{relative}\0{}\n", file.sha256));
+        }
+        any.then(|| format!("{:x}", hasher.finalize()))
+    }
+
+    /// Why tools of this banderole may misjudge the bundle, when it was built against another
+    /// runner schema
+    pub fn compatibility_warning(&self) -> Option<String> {
+        match self.runner_schema.cmp(&RUNNER_SCHEMA) {
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Less => Some(format!(
+                "{} was built by banderole {} (runner schema {}, current {RUNNER_SCHEMA}); its runtime behavior differs from bundles built now, rebundle to pick up the changes",
+                self.app_name, self.banderole_version, self.runner_schema
+            )),
+            std::cmp::Ordering::Greater => Some(format!(
+                "{} was built by a newer banderole {} (runner schema {}, this banderole knows {RUNNER_SCHEMA}); update banderole to inspect it reliably",
+                self.app_name, self.banderole_version, self.runner_schema
+            )),
+        }
+    }
+
+    /// Bytes written next to the runner sources and embedded verbatim into the executable
+    pub fn to_embedded(&self) -> Result<Vec<u8>> {
+        let mut data = MARKER_START.to_vec();
+        serde_json::to_writer(&mut data, self).context("Failed to serialize bundle manifest")?;
+        data.extend_from_slice(MARKER_END);
+        Ok(data)
+    }
+
+    /// Read the manifest embedded into an executable produced by banderole
+    pub fn read_from_executable(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut offset = 0;
+        while let Some(found) = find(&data[offset..], MARKER_START) {
+            let start = offset + found + MARKER_START.len();
+            if let Some(len) = find(&data[start..], MARKER_END) {
+                if let Ok(manifest) = serde_json::from_slice(&data[start..start + len]) {
+                    return Ok(manifest);
+                }
+            }
+            offset = start;
+        }
+        Err(NotABundle(path.to_path_buf()).into())
+    }
+}
+
+/// The file carries no manifest: not built by banderole, or by a version before manifests
+#[derive(Debug)]
+pub struct NotABundle(pub PathBuf);
+
+impl std::fmt::Display for NotABundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} has no bundle manifest (not built by banderole, or by a version before manifests)",
+            self.0.display()
+        )
+    }
+}
+
+impl std::error::Error for NotABundle {}
+
+/// Whether `data` is an executable produced by banderole, e.g. the previous output of a rebundle
+/// lying in the project directory.
+///
+/// Outputs from before manifests are recognised by the runner's flag reference.
+pub fn is_bundle_output(data: &[u8]) -> bool {
+    const RUNNER_HELP: &[u8] =
+        b"Runner flags (consumed by the executable, never forwarded to the app)";
+    let executable = [
+        &b"\x7fELF"[..],
+        b"MZ",
+        b"\xcf\xfa\xed\xfe",
+        b"\xca\xfe\xba\xbe",
+    ]
+    .iter()
+    .any(|magic| data.starts_with(magic));
+    // Runners alone are several megabytes; skip searching small files
+    if !executable || data.len() < 1024 * 1024 {
+        return false;
+    }
+    let mut manifest = MARKER_START.to_vec();
+    manifest.push(b'{');
+    find(data, &manifest).is_some() || find(data, RUNNER_HELP).is_some()
+}
+
+/// When the bundle was built: `SOURCE_DATE_EPOCH` if set, so reproducible builds embed identical
+/// manifests, otherwise now
+fn bundle_time() -> DateTime<Utc> {
+    source_date_epoch(std::env::var("SOURCE_DATE_EPOCH").ok().as_deref()).unwrap_or_else(Utc::now)
+}
+
+fn source_date_epoch(value: Option<&str>) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(value?.trim().parse().ok()?, 0)
+}
+
+/// `app/<location>/package.json` of an installed package (not a file inside one of its folders)
+fn package_location(name: &str) -> Option<&str> {
+    let location = name.strip_prefix("app/")?.strip_suffix("/package.json")?;
+    let (_, package) = location.rsplit_once("node_modules/")?;
+    let segments: Vec<&str> = package.split('/').collect();
+    let is_package = match segments.as_slice() {
+        [scope, name] => scope.starts_with('@') && !name.is_empty(),
+        [name] => !name.is_empty() && !name.starts_with('.'),
+        _ => false,
+    };
+    is_package.then_some(location)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_location() {
+        assert_eq!(
+            package_location("app/node_modules/lodash/package.json"),
+            Some("node_modules/lodash")
+        );
+        assert_eq!(
+            package_location("app/node_modules/a/node_modules/@s/b/package.json"),
+            Some("node_modules/a/node_modules/@s/b")
+        );
+        assert_eq!(
+            package_location("app/node_modules/a/lib/package.json"),
+            None
+        );
+        assert_eq!(package_location("app/package.json"), None);
+    }
+
+    #[test]
+    fn test_source_date_epoch() {
+        assert_eq!(
+            source_date_epoch(Some("1717245000")),
+            "2024-06-01T12:30:00Z".parse().ok()
+        );
+        assert_eq!(source_date_epoch(Some("not a time")), None);
+        assert_eq!(source_date_epoch(None), None);
+    }
+
+    #[test]
+    fn test_is_bundle_output() {
+        let mut runner = b"\x7fELF".to_vec();
+        runner.resize(2 * 1024 * 1024, 0);
+        assert!(!is_bundle_output(&runner));
+
+        let manifest = [MARKER_START, b"{}", MARKER_END].concat();
+        runner[4096..4096 + manifest.len()].copy_from_slice(&manifest);
+        assert!(is_bundle_output(&runner));
+        assert!(!is_bundle_output(&runner[4..]));
+    }
+
+    #[test]
+    fn test_content_digest() {
+        let file = |sha256: &str| ManifestFile {
+            size: 1,
+            sha256: sha256.into(),
+        };
+        let mut manifest = BundleManifest {
+            banderole_version: "1.0.0".into(),
+            runner_schema: RUNNER_SCHEMA,
+            app_name: "demo".into(),
+            app_version: "1.0.0".into(),
+            runtime: RuntimeKind::Node,
+            runtime_version: "22.1.0".into(),
+            bundled_at: None,
+            packages: BTreeMap::new(),
+            files: BTreeMap::from([
+                ("app/index.js".into(), file("a")),
+                ("node/bin/node".into(), file("b")),
+                ("node/lib/x.js".into(), file("c")),
+            ]),
+            payload: None,
+            layers: Vec::new(),
+        };
+        let digest = manifest.content_digest("node/").unwrap();
+        assert_eq!(manifest.content_digest("bun/"), None);
+
+        // App changes do not affect the runtime's identity; runtime changes do
+        manifest
+            .files
+            .insert("app/index.js".into(), file("changed"));
+        assert_eq!(
+            manifest.content_digest("node/").as_deref(),
+            Some(digest.as_str())
+        );
+        manifest
+            .files
+            .insert("node/lib/x.js".into(), file("changed"));
+        assert_ne!(manifest.content_digest("node/").unwrap(), digest);
+    }
+
+    #[test]
+    fn test_compatibility_warning() {
+        let mut manifest: BundleManifest = serde_json::from_str(
+            r#"{"banderoleVersion":"0.2.0","appName":"demo","appVersion":"1.0.0","runtime":"node",
+                "runtimeVersion":"22.1.0","packages":{},"files":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.runner_schema, 0);
+        assert!(manifest
+            .compatibility_warning()
+            .is_some_and(|warning| warning.contains("rebundle")));
+
+        manifest.runner_schema = RUNNER_SCHEMA;
+        assert_eq!(manifest.compatibility_warning(), None);
+        manifest.runner_schema = RUNNER_SCHEMA + 1;
+        assert!(manifest
+            .compatibility_warning()
+            .is_some_and(|warning| warning.contains("update banderole")));
+    }
+
+    #[test]
+    fn test_embedded_round_trip() {
+        let manifest = BundleManifest {
+            banderole_version: "1.0.0".into(),
+            runner_schema: RUNNER_SCHEMA,
+            app_name: "demo".into(),
+            app_version: "1.2.3".into(),
+            runtime: RuntimeKind::Node,
+            runtime_version: "22.1.0".into(),
+            bundled_at: None,
+            packages: BTreeMap::from([("node_modules/a".into(), "1.0.0".into())]),
+            files: BTreeMap::new(),
+            payload: None,
+            layers: Vec::new(),
+        };
+        let dir = tempfile::TempDir::new().unwrap();
+        let exe = dir.path().join("demo");
+        let mut data = b"\x7fELF runner code".to_vec();
+        data.extend(manifest.to_embedded().unwrap());
+        data.extend(b"more code");
+        std::fs::write(&exe, data).unwrap();
+
+        assert_eq!(
+            BundleManifest::read_from_executable(&exe).unwrap(),
+            manifest
+        );
+    }
+}
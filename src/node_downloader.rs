@@ -1,16 +1,172 @@
+use crate::cache;
+use crate::download;
 use crate::node_version_manager::NodeVersionManager;
 use crate::platform::Platform;
+use crate::runtime_config::ExternalRuntime;
 use anyhow::{Context, Result};
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
+/// Official distribution server, used unless a mirror is configured
+const DEFAULT_NODE_MIRROR: &str = "https://nodejs.org/dist";
+
+/// Base URL for the reduced-ICU, flavor and musl builds, which nodejs.org does not publish
+const NODE_BUILDS_MIRROR: &str = "https://unofficial-builds.nodejs.org/download/release";
+
+/// Which build of Node.js is bundled: an ICU configuration or a special-purpose flavor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeBuild {
+    /// Official build with full ICU data
+    #[default]
+    Full,
+    /// English-only ICU data (~20MB smaller)
+    SmallIcu,
+    /// No `Intl` support at all
+    WithoutIntl,
+    /// V8 pointer compression: less memory per object, heap limited to 4GB (linux-x64 only)
+    PointerCompression,
+    /// Debug build with assertions and symbols, for diagnostics bundles (linux-x64 only)
+    Debug,
+}
+
+impl NodeBuild {
+    fn suffix(self) -> Option<&'static str> {
+        match self {
+            NodeBuild::Full => None,
+            NodeBuild::SmallIcu => Some("small-icu"),
+            NodeBuild::WithoutIntl => Some("without-intl"),
+            NodeBuild::PointerCompression => Some("pointer-compression"),
+            NodeBuild::Debug => Some("debug"),
+        }
+    }
+
+    /// Flavors change how node itself behaves rather than which ICU data it carries
+    fn is_flavor(self) -> bool {
+        matches!(self, NodeBuild::PointerCompression | NodeBuild::Debug)
+    }
+
+    /// unofficial-builds publishes the flavors for linux-x64 only
+    fn ensure_published(self, platform: Platform) -> Result<()> {
+        if platform.custom().is_some() && self != NodeBuild::Full {
+            anyhow::bail!(
+                "{platform} is bundled with the Node.js build its nodeUrl points to; leave nodeBuild unset"
+            );
+        }
+        if self.is_flavor() && platform != Platform::LinuxX64 {
+            anyhow::bail!(
+                "The {} build of Node.js is only published for linux-x64, not {platform}; pass a self-built archive with --node-archive",
+                self.suffix().unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+
+    /// Archive name on the download server, e.g. `node-v22.0.0-linux-x64-small-icu.tar.xz`
+    fn archive_name(self, platform: Platform, version: &str) -> String {
+        let official = platform.node_archive_name(version);
+        match self.suffix() {
+            None => official,
+            Some(suffix) => {
+                let ext = [".tar.xz", ".7z", ".zip"]
+                    .into_iter()
+                    .find(|ext| official.ends_with(ext))
+                    .unwrap_or_default();
+                let stem = official.strip_suffix(ext).unwrap_or(&official);
+                format!("{stem}-{suffix}{ext}")
+            }
+        }
+    }
+
+    /// Top-level folder of the release archive, e.g. `node-v22.0.0-linux-x64-small-icu`; custom
+    /// platforms name theirs freely
+    fn release_dir(self, platform: Platform, version: &str) -> Option<String> {
+        if platform.custom().is_some() {
+            return None;
+        }
+        let name = self.archive_name(platform, version);
+        [".tar.xz", ".7z", ".zip"]
+            .into_iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            .map(str::to_string)
+    }
+
+    /// Whether the archive comes from unofficial-builds rather than the mirror of official builds
+    fn is_unofficial(self, platform: Platform) -> bool {
+        platform.custom().is_none() && (self != NodeBuild::Full || platform.is_musl())
+    }
+
+    fn download_base(self, platform: Platform, mirror: &str) -> String {
+        match self {
+            NodeBuild::Full if !platform.is_musl() => mirror.to_string(),
+            _ => std::env::var("BANDEROLE_NODE_BUILDS_MIRROR")
+                .unwrap_or_else(|_| NODE_BUILDS_MIRROR.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+}
+
+/// Where Node.js distributions come from
+#[derive(Debug, Clone, Default)]
+pub struct NodeSource {
+    pub build: NodeBuild,
+    /// Base URL laid out like nodejs.org/dist (`index.json`, `v<version>/<archive>`); falls back
+    /// to `NODE_MIRROR`, then nodejs.org
+    pub mirror: Option<String>,
+    /// Pre-downloaded distribution archive used instead of downloading
+    pub archive: Option<PathBuf>,
+    /// Check the GPG signature of `SHASUMS256.txt` with the local keyring before trusting it
+    pub verify_signature: bool,
+}
+
+impl NodeSource {
+    pub fn mirror(&self) -> String {
+        self.mirror
+            .clone()
+            .or_else(|| std::env::var("NODE_MIRROR").ok())
+            .filter(|mirror| !mirror.is_empty())
+            .unwrap_or_else(|| DEFAULT_NODE_MIRROR.to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Version encoded in the archive's file name, e.g. `node-v22.1.0-linux-x64.tar.xz`
+    pub fn archive_version(&self) -> Result<Option<String>> {
+        let Some(archive) = &self.archive else {
+            return Ok(None);
+        };
+        let name = archive
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let version = name
+            .strip_prefix("node-v")
+            .and_then(|rest| rest.split('-').next())
+            .and_then(parse_full_version_spec)
+            .with_context(|| {
+                format!("Cannot read the Node.js version from archive name '{name}' (expected node-v<version>-<platform>.<ext>)")
+            })?;
+        Ok(Some(version))
+    }
+}
+
+/// Keyring of the people allowed to sign Node.js releases
+const NODE_RELEASE_KEYRING_URL: &str =
+    "https://github.com/nodejs/release-keys/raw/HEAD/gpg/pubring.kbx";
+
+/// Overrides the keyring `verifyNodeSignature` checks signatures against
+const NODE_KEYRING_ENV: &str = "BANDEROLE_NODE_KEYRING";
+
 lazy_static! {
     static ref NODE_VERSION_CACHE: Mutex<HashMap<String, PathBuf>> = Mutex::new(HashMap::new());
 }
@@ -19,12 +175,17 @@ pub struct NodeDownloader {
     platform: Platform,
     cache_dir: PathBuf,
     node_version: String,
+    source: NodeSource,
 }
 
 impl NodeDownloader {
-    pub async fn new_with_persistent_cache(version_spec: &str) -> Result<Self> {
-        let cache_dir = Self::get_persistent_cache_dir()?;
-        let version_resolver = NodeVersionManager::new();
+    pub async fn new_with_persistent_cache(
+        version_spec: &str,
+        platform: Platform,
+        source: NodeSource,
+    ) -> Result<Self> {
+        let cache_dir = cache::persistent_cache_dir()?;
+        let version_resolver = NodeVersionManager::with_mirror(&source.mirror());
 
         // Resolve the version specification to a concrete version
         let resolved_version = match parse_full_version_spec(version_spec) {
@@ -40,27 +201,41 @@ impl NodeDownloader {
         info!("Resolved '{version_spec}' to Node.js version {resolved_version}");
 
         Ok(Self {
-            platform: Platform::current(),
+            platform,
             cache_dir,
             node_version: resolved_version,
+            source,
         })
     }
 
-    fn get_persistent_cache_dir() -> Result<PathBuf> {
-        let cache_dir = if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
-            PathBuf::from(cache_home).join("banderole")
-        } else if let Some(home) = std::env::var_os("HOME") {
-            PathBuf::from(home).join(".cache").join("banderole")
-        } else if let Some(appdata) = std::env::var_os("APPDATA") {
-            PathBuf::from(appdata).join("banderole").join("cache")
-        } else {
-            std::env::temp_dir().join("banderole-cache")
-        };
+    /// Concrete Node.js version this downloader fetches
+    pub fn version(&self) -> &str {
+        &self.node_version
+    }
+
+    /// Cache directory name: the platform, suffixed with the build variant when not full ICU
+    fn platform_dir(&self) -> String {
+        match self.source.build.suffix() {
+            Some(suffix) => format!("{}-{suffix}", self.platform),
+            None => self.platform.to_string(),
+        }
+    }
 
-        std::fs::create_dir_all(&cache_dir)
-            .context("Failed to create persistent cache directory")?;
+    /// Extraction directory in the persistent cache: `node/<version>/<platform>`
+    fn node_dir(&self) -> PathBuf {
+        self.cache_dir
+            .join("node")
+            .join(&self.node_version)
+            .join(self.platform_dir())
+    }
 
-        Ok(cache_dir)
+    /// The cached release directory, when this version was downloaded before
+    pub fn cached_dir(&self) -> Option<PathBuf> {
+        let node_dir = self.node_dir();
+        node_dir
+            .join(self.platform.node_executable_path())
+            .exists()
+            .then_some(node_dir)
     }
 
     /// Same as ensure_node_binary but reports progress to the provided ProgressBar if any
@@ -73,7 +248,7 @@ impl NodeDownloader {
 
     async fn ensure_node_binary_inner(&self, progress: Option<&ProgressBar>) -> Result<PathBuf> {
         // Create cache key for this version and platform
-        let cache_key = format!("{}:{}", self.node_version, self.platform);
+        let cache_key = format!("{}:{}", self.node_version, self.platform_dir());
 
         // Check in-memory cache first
         {
@@ -88,11 +263,7 @@ impl NodeDownloader {
         }
 
         // Check disk cache
-        let node_dir = self
-            .cache_dir
-            .join("node")
-            .join(&self.node_version)
-            .join(self.platform.to_string());
+        let node_dir = self.node_dir();
 
         let mut node_executable = node_dir.join(self.platform.node_executable_path());
 
@@ -105,18 +276,54 @@ impl NodeDownloader {
             return Ok(node_executable);
         }
 
+        // Another process may be filling the same entry; wait for it and check again
+        let version_dir = node_dir.parent().unwrap_or(&self.cache_dir);
+        fs::create_dir_all(version_dir)
+            .await
+            .context("Failed to create node cache directory")?;
+        let _lock =
+            cache::lock_exclusive(&version_dir.join(format!("{}.lock", self.platform_dir())))
+                .await?;
+        if node_executable.exists() {
+            return Ok(node_executable);
+        }
+        if self.source.archive.is_none() {
+            download::ensure_online(&format!(
+                "Node.js {} for {}",
+                self.node_version,
+                self.platform_dir()
+            ))?;
+        }
+
         info!(
             "Fetching Node.js {} for {}",
-            self.node_version, self.platform
+            self.node_version,
+            self.platform_dir()
         );
 
-        // Create cache directory
-        fs::create_dir_all(&node_dir)
+        // Download and extract next to the final location, then move it into place in one step
+        // so an interrupted extraction never looks like a usable runtime
+        let staging_dir = version_dir.join(format!(
+            "{}.tmp-{}",
+            self.platform_dir(),
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&staging_dir)
             .await
             .context("Failed to create node cache directory")?;
-
-        // Download and extract Node.js
-        self.download_and_extract_node(&node_dir, progress).await?;
+        if let Err(e) = self.download_and_extract_node(&staging_dir, progress).await {
+            let _ = fs::remove_dir_all(&staging_dir).await;
+            return Err(e);
+        }
+        if fs::metadata(&node_dir).await.is_ok() {
+            // Leftover of an extraction from before staging directories were used
+            fs::remove_dir_all(&node_dir)
+                .await
+                .context("Failed to remove incomplete node cache directory")?;
+        }
+        fs::rename(&staging_dir, &node_dir)
+            .await
+            .context("Failed to move Node.js into the cache")?;
 
         // Validate presence; if not in expected location, search recursively as a fallback
         if !node_executable.exists() {
@@ -178,6 +385,10 @@ impl NodeDownloader {
             fs::set_permissions(&node_executable, perms).await?;
         }
 
+        NODE_VERSION_CACHE
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Failed to acquire cache lock: {}", e))?
+            .insert(cache_key, node_executable.clone());
         Ok(node_executable)
     }
 
@@ -186,25 +397,177 @@ impl NodeDownloader {
         target_dir: &Path,
         progress: Option<&ProgressBar>,
     ) -> Result<()> {
-        let archive_name = self.platform.node_archive_name(&self.node_version);
-        let url = format!(
-            "https://nodejs.org/dist/v{}/{}",
-            self.node_version, archive_name
-        );
+        // A pre-downloaded archive is extracted in place and left untouched
+        let (archive_path, downloaded) = match &self.source.archive {
+            Some(archive) => {
+                self.verify_local_archive(archive, target_dir).await?;
+                (archive.clone(), false)
+            }
+            None => (self.download_archive(target_dir, progress).await?, true),
+        };
 
-        // Download the archive
-        let response = reqwest::get(&url)
-            .await
-            .context("Failed to download Node.js archive")?;
+        // Extract the archive with determinate progress
+        if let Some(pb) = progress {
+            pb.set_style(
+                ProgressStyle::with_template("[ {wide_bar} ] {pos}/{len}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+            pb.set_length(0);
+            pb.set_position(0);
+        }
+        let archive_file_name = archive_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let top_level = if archive_file_name.ends_with(".7z") {
+            self.extract_7z(&archive_path, target_dir, progress).await?
+        } else if archive_file_name.ends_with(".tar.xz") {
+            self.extract_tar_xz(&archive_path, target_dir, progress)
+                .await?
+        } else if archive_file_name.ends_with(".zip") {
+            self.extract_zip(&archive_path, target_dir, progress)
+                .await?
+        } else {
+            anyhow::bail!(
+                "Unsupported Node.js archive format: {archive_file_name} (expected .tar.xz, .7z or .zip)"
+            );
+        };
 
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to download Node.js: HTTP {}", response.status());
+        // The release folder names the version, platform and build; flavors ship the same files
+        // as the full build, so nothing else tells them apart
+        if let Some(expected) = self
+            .source
+            .build
+            .release_dir(self.platform, &self.node_version)
+        {
+            anyhow::ensure!(
+                top_level.as_deref() == Some(expected.as_str()),
+                "{archive_file_name} is not Node.js {} for {}: its top-level folder is {}, expected {expected}",
+                self.node_version,
+                self.platform,
+                top_level.as_deref().unwrap_or("missing")
+            );
         }
 
+        // Clean up archive
+        if downloaded {
+            fs::remove_file(&archive_path)
+                .await
+                .context("Failed to remove archive file")?;
+        }
+
+        // Let caller finish the progress bar for this step
+        Ok(())
+    }
+
+    async fn download_archive(
+        &self,
+        target_dir: &Path,
+        progress: Option<&ProgressBar>,
+    ) -> Result<PathBuf> {
+        self.source.build.ensure_published(self.platform)?;
+        let archive_name = self
+            .source
+            .build
+            .archive_name(self.platform, &self.node_version);
+        self.ensure_listed(&archive_name).await?;
+        let (url, shasums_url) = self.archive_urls(&archive_name);
+
+        // Bytes received so far stay in a .part file that retries resume from
+        let partial_path = target_dir.join(format!("{archive_name}.part"));
+        let fetched = download::with_retries("Node.js download", || {
+            self.fetch_archive(&url, &partial_path, progress)
+        })
+        .await;
+        if let Err(e) = fetched {
+            let _ = fs::remove_file(&partial_path).await;
+            if self.source.build != NodeBuild::Full
+                && e.downcast_ref::<download::HttpStatusError>().is_some()
+            {
+                return Err(e.context(format!(
+                    "Failed to download Node.js {archive_name} (set BANDEROLE_NODE_BUILDS_MIRROR to a server publishing this build)"
+                )));
+            }
+            return Err(e.context("Failed to download Node.js archive"));
+        }
         let archive_path = target_dir.join(&archive_name);
-        let mut file = fs::File::create(&archive_path)
+        fs::rename(&partial_path, &archive_path)
+            .await
+            .context("Failed to store Node.js archive")?;
+
+        let Some(shasums_url) = shasums_url else {
+            warn!(
+                "Not verifying {archive_name}: platform {} sets insecureSkipChecksum",
+                self.platform
+            );
+            return Ok(archive_path);
+        };
+        let digest = sha256_file(&archive_path).await?;
+        if let Err(e) = self
+            .verify_download(&shasums_url, &archive_name, &digest, target_dir)
+            .await
+        {
+            // Never leave a rejected archive where it could be mistaken for a good one
+            let _ = fs::remove_file(&archive_path).await;
+            return Err(e);
+        }
+        Ok(archive_path)
+    }
+
+    /// One download attempt, continuing `partial_path` with a Range request when it has data
+    async fn fetch_archive(
+        &self,
+        url: &str,
+        partial_path: &Path,
+        progress: Option<&ProgressBar>,
+    ) -> Result<()> {
+        let offset = fs::metadata(partial_path)
+            .await
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+        let mut request = download::client()?.get(url);
+        if offset > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+        }
+        let response = request
+            .send()
             .await
-            .context("Failed to create archive file")?;
+            .context("Failed to request Node.js archive")?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file does not match the remote one; start over on the next attempt
+            let _ = fs::remove_file(partial_path).await;
+        }
+        if !status.is_success() {
+            return Err(download::HttpStatusError {
+                url: url.to_string(),
+                status,
+            }
+            .into());
+        }
+        // Servers ignoring the Range header send the whole file again
+        let resumed = offset > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT;
+        if offset > 0 {
+            if resumed {
+                info!("Resuming Node.js download at {offset} bytes");
+            } else {
+                info!("Server does not support resuming, restarting Node.js download");
+            }
+        }
+        let mut file = if resumed {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(partial_path)
+                .await
+                .context("Failed to reopen partial archive file")?
+        } else {
+            fs::File::create(partial_path)
+                .await
+                .context("Failed to create archive file")?
+        };
+        let already = if resumed { offset } else { 0 };
 
         // Configure a download progress bar style like the indicatif example
         // Template inspired by download-speed.rs example
@@ -216,7 +579,8 @@ impl NodeDownloader {
                 .unwrap()
                 .progress_chars("#>-"),
             );
-            pb.set_length(total);
+            pb.set_length(already + total);
+            pb.set_position(already);
         } else if let Some(pb) = progress {
             pb.set_style(
                 ProgressStyle::with_template(
@@ -244,54 +608,280 @@ impl NodeDownloader {
         }
 
         file.flush().await.context("Failed to flush archive file")?;
-        drop(file);
+        Ok(())
+    }
 
-        // Extract the archive with determinate progress
-        if let Some(pb) = progress {
-            pb.set_style(
-                ProgressStyle::with_template("[ {wide_bar} ] {pos}/{len}")
-                    .unwrap()
-                    .progress_chars("#>-"),
-            );
-            pb.set_length(0);
-            pb.set_position(0);
-        }
-        if self.platform.is_windows() {
-            self.extract_7z(&archive_path, target_dir, progress).await?;
-        } else {
-            self.extract_tar_xz(&archive_path, target_dir, progress)
-                .await?;
-        }
+    /// Download URL and SHA-256 of the release archive the runner fetches itself for
+    /// `--external-runtime`: the `.zip` on Windows, which it can unpack without 7-Zip
+    pub async fn remote_archive(&self) -> Result<ExternalRuntime> {
+        anyhow::ensure!(
+            self.source.archive.is_none(),
+            "--external-runtime makes the app download Node.js from the mirror and cannot be combined with --node-archive"
+        );
+        self.source.build.ensure_published(self.platform)?;
+        download::ensure_online(&format!(
+            "checksums of Node.js {} for {}",
+            self.node_version,
+            self.platform_dir()
+        ))?;
+        let archive_name = self
+            .source
+            .build
+            .archive_name(self.platform, &self.node_version);
+        let archive_name = match archive_name.strip_suffix(".7z") {
+            Some(stem) => format!("{stem}.zip"),
+            None => archive_name,
+        };
+        self.ensure_listed(&archive_name).await?;
+        let (url, shasums_url) = self.archive_urls(&archive_name);
+        let shasums_url = shasums_url.with_context(|| {
+            format!(
+                "--external-runtime needs the archive's checksum; set checksumsUrl for platform {}",
+                self.platform
+            )
+        })?;
+        let temp_dir = tempfile::TempDir::new().context("Failed to create temporary directory")?;
+        let (shasums, _) = self.fetch_shasums(&shasums_url, temp_dir.path()).await?;
+        let sha256 = expected_checksum(&shasums, &archive_name)
+            .with_context(|| format!("{archive_name} is not listed in {shasums_url}"))?
+            .to_ascii_lowercase();
+        Ok(ExternalRuntime { url, sha256 })
+    }
 
-        // Clean up archive
-        fs::remove_file(&archive_path)
+    /// unofficial-builds only publishes some builds for some platforms and releases: check the
+    /// `files` its `index.json` lists for the release before downloading
+    async fn ensure_listed(&self, archive_name: &str) -> Result<()> {
+        if !self.source.build.is_unofficial(self.platform) {
+            return Ok(());
+        }
+        let base = self
+            .source
+            .build
+            .download_base(self.platform, &self.source.mirror());
+        let url = format!("{base}/index.json");
+        download::ensure_online(&format!("The Node.js build index of {base}"))?;
+        let releases: Vec<serde_json::Value> = download::client()?
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request {url}"))?
+            .error_for_status()?
+            .json()
             .await
-            .context("Failed to remove archive file")?;
+            .with_context(|| format!("Failed to parse {url}"))?;
+        let tag = format!("v{}", self.node_version);
+        let files: Vec<&str> = releases
+            .iter()
+            .find(|release| release["version"] == tag.as_str())
+            .and_then(|release| release["files"].as_array())
+            .map(|files| files.iter().filter_map(|file| file.as_str()).collect())
+            .unwrap_or_default();
+        let key = index_file_key(archive_name, &self.node_version);
+        anyhow::ensure!(
+            is_listed(&files, &key),
+            "{base} does not publish {archive_name}: Node.js {} has {}. Pick another version or nodeBuild, or pass a self-built archive with --node-archive",
+            self.node_version,
+            if files.is_empty() {
+                "no builds there".to_string()
+            } else {
+                format!("builds for {}", files.join(", "))
+            }
+        );
+        Ok(())
+    }
 
-        // Update in-memory cache with the path to the node executable
-        let node_executable_path = target_dir.join(self.platform.node_executable_path());
-        let mut cache = NODE_VERSION_CACHE
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to acquire cache lock: {}", e))?;
-        cache.insert(
-            format!("{}:{}", self.node_version, self.platform),
-            node_executable_path.clone(),
+    /// Download URL of `archive_name` and of the `SHASUMS256.txt` listing it: the release
+    /// directory on the mirror, or the URLs a custom platform declares
+    fn archive_urls(&self, archive_name: &str) -> (String, Option<String>) {
+        if let Some(custom) = self.platform.custom() {
+            return (
+                custom.node_url(&self.node_version),
+                custom.checksums_url(&self.node_version),
+            );
+        }
+        let release_url = format!(
+            "{}/v{}",
+            self.source
+                .build
+                .download_base(self.platform, &self.source.mirror()),
+            self.node_version
         );
+        (
+            format!("{release_url}/{archive_name}"),
+            Some(format!("{release_url}/SHASUMS256.txt")),
+        )
+    }
 
-        // Let caller finish the progress bar for this step
+    /// Check a `--node-archive` like a download, against the `SHASUMS256.txt` stored next to it
+    /// or else the mirror's
+    async fn verify_local_archive(&self, archive: &Path, target_dir: &Path) -> Result<()> {
+        let archive_name = archive
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (_, shasums_url) = self.archive_urls(&archive_name);
+        let Some(shasums_url) = shasums_url else {
+            warn!(
+                "Not verifying {archive_name}: platform {} sets insecureSkipChecksum",
+                self.platform
+            );
+            return Ok(());
+        };
+        let digest = sha256_file(archive).await?;
+        self.verify_download(&shasums_url, &archive_name, &digest, target_dir)
+            .await
+            .with_context(|| {
+                format!(
+                    "{} does not match the published Node.js release (put the SHASUMS256.txt listing a self-built archive next to it)",
+                    archive.display()
+                )
+            })
+    }
+
+    /// Check the archive against the release's `SHASUMS256.txt`
+    async fn verify_download(
+        &self,
+        shasums_url: &str,
+        archive_name: &str,
+        digest: &str,
+        target_dir: &Path,
+    ) -> Result<()> {
+        let (shasums, origin) = self.fetch_shasums(shasums_url, target_dir).await?;
+        let expected = expected_checksum(&shasums, archive_name)
+            .with_context(|| format!("{archive_name} is not listed in {origin}"))?;
+        if !expected.eq_ignore_ascii_case(digest) {
+            return Err(anyhow::Error::new(download::ChecksumMismatch {
+                what: archive_name.to_string(),
+                expected: expected.to_string(),
+                actual: digest.to_string(),
+            })
+            .context("The Node.js download is corrupted or was tampered with"));
+        }
+        info!("Verified SHA-256 of {archive_name}");
         Ok(())
     }
 
+    /// The release's `SHASUMS256.txt` and where it came from, its GPG signature verified first
+    /// when requested so a compromised mirror cannot swap both. A `--node-archive` may bring its
+    /// own copy (and `.sig`) in the same directory, for bundling offline
+    async fn fetch_shasums(
+        &self,
+        shasums_url: &str,
+        target_dir: &Path,
+    ) -> Result<(String, String)> {
+        let local = self
+            .source
+            .archive
+            .as_deref()
+            .and_then(Path::parent)
+            .map(|dir| dir.join("SHASUMS256.txt"))
+            .filter(|path| path.is_file());
+        let (shasums, origin) = match &local {
+            Some(path) => (
+                fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+                path.display().to_string(),
+            ),
+            None => (
+                download::client()?
+                    .get(shasums_url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| format!("Failed to download {shasums_url}"))?
+                    .text()
+                    .await
+                    .context("Failed to read SHASUMS256.txt")?,
+                shasums_url.to_string(),
+            ),
+        };
+
+        if self.source.verify_signature {
+            let keyring = self.node_keyring().await?;
+            let signature_path = target_dir.join("SHASUMS256.txt.sig");
+            let shasums_path = target_dir.join("SHASUMS256.txt");
+            match local.as_ref().map(|path| path.with_extension("txt.sig")) {
+                Some(local_signature) if local_signature.is_file() => {
+                    fs::copy(&local_signature, &signature_path)
+                        .await
+                        .with_context(|| format!("Failed to read {}", local_signature.display()))?;
+                }
+                _ => {
+                    download::download_to(&format!("{shasums_url}.sig"), &signature_path, None)
+                        .await?
+                }
+            }
+            fs::write(&shasums_path, &shasums)
+                .await
+                .context("Failed to write SHASUMS256.txt")?;
+            // Only the Node.js release keys may vouch for a release, not whatever the user's
+            // default keyring holds
+            let output = tokio::process::Command::new("gpg")
+                .arg("--batch")
+                .arg("--no-default-keyring")
+                .arg("--keyring")
+                .arg(&keyring)
+                .arg("--verify")
+                .arg(&signature_path)
+                .arg(&shasums_path)
+                .output()
+                .await;
+            let _ = fs::remove_file(&signature_path).await;
+            let _ = fs::remove_file(&shasums_path).await;
+            let output = output.context(
+                "Failed to run gpg for Node.js signature verification (is GnuPG installed?)",
+            )?;
+            anyhow::ensure!(
+                output.status.success(),
+                "GPG signature of {origin} could not be verified with the Node.js release keys in {} (delete it to fetch the current keys, or point {NODE_KEYRING_ENV} at a keyring): {}",
+                keyring.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            info!("Verified GPG signature of {origin}");
+        }
+        Ok((shasums, origin))
+    }
+
+    /// Keyring holding only the Node.js release keys: `BANDEROLE_NODE_KEYRING`, or the one
+    /// published by nodejs/release-keys, fetched into the cache on first use
+    async fn node_keyring(&self) -> Result<PathBuf> {
+        if let Some(keyring) = std::env::var_os(NODE_KEYRING_ENV) {
+            let keyring = std::path::absolute(PathBuf::from(keyring))
+                .with_context(|| format!("Invalid {NODE_KEYRING_ENV}"))?;
+            anyhow::ensure!(
+                keyring.is_file(),
+                "{NODE_KEYRING_ENV} points to {}, which does not exist",
+                keyring.display()
+            );
+            return Ok(keyring);
+        }
+        // gpg reads a bare --keyring name from its home directory, so always pass a full path
+        let keyring = std::path::absolute(self.cache_dir.join("node-release-keys.kbx"))?;
+        if !keyring.is_file() {
+            download::ensure_online("The Node.js release keys")?;
+            let partial = keyring.with_extension("kbx.part");
+            download::download_to(NODE_RELEASE_KEYRING_URL, &partial, None)
+                .await
+                .context("Failed to download the Node.js release keys")?;
+            fs::rename(&partial, &keyring)
+                .await
+                .context("Failed to store the Node.js release keys")?;
+        }
+        Ok(keyring)
+    }
+
+    /// Extract into `target_dir`, returning the archive's top-level folder that was flattened
     async fn extract_7z(
         &self,
         archive_path: &Path,
         target_dir: &Path,
         progress: Option<&ProgressBar>,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let archive_path = archive_path.to_path_buf();
         let target_dir = target_dir.to_path_buf();
         let progress = progress.cloned();
-        tokio::task::spawn_blocking(move || -> Result<()> {
+        tokio::task::spawn_blocking(move || -> Result<Option<String>> {
             if let Some(pb) = &progress {
                 pb.set_message("Extracting 7z archive");
             }
@@ -310,7 +900,9 @@ impl NodeDownloader {
             let top_files_exist = entries
                 .iter()
                 .any(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false));
+            let mut top_level = None;
             if top_dirs.len() == 1 && !top_files_exist {
+                top_level = Some(top_dirs[0].file_name().to_string_lossy().into_owned());
                 let inner = top_dirs[0].path();
                 for inner_entry in std::fs::read_dir(&inner)? {
                     let inner_entry = inner_entry?;
@@ -345,24 +937,23 @@ impl NodeDownloader {
             if let Some(pb) = &progress {
                 pb.finish_and_clear();
             }
-            Ok(())
+            Ok(top_level)
         })
-        .await??;
-
-        Ok(())
+        .await?
     }
 
+    /// Extract into `target_dir` without the archive's top-level folder, which is returned
     async fn extract_tar_xz(
         &self,
         archive_path: &Path,
         target_dir: &Path,
         progress: Option<&ProgressBar>,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let archive_path = archive_path.to_path_buf();
         let target_dir = target_dir.to_path_buf();
         let progress = progress.cloned();
 
-        tokio::task::spawn_blocking(move || -> Result<()> {
+        tokio::task::spawn_blocking(move || -> Result<Option<String>> {
             use std::io::Cursor;
             use tar::Archive;
 
@@ -400,6 +991,7 @@ impl NodeDownloader {
 
             // Second pass: extract
             let mut archive = Archive::new(Cursor::new(&tar_bytes));
+            let mut top_level = None;
 
             for entry in archive.entries().context("Failed to iterate tar entries")? {
                 let mut entry = entry.context("Failed to read tar entry")?;
@@ -408,7 +1000,10 @@ impl NodeDownloader {
                 // Strip the first component from the path
                 let mut components = path.components();
                 // discard first component
-                components.next();
+                if let Some(first) = components.next() {
+                    top_level
+                        .get_or_insert_with(|| first.as_os_str().to_string_lossy().into_owned());
+                }
                 let stripped: PathBuf = components.collect();
                 if stripped.as_os_str().is_empty() {
                     if let Some(pb) = &progress {
@@ -429,14 +1024,98 @@ impl NodeDownloader {
                 }
             }
 
-            Ok(())
+            Ok(top_level)
         })
-        .await??;
+        .await?
+    }
 
-        Ok(())
+    /// Extract into `target_dir` without the archive's top-level folder, which is returned
+    async fn extract_zip(
+        &self,
+        archive_path: &Path,
+        target_dir: &Path,
+        progress: Option<&ProgressBar>,
+    ) -> Result<Option<String>> {
+        let archive_path = archive_path.to_path_buf();
+        let target_dir = target_dir.to_path_buf();
+        let progress = progress.cloned();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<String>> {
+            let file = std::fs::File::open(&archive_path).context("Failed to open .zip archive")?;
+            let mut archive = zip::ZipArchive::new(std::io::BufReader::new(file))
+                .context("Failed to read .zip archive")?;
+            if let Some(pb) = &progress {
+                pb.set_length(archive.len() as u64);
+                pb.set_position(0);
+            }
+
+            let mut top_level = None;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i).context("Failed to read zip entry")?;
+                let path = entry
+                    .enclosed_name()
+                    .with_context(|| format!("Unsafe path in zip archive: {}", entry.name()))?;
+
+                let mut components = path.components();
+                if let Some(first) = components.next() {
+                    top_level
+                        .get_or_insert_with(|| first.as_os_str().to_string_lossy().into_owned());
+                }
+                let stripped: PathBuf = components.collect();
+                if !stripped.as_os_str().is_empty() {
+                    let outpath = target_dir.join(stripped);
+                    if entry.is_dir() {
+                        std::fs::create_dir_all(&outpath)?;
+                    } else {
+                        if let Some(parent) = outpath.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        let mut out = std::fs::File::create(&outpath)
+                            .with_context(|| format!("Failed to create {}", outpath.display()))?;
+                        std::io::copy(&mut entry, &mut out)
+                            .context("Failed to unpack zip entry")?;
+                        #[cfg(unix)]
+                        if let Some(mode) = entry.unix_mode() {
+                            use std::os::unix::fs::PermissionsExt;
+                            std::fs::set_permissions(
+                                &outpath,
+                                std::fs::Permissions::from_mode(mode & 0o7777),
+                            )?;
+                        }
+                    }
+                }
+
+                if let Some(pb) = &progress {
+                    pb.inc(1);
+                }
+            }
+
+            Ok(top_level)
+        })
+        .await?
     }
 }
 
+async fn sha256_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<String> {
+        let mut file = std::fs::File::open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher).context("Failed to hash Node.js archive")?;
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+/// Hash listed for `file` in a `SHASUMS256.txt` (`<hex>  <file>` per line)
+fn expected_checksum<'a>(shasums: &'a str, file: &str) -> Option<&'a str> {
+    shasums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim_start().trim_start_matches('*') == file).then_some(hash)
+    })
+}
+
 fn parse_full_version_spec(spec: &str) -> Option<String> {
     let cleaned = spec.trim().trim_start_matches('v');
     let parts: Vec<&str> = cleaned.split('.').collect();
@@ -446,3 +1125,241 @@ fn parse_full_version_spec(spec: &str) -> Option<String> {
         None
     }
 }
+
+/// How `index.json` names an archive in `files`: its platform and build, with `osx` for `darwin`
+/// (`node-v22.1.0-linux-x64-musl.tar.xz` is `linux-x64-musl`)
+fn index_file_key(archive_name: &str, version: &str) -> String {
+    let stem = archive_name
+        .strip_prefix(&format!("node-v{version}-"))
+        .unwrap_or(archive_name);
+    let stem = [".tar.xz", ".7z", ".zip"]
+        .into_iter()
+        .find_map(|ext| stem.strip_suffix(ext))
+        .unwrap_or(stem);
+    match stem.strip_prefix("darwin-") {
+        Some(rest) => format!("osx-{rest}"),
+        None => stem.to_string(),
+    }
+}
+
+/// `files` entries may add the archive format, as in `osx-arm64-tar` or `win-x64-zip`
+fn is_listed(files: &[&str], key: &str) -> bool {
+    files.iter().any(|file| {
+        file.strip_prefix(key)
+            .is_some_and(|rest| rest.is_empty() || matches!(rest, "-tar" | "-zip" | "-7z"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unofficial_builds_are_looked_up_in_the_index() {
+        let key = |build: NodeBuild, platform| {
+            index_file_key(&build.archive_name(platform, "22.1.0"), "22.1.0")
+        };
+        assert_eq!(
+            key(NodeBuild::Full, Platform::LinuxX64Musl),
+            "linux-x64-musl"
+        );
+        assert_eq!(
+            key(NodeBuild::SmallIcu, Platform::MacosArm64),
+            "osx-arm64-small-icu"
+        );
+        assert_eq!(
+            key(NodeBuild::WithoutIntl, Platform::WindowsX64),
+            "win-x64-without-intl"
+        );
+        let files = [
+            "linux-x64-musl",
+            "linux-x64-pointer-compression",
+            "osx-arm64-tar",
+        ];
+        assert!(is_listed(&files, "linux-x64-musl"));
+        assert!(is_listed(&files, "osx-arm64"));
+        assert!(!is_listed(&files, "linux-x64"));
+        assert!(!is_listed(&files, "linux-x64-small-icu"));
+    }
+
+    #[test]
+    fn test_release_dirs() {
+        assert_eq!(
+            NodeBuild::Full.release_dir(Platform::WindowsX64, "22.1.0"),
+            Some("node-v22.1.0-win-x64".to_string())
+        );
+        assert_eq!(
+            NodeBuild::PointerCompression.release_dir(Platform::LinuxX64, "22.1.0"),
+            Some("node-v22.1.0-linux-x64-pointer-compression".to_string())
+        );
+        assert_eq!(
+            NodeBuild::Full.release_dir(Platform::LinuxArm64Musl, "20.0.0"),
+            Some("node-v20.0.0-linux-arm64-musl".to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_archive_names() {
+        assert_eq!(
+            NodeBuild::Full.archive_name(Platform::LinuxX64, "22.1.0"),
+            "node-v22.1.0-linux-x64.tar.xz"
+        );
+        assert_eq!(
+            NodeBuild::SmallIcu.archive_name(Platform::MacosArm64, "22.1.0"),
+            "node-v22.1.0-darwin-arm64-small-icu.tar.xz"
+        );
+        assert_eq!(
+            NodeBuild::WithoutIntl.archive_name(Platform::WindowsX64, "22.1.0"),
+            "node-v22.1.0-win-x64-without-intl.7z"
+        );
+        assert_eq!(
+            NodeBuild::Full.archive_name(Platform::LinuxArmv7, "22.1.0"),
+            "node-v22.1.0-linux-armv7l.tar.xz"
+        );
+        assert_eq!(
+            NodeBuild::SmallIcu.archive_name(Platform::WindowsArm64, "22.1.0"),
+            "node-v22.1.0-win-arm64-small-icu.zip"
+        );
+        assert_eq!(
+            NodeBuild::PointerCompression.archive_name(Platform::LinuxX64, "22.1.0"),
+            "node-v22.1.0-linux-x64-pointer-compression.tar.xz"
+        );
+        assert!(NodeBuild::Debug
+            .ensure_published(Platform::LinuxX64)
+            .is_ok());
+        assert!(NodeBuild::Debug
+            .ensure_published(Platform::MacosArm64)
+            .is_err());
+        assert_eq!(
+            NodeBuild::Debug.download_base(Platform::LinuxX64, "https://nodejs.org/dist"),
+            NODE_BUILDS_MIRROR
+        );
+        assert_eq!(
+            NodeBuild::Full.archive_name(Platform::LinuxX64Musl, "22.1.0"),
+            "node-v22.1.0-linux-x64-musl.tar.xz"
+        );
+        assert_eq!(
+            NodeBuild::Full.download_base(Platform::LinuxArm64Musl, "https://nodejs.org/dist"),
+            NODE_BUILDS_MIRROR
+        );
+        assert_eq!(
+            NodeBuild::Full.download_base(Platform::LinuxX64, "https://nodejs.org/dist"),
+            "https://nodejs.org/dist"
+        );
+    }
+
+    #[test]
+    fn test_archive_version() {
+        let source = |name: &str| NodeSource {
+            archive: Some(PathBuf::from("/downloads").join(name)),
+            ..Default::default()
+        };
+        assert_eq!(
+            source("node-v22.17.1-linux-x64.tar.xz")
+                .archive_version()
+                .unwrap()
+                .as_deref(),
+            Some("22.17.1")
+        );
+        assert!(source("node.tar.xz").archive_version().is_err());
+        assert_eq!(NodeSource::default().archive_version().unwrap(), None);
+    }
+
+    #[test]
+    fn test_expected_checksum() {
+        let shasums = "\
+1111  node-v22.1.0-darwin-arm64.tar.xz
+2222  node-v22.1.0-linux-x64.tar.xz
+3333  node-v22.1.0-linux-x64.tar.xz.sig
+4444  win-x64/node.exe
+";
+        assert_eq!(
+            expected_checksum(shasums, "node-v22.1.0-linux-x64.tar.xz"),
+            Some("2222")
+        );
+        assert_eq!(expected_checksum(shasums, "node.exe"), None);
+    }
+
+    fn zip_archive(dir: &Path, entries: &[(&str, u32)]) -> PathBuf {
+        use std::io::Write;
+        let path = dir.join("archive.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (name, mode) in entries {
+            let options = zip::write::SimpleFileOptions::default().unix_permissions(*mode);
+            if name.ends_with('/') {
+                zip.add_directory(*name, options).unwrap();
+            } else {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(name.as_bytes()).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    fn downloader(cache_dir: &Path) -> NodeDownloader {
+        NodeDownloader {
+            platform: Platform::WindowsX64,
+            cache_dir: cache_dir.to_path_buf(),
+            node_version: "22.1.0".to_string(),
+            source: NodeSource::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_zip_strips_the_top_level_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let archive = zip_archive(
+            dir.path(),
+            &[
+                ("node-v22.1.0-win-x64/", 0o755),
+                ("node-v22.1.0-win-x64/node.exe", 0o755),
+                ("node-v22.1.0-win-x64/node_modules/npm/package.json", 0o644),
+                ("node-v22.1.0-win-x64/empty/", 0o755),
+            ],
+        );
+        let target = dir.path().join("out");
+        let top_level = downloader(dir.path())
+            .extract_zip(&archive, &target, None)
+            .await
+            .unwrap();
+
+        assert_eq!(top_level.as_deref(), Some("node-v22.1.0-win-x64"));
+        assert_eq!(
+            std::fs::read_to_string(target.join("node.exe")).unwrap(),
+            "node-v22.1.0-win-x64/node.exe"
+        );
+        assert!(target.join("node_modules/npm/package.json").is_file());
+        assert!(target.join("empty").is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |name: &str| {
+                std::fs::metadata(target.join(name))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o777
+            };
+            assert_eq!(mode("node.exe"), 0o755);
+            assert_eq!(mode("node_modules/npm/package.json"), 0o644);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_zip_rejects_paths_leaving_the_target() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let archive = zip_archive(
+            dir.path(),
+            &[("node/node.exe", 0o755), ("node/../../escaped", 0o644)],
+        );
+        let target = dir.path().join("out");
+        let error = downloader(dir.path())
+            .extract_zip(&archive, &target, None)
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("Unsafe path"), "{error}");
+        assert!(!dir.path().join("escaped").exists());
+    }
+}
@@ -1,8 +1,15 @@
+use crate::cache;
+use crate::download;
 use anyhow::{Context, Result};
 use lazy_static::lazy_static;
+use log::debug;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{Duration, Instant};
 
 lazy_static! {
@@ -13,6 +20,15 @@ lazy_static! {
 pub struct NodeVersion {
     pub version: String,
     pub date: String,
+    /// `false`, or the codename of the LTS line this release belongs to
+    #[serde(default)]
+    pub lts: serde_json::Value,
+}
+
+impl NodeVersion {
+    fn lts_codename(&self) -> Option<&str> {
+        self.lts.as_str()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +60,59 @@ impl VersionCache {
     }
 }
 
+/// How long the on-disk copy of a mirror's `index.json` is trusted
+const DISK_CACHE_TTL: Duration = Duration::from_secs(86400);
+
+/// A mirror's version index and the specs already resolved against it, kept between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiskCache {
+    /// Unix time the index was downloaded
+    fetched_at: u64,
+    versions: Vec<NodeVersion>,
+    /// Version spec → resolved version
+    resolutions: BTreeMap<String, String>,
+}
+
+impl DiskCache {
+    /// The cache at `path`, unless it is missing, unreadable or older than [`DISK_CACHE_TTL`].
+    ///
+    /// Offline, a stale index is still better than none.
+    fn load(path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        let cache: Self = serde_json::from_slice(&data)
+            .inspect_err(|e| debug!("Ignoring unreadable {}: {e}", path.display()))
+            .ok()?;
+        let age = unix_now().saturating_sub(cache.fetched_at);
+        (age < DISK_CACHE_TTL.as_secs() || download::offline()).then_some(cache)
+    }
+
+    /// Best effort: a failed write only costs a download next time
+    fn store(&self, path: &Path) {
+        let result = (|| -> Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Write then rename so concurrent bundles never read a half-written file
+            let temp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&temp, serde_json::to_vec(self)?)?;
+            std::fs::rename(&temp, path).inspect_err(|_| {
+                let _ = std::fs::remove_file(&temp);
+            })?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            debug!("Failed to write {}: {e:#}", path.display());
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ParsedVersion {
     pub major: u32,
@@ -123,34 +192,259 @@ impl Ord for ParsedVersion {
 
 impl Eq for ParsedVersion {}
 
+/// A semver range as found in `engines.node`: comparator sets joined by `||`, e.g. `>=18 <21`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionRange {
+    /// Alternatives; a version matches when it satisfies every bound of one of them
+    sets: Vec<Vec<Bound>>,
+}
+
+/// Inclusive lower or exclusive upper limit; partial versions are expanded when parsing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    AtLeast((u32, u32, u32)),
+    Below((u32, u32, u32)),
+}
+
+impl VersionRange {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let sets = spec
+            .split("||")
+            .map(|set| {
+                let mut tokens: Vec<String> = set.split_whitespace().map(str::to_string).collect();
+                // Hyphen ranges: `18 - 20.1` is `>=18 <=20.1`
+                while let Some(i) = tokens.iter().position(|token| token == "-") {
+                    anyhow::ensure!(
+                        i > 0 && i + 1 < tokens.len(),
+                        "Invalid version range '{spec}': '-' needs a version on both sides"
+                    );
+                    let upper = format!("<={}", tokens.remove(i + 1));
+                    tokens[i] = upper;
+                    tokens[i - 1] = format!(">={}", tokens[i - 1]);
+                }
+                let mut bounds = Vec::new();
+                let mut pending_op = String::new();
+                for token in &tokens {
+                    // `>= 18` is the same as `>=18`
+                    if token
+                        .chars()
+                        .all(|c| matches!(c, '<' | '>' | '=' | '^' | '~'))
+                    {
+                        pending_op.push_str(token);
+                        continue;
+                    }
+                    let comparator = format!("{pending_op}{token}");
+                    pending_op.clear();
+                    bounds.extend(parse_comparator(&comparator).with_context(|| {
+                        format!("Invalid version range '{spec}' at '{comparator}'")
+                    })?);
+                }
+                anyhow::ensure!(
+                    pending_op.is_empty(),
+                    "Invalid version range '{spec}': '{pending_op}' without a version"
+                );
+                Ok(bounds)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { sets })
+    }
+
+    pub fn matches(&self, version: &ParsedVersion) -> bool {
+        let version = (
+            version.major,
+            version.minor.unwrap_or(0),
+            version.patch.unwrap_or(0),
+        );
+        self.sets.iter().any(|bounds| {
+            bounds.iter().all(|bound| match *bound {
+                Bound::AtLeast(lower) => version >= lower,
+                Bound::Below(upper) => version < upper,
+            })
+        })
+    }
+}
+
+/// Version with `x`/`*` wildcards stripped, plus the number of components given
+fn parse_partial(version: &str) -> Result<((u32, u32, u32), usize)> {
+    let mut parts = [0; 3];
+    let mut given = 0;
+    for (i, part) in version.trim_start_matches('v').split('.').enumerate() {
+        anyhow::ensure!(i < 3, "too many version components");
+        if matches!(part, "x" | "X" | "*") {
+            break;
+        }
+        parts[i] = part.parse().context("invalid version number")?;
+        given = i + 1;
+    }
+    Ok(((parts[0], parts[1], parts[2]), given))
+}
+
+/// First version past everything matching a partial version (`18` -> `19.0.0`)
+fn bump((major, minor, patch): (u32, u32, u32), given: usize) -> (u32, u32, u32) {
+    match given {
+        1 => (major + 1, 0, 0),
+        2 => (major, minor + 1, 0),
+        _ => (major, minor, patch + 1),
+    }
+}
+
+fn parse_comparator(comparator: &str) -> Result<Vec<Bound>> {
+    let (op, version) = match comparator.find(|c: char| c.is_ascii_alphanumeric() || c == '*') {
+        Some(index) => comparator.split_at(index),
+        None => (comparator, ""),
+    };
+    let (version, given) = if version.is_empty() {
+        ((0, 0, 0), 0)
+    } else {
+        parse_partial(version)?
+    };
+    if given == 0 {
+        // `*`, `x` or `>=*`: no constraint
+        anyhow::ensure!(
+            op.is_empty() || op == ">=" || op == "=",
+            "unsupported comparator"
+        );
+        return Ok(Vec::new());
+    }
+    Ok(match op {
+        ">=" => vec![Bound::AtLeast(version)],
+        ">" => vec![Bound::AtLeast(bump(version, given))],
+        "<" => vec![Bound::Below(version)],
+        "<=" => vec![Bound::Below(bump(version, given))],
+        "" | "=" => vec![Bound::AtLeast(version), Bound::Below(bump(version, given))],
+        // Changes that do not modify the left-most non-zero component: ^1.2 is <2, ^0.2 is <0.3
+        "^" => {
+            let parts = [version.0, version.1, version.2];
+            let significant = parts[..given]
+                .iter()
+                .position(|&part| part != 0)
+                .map_or(given, |index| index + 1);
+            vec![
+                Bound::AtLeast(version),
+                Bound::Below(bump(version, significant)),
+            ]
+        }
+        // Patch-level changes if a minor version is given, minor-level otherwise
+        "~" | "~>" => vec![
+            Bound::AtLeast(version),
+            Bound::Below(bump(version, given.min(2))),
+        ],
+        other => anyhow::bail!("unsupported operator '{other}'"),
+    })
+}
+
 pub struct NodeVersionManager {
-    client: reqwest::Client,
+    mirror: String,
 }
 
 impl NodeVersionManager {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_mirror("https://nodejs.org/dist")
+    }
+
+    /// Resolve against a nodejs.org/dist compatible mirror (must serve `index.json`)
+    pub fn with_mirror(mirror: &str) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            mirror: mirror.trim_end_matches('/').to_string(),
         }
     }
 
-    /// Resolve a version specification like "23", "23.5", "v22.1.0" to a complete version
+    /// Resolve a version specification like "23", "23.5", "v22.1.0" to a complete version.
+    ///
+    /// The mirror's index and earlier resolutions are cached on disk for a day;
+    /// `ignore_cached_versions` skips (and refreshes) that cache.
     pub async fn resolve_version(
         &self,
         version_spec: &str,
         ignore_cached_versions: bool,
     ) -> Result<String> {
-        let versions = self.fetch_versions(ignore_cached_versions).await?;
-        let parsed_spec = self.parse_version_spec(version_spec)?;
+        let cache_path = self.disk_cache_path();
+        if !ignore_cached_versions {
+            let cached = cache_path
+                .as_deref()
+                .and_then(DiskCache::load)
+                .and_then(|mut cache| cache.resolutions.remove(version_spec));
+            if let Some(version) = cached {
+                debug!("Using cached resolution {version_spec} -> {version}");
+                return Ok(version);
+            }
+        }
 
-        let matching_versions = self.find_matching_versions(&versions, &parsed_spec);
+        let versions = self.fetch_versions(ignore_cached_versions).await?;
+        let matching_versions = self.find_matching_spec(&versions, version_spec)?;
 
         if matching_versions.is_empty() {
             anyhow::bail!("No Node.js version found matching '{}'", version_spec);
         }
 
         let latest = matching_versions.last().unwrap();
-        Ok(latest.version.trim_start_matches('v').to_string())
+        let version = latest.version.trim_start_matches('v').to_string();
+
+        if let Some(path) = &cache_path {
+            if let Some(mut cache) = DiskCache::load(path) {
+                cache
+                    .resolutions
+                    .insert(version_spec.to_string(), version.clone());
+                cache.store(path);
+            }
+        }
+        Ok(version)
+    }
+
+    /// `<cache>/node-versions/<mirror hash>.json`; `None` when there is no usable cache directory
+    fn disk_cache_path(&self) -> Option<PathBuf> {
+        let key = format!("{:x}", Sha256::digest(self.mirror.as_bytes()));
+        cache::persistent_cache_dir().ok().map(|dir| {
+            dir.join("node-versions")
+                .join(format!("{}.json", &key[..16]))
+        })
+    }
+
+    /// Versions matching a prefix (`22.1`), an nvm alias (`lts/*`, `lts/iron`, `latest`) or a
+    /// semver range (`^20.9`, `>=18 <20`), oldest first
+    fn find_matching_spec<'a>(
+        &self,
+        versions: &'a [NodeVersion],
+        version_spec: &str,
+    ) -> Result<Vec<&'a NodeVersion>> {
+        let alias = version_spec.trim().to_ascii_lowercase();
+        if matches!(alias.as_str(), "latest" | "node" | "current") {
+            return Ok(versions.iter().collect());
+        }
+        if let Some(codename) = alias.strip_prefix("lts") {
+            let codename = codename.trim_start_matches('/');
+            return Ok(versions
+                .iter()
+                .filter(|version| {
+                    version.lts_codename().is_some_and(|name| {
+                        codename.is_empty()
+                            || codename == "*"
+                            || name.eq_ignore_ascii_case(codename)
+                    })
+                })
+                .collect());
+        }
+
+        Ok(match self.parse_version_spec(version_spec) {
+            Ok(parsed_spec) => self.find_matching_versions(versions, &parsed_spec),
+            // Anything beyond a version prefix is treated as a semver range (`engines.node`)
+            Err(_) => {
+                let range = VersionRange::parse(version_spec)?;
+                let mut matching: Vec<&NodeVersion> = versions
+                    .iter()
+                    .filter(|version| {
+                        self.parse_node_version(&version.version)
+                            .is_ok_and(|parsed| range.matches(&parsed))
+                    })
+                    .collect();
+                matching.sort_by_cached_key(|version| {
+                    self.parse_node_version(&version.version)
+                        .unwrap_or_default()
+                });
+                matching
+            }
+        })
     }
 
     async fn fetch_versions(&self, ignore_cached_versions: bool) -> Result<Vec<NodeVersion>> {
@@ -165,10 +459,24 @@ impl NodeVersionManager {
             }
         }
 
-        let url = "https://nodejs.org/dist/index.json";
-        let response = self
-            .client
-            .get(url)
+        let cache_path = self.disk_cache_path();
+        if !ignore_cached_versions {
+            if let Some(cached) = cache_path.as_deref().and_then(DiskCache::load) {
+                if !cached.versions.is_empty() {
+                    debug!("Using cached Node.js version index for {}", self.mirror);
+                    VERSION_CACHE
+                        .lock()
+                        .map_err(|e| anyhow::anyhow!("Failed to acquire cache lock: {}", e))?
+                        .update(cached.versions.clone());
+                    return Ok(cached.versions);
+                }
+            }
+        }
+
+        download::ensure_online(&format!("The Node.js version index of {}", self.mirror))?;
+        let url = format!("{}/index.json", self.mirror);
+        let response = download::client()?
+            .get(&url)
             .timeout(Duration::from_secs(30))
             .send()
             .await
@@ -197,6 +505,16 @@ impl NodeVersionManager {
             cache.update(versions.clone());
         }
 
+        // A fresh index invalidates every earlier resolution
+        if let Some(path) = &cache_path {
+            DiskCache {
+                fetched_at: unix_now(),
+                versions: versions.clone(),
+                resolutions: BTreeMap::new(),
+            }
+            .store(path);
+        }
+
         Ok(versions)
     }
 
@@ -280,6 +598,31 @@ impl Default for ParsedVersion {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_disk_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("node-versions").join("mirror.json");
+        assert!(DiskCache::load(&path).is_none());
+
+        let mut cache = DiskCache {
+            fetched_at: unix_now(),
+            versions: vec![NodeVersion {
+                version: "v22.1.0".into(),
+                date: "2024-05-02".into(),
+                lts: serde_json::Value::Bool(false),
+            }],
+            resolutions: BTreeMap::from([("22".into(), "22.1.0".into())]),
+        };
+        cache.store(&path);
+        let loaded = DiskCache::load(&path).unwrap();
+        assert_eq!(loaded.versions.len(), 1);
+        assert_eq!(loaded.resolutions["22"], "22.1.0");
+
+        cache.fetched_at -= DISK_CACHE_TTL.as_secs() + 1;
+        cache.store(&path);
+        assert!(DiskCache::load(&path).is_none());
+    }
+
     #[test]
     fn test_parse_version_spec() {
         let resolver = NodeVersionManager::new();
@@ -329,6 +672,69 @@ mod tests {
         assert!(v3 < v4);
     }
 
+    #[test]
+    fn test_version_range() {
+        let matches = |range: &str, version: (u32, u32, u32)| {
+            VersionRange::parse(range)
+                .unwrap()
+                .matches(&ParsedVersion::new(
+                    version.0,
+                    Some(version.1),
+                    Some(version.2),
+                ))
+        };
+        assert!(matches(">=18 <21", (20, 11, 1)));
+        assert!(!matches(">=18 <21", (21, 0, 0)));
+        assert!(!matches(">=18 <21", (16, 20, 2)));
+        assert!(matches("<=20", (20, 9, 0)));
+        assert!(matches(">18.1", (18, 2, 0)));
+        assert!(!matches(">18.1", (18, 1, 9)));
+        assert!(matches("16.x || >=20", (16, 20, 2)));
+        assert!(matches("16.x || >=20", (22, 0, 0)));
+        assert!(!matches("16.x || >=20", (18, 0, 0)));
+        assert!(matches("*", (18, 0, 0)));
+        assert!(matches(">= 18.17", (18, 17, 0)));
+        assert!(matches("^20.9", (20, 18, 0)));
+        assert!(!matches("^20.9", (20, 8, 0)));
+        assert!(!matches("^20.9", (21, 0, 0)));
+        assert!(matches("^0.2.3", (0, 2, 9)));
+        assert!(!matches("^0.2.3", (0, 3, 0)));
+        assert!(matches("~22.1", (22, 1, 5)));
+        assert!(!matches("~22.1", (22, 2, 0)));
+        assert!(matches("~22", (22, 9, 0)));
+        assert!(matches("18 - 20.1", (20, 1, 7)));
+        assert!(!matches("18 - 20.1", (20, 2, 0)));
+        assert!(!matches("18 - 20.1", (17, 9, 0)));
+        assert!(VersionRange::parse(">=abc").is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let resolver = NodeVersionManager::new();
+        let versions: Vec<NodeVersion> = serde_json::from_str(
+            r#"[
+                {"version":"v18.20.4","date":"2024-07-08","lts":"Hydrogen"},
+                {"version":"v20.17.0","date":"2024-08-21","lts":"Iron"},
+                {"version":"v22.8.0","date":"2024-09-03","lts":false},
+                {"version":"v23.0.0","date":"2024-10-16"}
+            ]"#,
+        )
+        .unwrap();
+        let latest = |spec: &str| {
+            resolver
+                .find_matching_spec(&versions, spec)
+                .unwrap()
+                .last()
+                .map(|v| v.version.clone())
+        };
+        assert_eq!(latest("lts/*").as_deref(), Some("v20.17.0"));
+        assert_eq!(latest("lts/hydrogen").as_deref(), Some("v18.20.4"));
+        assert_eq!(latest("latest").as_deref(), Some("v23.0.0"));
+        assert_eq!(latest("^20.9").as_deref(), Some("v20.17.0"));
+        assert_eq!(latest(">=18 <20").as_deref(), Some("v18.20.4"));
+        assert_eq!(latest("lts/argon"), None);
+    }
+
     #[tokio::test]
     async fn test_version_resolution() {
         let resolver = NodeVersionManager::new();
@@ -0,0 +1,146 @@
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Pick the human-readable output style: `--quiet` prints no stage messages at all, while
+/// `NO_COLOR` or a stdout that is not a terminal (CI logs, pipes) get plain single-line ones
+pub fn configure(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if no_color || !console::Term::stdout().is_term() {
+        PLAIN.store(true, Ordering::Relaxed);
+        console::set_colors_enabled(false);
+    }
+    if no_color {
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+/// `--quiet`: only errors, warnings and explicitly requested reports are printed
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// No colors, emoji or redrawn lines
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Whether progress bars and spinners are drawn
+pub fn progress_visible() -> bool {
+    !(json() || quiet() || plain())
+}
+
+/// Switch stdout to JSON lines for `bundle --json`; human-readable output is suppressed
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+/// Whether stdout carries JSON events instead of human-readable progress
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// One line of `--json` output, tagged by its `event` field
+#[derive(Debug, Serialize)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Event<'a> {
+    /// The runtime that is bundled, once it is resolved
+    Runtime { name: &'a str, version: &'a str },
+    /// A bundling stage started
+    Stage { step: u8, total: u8, name: &'a str },
+    /// A warning that was also logged to stderr
+    Warning { message: &'a str },
+    /// `--analyze` breakdown, instead of the text report
+    SizeReport {
+        report: &'a crate::size_report::SizeReport,
+    },
+    /// What `--dry-run` would bundle, instead of the stages
+    Plan {
+        plan: &'a crate::bundler::plan::BundlePlan,
+    },
+    /// `--analyze=json`/`--analyze=html` report file
+    ReportWritten { path: &'a Path },
+    /// Final line of a successful bundle
+    Result(&'a crate::bundler::BundleSummary),
+    /// Final line of a failed run
+    Error { message: &'a str, exit_code: i32 },
+}
+
+/// Print `event` as a single JSON line on stdout
+pub fn emit(event: &Event) {
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{line}"),
+        Err(e) => log::debug!("Failed to serialize {event:?}: {e}"),
+    }
+}
+
+/// Warnings logged so far, in order; cleared by the call
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut WARNINGS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Logger adapter that reports warnings as [`Event::Warning`] and keeps them for the result
+pub struct RecordWarnings<L>(pub L);
+
+impl<L: log::Log> log::Log for RecordWarnings<L> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() == log::Level::Warn && self.0.enabled(record.metadata()) {
+            let message = record.args().to_string();
+            emit(&Event::Warning { message: &message });
+            WARNINGS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(message);
+        }
+        self.0.log(record);
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_tagged_camel_case() {
+        let line = serde_json::to_string(&Event::Error {
+            message: "boom",
+            exit_code: 1,
+        })
+        .unwrap();
+        assert_eq!(line, r#"{"event":"error","message":"boom","exitCode":1}"#);
+
+        let summary = crate::bundler::BundleSummary {
+            output: "/tmp/app".into(),
+            size: 42,
+            runtime: "Node.js".to_string(),
+            runtime_version: "22.17.1".to_string(),
+            duration_ms: 1500,
+            warnings: vec!["stale".to_string()],
+            dry_run: false,
+        };
+        let value = serde_json::to_value(Event::Result(&summary)).unwrap();
+        assert_eq!(value["event"], "result");
+        assert_eq!(value["runtimeVersion"], "22.17.1");
+        assert_eq!(value["durationMs"], 1500);
+        assert_eq!(value["warnings"][0], "stale");
+    }
+}
@@ -0,0 +1,400 @@
+//! `banderole package`: wrap a built executable into a native package: deb or rpm for Linux,
+//! msi for Windows.
+//!
+//! deb and rpm are written directly, without dpkg-deb, rpmbuild or fpm. The executable is
+//! installed into `--install-dir` (default `/usr/bin`) and systemd units into
+//! `/usr/lib/systemd/system`, with maintainer scripts that enable and restart the units on
+//! install and disable them on removal. msi installers are compiled with WiX (see [`msi`]).
+
+mod deb;
+mod msi;
+mod rpm;
+
+use crate::manifest::BundleManifest;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Install directory of deb and rpm packages unless `--install-dir` is given
+const DEFAULT_INSTALL_DIR: &str = "/usr/bin";
+
+/// Where systemd units shipped by packages live on current distributions
+const SYSTEMD_UNIT_DIR: &str = "/usr/lib/systemd/system";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PackageFormat {
+    /// Debian/Ubuntu `.deb`
+    Deb,
+    /// Fedora/RHEL/SUSE `.rpm`
+    Rpm,
+    /// Windows Installer `.msi`
+    Msi,
+}
+
+/// Package metadata from the command line; unset fields come from the executable's manifest
+#[derive(Debug, Clone, Default)]
+pub struct PackageOptions {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Packaging revision of the same app version
+    pub release: String,
+    pub description: Option<String>,
+    pub maintainer: String,
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+    /// Directory the executable is installed into (deb and rpm)
+    pub install_dir: Option<PathBuf>,
+    /// systemd unit files installed next to the executable and enabled on install (deb and rpm)
+    pub systemd_units: Vec<PathBuf>,
+    /// PKCS#12 certificate the installer and executable are signed with (msi)
+    pub certificate: Option<PathBuf>,
+    /// RFC 3161 timestamp server used when signing (msi)
+    pub timestamp_url: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+/// CPU architecture of a Linux executable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arch {
+    X64,
+    Arm64,
+    Armv7,
+}
+
+impl Arch {
+    /// From the ELF header's `e_machine`
+    fn of_executable(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"\x7fELF") || data.len() < 20 {
+            bail!("deb and rpm packages wrap Linux executables; build the bundle on Linux");
+        }
+        Ok(match u16::from_le_bytes([data[18], data[19]]) {
+            0x3e => Arch::X64,
+            0xb7 => Arch::Arm64,
+            0x28 => Arch::Armv7,
+            machine => bail!("Unsupported ELF machine type {machine:#x}"),
+        })
+    }
+
+    fn deb_name(self) -> &'static str {
+        match self {
+            Arch::X64 => "amd64",
+            Arch::Arm64 => "arm64",
+            Arch::Armv7 => "armhf",
+        }
+    }
+
+    fn rpm_name(self) -> &'static str {
+        match self {
+            Arch::X64 => "x86_64",
+            Arch::Arm64 => "aarch64",
+            Arch::Armv7 => "armv7hl",
+        }
+    }
+}
+
+/// A file installed by the package
+struct PackageFile {
+    /// Absolute install path
+    path: String,
+    contents: Vec<u8>,
+    mode: u32,
+}
+
+/// Everything both package writers need
+struct PackageSpec {
+    name: String,
+    /// Upstream version with prerelease hyphens turned into `~`, so they sort before the release
+    version: String,
+    release: String,
+    summary: String,
+    maintainer: String,
+    license: Option<String>,
+    homepage: Option<String>,
+    arch: Arch,
+    files: Vec<PackageFile>,
+    units: Vec<String>,
+    /// Seconds since the epoch, used for file and build times
+    mtime: u64,
+}
+
+impl PackageSpec {
+    /// Shell script run after installing or upgrading
+    fn post_install(&self) -> Option<String> {
+        (!self.units.is_empty()).then(|| {
+            let units = self.units.join(" ");
+            format!(
+                "#!/bin/sh\nset -e\nif [ -d /run/systemd/system ]; then\n  systemctl daemon-reload\n  systemctl enable {units}\n  systemctl restart {units}\nfi\n"
+            )
+        })
+    }
+
+    /// Shell script run before removing; `removing` is the shell test that is true on removal
+    /// rather than on upgrade
+    fn pre_remove(&self, removing: &str) -> Option<String> {
+        (!self.units.is_empty()).then(|| {
+            let units = self.units.join(" ");
+            format!(
+                "#!/bin/sh\nif [ -d /run/systemd/system ] && {removing}; then\n  systemctl disable --now {units} || true\nfi\n"
+            )
+        })
+    }
+
+    /// Shell script run after removing
+    fn post_remove(&self) -> Option<String> {
+        (!self.units.is_empty()).then(|| {
+            "#!/bin/sh\nif [ -d /run/systemd/system ]; then\n  systemctl daemon-reload || true\nfi\n"
+                .to_string()
+        })
+    }
+}
+
+/// Build the package for `executable` and return where it was written
+pub fn package(
+    executable: &Path,
+    format: PackageFormat,
+    options: PackageOptions,
+) -> Result<PathBuf> {
+    let manifest = BundleManifest::read_from_executable(executable)?;
+    let data =
+        fs::read(executable).with_context(|| format!("Failed to read {}", executable.display()))?;
+    if format == PackageFormat::Msi {
+        anyhow::ensure!(
+            options.install_dir.is_none() && options.systemd_units.is_empty(),
+            "--install-dir and --systemd-unit apply to deb and rpm packages; msi installers use Program Files"
+        );
+        return msi::build(executable, &data, &manifest, options);
+    }
+    anyhow::ensure!(
+        options.certificate.is_none(),
+        "--certificate applies to msi installers; sign deb and rpm packages with your distribution's tools"
+    );
+    let arch = Arch::of_executable(&data)?;
+
+    let name = package_name(options.name.as_deref().unwrap_or(&manifest.app_name));
+    anyhow::ensure!(
+        !name.is_empty(),
+        "Cannot derive a package name from '{}'; pass --name",
+        manifest.app_name
+    );
+    let version = options
+        .version
+        .unwrap_or_else(|| manifest.app_version.clone())
+        .replace('-', "~");
+    anyhow::ensure!(
+        version.starts_with(|c: char| c.is_ascii_digit()),
+        "Package versions must start with a digit, got '{version}'; pass --version"
+    );
+    let install_dir = options
+        .install_dir
+        .as_deref()
+        .unwrap_or(Path::new(DEFAULT_INSTALL_DIR))
+        .to_string_lossy()
+        .trim_end_matches('/')
+        .to_string();
+    anyhow::ensure!(
+        install_dir.starts_with('/'),
+        "--install-dir must be absolute, got '{install_dir}'"
+    );
+    let binary = executable
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| name.clone());
+
+    let mut files = vec![PackageFile {
+        path: format!("{install_dir}/{binary}"),
+        contents: data,
+        mode: 0o755,
+    }];
+    let mut units = Vec::new();
+    for unit in &options.systemd_units {
+        let unit_name = unit
+            .file_name()
+            .with_context(|| format!("{} is not a unit file", unit.display()))?
+            .to_string_lossy()
+            .into_owned();
+        files.push(PackageFile {
+            path: format!("{SYSTEMD_UNIT_DIR}/{unit_name}"),
+            contents: fs::read(unit)
+                .with_context(|| format!("Failed to read systemd unit {}", unit.display()))?,
+            mode: 0o644,
+        });
+        units.push(unit_name);
+    }
+
+    let spec = PackageSpec {
+        summary: options
+            .description
+            .unwrap_or_else(|| format!("{} {}", manifest.app_name, manifest.app_version)),
+        name,
+        version,
+        release: options.release,
+        maintainer: options.maintainer,
+        license: options.license,
+        homepage: options.homepage,
+        arch,
+        files,
+        units,
+        mtime: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0),
+    };
+
+    let (file_name, contents) = match format {
+        PackageFormat::Deb => (
+            format!(
+                "{}_{}-{}_{}.deb",
+                spec.name,
+                spec.version,
+                spec.release,
+                spec.arch.deb_name()
+            ),
+            deb::build(&spec)?,
+        ),
+        PackageFormat::Rpm => (
+            format!(
+                "{}-{}-{}.{}.rpm",
+                spec.name,
+                spec.version,
+                spec.release,
+                spec.arch.rpm_name()
+            ),
+            rpm::build(&spec)?,
+        ),
+        PackageFormat::Msi => unreachable!("msi installers are built above"),
+    };
+    let output = options.output.unwrap_or_else(|| {
+        executable
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(file_name)
+    });
+    fs::write(&output, contents)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(output)
+}
+
+/// A name both dpkg and rpm accept: lowercase letters, digits, `+`, `-` and `.`; npm scopes
+/// (`@acme/tool`) become a prefix (`acme-tool`). Also names services and extraction directories.
+pub(crate) fn package_name(name: &str) -> String {
+    let name: String = name
+        .trim_start_matches('@')
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '+' | '-' | '.' => c,
+            _ => '-',
+        })
+        .collect();
+    name.trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(package_name("@acme/My_Tool"), "acme-my-tool");
+        assert_eq!(package_name("server"), "server");
+        assert_eq!(package_name("--"), "");
+    }
+
+    fn bundle(dir: &Path) -> PathBuf {
+        let manifest: BundleManifest = serde_json::from_str(
+            r#"{"banderoleVersion":"0.2.1","appName":"@acme/server","appVersion":"1.2.0-beta.1",
+                "runtime":"node","runtimeVersion":"22.1.0","packages":{},"files":{}}"#,
+        )
+        .unwrap();
+        let mut executable = b"\x7fELF".to_vec();
+        executable.resize(64, 0);
+        executable[18] = 0x3e;
+        executable.extend(manifest.to_embedded().unwrap());
+        let path = dir.join("server");
+        fs::write(&path, executable).unwrap();
+        path
+    }
+
+    fn options(dir: &Path) -> PackageOptions {
+        let unit = dir.join("server.service");
+        fs::write(&unit, "[Service]\nExecStart=/usr/bin/server\n").unwrap();
+        PackageOptions {
+            release: "1".into(),
+            maintainer: "Ops <ops@example.com>".into(),
+            systemd_units: vec![unit],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_deb_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let executable = bundle(dir.path());
+        let path = package(&executable, PackageFormat::Deb, options(dir.path())).unwrap();
+        assert_eq!(
+            path.file_name().unwrap(),
+            "acme-server_1.2.0~beta.1-1_amd64.deb"
+        );
+        let deb = fs::read(&path).unwrap();
+        assert!(deb.starts_with(b"!<arch>\ndebian-binary   "));
+        let members: Vec<&str> = ["debian-binary", "control.tar.gz", "data.tar.gz"]
+            .into_iter()
+            .filter(|name| deb.windows(name.len()).any(|w| w == name.as_bytes()))
+            .collect();
+        assert_eq!(members.len(), 3);
+    }
+
+    #[test]
+    fn test_rpm_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let executable = bundle(dir.path());
+        let path = package(&executable, PackageFormat::Rpm, options(dir.path())).unwrap();
+        assert_eq!(
+            path.file_name().unwrap(),
+            "acme-server-1.2.0~beta.1-1.x86_64.rpm"
+        );
+        let rpm = ::rpm::Package::open(&path).unwrap();
+        rpm.verify_digests().unwrap();
+        let metadata = &rpm.metadata;
+        assert_eq!(metadata.get_name().unwrap(), "acme-server");
+        assert_eq!(metadata.get_version().unwrap(), "1.2.0~beta.1");
+        assert_eq!(metadata.get_release().unwrap(), "1");
+        assert_eq!(metadata.get_arch().unwrap(), "x86_64");
+        let files = metadata.get_file_paths().unwrap();
+        assert!(files.contains(&PathBuf::from("/usr/bin/server")));
+        assert!(files.contains(&PathBuf::from("/usr/lib/systemd/system/server.service")));
+
+        // rpm itself, where installed, reads the package too
+        if let Ok(output) = std::process::Command::new("rpm")
+            .arg("-qip")
+            .arg(&path)
+            .output()
+        {
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert!(String::from_utf8_lossy(&output.stdout).contains("acme-server"));
+        }
+    }
+
+    #[test]
+    fn test_arch_of_executable() {
+        let mut elf = b"\x7fELF".to_vec();
+        elf.resize(64, 0);
+        elf[18] = 0xb7;
+        assert_eq!(Arch::of_executable(&elf).unwrap(), Arch::Arm64);
+        assert!(Arch::of_executable(b"MZ\x90\x00").is_err());
+    }
+
+    #[test]
+    fn test_msi_rejects_linux_options() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let executable = bundle(dir.path());
+        let error = package(&executable, PackageFormat::Msi, options(dir.path())).unwrap_err();
+        assert!(error.to_string().contains("--systemd-unit"));
+    }
+}
@@ -0,0 +1,115 @@
+//! Debian binary packages: an `ar` archive of `debian-binary`, `control.tar.gz` and
+//! `data.tar.gz`.
+
+use super::PackageSpec;
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::Write;
+
+pub(super) fn build(spec: &PackageSpec) -> Result<Vec<u8>> {
+    let mut control_files = vec![("control".to_string(), control(spec).into_bytes(), 0o644)];
+    let scripts = [
+        ("postinst", spec.post_install()),
+        ("prerm", spec.pre_remove(r#"[ "$1" = remove ]"#)),
+        ("postrm", spec.post_remove()),
+    ];
+    for (name, script) in scripts {
+        if let Some(script) = script {
+            control_files.push((name.to_string(), script.into_bytes(), 0o755));
+        }
+    }
+    let control = tar_gz(&control_files, spec.mtime)?;
+
+    let data_files: Vec<_> = spec
+        .files
+        .iter()
+        .map(|file| {
+            (
+                file.path.trim_start_matches('/').to_string(),
+                file.contents.clone(),
+                file.mode,
+            )
+        })
+        .collect();
+    let data = tar_gz(&data_files, spec.mtime)?;
+
+    let mut deb = b"!<arch>\n".to_vec();
+    for (name, contents) in [
+        ("debian-binary", &b"2.0\n"[..]),
+        ("control.tar.gz", &control),
+        ("data.tar.gz", &data),
+    ] {
+        writeln!(
+            deb,
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`",
+            name,
+            spec.mtime,
+            0,
+            0,
+            100644,
+            contents.len()
+        )?;
+        deb.extend_from_slice(contents);
+        if contents.len() % 2 == 1 {
+            deb.push(b'\n');
+        }
+    }
+    Ok(deb)
+}
+
+fn control(spec: &PackageSpec) -> String {
+    let installed_kb: u64 = spec
+        .files
+        .iter()
+        .map(|file| (file.contents.len() as u64).div_ceil(1024))
+        .sum();
+    let mut control = String::new();
+    let _ = writeln!(control, "Package: {}", spec.name);
+    let _ = writeln!(control, "Version: {}-{}", spec.version, spec.release);
+    let _ = writeln!(control, "Architecture: {}", spec.arch.deb_name());
+    let _ = writeln!(control, "Maintainer: {}", spec.maintainer);
+    let _ = writeln!(control, "Installed-Size: {installed_kb}");
+    let _ = writeln!(control, "Section: misc");
+    let _ = writeln!(control, "Priority: optional");
+    if let Some(homepage) = &spec.homepage {
+        let _ = writeln!(control, "Homepage: {homepage}");
+    }
+    let _ = writeln!(control, "Description: {}", spec.summary.replace('\n', " "));
+    control
+}
+
+/// A gzip-compressed tarball of `files` (relative paths), with their parent directories, owned
+/// by root
+fn tar_gz(files: &[(String, Vec<u8>, u32)], mtime: u64) -> Result<Vec<u8>> {
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::best()));
+    let mut directories = BTreeSet::from([String::new()]);
+    for (path, _, _) in files {
+        let mut parent = path.as_str();
+        while let Some((dir, _)) = parent.rsplit_once('/') {
+            directories.insert(format!("{dir}/"));
+            parent = dir;
+        }
+    }
+    let header = |size: u64, mode: u32, kind: tar::EntryType| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(mode);
+        header.set_entry_type(kind);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(mtime);
+        header
+    };
+    for dir in &directories {
+        let mut header = header(0, 0o755, tar::EntryType::Directory);
+        tar.append_data(&mut header, format!("./{dir}"), std::io::empty())?;
+    }
+    for (path, contents, mode) in files {
+        let mut header = header(contents.len() as u64, *mode, tar::EntryType::Regular);
+        tar.append_data(&mut header, format!("./{path}"), contents.as_slice())?;
+    }
+    Ok(tar.into_inner()?.finish()?)
+}
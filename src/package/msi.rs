@@ -0,0 +1,433 @@
+//! Windows installers: a WiX source compiled with the WiX Toolset v3 (`candle` and `light`) or
+//! msitools' `wixl`, optionally Authenticode-signed with `signtool` or `osslsigncode`.
+//!
+//! The installer puts the executable into `Program Files\<name>`, adds a Start Menu shortcut
+//! and is registered in "Apps & features" like every MSI; a newer version replaces an older one
+//! (the upgrade code is derived from the package name).
+
+use super::{package_name, PackageOptions};
+use crate::manifest::BundleManifest;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Environment variable holding the password of `--certificate`
+const SIGN_PASSWORD_ENV: &str = "BANDEROLE_SIGN_PASSWORD";
+
+/// Everything the WiX source needs
+struct Installer {
+    /// Shown in the Start Menu and in "Apps & features"
+    product: String,
+    /// `major.minor.build`, the only version form Windows Installer compares
+    version: String,
+    manufacturer: String,
+    description: String,
+    homepage: Option<String>,
+    /// File name of the installed executable
+    binary: String,
+    /// Seed for the stable upgrade and component GUIDs
+    name: String,
+}
+
+pub(super) fn build(
+    executable: &Path,
+    data: &[u8],
+    manifest: &BundleManifest,
+    options: PackageOptions,
+) -> Result<PathBuf> {
+    let arch = pe_arch(data)?;
+    let name = package_name(options.name.as_deref().unwrap_or(&manifest.app_name));
+    anyhow::ensure!(
+        !name.is_empty(),
+        "Cannot derive a package name from '{}'; pass --name",
+        manifest.app_name
+    );
+    let version = options
+        .version
+        .unwrap_or_else(|| manifest.app_version.clone());
+    let mut binary = executable
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| name.clone());
+    if !binary.to_ascii_lowercase().ends_with(".exe") {
+        binary.push_str(".exe");
+    }
+    let installer = Installer {
+        product: options.name.unwrap_or_else(|| manifest.app_name.clone()),
+        version: msi_version(&version)?,
+        manufacturer: options
+            .maintainer
+            .split('<')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        description: options
+            .description
+            .unwrap_or_else(|| format!("{} {}", manifest.app_name, manifest.app_version)),
+        homepage: options.homepage,
+        binary,
+        name,
+    };
+
+    let staging = tempfile::TempDir::new().context("Failed to create a staging directory")?;
+    let staged = staging.path().join(&installer.binary);
+    fs::write(&staged, data).with_context(|| format!("Failed to write {}", staged.display()))?;
+    if let Some(certificate) = &options.certificate {
+        if !crate::verify::pe_is_signed(data) {
+            sign(&staged, certificate, options.timestamp_url.as_deref())?;
+        }
+    }
+    let wxs = staging.path().join("installer.wxs");
+    fs::write(&wxs, installer.wxs())
+        .with_context(|| format!("Failed to write {}", wxs.display()))?;
+
+    let output = options.output.unwrap_or_else(|| {
+        executable
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(format!("{}-{}-{}.msi", installer.name, version, arch))
+    });
+    // The tools run inside the staging directory
+    let output = std::path::absolute(&output)
+        .with_context(|| format!("Failed to resolve {}", output.display()))?;
+    compile(&wxs, arch, &output)?;
+    if let Some(certificate) = &options.certificate {
+        sign(&output, certificate, options.timestamp_url.as_deref())?;
+    }
+    Ok(output)
+}
+
+impl Installer {
+    fn wxs(&self) -> String {
+        let product = xml_escape(&self.product);
+        let manufacturer = xml_escape(&self.manufacturer);
+        let binary = xml_escape(&self.binary);
+        let mut wxs = String::new();
+        let _ = writeln!(wxs, r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        let _ = writeln!(
+            wxs,
+            r#"<Wix xmlns="http://schemas.microsoft.com/wix/2006/wi">"#
+        );
+        let _ = writeln!(
+            wxs,
+            r#"  <Product Id="*" Name="{product}" Language="1033" Version="{}" Manufacturer="{manufacturer}" UpgradeCode="{}">"#,
+            self.version,
+            guid(&format!("upgrade:{}", self.name))
+        );
+        let _ = writeln!(
+            wxs,
+            r#"    <Package InstallerVersion="500" Compressed="yes" InstallScope="perMachine" Description="{}" />"#,
+            xml_escape(&self.description)
+        );
+        let _ = writeln!(
+            wxs,
+            r#"    <MajorUpgrade DowngradeErrorMessage="A newer version of {product} is already installed." />"#
+        );
+        let _ = writeln!(
+            wxs,
+            r#"    <Media Id="1" Cabinet="app.cab" EmbedCab="yes" />"#
+        );
+        if let Some(homepage) = &self.homepage {
+            let _ = writeln!(
+                wxs,
+                r#"    <Property Id="ARPURLINFOABOUT" Value="{}" />"#,
+                xml_escape(homepage)
+            );
+        }
+        let _ = writeln!(wxs, r#"    <Directory Id="TARGETDIR" Name="SourceDir">"#);
+        let _ = writeln!(wxs, r#"      <Directory Id="ProgramFiles64Folder">"#);
+        let _ = writeln!(
+            wxs,
+            r#"        <Directory Id="INSTALLDIR" Name="{product}">"#
+        );
+        let _ = writeln!(
+            wxs,
+            r#"          <Component Id="Executable" Guid="{}">"#,
+            guid(&format!("executable:{}", self.name))
+        );
+        let _ = writeln!(
+            wxs,
+            r#"            <File Id="Executable" Name="{binary}" Source="{binary}" KeyPath="yes" />"#
+        );
+        let _ = writeln!(wxs, r#"          </Component>"#);
+        let _ = writeln!(wxs, r#"        </Directory>"#);
+        let _ = writeln!(wxs, r#"      </Directory>"#);
+        let _ = writeln!(wxs, r#"      <Directory Id="ProgramMenuFolder">"#);
+        let _ = writeln!(
+            wxs,
+            r#"        <Directory Id="ShortcutDir" Name="{product}">"#
+        );
+        let _ = writeln!(
+            wxs,
+            r#"          <Component Id="Shortcut" Guid="{}">"#,
+            guid(&format!("shortcut:{}", self.name))
+        );
+        let _ = writeln!(
+            wxs,
+            r#"            <Shortcut Id="Shortcut" Name="{product}" Target="[INSTALLDIR]{binary}" WorkingDirectory="INSTALLDIR" />"#
+        );
+        let _ = writeln!(
+            wxs,
+            r#"            <RemoveFolder Id="ShortcutDir" On="uninstall" />"#
+        );
+        // Shortcuts need a registry key path; it is removed with the product
+        let _ = writeln!(
+            wxs,
+            r#"            <RegistryValue Root="HKCU" Key="Software\{manufacturer}\{product}" Name="installed" Type="integer" Value="1" KeyPath="yes" />"#
+        );
+        let _ = writeln!(wxs, r#"          </Component>"#);
+        let _ = writeln!(wxs, r#"        </Directory>"#);
+        let _ = writeln!(wxs, r#"      </Directory>"#);
+        let _ = writeln!(wxs, r#"    </Directory>"#);
+        let _ = writeln!(wxs, r#"    <Feature Id="Main" Level="1">"#);
+        let _ = writeln!(wxs, r#"      <ComponentRef Id="Executable" />"#);
+        let _ = writeln!(wxs, r#"      <ComponentRef Id="Shortcut" />"#);
+        let _ = writeln!(wxs, r#"    </Feature>"#);
+        let _ = writeln!(wxs, r#"  </Product>"#);
+        let _ = writeln!(wxs, r#"</Wix>"#);
+        wxs
+    }
+}
+
+/// WiX architecture of a Windows executable, from the PE header's machine field
+fn pe_arch(data: &[u8]) -> Result<&'static str> {
+    let pe = data
+        .get(0x3c..0x40)
+        .filter(|_| data.starts_with(b"MZ"))
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let Some(machine) = pe
+        .filter(|&pe| data.get(pe..pe + 4) == Some(b"PE\0\0"))
+        .and_then(|pe| data.get(pe + 4..pe + 6))
+    else {
+        bail!("msi packages wrap Windows executables; build the bundle for Windows");
+    };
+    Ok(match u16::from_le_bytes([machine[0], machine[1]]) {
+        0x8664 => "x64",
+        0xaa64 => "arm64",
+        machine => bail!("Unsupported PE machine type {machine:#x}"),
+    })
+}
+
+/// `major.minor.build` from a semver-like version; Windows Installer ignores anything else
+/// (prerelease tags included) when comparing versions
+fn msi_version(version: &str) -> Result<String> {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts: Vec<u32> = core
+        .split('.')
+        .take(3)
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()
+        .with_context(|| {
+            format!(
+                "msi versions must be numeric (major.minor.build), got '{version}'; pass --version"
+            )
+        })?;
+    let part = |index: usize| parts.get(index).copied().unwrap_or(0);
+    anyhow::ensure!(
+        part(0) <= 255 && part(1) <= 255 && part(2) <= 65535,
+        "msi versions are limited to 255.255.65535, got '{version}'"
+    );
+    Ok(format!("{}.{}.{}", part(0), part(1), part(2)))
+}
+
+/// A GUID derived from `seed`, so rebuilding the installer keeps its upgrade and component codes
+fn guid(seed: &str) -> String {
+    let mut bytes: [u8; 16] = Sha256::digest(format!("banderole:{seed}").as_bytes())[..16]
+        .try_into()
+        .expect("16 bytes");
+    // Name-based UUID layout (version 5, RFC 4122 variant)
+    bytes[6] = (bytes[6] & 0x0f) | 0x50;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02X}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A WiX v3 tool from `%WIX%\bin` (set by the WiX installer, which does not touch the PATH) or
+/// the PATH
+fn wix_tool(name: &str) -> PathBuf {
+    std::env::var_os("WIX")
+        .map(|wix| PathBuf::from(wix).join("bin").join(format!("{name}.exe")))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Compile `wxs` (whose sources are next to it) into `output`
+fn compile(wxs: &Path, arch: &str, output: &Path) -> Result<()> {
+    let dir = wxs.parent().unwrap_or_else(|| Path::new("."));
+    let object = dir.join("installer.wixobj");
+    let candle = Command::new(wix_tool("candle"))
+        .args(["-nologo", "-arch", arch, "-out"])
+        .arg(&object)
+        .arg(wxs)
+        .current_dir(dir)
+        .output();
+    match candle {
+        Ok(candle) => {
+            check("candle", candle)?;
+            let light = Command::new(wix_tool("light"))
+                .args(["-nologo", "-out"])
+                .arg(output)
+                .arg(&object)
+                .current_dir(dir)
+                .output()
+                .context("Failed to run light from the WiX Toolset")?;
+            check("light", light)
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let wixl = Command::new("wixl")
+                .args(["--arch", arch, "--output"])
+                .arg(output)
+                .arg(wxs)
+                .current_dir(dir)
+                .output()
+                .context("--format msi needs the WiX Toolset v3 (candle and light) or msitools' wixl on the PATH")?;
+            check("wixl", wixl)
+        }
+        Err(e) => Err(e).context("Failed to run candle from the WiX Toolset"),
+    }
+}
+
+/// Authenticode-sign `path` in place with the PKCS#12 `certificate`, using `signtool` on Windows
+/// and `osslsigncode` elsewhere. The password never appears on a command line, where other users
+/// could read it: `osslsigncode` reads it from a private file, and on Windows a password-protected
+/// certificate is loaded by PowerShell from the environment, since `signtool` only takes it as
+/// an argument.
+fn sign(path: &Path, certificate: &Path, timestamp_url: Option<&str>) -> Result<()> {
+    let password = std::env::var(SIGN_PASSWORD_ENV).ok();
+    if cfg!(windows) {
+        if password.is_some() {
+            let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+            let mut script = format!(
+                "$c = New-Object System.Security.Cryptography.X509Certificates.X509Certificate2({}, $env:{SIGN_PASSWORD_ENV}); \
+                 $s = Set-AuthenticodeSignature -LiteralPath {} -Certificate $c -HashAlgorithm SHA256",
+                quote(&certificate.display().to_string()),
+                quote(&path.display().to_string())
+            );
+            if let Some(url) = timestamp_url {
+                script.push_str(&format!(" -TimestampServer {}", quote(url)));
+            }
+            script
+                .push_str("; if ($s.Status -ne 'Valid') { Write-Error $s.StatusMessage; exit 1 }");
+            let output = Command::new("powershell")
+                .args(["-NoProfile", "-Command", &script])
+                .output()
+                .context("Failed to run PowerShell's Set-AuthenticodeSignature")?;
+            return check("Set-AuthenticodeSignature", output);
+        }
+        let mut cmd = Command::new("signtool");
+        cmd.args(["sign", "/fd", "sha256", "/f"]).arg(certificate);
+        if let Some(url) = timestamp_url {
+            cmd.args(["/tr", url, "/td", "sha256"]);
+        }
+        let output = cmd
+            .arg(path)
+            .output()
+            .context("--certificate needs signtool from the Windows SDK on the PATH")?;
+        return check("signtool", output);
+    }
+    let signed = path.with_extension("signed");
+    let mut cmd = Command::new("osslsigncode");
+    cmd.args(["sign", "-h", "sha256", "-pkcs12"])
+        .arg(certificate);
+    // Created readable by the owner only, and removed once signed
+    let password_file = match &password {
+        Some(password) => {
+            let mut file = tempfile::NamedTempFile::new()
+                .context("Failed to create a file for the certificate password")?;
+            file.write_all(password.as_bytes())?;
+            cmd.arg("-readpass").arg(file.path());
+            Some(file)
+        }
+        None => None,
+    };
+    if let Some(url) = timestamp_url {
+        cmd.args(["-ts", url]);
+    }
+    let output = cmd
+        .arg("-in")
+        .arg(path)
+        .arg("-out")
+        .arg(&signed)
+        .output()
+        .context("--certificate needs osslsigncode on the PATH")?;
+    drop(password_file);
+    check("osslsigncode", output)?;
+    fs::rename(&signed, path)
+        .with_context(|| format!("Failed to replace {} with its signed copy", path.display()))
+}
+
+fn check(tool: &str, output: Output) -> Result<()> {
+    if !output.status.success() {
+        bail!(
+            "{tool} failed: {}{}",
+            String::from_utf8_lossy(&output.stdout).trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msi_version() {
+        assert_eq!(msi_version("1.2.0-beta.1").unwrap(), "1.2.0");
+        assert_eq!(msi_version("3").unwrap(), "3.0.0");
+        assert!(msi_version("next").is_err());
+        assert!(msi_version("1.256.0").is_err());
+    }
+
+    #[test]
+    fn test_wxs() {
+        let installer = Installer {
+            product: "@acme/server".into(),
+            version: "1.2.0".into(),
+            manufacturer: "Acme & Co".into(),
+            description: "Server".into(),
+            homepage: None,
+            binary: "server.exe".into(),
+            name: "acme-server".into(),
+        };
+        let wxs = installer.wxs();
+        assert!(wxs.contains(r#"Manufacturer="Acme &amp; Co""#));
+        assert!(wxs.contains(r#"Target="[INSTALLDIR]server.exe""#));
+        assert!(wxs.contains(&format!(r#"UpgradeCode="{}""#, guid("upgrade:acme-server"))));
+        assert!(wxs.contains(r#"<Directory Id="ProgramFiles64Folder">"#));
+        // Stable across builds
+        assert_eq!(wxs, installer.wxs());
+        assert_eq!(guid("upgrade:acme-server").len(), 36);
+    }
+
+    #[test]
+    fn test_pe_arch() {
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x80, 0);
+        pe[0x3c] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x44..0x46].copy_from_slice(&0xaa64u16.to_le_bytes());
+        assert_eq!(pe_arch(&pe).unwrap(), "arm64");
+        assert!(pe_arch(b"\x7fELF").is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! RPM v3 packages: the lead, a signature header with digests of the main header and payload,
+//! the main header with metadata, file list and scriptlets, and a gzip-compressed `newc` cpio
+//! payload.
+
+use super::PackageSpec;
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+
+const LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+
+// Header entry types
+const INT16: u32 = 3;
+const INT32: u32 = 4;
+const STRING: u32 = 6;
+const BIN: u32 = 7;
+const STRING_ARRAY: u32 = 8;
+const I18NSTRING: u32 = 9;
+
+// Region tags marking a header as immutable (main) or as the signature header
+const HEADER_SIGNATURES: u32 = 62;
+const HEADER_IMMUTABLE: u32 = 63;
+const HEADER_I18NTABLE: u32 = 100;
+
+// Signature header tags
+const SIG_SHA1: u32 = 269;
+const SIG_SHA256: u32 = 273;
+const SIG_SIZE: u32 = 1000;
+const SIG_PAYLOADSIZE: u32 = 1007;
+
+// Main header tags
+const NAME: u32 = 1000;
+const VERSION: u32 = 1001;
+const RELEASE: u32 = 1002;
+const SUMMARY: u32 = 1004;
+const DESCRIPTION: u32 = 1005;
+const BUILDTIME: u32 = 1006;
+const BUILDHOST: u32 = 1007;
+const SIZE: u32 = 1009;
+const LICENSE: u32 = 1014;
+const PACKAGER: u32 = 1015;
+const GROUP: u32 = 1016;
+const URL: u32 = 1020;
+const OS: u32 = 1021;
+const ARCH: u32 = 1022;
+const POSTIN: u32 = 1024;
+const PREUN: u32 = 1025;
+const POSTUN: u32 = 1026;
+const FILESIZES: u32 = 1028;
+const FILEMODES: u32 = 1030;
+const FILERDEVS: u32 = 1033;
+const FILEMTIMES: u32 = 1034;
+const FILEDIGESTS: u32 = 1035;
+const FILELINKTOS: u32 = 1036;
+const FILEFLAGS: u32 = 1037;
+const FILEUSERNAME: u32 = 1039;
+const FILEGROUPNAME: u32 = 1040;
+const FILEVERIFYFLAGS: u32 = 1045;
+const PROVIDENAME: u32 = 1047;
+const REQUIREFLAGS: u32 = 1048;
+const REQUIRENAME: u32 = 1049;
+const REQUIREVERSION: u32 = 1050;
+const POSTINPROG: u32 = 1086;
+const PREUNPROG: u32 = 1087;
+const POSTUNPROG: u32 = 1088;
+const FILEDEVICES: u32 = 1095;
+const FILEINODES: u32 = 1096;
+const FILELANGS: u32 = 1097;
+const PROVIDEFLAGS: u32 = 1112;
+const PROVIDEVERSION: u32 = 1113;
+const DIRINDEXES: u32 = 1116;
+const BASENAMES: u32 = 1117;
+const DIRNAMES: u32 = 1118;
+const PAYLOADFORMAT: u32 = 1124;
+const PAYLOADCOMPRESSOR: u32 = 1125;
+const PAYLOADFLAGS: u32 = 1126;
+const FILEDIGESTALGO: u32 = 5011;
+const PAYLOADDIGEST: u32 = 5092;
+const PAYLOADDIGESTALGO: u32 = 5093;
+
+const SENSE_EQUAL: u32 = 1 << 3;
+const SENSE_LESS: u32 = 1 << 1;
+const SENSE_RPMLIB: u32 = 1 << 24;
+const SENSE_INTERP: u32 = 1 << 8;
+const DIGEST_SHA256: u32 = 8;
+
+/// Features of rpm the package relies on, with the rpm version that introduced them
+const RPMLIB_FEATURES: &[(&str, &str)] = &[
+    ("rpmlib(CompressedFileNames)", "3.0.4-1"),
+    ("rpmlib(FileDigests)", "4.6.0-1"),
+    ("rpmlib(PayloadFilesHavePrefix)", "4.0-1"),
+];
+
+pub(super) fn build(spec: &PackageSpec) -> Result<Vec<u8>> {
+    let cpio = cpio(spec)?;
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+    gzip.write_all(&cpio)?;
+    let payload = gzip.finish()?;
+
+    let header = main_header(spec, &payload).build(HEADER_IMMUTABLE);
+    let mut signature = Header::default();
+    signature.string(SIG_SHA1, &format!("{:x}", Sha1::digest(&header)));
+    signature.string(SIG_SHA256, &format!("{:x}", Sha256::digest(&header)));
+    signature.int32(SIG_SIZE, &[(header.len() + payload.len()) as u32]);
+    signature.int32(SIG_PAYLOADSIZE, &[cpio.len() as u32]);
+    let mut signature = signature.build(HEADER_SIGNATURES);
+    // The main header starts 8-byte aligned
+    signature.resize(signature.len().next_multiple_of(8), 0);
+
+    let mut rpm = lead(spec);
+    rpm.extend(signature);
+    rpm.extend(header);
+    rpm.extend(payload);
+    Ok(rpm)
+}
+
+/// The 96-byte lead; rpm only checks its magic, everything else lives in the headers
+fn lead(spec: &PackageSpec) -> Vec<u8> {
+    let mut lead = LEAD_MAGIC.to_vec();
+    lead.extend([3, 0]); // format 3.0
+    lead.extend(0u16.to_be_bytes()); // binary package
+    lead.extend(1u16.to_be_bytes()); // architecture number, unused
+    let mut name = format!("{}-{}-{}", spec.name, spec.version, spec.release).into_bytes();
+    name.resize(66, 0);
+    name[65] = 0;
+    lead.extend(name);
+    lead.extend(1u16.to_be_bytes()); // Linux
+    lead.extend(5u16.to_be_bytes()); // signature in a header structure
+    lead.resize(96, 0);
+    lead
+}
+
+fn main_header(spec: &PackageSpec, payload: &[u8]) -> Header {
+    let mut header = Header::default();
+    header.string_array(HEADER_I18NTABLE, &["C"]);
+    header.string(NAME, &spec.name);
+    header.string(VERSION, &spec.version);
+    header.string(RELEASE, &spec.release);
+    header.i18n(SUMMARY, &spec.summary);
+    header.i18n(DESCRIPTION, &spec.summary);
+    header.int32(BUILDTIME, &[spec.mtime as u32]);
+    header.string(BUILDHOST, "localhost");
+    let size: usize = spec.files.iter().map(|file| file.contents.len()).sum();
+    header.int32(SIZE, &[size as u32]);
+    header.string(LICENSE, spec.license.as_deref().unwrap_or("Proprietary"));
+    header.string(PACKAGER, &spec.maintainer);
+    header.i18n(GROUP, "Unspecified");
+    if let Some(homepage) = &spec.homepage {
+        header.string(URL, homepage);
+    }
+    header.string(OS, "linux");
+    header.string(ARCH, spec.arch.rpm_name());
+
+    let scripts = [
+        (POSTIN, POSTINPROG, spec.post_install()),
+        (PREUN, PREUNPROG, spec.pre_remove(r#"[ "$1" -eq 0 ]"#)),
+        (POSTUN, POSTUNPROG, spec.post_remove()),
+    ];
+    for (tag, program_tag, script) in scripts {
+        if let Some(script) = script {
+            header.string(tag, &script);
+            header.string(program_tag, "/bin/sh");
+        }
+    }
+
+    let count = spec.files.len();
+    let (mut dirs, mut dir_indexes, mut basenames) = (Vec::<String>::new(), Vec::new(), Vec::new());
+    for file in &spec.files {
+        let (dir, base) = file.path.rsplit_once('/').unwrap_or(("", &file.path));
+        let dir = format!("{dir}/");
+        let index = dirs.iter().position(|d| *d == dir).unwrap_or_else(|| {
+            dirs.push(dir);
+            dirs.len() - 1
+        });
+        dir_indexes.push(index as u32);
+        basenames.push(base.to_string());
+    }
+    let sizes: Vec<u32> = spec.files.iter().map(|f| f.contents.len() as u32).collect();
+    let modes: Vec<u16> = spec
+        .files
+        .iter()
+        .map(|f| (0o100000 | f.mode) as u16)
+        .collect();
+    let digests: Vec<String> = spec
+        .files
+        .iter()
+        .map(|f| format!("{:x}", Sha256::digest(&f.contents)))
+        .collect();
+    let inodes: Vec<u32> = (1..=count as u32).collect();
+    header.int32(FILESIZES, &sizes);
+    header.int16(FILEMODES, &modes);
+    header.int16(FILERDEVS, &vec![0; count]);
+    header.int32(FILEMTIMES, &vec![spec.mtime as u32; count]);
+    header.string_array(FILEDIGESTS, &digests);
+    header.string_array(FILELINKTOS, &vec![""; count]);
+    header.int32(FILEFLAGS, &vec![0; count]);
+    header.string_array(FILEUSERNAME, &vec!["root"; count]);
+    header.string_array(FILEGROUPNAME, &vec!["root"; count]);
+    header.int32(FILEVERIFYFLAGS, &vec![u32::MAX; count]);
+    header.int32(FILEDEVICES, &vec![1; count]);
+    header.int32(FILEINODES, &inodes);
+    header.string_array(FILELANGS, &vec![""; count]);
+    header.int32(DIRINDEXES, &dir_indexes);
+    header.string_array(BASENAMES, &basenames);
+    header.string_array(DIRNAMES, &dirs);
+    header.int32(FILEDIGESTALGO, &[DIGEST_SHA256]);
+
+    let full_version = format!("{}-{}", spec.version, spec.release);
+    header.string_array(PROVIDENAME, &[spec.name.as_str()]);
+    header.int32(PROVIDEFLAGS, &[SENSE_EQUAL]);
+    header.string_array(PROVIDEVERSION, &[full_version.as_str()]);
+    let mut requires: Vec<(&str, &str, u32)> = RPMLIB_FEATURES
+        .iter()
+        .map(|(name, version)| (*name, *version, SENSE_RPMLIB | SENSE_LESS | SENSE_EQUAL))
+        .collect();
+    if !spec.units.is_empty() {
+        requires.push(("/bin/sh", "", SENSE_INTERP));
+    }
+    let names: Vec<&str> = requires.iter().map(|r| r.0).collect();
+    let versions: Vec<&str> = requires.iter().map(|r| r.1).collect();
+    let flags: Vec<u32> = requires.iter().map(|r| r.2).collect();
+    header.int32(REQUIREFLAGS, &flags);
+    header.string_array(REQUIRENAME, &names);
+    header.string_array(REQUIREVERSION, &versions);
+
+    header.string(PAYLOADFORMAT, "cpio");
+    header.string(PAYLOADCOMPRESSOR, "gzip");
+    header.string(PAYLOADFLAGS, "9");
+    header.string_array(
+        PAYLOADDIGEST,
+        &[format!("{:x}", Sha256::digest(payload)).as_str()],
+    );
+    header.int32(PAYLOADDIGESTALGO, &[DIGEST_SHA256]);
+    header
+}
+
+/// The files as a `newc` cpio archive with `./`-prefixed absolute paths
+fn cpio(spec: &PackageSpec) -> Result<Vec<u8>> {
+    let mut archive = Vec::new();
+    let mut entry = |name: &str, ino: u32, mode: u32, contents: &[u8]| -> Result<()> {
+        write!(
+            archive,
+            "070701{ino:08x}{mode:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+            0, // uid
+            0, // gid
+            1, // nlink
+            spec.mtime as u32,
+            contents.len(),
+            0, // devmajor
+            0, // devminor
+            0, // rdevmajor
+            0, // rdevminor
+            name.len() + 1,
+            0, // check
+        )?;
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        archive.extend_from_slice(contents);
+        archive.resize(archive.len().next_multiple_of(4), 0);
+        Ok(())
+    };
+    for (i, file) in spec.files.iter().enumerate() {
+        entry(
+            &format!(".{}", file.path),
+            i as u32 + 1,
+            0o100000 | file.mode,
+            &file.contents,
+        )?;
+    }
+    entry("TRAILER!!!", 0, 0, &[])?;
+    Ok(archive)
+}
+
+/// An rpm header structure: an index of typed entries followed by their data
+#[derive(Default)]
+struct Header {
+    /// Tag, type, count and encoded data of each entry
+    entries: Vec<(u32, u32, u32, Vec<u8>)>,
+}
+
+impl Header {
+    fn string(&mut self, tag: u32, value: &str) {
+        self.entries
+            .push((tag, STRING, 1, nul_terminated(&[value])));
+    }
+
+    fn i18n(&mut self, tag: u32, value: &str) {
+        self.entries
+            .push((tag, I18NSTRING, 1, nul_terminated(&[value])));
+    }
+
+    fn string_array<S: AsRef<str>>(&mut self, tag: u32, values: &[S]) {
+        let values: Vec<&str> = values.iter().map(AsRef::as_ref).collect();
+        self.entries.push((
+            tag,
+            STRING_ARRAY,
+            values.len() as u32,
+            nul_terminated(&values),
+        ));
+    }
+
+    fn int16(&mut self, tag: u32, values: &[u16]) {
+        let data = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.entries.push((tag, INT16, values.len() as u32, data));
+    }
+
+    fn int32(&mut self, tag: u32, values: &[u32]) {
+        let data = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.entries.push((tag, INT32, values.len() as u32, data));
+    }
+
+    /// Serialize with a leading `region` entry whose trailer closes the data, as rpm expects of
+    /// the signature and immutable headers
+    fn build(mut self, region: u32) -> Vec<u8> {
+        self.entries.sort_by_key(|entry| entry.0);
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (tag, kind, count, value) in &self.entries {
+            let align = match *kind {
+                INT16 => 2,
+                INT32 => 4,
+                _ => 1,
+            };
+            data.resize(data.len().next_multiple_of(align), 0);
+            index.push((*tag, *kind, data.len() as u32, *count));
+            data.extend_from_slice(value);
+        }
+        let entries = index.len() as u32 + 1;
+        let trailer_offset = data.len() as u32;
+        for value in [region, BIN, (-(entries as i32 * 16)) as u32, 16] {
+            data.extend(value.to_be_bytes());
+        }
+        index.insert(0, (region, BIN, trailer_offset, 16));
+
+        let mut header = HEADER_MAGIC.to_vec();
+        header.extend(entries.to_be_bytes());
+        header.extend((data.len() as u32).to_be_bytes());
+        for (tag, kind, offset, count) in index {
+            for value in [tag, kind, offset, count] {
+                header.extend(value.to_be_bytes());
+            }
+        }
+        header.extend(data);
+        header
+    }
+}
+
+fn nul_terminated(values: &[&str]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in values {
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+    }
+    data
+}
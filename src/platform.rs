@@ -1,52 +1,282 @@
+use serde::Deserialize;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Set by `--musl`: bundle for musl-based Linux (Alpine) even on a glibc host
+static TARGET_MUSL: AtomicBool = AtomicBool::new(false);
+
+/// The `platforms` entry matching a host banderole has no built-in support for
+static CUSTOM: OnceLock<CustomPlatform> = OnceLock::new();
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
     LinuxX64,
     LinuxArm64,
+    /// 32-bit ARM with hardware floating point (Raspberry Pi OS and similar)
+    LinuxArmv7,
+    /// musl libc (Alpine); Node.js comes from the unofficial builds
+    LinuxX64Musl,
+    LinuxArm64Musl,
     MacosX64,
     MacosArm64,
     WindowsX64,
     WindowsArm64,
+    /// Declared in the `platforms` configuration, see [`register_custom`]
+    Custom,
+}
+
+/// A platform without built-in support, e.g. FreeBSD, bundled with a Node.js build the user
+/// provides
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CustomPlatform {
+    /// Name in messages and cache directories, e.g. `freebsd-x64`
+    pub name: String,
+    /// Rust `target_os` of the hosts it applies to, e.g. `freebsd`
+    pub os: String,
+    /// Rust `target_arch` of the hosts it applies to, e.g. `x86_64`
+    pub arch: String,
+    /// Node.js archive (`.tar.xz`, `.7z` or `.zip`) with `{version}` for the Node.js version
+    pub node_url: String,
+    /// `SHASUMS256.txt`-style list with the archive's SHA-256, same placeholder; required unless
+    /// `insecure_skip_checksum` is set
+    pub checksums_url: Option<String>,
+    /// Use the archive unverified when there is no `checksums_url`
+    #[serde(default)]
+    pub insecure_skip_checksum: bool,
+    /// Rust target triple of the runner, e.g. `x86_64-unknown-freebsd`
+    pub target: String,
+}
+
+impl CustomPlatform {
+    pub fn node_url(&self, version: &str) -> String {
+        self.node_url.replace("{version}", version)
+    }
+
+    pub fn checksums_url(&self, version: &str) -> Option<String> {
+        self.checksums_url
+            .as_ref()
+            .map(|url| url.replace("{version}", version))
+    }
+}
+
+/// Use the entry of `platforms` matching the host if banderole does not know the host itself.
+/// Entries for other hosts are checked but otherwise ignored, so one configuration can list
+/// several platforms; none of them can be a `--target`.
+pub fn register_custom(platforms: &[CustomPlatform]) -> anyhow::Result<()> {
+    for platform in platforms {
+        anyhow::ensure!(
+            platform.checksums_url.is_some() || platform.insecure_skip_checksum,
+            "Platform {} needs a checksumsUrl to verify its Node.js archive (or \"insecureSkipChecksum\": true to use it unverified)",
+            platform.name
+        );
+    }
+    let Some(platform) = platforms
+        .iter()
+        .find(|platform| platform.os == env::consts::OS && platform.arch == env::consts::ARCH)
+    else {
+        return Ok(());
+    };
+    anyhow::ensure!(
+        platform.node_url.contains("{version}"),
+        "nodeUrl of platform {} needs a {{version}} placeholder",
+        platform.name
+    );
+    let registered = CUSTOM.get_or_init(|| platform.clone());
+    anyhow::ensure!(
+        registered == platform,
+        "Platform {} is declared differently by the projects being bundled",
+        platform.name
+    );
+    Ok(())
+}
+
+/// The machine banderole runs on has no Node.js/runner support
+#[derive(Debug)]
+pub struct UnsupportedPlatform {
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl std::fmt::Display for UnsupportedPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unsupported platform: {}-{}; declare it under \"platforms\" with a Node.js build to bundle on it",
+            self.os, self.arch
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedPlatform {}
+
+/// Bundle for musl-based Linux from now on; fails on other operating systems
+pub fn target_musl() -> Result<(), UnsupportedPlatform> {
+    let platform = Platform::detect()?;
+    if platform.os() != "linux" {
+        return Err(UnsupportedPlatform {
+            os: platform.os(),
+            arch: "musl",
+        });
+    }
+    TARGET_MUSL.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Whether the host's libc is musl: banderole itself is a musl build, or the musl loader exists
+fn host_is_musl(arch: &str) -> bool {
+    cfg!(target_env = "musl") || Path::new(&format!("/lib/ld-musl-{arch}.so.1")).exists()
 }
 
 impl Platform {
-    pub fn current() -> Self {
+    pub fn detect() -> Result<Self, UnsupportedPlatform> {
+        Self::detect_host(TARGET_MUSL.load(Ordering::Relaxed))
+    }
+
+    /// The machine banderole runs on, whatever the bundles target
+    pub fn host() -> Result<Self, UnsupportedPlatform> {
+        Self::detect_host(false)
+    }
+
+    fn detect_host(target_musl: bool) -> Result<Self, UnsupportedPlatform> {
         let os = env::consts::OS;
         let arch = env::consts::ARCH;
+        let musl = os == "linux" && (target_musl || host_is_musl(arch));
 
-        match (os, arch) {
+        Ok(match (os, arch) {
+            ("linux", "x86_64") if musl => Platform::LinuxX64Musl,
+            ("linux", "aarch64") if musl => Platform::LinuxArm64Musl,
             ("linux", "x86_64") => Platform::LinuxX64,
             ("linux", "aarch64") => Platform::LinuxArm64,
+            // Node.js publishes no musl build for 32-bit ARM
+            ("linux", "arm") if !musl => Platform::LinuxArmv7,
             ("macos", "x86_64") => Platform::MacosX64,
             ("macos", "aarch64") => Platform::MacosArm64,
             ("windows", "x86_64") => Platform::WindowsX64,
             ("windows", "aarch64") => Platform::WindowsArm64,
-            _ => panic!("Unsupported platform: {os}-{arch}"),
+            _ if CUSTOM.get().is_some() => Platform::Custom,
+            _ => return Err(UnsupportedPlatform { os, arch }),
+        })
+    }
+
+    /// Like [`Platform::detect`]; bundling has already rejected unsupported hosts
+    pub fn current() -> Self {
+        Self::detect().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The `platforms` entry of [`Platform::Custom`]
+    pub fn custom(&self) -> Option<&'static CustomPlatform> {
+        match self {
+            Platform::Custom => CUSTOM.get(),
+            _ => None,
         }
     }
 
+    fn custom_definition(&self) -> &'static CustomPlatform {
+        self.custom()
+            .expect("custom platforms are only detected once registered")
+    }
+
     pub fn node_archive_name(&self, version: &str) -> String {
         match self {
             Platform::LinuxX64 => format!("node-v{version}-linux-x64.tar.xz"),
             Platform::LinuxArm64 => format!("node-v{version}-linux-arm64.tar.xz"),
+            Platform::LinuxArmv7 => format!("node-v{version}-linux-armv7l.tar.xz"),
+            Platform::LinuxX64Musl => format!("node-v{version}-linux-x64-musl.tar.xz"),
+            Platform::LinuxArm64Musl => format!("node-v{version}-linux-arm64-musl.tar.xz"),
             Platform::MacosX64 => format!("node-v{version}-darwin-x64.tar.xz"),
             Platform::MacosArm64 => format!("node-v{version}-darwin-arm64.tar.xz"),
             Platform::WindowsX64 => format!("node-v{version}-win-x64.7z"),
-            Platform::WindowsArm64 => format!("node-v{version}-win-arm64.7z"),
+            // 7-Zip's ARM64 branch filter, used for the arm64 7z, is not supported by sevenz-rust;
+            // the zip of the same release holds the same files
+            Platform::WindowsArm64 => format!("node-v{version}-win-arm64.zip"),
+            Platform::Custom => {
+                let url = self.custom_definition().node_url(version);
+                let name = url.rsplit('/').next().unwrap_or(&url);
+                name.split(['?', '#']).next().unwrap_or(name).to_string()
+            }
         }
     }
 
     pub fn node_executable_path(&self) -> PathBuf {
+        if self.is_windows() {
+            PathBuf::from("node.exe")
+        } else {
+            PathBuf::from("bin").join("node")
+        }
+    }
+
+    /// Electron publishes no musl builds; [`Platform::is_musl`] targets are rejected before this
+    pub fn electron_archive_name(&self, version: &str) -> String {
+        match self {
+            Platform::LinuxX64 | Platform::LinuxX64Musl => {
+                format!("electron-v{version}-linux-x64.zip")
+            }
+            Platform::LinuxArm64 | Platform::LinuxArm64Musl => {
+                format!("electron-v{version}-linux-arm64.zip")
+            }
+            Platform::LinuxArmv7 => format!("electron-v{version}-linux-armv7l.zip"),
+            Platform::MacosX64 => format!("electron-v{version}-darwin-x64.zip"),
+            Platform::MacosArm64 => format!("electron-v{version}-darwin-arm64.zip"),
+            Platform::WindowsX64 => format!("electron-v{version}-win32-x64.zip"),
+            Platform::WindowsArm64 => format!("electron-v{version}-win32-arm64.zip"),
+            Platform::Custom => format!("electron-v{version}-{self}.zip"),
+        }
+    }
+
+    pub fn electron_executable_path(&self) -> PathBuf {
+        match self {
+            Platform::WindowsX64 | Platform::WindowsArm64 => PathBuf::from("electron.exe"),
+            Platform::MacosX64 | Platform::MacosArm64 => PathBuf::from("Electron.app")
+                .join("Contents")
+                .join("MacOS")
+                .join("Electron"),
+            _ => PathBuf::from("electron"),
+        }
+    }
+
+    /// Rust `target_os` name of the platform
+    pub fn os(&self) -> &'static str {
+        match self {
+            Platform::LinuxX64
+            | Platform::LinuxArm64
+            | Platform::LinuxArmv7
+            | Platform::LinuxX64Musl
+            | Platform::LinuxArm64Musl => "linux",
+            Platform::MacosX64 | Platform::MacosArm64 => "macos",
+            Platform::WindowsX64 | Platform::WindowsArm64 => "windows",
+            Platform::Custom => &self.custom_definition().os,
+        }
+    }
+
+    /// Rust `target_arch` name of the platform
+    pub fn arch(&self) -> &'static str {
+        match self {
+            Platform::LinuxX64
+            | Platform::LinuxX64Musl
+            | Platform::MacosX64
+            | Platform::WindowsX64 => "x86_64",
+            Platform::LinuxArm64
+            | Platform::LinuxArm64Musl
+            | Platform::MacosArm64
+            | Platform::WindowsArm64 => "aarch64",
+            Platform::LinuxArmv7 => "arm",
+            Platform::Custom => &self.custom_definition().arch,
+        }
+    }
+
+    pub fn is_musl(&self) -> bool {
         match self {
-            Platform::WindowsX64 | Platform::WindowsArm64 => PathBuf::from("node.exe"),
-            _ => PathBuf::from("bin").join("node"),
+            Platform::LinuxX64Musl | Platform::LinuxArm64Musl => true,
+            Platform::Custom => self.custom_definition().target.ends_with("-musl"),
+            _ => false,
         }
     }
 
     pub fn is_windows(&self) -> bool {
-        matches!(self, Platform::WindowsX64 | Platform::WindowsArm64)
+        self.os() == "windows"
     }
 }
 
@@ -55,10 +285,14 @@ impl std::fmt::Display for Platform {
         match self {
             Self::LinuxX64 => write!(f, "linux-x64"),
             Self::LinuxArm64 => write!(f, "linux-arm64"),
+            Self::LinuxArmv7 => write!(f, "linux-armv7l"),
+            Self::LinuxX64Musl => write!(f, "linux-x64-musl"),
+            Self::LinuxArm64Musl => write!(f, "linux-arm64-musl"),
             Self::MacosX64 => write!(f, "darwin-x64"),
             Self::MacosArm64 => write!(f, "darwin-arm64"),
             Self::WindowsX64 => write!(f, "win32-x64"),
             Self::WindowsArm64 => write!(f, "win32-arm64"),
+            Self::Custom => write!(f, "{}", self.custom_definition().name),
         }
     }
 }
@@ -0,0 +1,188 @@
+use crate::download;
+use anyhow::{Context, Result};
+use log::info;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Project directory to bundle, possibly unpacked from an archive that lives as long as this value
+pub struct ProjectInput {
+    path: PathBuf,
+    _unpacked: Option<TempDir>,
+}
+
+impl ProjectInput {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Turn the `bundle` argument into a project directory.
+///
+/// Accepts a directory, a `.tgz`/`.tar.gz`/`.zip` of one (e.g. from `npm pack`), or an http(s)
+/// URL to such an archive. `sha256` is checked against the archive before it is unpacked, and
+/// the production dependencies of an unpacked project are installed unless it ships them.
+pub async fn resolve(input: &str, sha256: Option<&str>) -> Result<ProjectInput> {
+    let is_url = input.starts_with("https://") || input.starts_with("http://");
+    if !is_url && Path::new(input).is_dir() {
+        anyhow::ensure!(
+            sha256.is_none(),
+            "--sha256 only applies to archive or URL inputs"
+        );
+        return Ok(ProjectInput {
+            path: PathBuf::from(input),
+            _unpacked: None,
+        });
+    }
+
+    let temp = TempDir::new().context("Failed to create temporary directory")?;
+    let archive = if is_url {
+        let name = input
+            .split(['?', '#'])
+            .next()
+            .and_then(|url| url.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("project.tgz");
+        let archive = temp.path().join(name);
+        info!("Downloading project from {input}");
+        download::download_to(input, &archive, None)
+            .await
+            .with_context(|| format!("Failed to download project archive {input}"))?;
+        archive
+    } else {
+        let archive = PathBuf::from(input);
+        anyhow::ensure!(archive.is_file(), "Project path {input} does not exist");
+        archive
+    };
+
+    if let Some(expected) = sha256 {
+        let data =
+            fs::read(&archive).with_context(|| format!("Failed to read {}", archive.display()))?;
+        let actual = format!("{:x}", Sha256::digest(&data));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(download::ChecksumMismatch {
+                what: input.to_string(),
+                expected: expected.to_string(),
+                actual,
+            }
+            .into());
+        }
+    }
+
+    let root = temp.path().join("project");
+    unpack(&archive, &root)?;
+    let path = project_root(&root)?;
+    install_dependencies(&path).await?;
+    Ok(ProjectInput {
+        path,
+        _unpacked: Some(temp),
+    })
+}
+
+/// `npm pack` leaves `node_modules` out (except `bundleDependencies`), so install what the
+/// package depends on at run time
+async fn install_dependencies(project: &Path) -> Result<()> {
+    let manifest = fs::read_to_string(project.join("package.json"))
+        .context("Failed to read the project's package.json")?;
+    let manifest: serde_json::Value =
+        serde_json::from_str(&manifest).context("Failed to parse the project's package.json")?;
+    let has_dependencies = manifest["dependencies"]
+        .as_object()
+        .is_some_and(|deps| !deps.is_empty());
+    if !has_dependencies || project.join("node_modules").exists() {
+        return Ok(());
+    }
+
+    // npm is a batch-file shim on Windows
+    let program = if cfg!(windows) { "npm.cmd" } else { "npm" };
+    info!("Installing the project's dependencies with {program} install --omit=dev");
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(["install", "--omit=dev", "--no-audit", "--no-fund"])
+        .current_dir(project);
+    if crate::output::json() {
+        // Keep stdout free for JSON events
+        command.stdout(std::io::stderr());
+    }
+    let status = command
+        .status()
+        .await
+        .with_context(|| format!("Failed to run {program} (is it installed?)"))?;
+    anyhow::ensure!(
+        status.success(),
+        "Installing the dependencies of the project archive failed ({status})"
+    );
+    Ok(())
+}
+
+fn unpack(archive: &Path, dest: &Path) -> Result<()> {
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file =
+        fs::File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    fs::create_dir_all(dest).context("Failed to create project directory")?;
+    if name.ends_with(".zip") {
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dest))
+            .context("Failed to unpack project zip")?;
+    } else if name.ends_with(".tgz") || name.ends_with(".tar.gz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(file))
+            .unpack(dest)
+            .context("Failed to unpack project tarball")?;
+    } else {
+        anyhow::bail!("Unsupported project archive {name} (expected .tgz, .tar.gz or .zip)");
+    }
+    Ok(())
+}
+
+/// The unpacked directory itself, or its single top-level folder (`package/` for `npm pack`)
+fn project_root(dir: &Path) -> Result<PathBuf> {
+    if dir.join("package.json").exists() {
+        return Ok(dir.to_path_buf());
+    }
+    let entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    match entries.as_slice() {
+        [single] if single.join("package.json").exists() => Ok(single.clone()),
+        _ => anyhow::bail!("Project archive contains no package.json at its root"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_npm_pack_tarball() {
+        let dir = TempDir::new().unwrap();
+        let tarball = dir.path().join("demo-1.0.0.tgz");
+        {
+            let encoder = flate2::write::GzEncoder::new(
+                fs::File::create(&tarball).unwrap(),
+                flate2::Compression::default(),
+            );
+            let mut builder = tar::Builder::new(encoder);
+            let manifest = br#"{"name":"demo","version":"1.0.0"}"#;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(manifest.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "package/package.json", &manifest[..])
+                .unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+        let tarball_str = tarball.to_str().unwrap();
+        let checksum = format!("{:x}", Sha256::digest(fs::read(&tarball).unwrap()));
+
+        let input = resolve(tarball_str, Some(&checksum)).await.unwrap();
+        assert!(input.path().ends_with("package"));
+        assert!(input.path().join("package.json").exists());
+
+        assert!(resolve(tarball_str, Some("00")).await.is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::config::ServiceRestart;
+use crate::node_downloader::NodeBuild;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Settings embedded into the runner as `runtime_config.json` and read at startup.
+///
+/// Field names must stay in sync with `RuntimeConfig` in the runner template.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RuntimeConfig {
+    pub app_name: String,
+    pub app_version: String,
+    /// Platform the bundle was built for, e.g. `linux-x64`, named when it runs on another CPU
+    pub platform: String,
+    pub runtime: RuntimeKind,
+    /// ICU variant or flavor of the bundled node; reduced ICU builds get a runtime warning when `Intl` falls short
+    pub node_build: NodeBuild,
+    /// Fetch full ICU data on the first run that needs it (small-icu builds only)
+    pub lazy_icu: bool,
+    /// Download URL template for the ICU data zip, `{version}`/`{major}`/`{minor}` substituted
+    pub icu_data_url: Option<String>,
+    /// Entry script chosen at bundle time (`main` or a `bin` target), relative to `app/`
+    pub main_script: Option<String>,
+    pub notice: Option<RuntimeNotice>,
+    pub entrypoints: Vec<RuntimeEntrypoint>,
+    pub default_entrypoint: Option<String>,
+    pub entry_override_disabled: bool,
+    pub shell_disabled: bool,
+    pub exec_path_shim: bool,
+    /// Yarn Plug'n'Play install: node starts with the bundled `.pnp.cjs` (and its ESM loader)
+    pub pnp: bool,
+    /// Environment defaults for the app (callers' values win)
+    pub env: BTreeMap<String, String>,
+    /// npm's entry script relative to the runtime directory, when npm is bundled
+    pub npm_cli: Option<String>,
+    /// Drop the host's Node.js variables (`NODE_PATH`, `NODE_OPTIONS`, ...) before launching
+    pub isolate_env: bool,
+    /// Arguments for node placed before the entry script
+    pub node_args: Vec<String>,
+    /// Glob patterns relative to `app/` that verification skips (code files excluded)
+    pub mutable_paths: Vec<String>,
+    /// Check the extracted files before every launch, as `--banderole-verify-on-start` does
+    pub verify_on_start: bool,
+    /// App arguments run for `--banderole-completions <shell>`, with `{shell}` substituted
+    pub completion_args: Vec<String>,
+    /// Completion script text by shell, printed and installed without running the app
+    pub completion_scripts: BTreeMap<String, String>,
+    /// Content hash of the runtime directory; runners extract each digest once per machine and
+    /// link it into every app directory
+    pub runtime_digest: Option<String>,
+    /// Content hash of `app/node_modules` when it is embedded as its own layer; shared between
+    /// extractions like the runtime
+    pub deps_digest: Option<String>,
+    /// Runtime left out of the payload; the runner downloads it on first use
+    pub external_runtime: Option<ExternalRuntime>,
+    /// File the runner appends per-launch startup timings to, as JSON lines
+    pub metrics_file: Option<String>,
+    /// Command the runner starts on every launch with the timings in `BANDEROLE_METRICS`
+    pub metrics_command: Option<String>,
+    /// Shell command run once after extraction, before the app directory is marked ready
+    pub post_extract: Option<String>,
+    /// Where the runner extracts by default
+    pub cache_scope: CacheScope,
+    /// `<name>/<version>` directory below the cache holding the build's extraction; the
+    /// extraction is named by the build ID directly when unset
+    pub extraction_dir: Option<String>,
+    /// Key file of an encrypted payload, unless `BANDEROLE_ENCRYPTION_KEY(_FILE)` says otherwise
+    pub encryption_key_file: Option<String>,
+    /// Service definition for `--banderole-install-service`
+    pub service: Option<RuntimeService>,
+}
+
+/// Runtime shipped next to the app, stored under [`RuntimeKind::dir_name`] in the extracted bundle
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeKind {
+    #[default]
+    Node,
+    Electron,
+    Bun,
+    Deno,
+}
+
+impl RuntimeKind {
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            RuntimeKind::Node => "node",
+            RuntimeKind::Electron => "electron",
+            RuntimeKind::Bun => "bun",
+            RuntimeKind::Deno => "deno",
+        }
+    }
+}
+
+/// Whose cache the runner extracts into: the user's own, or one shared by every user of the machine
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheScope {
+    #[default]
+    User,
+    /// `/opt/banderole`, or `%ProgramData%\banderole` on Windows
+    System,
+}
+
+/// Release archive of the runtime, pinned by its SHA-256, which also serves as the runtime digest
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalRuntime {
+    pub url: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeEntrypoint {
+    pub name: String,
+    /// Script path relative to the extracted `app/` directory
+    pub script: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeService {
+    pub name: String,
+    pub description: String,
+    pub args: Vec<String>,
+    pub user: Option<String>,
+    pub restart: ServiceRestart,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeNotice {
+    pub text: String,
+    pub require_acceptance: bool,
+}
@@ -0,0 +1,432 @@
+use crate::bundler;
+use crate::cache;
+use crate::download;
+use crate::electron_downloader::{self, ElectronDownloader};
+use crate::node_downloader::{NodeBuild, NodeDownloader, NodeSource};
+use crate::platform::Platform;
+use crate::runtime_config::{ExternalRuntime, RuntimeKind};
+use anyhow::{Context, Result};
+use futures_util::future::BoxFuture;
+use indicatif::ProgressBar;
+use log::info;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// `--runtime name[@version]`, e.g. `bun@1.1`, `deno@1.44.4` or `node@20`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeSpec {
+    pub kind: RuntimeKind,
+    pub version: Option<String>,
+}
+
+impl FromStr for RuntimeSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name, Some(version.trim_start_matches('v').to_string())),
+            None => (spec, None),
+        };
+        let kind = match name {
+            "node" => RuntimeKind::Node,
+            "electron" => RuntimeKind::Electron,
+            "bun" => RuntimeKind::Bun,
+            "deno" => RuntimeKind::Deno,
+            other => {
+                return Err(format!(
+                    "unknown runtime '{other}' (expected node, bun, deno or electron)"
+                ))
+            }
+        };
+        Ok(Self { kind, version })
+    }
+}
+
+/// A JavaScript runtime that can be downloaded and shipped next to the app.
+pub trait RuntimeProvider: Send + Sync {
+    fn kind(&self) -> RuntimeKind;
+
+    /// Human readable name, e.g. `Node.js`
+    fn display_name(&self) -> &'static str;
+
+    /// Concrete version that will be bundled
+    fn version(&self) -> &str;
+
+    /// Whether symlinks in the runtime directory must survive bundling (macOS frameworks)
+    fn preserves_symlinks(&self) -> bool {
+        false
+    }
+
+    /// Make the runtime available locally and return the directory to bundle
+    fn ensure_runtime<'a>(
+        &'a self,
+        progress: Option<&'a ProgressBar>,
+    ) -> BoxFuture<'a, Result<PathBuf>>;
+
+    /// Directory `ensure_runtime` would return, when the runtime is in the cache already
+    fn cached_runtime(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Release archive the runner downloads itself when the runtime is not bundled
+    fn remote_archive(&self) -> BoxFuture<'_, Result<ExternalRuntime>> {
+        let name = self.display_name();
+        Box::pin(async move {
+            anyhow::bail!("--external-runtime is only supported for Node.js, not {name}")
+        })
+    }
+}
+
+/// Pick the provider for this bundle, fetching runtimes built for `platform`; without a spec,
+/// Node.js with the project's version is used
+pub async fn select(
+    spec: Option<&RuntimeSpec>,
+    platform: Platform,
+    project_path: &Path,
+    package_json: &Value,
+    ignore_cached_versions: bool,
+    node_source: NodeSource,
+) -> Result<Box<dyn RuntimeProvider>> {
+    let kind = spec.map(|spec| spec.kind).unwrap_or_default();
+    let version = spec.and_then(|spec| spec.version.clone());
+    anyhow::ensure!(
+        kind == RuntimeKind::Node || node_source.build == NodeBuild::Full,
+        "Node build variants only apply to the Node.js runtime"
+    );
+    anyhow::ensure!(
+        kind == RuntimeKind::Node || node_source.archive.is_none(),
+        "--node-archive only applies to the Node.js runtime"
+    );
+    Ok(match kind {
+        RuntimeKind::Node => {
+            let version = match (node_source.archive_version()?, version) {
+                (Some(archived), Some(requested)) if archived != requested => anyhow::bail!(
+                    "--node-archive contains Node.js {archived}, but {requested} was requested"
+                ),
+                (Some(archived), _) => archived,
+                (None, Some(version)) => version,
+                (None, None) => bundler::detect_node_version_with_workspace_support(
+                    project_path,
+                    ignore_cached_versions,
+                    &node_source.mirror(),
+                )
+                .await
+                .or_else(|e| match e.downcast_ref::<download::NotCached>() {
+                    // Offline, a default version would silently differ from the online result
+                    Some(_) => Err(e),
+                    None => Ok("22.17.1".to_string()),
+                })?,
+            };
+            Box::new(NodeProvider {
+                downloader: NodeDownloader::new_with_persistent_cache(
+                    &version,
+                    platform,
+                    node_source,
+                )
+                .await?,
+            })
+        }
+        RuntimeKind::Electron => {
+            let version = match version {
+                Some(version) => version,
+                None => electron_downloader::detect_electron_version(project_path, package_json)?,
+            };
+            Box::new(ElectronProvider {
+                downloader: ElectronDownloader::new_with_persistent_cache(&version, platform)?,
+                version,
+            })
+        }
+        RuntimeKind::Bun | RuntimeKind::Deno => {
+            Box::new(StandaloneProvider::new(kind, version.as_deref(), platform).await?)
+        }
+    })
+}
+
+struct NodeProvider {
+    downloader: NodeDownloader,
+}
+
+impl RuntimeProvider for NodeProvider {
+    fn kind(&self) -> RuntimeKind {
+        RuntimeKind::Node
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Node.js"
+    }
+
+    fn version(&self) -> &str {
+        self.downloader.version()
+    }
+
+    fn ensure_runtime<'a>(
+        &'a self,
+        progress: Option<&'a ProgressBar>,
+    ) -> BoxFuture<'a, Result<PathBuf>> {
+        Box::pin(async move {
+            let node_executable = self
+                .downloader
+                .ensure_node_binary_with_progress(progress)
+                .await?;
+            // The extraction target is cache_dir/node/<version>/<platform> on all platforms; bundle
+            // that whole directory under "node/" so the runtime finds binaries consistently.
+            let mut root = node_executable
+                .parent()
+                .expect("node executable must have a parent");
+            // If on Unix and we are at .../<platform>/bin, step up to <platform>
+            if root.file_name().is_some_and(|n| n == "bin") {
+                root = root.parent().unwrap_or(root);
+            }
+            Ok(root.to_path_buf())
+        })
+    }
+
+    fn cached_runtime(&self) -> Option<PathBuf> {
+        self.downloader.cached_dir()
+    }
+
+    fn remote_archive(&self) -> BoxFuture<'_, Result<ExternalRuntime>> {
+        Box::pin(self.downloader.remote_archive())
+    }
+}
+
+struct ElectronProvider {
+    downloader: ElectronDownloader,
+    version: String,
+}
+
+impl RuntimeProvider for ElectronProvider {
+    fn kind(&self) -> RuntimeKind {
+        RuntimeKind::Electron
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Electron"
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn preserves_symlinks(&self) -> bool {
+        true
+    }
+
+    fn ensure_runtime<'a>(
+        &'a self,
+        progress: Option<&'a ProgressBar>,
+    ) -> BoxFuture<'a, Result<PathBuf>> {
+        Box::pin(self.downloader.ensure_electron_dist_with_progress(progress))
+    }
+
+    fn cached_runtime(&self) -> Option<PathBuf> {
+        self.downloader.cached_dir()
+    }
+}
+
+/// Runtimes distributed as a single executable inside a zip (Bun, Deno)
+struct StandaloneProvider {
+    kind: RuntimeKind,
+    version: String,
+    platform: Platform,
+    cache_dir: PathBuf,
+}
+
+impl StandaloneProvider {
+    async fn new(kind: RuntimeKind, version: Option<&str>, platform: Platform) -> Result<Self> {
+        let repo = match kind {
+            RuntimeKind::Bun => "oven-sh/bun",
+            _ => "denoland/deno",
+        };
+        let version = match version.filter(|v| v.split('.').count() == 3) {
+            Some(exact) => exact.to_string(),
+            None => {
+                let prefix = version.map(|v| format!("{v}."));
+                let strip = |tag: &str| -> String {
+                    tag.trim_start_matches("bun-")
+                        .trim_start_matches('v')
+                        .to_string()
+                };
+                download::find_github_release_tag(repo, |tag| {
+                    prefix
+                        .as_deref()
+                        .is_none_or(|prefix| strip(tag).starts_with(prefix))
+                })
+                .await?
+                .map(|tag| strip(&tag))
+                .with_context(|| {
+                    format!(
+                        "No {repo} release matches version '{}'",
+                        version.unwrap_or("latest")
+                    )
+                })?
+            }
+        };
+        info!("Resolved {repo} to version {version}");
+        Ok(Self {
+            kind,
+            version,
+            platform,
+            cache_dir: cache::persistent_cache_dir()?,
+        })
+    }
+
+    fn binary_name(&self) -> &'static str {
+        match (self.kind, self.platform.is_windows()) {
+            (RuntimeKind::Bun, false) => "bun",
+            (RuntimeKind::Bun, true) => "bun.exe",
+            (_, false) => "deno",
+            (_, true) => "deno.exe",
+        }
+    }
+
+    /// Cache directory holding the binary: `<runtime>/<version>/<platform>`
+    fn runtime_dir(&self) -> PathBuf {
+        self.cache_dir
+            .join(self.kind.dir_name())
+            .join(&self.version)
+            .join(self.platform.to_string())
+    }
+
+    fn archive_url(&self) -> Result<String> {
+        let version = &self.version;
+        Ok(match self.kind {
+            RuntimeKind::Bun => {
+                let target = match self.platform {
+                    Platform::LinuxX64 => "linux-x64",
+                    Platform::LinuxArm64 => "linux-aarch64",
+                    Platform::LinuxArmv7 => anyhow::bail!("Bun has no 32-bit ARM build"),
+                    Platform::LinuxX64Musl => "linux-x64-musl",
+                    Platform::LinuxArm64Musl => "linux-aarch64-musl",
+                    Platform::MacosX64 => "darwin-x64",
+                    Platform::MacosArm64 => "darwin-aarch64",
+                    Platform::WindowsX64 => "windows-x64",
+                    Platform::WindowsArm64 => anyhow::bail!("Bun has no Windows arm64 build"),
+                    Platform::Custom => anyhow::bail!("Bun has no build for {}", self.platform),
+                };
+                format!("https://github.com/oven-sh/bun/releases/download/bun-v{version}/bun-{target}.zip")
+            }
+            _ => {
+                let target = match self.platform {
+                    Platform::LinuxX64 => "x86_64-unknown-linux-gnu",
+                    Platform::LinuxArm64 => "aarch64-unknown-linux-gnu",
+                    Platform::LinuxArmv7 => anyhow::bail!("Deno has no 32-bit ARM build"),
+                    Platform::LinuxX64Musl | Platform::LinuxArm64Musl => {
+                        anyhow::bail!("Deno has no musl build")
+                    }
+                    Platform::MacosX64 => "x86_64-apple-darwin",
+                    Platform::MacosArm64 => "aarch64-apple-darwin",
+                    Platform::WindowsX64 => "x86_64-pc-windows-msvc",
+                    Platform::WindowsArm64 => "aarch64-pc-windows-msvc",
+                    Platform::Custom => anyhow::bail!("Deno has no build for {}", self.platform),
+                };
+                format!("https://github.com/denoland/deno/releases/download/v{version}/deno-{target}.zip")
+            }
+        })
+    }
+}
+
+impl RuntimeProvider for StandaloneProvider {
+    fn kind(&self) -> RuntimeKind {
+        self.kind
+    }
+
+    fn display_name(&self) -> &'static str {
+        match self.kind {
+            RuntimeKind::Bun => "Bun",
+            _ => "Deno",
+        }
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn ensure_runtime<'a>(
+        &'a self,
+        progress: Option<&'a ProgressBar>,
+    ) -> BoxFuture<'a, Result<PathBuf>> {
+        Box::pin(async move {
+            let dir = self.runtime_dir();
+            let binary = dir.join(self.binary_name());
+            if binary.exists() {
+                return Ok(dir);
+            }
+
+            info!(
+                "Fetching {} {} for {}",
+                self.display_name(),
+                self.version,
+                self.platform
+            );
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .context("Failed to create runtime cache directory")?;
+            let archive_path = dir.join("runtime.zip");
+            let url = self.archive_url()?;
+            download::download_to(&url, &archive_path, progress)
+                .await
+                .with_context(|| format!("Failed to download {}", self.display_name()))?;
+
+            let binary_name = self.binary_name();
+            let target = binary.clone();
+            let archive = archive_path.clone();
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                let file =
+                    std::fs::File::open(&archive).context("Failed to open runtime archive")?;
+                let mut zip =
+                    zip::ZipArchive::new(file).context("Failed to read runtime archive")?;
+                // Archives nest the binary in a platform directory (bun) or not at all (deno)
+                let index = (0..zip.len())
+                    .find(|&i| {
+                        zip.name_for_index(i)
+                            .is_some_and(|name| name.rsplit('/').next() == Some(binary_name))
+                    })
+                    .with_context(|| format!("{binary_name} not found in runtime archive"))?;
+                let mut entry = zip.by_index(index)?;
+                let mut out = std::fs::File::create(&target)
+                    .with_context(|| format!("Failed to create {}", target.display()))?;
+                std::io::copy(&mut entry, &mut out).context("Failed to extract runtime binary")?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o755))?;
+                }
+                Ok(())
+            })
+            .await??;
+            tokio::fs::remove_file(&archive_path)
+                .await
+                .context("Failed to remove archive file")?;
+            Ok(dir)
+        })
+    }
+
+    fn cached_runtime(&self) -> Option<PathBuf> {
+        let dir = self.runtime_dir();
+        dir.join(self.binary_name()).exists().then_some(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_runtime_spec() {
+        let spec: RuntimeSpec = "bun@1.1".parse().unwrap();
+        assert_eq!(spec.kind, RuntimeKind::Bun);
+        assert_eq!(spec.version.as_deref(), Some("1.1"));
+
+        let spec: RuntimeSpec = "deno@v1.44.4".parse().unwrap();
+        assert_eq!(spec.kind, RuntimeKind::Deno);
+        assert_eq!(spec.version.as_deref(), Some("1.44.4"));
+
+        let spec: RuntimeSpec = "node".parse().unwrap();
+        assert_eq!(spec.kind, RuntimeKind::Node);
+        assert_eq!(spec.version, None);
+
+        assert!("python@3".parse::<RuntimeSpec>().is_err());
+    }
+}
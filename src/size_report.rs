@@ -0,0 +1,328 @@
+use crate::diff::runtime_name;
+use crate::manifest::BundleManifest;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Packages listed by the text report; JSON and HTML reports include all of them
+const TOP_PACKAGES: usize = 15;
+
+/// How `bundle --analyze` presents the size breakdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AnalyzeFormat {
+    /// Summary with the largest packages, printed after bundling
+    #[default]
+    Text,
+    /// Full breakdown written next to the executable as `<name>.size.json`
+    Json,
+    /// Treemap written next to the executable as `<name>.size.html`
+    Html,
+}
+
+/// Where the bytes of a bundle go.
+///
+/// Contents are measured uncompressed from the manifest; only the executable, its payload and
+/// the runner overhead are on-disk sizes.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeReport {
+    pub app_name: String,
+    pub runtime: String,
+    pub executable: u64,
+    /// Compressed payload embedded into the runner
+    pub payload: u64,
+    /// Executable bytes besides the payload: the runner itself and the manifest
+    pub runner_overhead: u64,
+    pub runtime_files: u64,
+    /// App files outside `node_modules`
+    pub app_source: u64,
+    /// Installed packages, largest first
+    pub packages: Vec<PackageSize>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PackageSize {
+    pub name: String,
+    pub size: u64,
+}
+
+impl SizeReport {
+    pub fn new(manifest: &BundleManifest, executable: u64, payload: u64) -> Self {
+        let mut runtime_files = 0;
+        let mut app_source = 0;
+        let mut packages: BTreeMap<String, u64> = BTreeMap::new();
+        for (path, file) in &manifest.files {
+            match path.strip_prefix("app/") {
+                None => runtime_files += file.size,
+                Some(path) => match owning_package(path) {
+                    Some(name) => *packages.entry(name).or_default() += file.size,
+                    None => app_source += file.size,
+                },
+            }
+        }
+        let mut packages: Vec<PackageSize> = packages
+            .into_iter()
+            .map(|(name, size)| PackageSize { name, size })
+            .collect();
+        packages.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        Self {
+            app_name: manifest.app_name.clone(),
+            runtime: format!(
+                "{} {}",
+                runtime_name(manifest.runtime),
+                manifest.runtime_version
+            ),
+            executable,
+            payload,
+            runner_overhead: executable.saturating_sub(payload),
+            runtime_files,
+            app_source,
+            packages,
+        }
+    }
+
+    pub fn render_text(&self) -> String {
+        let size = |bytes: u64| indicatif::HumanBytes(bytes).to_string();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Size of {}: {} ({} compressed payload, {} runner overhead)",
+            self.app_name,
+            size(self.executable),
+            size(self.payload),
+            size(self.runner_overhead)
+        );
+        let _ = writeln!(out, "Unpacked contents:");
+        let _ = writeln!(
+            out,
+            "  {:<40} {:>12}",
+            self.runtime,
+            size(self.runtime_files)
+        );
+        let _ = writeln!(out, "  {:<40} {:>12}", "App source", size(self.app_source));
+        let package_total: u64 = self.packages.iter().map(|package| package.size).sum();
+        let _ = writeln!(
+            out,
+            "  {:<40} {:>12}",
+            format!("node_modules ({} packages)", self.packages.len()),
+            size(package_total)
+        );
+        for package in self.packages.iter().take(TOP_PACKAGES) {
+            let _ = writeln!(out, "    {:<38} {:>12}", package.name, size(package.size));
+        }
+        if self.packages.len() > TOP_PACKAGES {
+            let _ = writeln!(
+                out,
+                "    ... {} more (--analyze=json lists all)",
+                self.packages.len() - TOP_PACKAGES
+            );
+        }
+        out
+    }
+
+    /// Self-contained HTML treemap of the unpacked contents and the runner
+    pub fn render_html(&self) -> String {
+        let mut items = vec![
+            (self.runtime.clone(), self.runtime_files, "#4e79a7"),
+            ("Runner".to_string(), self.runner_overhead, "#76b7b2"),
+            ("App source".to_string(), self.app_source, "#59a14f"),
+        ];
+        items.extend(
+            self.packages
+                .iter()
+                .map(|package| (package.name.clone(), package.size, "#f28e2b")),
+        );
+        items.retain(|(_, size, _)| *size > 0);
+        items.sort_by_key(|(_, size, _)| std::cmp::Reverse(*size));
+
+        let mut cells = String::new();
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        let sizes: Vec<u64> = items.iter().map(|(_, size, _)| *size).collect();
+        for (rect, (name, size, color)) in layout(&sizes, rect).into_iter().zip(&items) {
+            let label = format!("{} ({})", escape(name), indicatif::HumanBytes(*size));
+            let _ = writeln!(
+                cells,
+                r#"<div class="cell" style="left:{:.3}%;top:{:.3}%;width:{:.3}%;height:{:.3}%;background:{color}" title="{label}">{label}</div>"#,
+                rect.x, rect.y, rect.width, rect.height
+            );
+        }
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{name} size breakdown</title>
+<style>
+body {{ font-family: sans-serif; margin: 1em; }}
+#map {{ position: relative; width: 100%; height: 85vh; }}
+.cell {{ position: absolute; box-sizing: border-box; border: 1px solid #fff; overflow: hidden; font-size: 12px; padding: 2px; color: #fff; }}
+</style>
+</head>
+<body>
+<h1>{name}: {executable}</h1>
+<p>{payload} compressed payload and {runner} runner overhead. Areas show unpacked sizes.</p>
+<div id="map">
+{cells}</div>
+</body>
+</html>
+"#,
+            name = escape(&self.app_name),
+            executable = indicatif::HumanBytes(self.executable),
+            payload = indicatif::HumanBytes(self.payload),
+            runner = indicatif::HumanBytes(self.runner_overhead),
+        )
+    }
+}
+
+/// Innermost package a path below `app/` belongs to, e.g. `a/node_modules/@s/b` for
+/// `node_modules/a/node_modules/@s/b/index.js`
+pub(crate) fn owning_package(path: &str) -> Option<String> {
+    let (outer, rest) = path.rsplit_once("node_modules/")?;
+    let mut segments = rest.split('/');
+    let first = segments.next().filter(|segment| !segment.is_empty())?;
+    let package = match first.starts_with('@') {
+        true => format!("{first}/{}", segments.next()?),
+        false => first.to_string(),
+    };
+    // The last segment is the file itself
+    segments.next()?;
+    match outer.strip_prefix("node_modules/").unwrap_or(outer) {
+        "" => Some(package),
+        outer => Some(format!("{outer}node_modules/{package}")),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Treemap layout: split the (descending) sizes into two halves of similar weight and place
+/// them side by side along the longer edge, recursively
+fn layout(sizes: &[u64], rect: Rect) -> Vec<Rect> {
+    if sizes.len() <= 1 {
+        return vec![rect; sizes.len()];
+    }
+    let total: u64 = sizes.iter().sum();
+    let mut split = 1;
+    let mut first: u64 = sizes[0];
+    while split < sizes.len() - 1 && (first + sizes[split]) * 2 <= total {
+        first += sizes[split];
+        split += 1;
+    }
+    let ratio = if total == 0 {
+        0.5
+    } else {
+        first as f64 / total as f64
+    };
+    let (a, b) = if rect.width >= rect.height {
+        let width = rect.width * ratio;
+        (
+            Rect { width, ..rect },
+            Rect {
+                x: rect.x + width,
+                width: rect.width - width,
+                ..rect
+            },
+        )
+    } else {
+        let height = rect.height * ratio;
+        (
+            Rect { height, ..rect },
+            Rect {
+                y: rect.y + height,
+                height: rect.height - height,
+                ..rect
+            },
+        )
+    };
+    let mut rects = layout(&sizes[..split], a);
+    rects.extend(layout(&sizes[split..], b));
+    rects
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestFile;
+    use crate::runtime_config::RuntimeKind;
+
+    #[test]
+    fn test_size_report() {
+        let file = |size: u64| ManifestFile {
+            size,
+            sha256: "00".into(),
+        };
+        let manifest = BundleManifest {
+            banderole_version: "0.2.1".into(),
+            runner_schema: crate::schema::RUNNER_SCHEMA,
+            app_name: "demo".into(),
+            app_version: "1.0.0".into(),
+            runtime: RuntimeKind::Node,
+            runtime_version: "22.1.0".into(),
+            bundled_at: None,
+            packages: BTreeMap::new(),
+            files: BTreeMap::from([
+                ("node/bin/node".into(), file(100)),
+                ("app/index.js".into(), file(10)),
+                ("app/node_modules/a/index.js".into(), file(20)),
+                (
+                    "app/node_modules/a/node_modules/@s/b/index.js".into(),
+                    file(30),
+                ),
+                (
+                    "app/node_modules/a/node_modules/@s/b/lib/x.js".into(),
+                    file(5),
+                ),
+            ]),
+            payload: None,
+            layers: Vec::new(),
+        };
+        let report = SizeReport::new(&manifest, 1000, 800);
+        assert_eq!(report.runner_overhead, 200);
+        assert_eq!(report.runtime_files, 100);
+        assert_eq!(report.app_source, 10);
+        assert_eq!(
+            report.packages,
+            vec![
+                PackageSize {
+                    name: "a/node_modules/@s/b".into(),
+                    size: 35
+                },
+                PackageSize {
+                    name: "a".into(),
+                    size: 20
+                },
+            ]
+        );
+        assert!(report.render_text().contains("node_modules (2 packages)"));
+
+        let rects = layout(
+            &[50, 30, 20],
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            },
+        );
+        let area: f64 = rects.iter().map(|rect| rect.width * rect.height).sum();
+        assert!((area - 10_000.0).abs() < 1e-6);
+        assert!((rects[0].width * rects[0].height - 5_000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::platform::Platform;
+use anyhow::{Context, Result};
+
+/// Evaluate a Cargo-style `cfg(...)` expression against the platform being bundled for.
+///
+/// Supports `unix`, `windows`, `target_os = "..."`, `target_arch = "..."`, `target_family = "..."`
+/// and the `all(...)`, `any(...)` and `not(...)` combinators.
+pub fn matches(expr: &str, platform: Platform) -> Result<bool> {
+    let inner = expr
+        .trim()
+        .strip_prefix("cfg(")
+        .and_then(|rest| rest.strip_suffix(')'))
+        .with_context(|| format!("Invalid target '{expr}': expected cfg(...)"))?;
+    let mut parser = Parser {
+        input: inner,
+        pos: 0,
+    };
+    let result = parser
+        .predicate(platform)
+        .with_context(|| format!("Invalid target '{expr}'"))?;
+    parser.skip_whitespace();
+    anyhow::ensure!(
+        parser.pos == parser.input.len(),
+        "Invalid target '{expr}': unexpected '{}'",
+        &parser.input[parser.pos..]
+    );
+    Ok(result)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn predicate(&mut self, platform: Platform) -> Result<bool> {
+        let ident = self.ident()?;
+        self.skip_whitespace();
+        match ident.as_str() {
+            "all" | "any" | "not" => {
+                let args = self.arguments(platform)?;
+                match ident.as_str() {
+                    "all" => Ok(args.iter().all(|&arg| arg)),
+                    "any" => Ok(args.iter().any(|&arg| arg)),
+                    _ => {
+                        anyhow::ensure!(args.len() == 1, "not(...) takes exactly one predicate");
+                        Ok(!args[0])
+                    }
+                }
+            }
+            "unix" => Ok(!platform.is_windows()),
+            "windows" => Ok(platform.is_windows()),
+            key => {
+                anyhow::ensure!(self.eat('='), "expected `=` after '{key}'");
+                let value = self.string()?;
+                let actual = match key {
+                    "target_os" => platform.os(),
+                    "target_arch" => platform.arch(),
+                    "target_family" => {
+                        if platform.is_windows() {
+                            "windows"
+                        } else {
+                            "unix"
+                        }
+                    }
+                    _ => anyhow::bail!("unsupported cfg key '{key}'"),
+                };
+                Ok(value == actual)
+            }
+        }
+    }
+
+    fn arguments(&mut self, platform: Platform) -> Result<Vec<bool>> {
+        anyhow::ensure!(self.eat('('), "expected `(`");
+        let mut args = Vec::new();
+        loop {
+            if self.eat(')') {
+                return Ok(args);
+            }
+            args.push(self.predicate(platform)?);
+            if !self.eat(',') {
+                anyhow::ensure!(self.eat(')'), "expected `,` or `)`");
+                return Ok(args);
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        anyhow::ensure!(self.pos > start, "expected a cfg name");
+        Ok(self.input[start..self.pos].to_string())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        anyhow::ensure!(self.eat('"'), "expected a quoted value");
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != '"') {
+            self.pos += 1;
+        }
+        let value = self.input[start..self.pos].to_string();
+        anyhow::ensure!(self.eat('"'), "unterminated string");
+        Ok(value)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_predicates() {
+        assert!(matches("cfg(windows)", Platform::WindowsX64).unwrap());
+        assert!(!matches("cfg(windows)", Platform::LinuxX64).unwrap());
+        assert!(matches("cfg(unix)", Platform::MacosArm64).unwrap());
+        assert!(matches(r#"cfg(target_os = "macos")"#, Platform::MacosX64).unwrap());
+        assert!(matches(r#"cfg(target_arch = "aarch64")"#, Platform::LinuxArm64).unwrap());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let expr = r#"cfg(all(unix, not(target_os = "macos")))"#;
+        assert!(matches(expr, Platform::LinuxX64).unwrap());
+        assert!(!matches(expr, Platform::MacosArm64).unwrap());
+        assert!(matches(
+            "cfg(any(windows, target_os = \"linux\"))",
+            Platform::LinuxArm64
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert!(matches("windows", Platform::WindowsX64).is_err());
+        assert!(matches("cfg(target_vendor = \"apple\")", Platform::MacosX64).is_err());
+        assert!(matches("cfg(all(unix)", Platform::LinuxX64).is_err());
+    }
+}
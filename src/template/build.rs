@@ -9,6 +9,43 @@ fn main() {
     // Check if we have embedded data files
     let xz_data_path = Path::new("embedded_data.xz");
     let build_id_path = Path::new("build_id.txt");
+    let runtime_config_path = Path::new("runtime_config.json");
+    
+    // Copy the runtime config to OUT_DIR (an empty object when bundling without one)
+    let out_config_path = Path::new(&out_dir).join("runtime_config.json");
+    if runtime_config_path.exists() {
+        fs::copy(runtime_config_path, &out_config_path)
+            .expect("Failed to copy runtime config to OUT_DIR");
+    } else {
+        fs::write(&out_config_path, "{}").expect("Failed to write placeholder runtime config");
+    }
+    
+    // The bundle manifest is embedded verbatim so tools can read it from the finished binary
+    let manifest_path = Path::new("manifest.bin");
+    let out_manifest_path = Path::new(&out_dir).join("manifest.bin");
+    if manifest_path.exists() {
+        fs::copy(manifest_path, &out_manifest_path)
+            .expect("Failed to copy bundle manifest to OUT_DIR");
+    } else {
+        fs::write(&out_manifest_path, b"").expect("Failed to write placeholder manifest");
+    }
+    let manifest_len = fs::metadata(&out_manifest_path).map(|m| m.len()).unwrap_or(0);
+    let manifest_static = format!(
+        "static EMBEDDED_MANIFEST: [u8; {manifest_len}] = *include_bytes!(\"manifest.bin\");\n"
+    );
+    
+    // The dependency and runtime layers are optional: empty when the bundle has none
+    let mut layer_statics = String::new();
+    for (name, file) in [("DEPS_DATA", "embedded_deps.xz"), ("RUNTIME_DATA", "embedded_runtime.xz")] {
+        let out_path = Path::new(&out_dir).join(file);
+        if Path::new(file).exists() {
+            fs::copy(file, &out_path).expect("Failed to copy embedded layer to OUT_DIR");
+        } else {
+            fs::write(&out_path, b"").expect("Failed to write placeholder layer");
+        }
+        layer_statics.push_str(&format!("const {name}: &[u8] = include_bytes!(\"{file}\");\n"));
+        println!("cargo:rerun-if-changed={file}");
+    }
     
     if xz_data_path.exists() && build_id_path.exists() {
         // Read the build ID
@@ -26,25 +63,42 @@ fn main() {
 // Generated at build time - contains embedded application data (xz-compressed zip)
 const XZ_DATA: &[u8] = include_bytes!("embedded_data.xz");
 const BUILD_ID: &str = "{}";
-"#,
-            build_id.trim()
+const RUNTIME_CONFIG: &str = include_str!("runtime_config.json");
+{}{}"#,
+            build_id.trim(),
+            manifest_static,
+            layer_statics
         );
         
         fs::write(&dest_path, data_rs_content)
             .expect("Failed to write data.rs");
     } else {
         // Generate placeholder data for template compilation
-        let data_rs_content = r#"
+        let data_rs_content = format!(
+            r#"
 // Placeholder data for template compilation
 const XZ_DATA: &[u8] = &[];
 const BUILD_ID: &str = "template";
-"#;
+const RUNTIME_CONFIG: &str = include_str!("runtime_config.json");
+{}{}"#,
+            manifest_static,
+            layer_statics
+        );
         
         fs::write(&dest_path, data_rs_content)
             .expect("Failed to write placeholder data.rs");
     }
     
+    // GUI subsystem builds (no console window) are requested by banderole via the environment
+    println!("cargo:rustc-check-cfg=cfg(banderole_gui)");
+    if env::var("BANDEROLE_WINDOWS_SUBSYSTEM").as_deref() == Ok("windows") {
+        println!("cargo:rustc-cfg=banderole_gui");
+    }
+    println!("cargo:rerun-if-env-changed=BANDEROLE_WINDOWS_SUBSYSTEM");
+    
     // Tell Cargo to rerun this script if the embedded data changes
     println!("cargo:rerun-if-changed=embedded_data.xz");
     println!("cargo:rerun-if-changed=build_id.txt");
+    println!("cargo:rerun-if-changed=runtime_config.json");
+    println!("cargo:rerun-if-changed=manifest.bin");
 }
@@ -0,0 +1,73 @@
+//! Environment the runner hands the app and its `postExtract` command, so app code can find its
+//! files and start itself again without guessing from `process.execPath` or `__dirname`:
+//!
+//! - `BANDEROLE_APP_DIR`: the extracted app (the bundled project directory)
+//! - `BANDEROLE_NODE_BIN`: the bundled runtime executable (also `BANDEROLE_NODE_PATH`, its older name)
+//! - `BANDEROLE_BUNDLE_VERSION`: the version of the bundled app
+//! - `BANDEROLE_BUILD_ID`: the build ID naming the extraction
+//! - `BANDEROLE_EXECUTABLE`: absolute path of the bundle executable, for re-invoking the app
+//! - `BANDEROLE_INVOKED_AS`: the executable as it was invoked (`argv[0]`)
+
+use crate::config::RuntimeConfig;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Variables of the host that change how node resolves modules or behaves, left out with
+/// `isolateEnv`; the runner sets its own `NODE_PATH` and `NODE_OPTIONS` afterwards
+pub const ISOLATED: &[&str] = &[
+    "NODE_PATH",
+    "NODE_OPTIONS",
+    "NODE_PRESERVE_SYMLINKS",
+    "NODE_PRESERVE_SYMLINKS_MAIN",
+    "NODE_ICU_DATA",
+    "NODE_REPL_EXTERNAL_MODULE",
+    "NODE_PENDING_DEPRECATION",
+    "NODE_V8_COVERAGE",
+    "ELECTRON_RUN_AS_NODE",
+];
+
+/// Remove the [`ISOLATED`] variables from what `cmd` inherits when the bundle asks for it
+pub fn isolate(cmd: &mut Command, config: &RuntimeConfig) {
+    if config.isolate_env {
+        for name in ISOLATED {
+            cmd.env_remove(name);
+        }
+    }
+}
+
+/// `var` for the app: `first`, then the caller's entries unless the bundle isolates the app
+/// from them. Empty entries and repeats of `first` are dropped, so an inherited `NODE_PATH`
+/// can add directories but never shadow the bundled ones.
+pub fn path_list(var: &str, first: Vec<PathBuf>, config: &RuntimeConfig) -> OsString {
+    let mut paths = first.clone();
+    if !(config.isolate_env && ISOLATED.contains(&var)) {
+        if let Some(existing) = std::env::var_os(var) {
+            for path in std::env::split_paths(&existing) {
+                if !path.as_os_str().is_empty() && !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    std::env::join_paths(paths)
+        .or_else(|_| std::env::join_paths(first))
+        .unwrap_or_default()
+}
+
+pub fn apply(cmd: &mut Command, app_path: &Path, runtime: &Path, config: &RuntimeConfig) {
+    cmd.env("BANDEROLE_APP_DIR", app_path)
+        .env("BANDEROLE_NODE_BIN", runtime)
+        .env("BANDEROLE_NODE_PATH", runtime)
+        .env("BANDEROLE_BUNDLE_VERSION", &config.app_version)
+        .env("BANDEROLE_BUILD_ID", crate::BUILD_ID);
+    match std::env::current_exe() {
+        Ok(executable) => {
+            cmd.env("BANDEROLE_EXECUTABLE", executable);
+        }
+        Err(e) => verbose!("Cannot determine the executable path for BANDEROLE_EXECUTABLE: {e}"),
+    }
+    if let Some(invoked_as) = std::env::args_os().next() {
+        cmd.env("BANDEROLE_INVOKED_AS", invoked_as);
+    }
+}
@@ -0,0 +1,161 @@
+//! A bundle started on a machine with another CPU architecture only runs under emulation (qemu
+//! through binfmt_misc on Linux, Rosetta on macOS), where node fails with exec format errors or
+//! crashes in its JIT. The runner compares the machine with the architecture it was built for
+//! and names both before extracting anything; `BANDEROLE_IGNORE_ARCH=1` runs it anyway.
+
+use crate::config::RuntimeConfig;
+
+/// Stop with a readable error when the machine cannot run the bundle natively
+pub fn check(config: &RuntimeConfig) {
+    let Some(machine) = machine() else {
+        return;
+    };
+    if compatible(std::env::consts::ARCH, &machine) {
+        return;
+    }
+    let built_for = if config.platform.is_empty() { std::env::consts::ARCH } else { config.platform.as_str() };
+    let host = format!("{}-{}", node_os(), node_arch(&machine));
+    if cfg!(target_os = "macos") {
+        // Rosetta translates the whole process tree reliably, just more slowly
+        verbose!("Built for {built_for}, running under Rosetta on {host}");
+        return;
+    }
+    if std::env::var_os("BANDEROLE_IGNORE_ARCH").is_some_and(|value| !value.is_empty() && value != "0") {
+        verbose!("Built for {built_for}, running under emulation on {host} (BANDEROLE_IGNORE_ARCH)");
+        return;
+    }
+    eprintln!("Error: this executable was built for {built_for}, but this machine is {host}.");
+    eprintln!("Download the {host} build of {}; set BANDEROLE_IGNORE_ARCH=1 to run it under emulation anyway.", config.app_name);
+    std::process::exit(crate::exit_code::UNSUPPORTED_PLATFORM);
+}
+
+/// Whether code built for Rust's `arch` runs natively on the kernel's `machine`
+fn compatible(arch: &str, machine: &str) -> bool {
+    match arch {
+        "x86_64" => machine == "x86_64",
+        "aarch64" => matches!(machine, "aarch64" | "arm64"),
+        // 64-bit ARM kernels commonly run 32-bit ARM userlands, as on Raspberry Pi OS
+        "arm" => machine.starts_with("arm") || machine == "aarch64",
+        _ => true,
+    }
+}
+
+/// The machine's own architecture, which emulators do not translate
+#[cfg(target_os = "linux")]
+fn machine() -> Option<String> {
+    // qemu-user answers uname with the emulated CPU, but not this file (Linux 6.1 and later)
+    if let Some(arch) = std::fs::read_to_string("/proc/sys/kernel/arch").ok().map(|arch| arch.trim().to_string()).filter(|arch| !arch.is_empty()) {
+        return Some(arch);
+    }
+    // Older kernels: init is native unless the whole container is emulated. Reading it needs
+    // the permissions of its owner, usually root.
+    let mut header = [0u8; 20];
+    if std::fs::File::open("/proc/1/exe").and_then(|mut init| std::io::Read::read_exact(&mut init, &mut header)).is_ok() {
+        if let Some(arch) = elf_machine(&header) {
+            return Some(arch.to_string());
+        }
+    }
+    // Last resort, agreeing with the runner under emulators that translate it
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return None;
+    }
+    let machine = unsafe { std::ffi::CStr::from_ptr(name.machine.as_ptr()) };
+    Some(machine.to_string_lossy().into_owned()).filter(|arch| !arch.is_empty())
+}
+
+/// The kernel's name for the architecture of the ELF file starting with `header`
+#[cfg(any(target_os = "linux", test))]
+fn elf_machine(header: &[u8]) -> Option<&'static str> {
+    if header.len() < 20 || !header.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let machine = match header[5] {
+        1 => u16::from_le_bytes([header[18], header[19]]),
+        2 => u16::from_be_bytes([header[18], header[19]]),
+        _ => return None,
+    };
+    match machine {
+        3 => Some("i686"),
+        40 => Some("armv7l"),
+        62 => Some("x86_64"),
+        183 => Some("aarch64"),
+        243 => Some("riscv64"),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn machine() -> Option<String> {
+    let mut translated: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let name = c"sysctl.proc_translated";
+    let result = unsafe { libc::sysctlbyname(name.as_ptr(), (&mut translated as *mut libc::c_int).cast(), &mut size, std::ptr::null_mut(), 0) };
+    (result == 0 && translated == 1).then(|| "arm64".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn machine() -> Option<String> {
+    None
+}
+
+fn node_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    }
+}
+
+/// Node.js's name for the architecture, as in the bundle's platform name
+fn node_arch(machine: &str) -> &str {
+    match machine {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        machine => machine,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatible() {
+        assert!(compatible("x86_64", "x86_64"));
+        assert!(!compatible("x86_64", "aarch64"));
+        assert!(compatible("aarch64", "aarch64"));
+        assert!(compatible("aarch64", "arm64"));
+        assert!(!compatible("aarch64", "x86_64"));
+        assert!(compatible("arm", "armv7l"));
+        assert!(compatible("arm", "aarch64"));
+        assert!(!compatible("arm", "x86_64"));
+        // Architectures without a check are trusted
+        assert!(compatible("riscv64", "x86_64"));
+    }
+
+    #[test]
+    fn test_node_arch() {
+        assert_eq!(node_arch("x86_64"), "x64");
+        assert_eq!(node_arch("aarch64"), "arm64");
+        assert_eq!(node_arch("armv7l"), "armv7l");
+    }
+
+    #[test]
+    fn test_elf_machine() {
+        let header = |data: u8, machine: [u8; 2]| {
+            let mut header = [0u8; 20];
+            header[..4].copy_from_slice(b"\x7fELF");
+            header[5] = data;
+            header[18..20].copy_from_slice(&machine);
+            header
+        };
+        assert_eq!(elf_machine(&header(1, [62, 0])), Some("x86_64"));
+        assert_eq!(elf_machine(&header(1, [183, 0])), Some("aarch64"));
+        assert_eq!(elf_machine(&header(1, [40, 0])), Some("armv7l"));
+        assert_eq!(elf_machine(&header(2, [0, 62])), Some("x86_64"));
+        assert_eq!(elf_machine(&header(1, [0xff, 0xff])), None);
+        assert_eq!(elf_machine(b"#!/bin/sh\nexec init\n"), None);
+        assert_eq!(elf_machine(&header(1, [62, 0])[..10]), None);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::config::CacheScope;
+use anyhow::Result;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// Prefix reserved for flags consumed by the runner itself
+pub const RUNNER_FLAG_PREFIX: &str = "--banderole-";
+
+/// Arguments understood by the runner, plus everything that is forwarded to the app
+#[derive(Debug, Default)]
+pub struct RunnerArgs {
+    /// Print runner diagnostics to stderr
+    pub verbose: bool,
+    /// Append runner diagnostics to this file instead of stderr
+    pub log_file: Option<PathBuf>,
+    /// Extract the bundle into the cache and exit without launching the app
+    pub extract_only: bool,
+    /// Override the cache directory the bundle is extracted into
+    pub cache_dir: Option<PathBuf>,
+    /// Override the bundle's `cacheScope`
+    pub cache_scope: Option<CacheScope>,
+    /// Launch this script (relative to the extracted app) instead of the configured entry
+    pub entry: Option<PathBuf>,
+    /// Open a Node REPL inside the extracted app instead of running it
+    pub shell: bool,
+    /// Run the bundled npm in the extracted app with all following arguments
+    pub npm: bool,
+    /// Compare the extracted files with the embedded payload and exit
+    pub verify: bool,
+    /// Check every extracted file against the bundle before launching, refusing to run if any changed
+    pub verify_on_start: bool,
+    /// Print the embedded bundle manifest as JSON and exit, without extracting
+    pub manifest: bool,
+    /// Print the app's completion script for this shell (via its own completion command)
+    pub completions: Option<String>,
+    /// Install the app's completion script for this shell into the user's completion directory
+    pub install_completions: Option<String>,
+    /// Accept the embedded notice without prompting
+    pub accept_notice: bool,
+    /// Register the bundle's service with the platform's service manager and exit
+    pub install_service: bool,
+    /// Remove the service registered by `install_service` and exit
+    pub uninstall_service: bool,
+    /// Remove this bundle's extraction from the cache and exit
+    pub clean: bool,
+    /// Remove every banderole extraction and shared runtime from the cache and exit
+    pub clean_all: bool,
+    /// Print the runner flag reference and exit
+    pub help: bool,
+    /// Arguments forwarded untouched to the Node application
+    pub app_args: Vec<OsString>,
+}
+
+const RUNNER_HELP: &str = "\
+Runner flags (consumed by the executable, never forwarded to the app):
+  --banderole-verbose              Print runner diagnostics to stderr (also BANDEROLE_DEBUG=1)
+  --banderole-log-file <PATH>      Append runner diagnostics to PATH instead (also BANDEROLE_LOG_FILE)
+  --banderole-extract-only         Extract the bundle into the cache and exit
+  --banderole-cache-dir <DIR>      Use DIR instead of the default cache directory
+  --banderole-cache-scope <SCOPE>  Extract into the per-user (user) or machine-wide (system) cache
+  --banderole-entry <PATH>         Run PATH (relative to the bundled app) instead of the main entry
+  --banderole-shell                Open a Node REPL in the extracted app (app args go to node)
+  --banderole-npm <ARGS>...        Run the bundled npm in the extracted app; every later argument goes to npm
+  --banderole-verify               Check the extracted files against the bundle and exit (2 if modified)
+  --banderole-verify-on-start      Check the extracted files before launching; refuse to run if modified
+  --banderole-manifest             Print the bundle manifest (files, hashes, packages) as JSON and exit
+  --banderole-completions <SHELL>  Print the app's shell completion script (bash, zsh, fish, ...)
+  --banderole-install-completions <SHELL>
+                                   Install the app's completion script for bash, zsh or fish
+  --banderole-accept-notice        Accept the bundled notice/EULA without prompting
+  --banderole-install-service      Register the bundled service (systemd, launchd or a scheduled task) and start it
+  --banderole-uninstall-service    Stop and remove the registered service
+  --banderole-clean                Remove this app's extracted files from the cache and exit
+  --banderole-clean-all            Remove every banderole app and shared runtime from the cache and exit
+  --banderole-help                 Show this help and exit
+
+Runner flags are recognized up to the first `--`. The `--` and everything after it
+are forwarded to the application unchanged, so `-- --banderole-verbose` reaches the app.";
+
+pub fn print_runner_help() {
+    println!("{RUNNER_HELP}");
+}
+
+impl RunnerArgs {
+    /// Split the process arguments (without argv[0]) into runner flags and app arguments.
+    ///
+    /// `--banderole-*` flags are consumed wherever they appear before the first `--`.
+    /// The `--` itself and all following arguments are forwarded verbatim.
+    pub fn parse<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = OsString>,
+    {
+        let mut parsed = RunnerArgs::default();
+        let mut iter = args.into_iter();
+        let mut passthrough = false;
+
+        while let Some(arg) = iter.next() {
+            if passthrough {
+                parsed.app_args.push(arg);
+                continue;
+            }
+            if arg == "--" {
+                passthrough = true;
+                parsed.app_args.push(arg);
+                continue;
+            }
+
+            let Some((name, inline_value)) = split_runner_flag(&arg) else {
+                parsed.app_args.push(arg);
+                continue;
+            };
+
+            match name.as_str() {
+                "verbose" => parsed.verbose = true,
+                "extract-only" => parsed.extract_only = true,
+                "shell" => parsed.shell = true,
+                "npm" => {
+                    // Like `npm` itself: the rest of the command line belongs to it
+                    parsed.npm = true;
+                    passthrough = true;
+                }
+                "verify" => parsed.verify = true,
+                "verify-on-start" => parsed.verify_on_start = true,
+                "manifest" => parsed.manifest = true,
+                "accept-notice" => parsed.accept_notice = true,
+                "install-service" => parsed.install_service = true,
+                "uninstall-service" => parsed.uninstall_service = true,
+                "clean" => parsed.clean = true,
+                "clean-all" => parsed.clean_all = true,
+                "help" => parsed.help = true,
+                "entry" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.entry = Some(PathBuf::from(value));
+                }
+                "log-file" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.log_file = Some(PathBuf::from(value));
+                }
+                "cache-dir" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.cache_dir = Some(PathBuf::from(value));
+                }
+                "cache-scope" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.cache_scope = Some(match value.to_str() {
+                        Some("user") => CacheScope::User,
+                        Some("system") => CacheScope::System,
+                        _ => anyhow::bail!(
+                            "Runner flag '{RUNNER_FLAG_PREFIX}cache-scope' expects 'user' or 'system', got '{}'",
+                            value.to_string_lossy()
+                        ),
+                    });
+                }
+                "completions" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.completions = Some(value.to_string_lossy().into_owned());
+                }
+                "install-completions" => {
+                    let value = take_value(&name, inline_value, &mut iter)?;
+                    parsed.install_completions = Some(value.to_string_lossy().into_owned());
+                }
+                _ => anyhow::bail!(
+                    "Unknown runner flag '{RUNNER_FLAG_PREFIX}{name}'. Run with {RUNNER_FLAG_PREFIX}help to list supported flags, or pass it after `--` to forward it to the app."
+                ),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Returns the flag name (without prefix) and an optional `=value` part for `--banderole-*` args
+fn split_runner_flag(arg: &OsStr) -> Option<(String, Option<OsString>)> {
+    let text = arg.to_str()?;
+    let rest = text.strip_prefix(RUNNER_FLAG_PREFIX)?;
+    match rest.split_once('=') {
+        Some((name, value)) => Some((name.to_string(), Some(OsString::from(value)))),
+        None => Some((rest.to_string(), None)),
+    }
+}
+
+fn take_value<I>(name: &str, inline_value: Option<OsString>, iter: &mut I) -> Result<OsString>
+where
+    I: Iterator<Item = OsString>,
+{
+    if let Some(value) = inline_value {
+        return Ok(value);
+    }
+    iter.next()
+        .ok_or_else(|| anyhow::anyhow!("Runner flag '{RUNNER_FLAG_PREFIX}{name}' requires a value"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<RunnerArgs> {
+        RunnerArgs::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn test_runner_flags_are_consumed_and_the_rest_forwarded() {
+        let parsed = parse(&["serve", "--banderole-verbose", "--port", "80", "--banderole-cache-dir", "/tmp/c", "--banderole-entry=bin/x.js"]).unwrap();
+        assert!(parsed.verbose);
+        assert_eq!(parsed.cache_dir, Some(PathBuf::from("/tmp/c")));
+        assert_eq!(parsed.entry, Some(PathBuf::from("bin/x.js")));
+        assert_eq!(parsed.app_args, ["serve", "--port", "80"]);
+    }
+
+    #[test]
+    fn test_everything_after_double_dash_is_forwarded() {
+        let parsed = parse(&["--banderole-shell", "--", "--banderole-verbose", "-e", "1"]).unwrap();
+        assert!(parsed.shell);
+        assert!(!parsed.verbose);
+        assert_eq!(parsed.app_args, ["--", "--banderole-verbose", "-e", "1"]);
+
+        let parsed = parse(&["--banderole-npm", "install", "--banderole-clean"]).unwrap();
+        assert!(parsed.npm);
+        assert!(!parsed.clean);
+        assert_eq!(parsed.app_args, ["install", "--banderole-clean"]);
+    }
+
+    #[test]
+    fn test_invalid_runner_flags_are_rejected() {
+        assert!(parse(&["--banderole-nope"]).unwrap_err().to_string().contains("Unknown runner flag"));
+        assert!(parse(&["--banderole-cache-dir"]).unwrap_err().to_string().contains("requires a value"));
+        assert!(parse(&["--banderole-cache-scope", "global"]).is_err());
+        assert!(matches!(parse(&["--banderole-cache-scope=system"]).unwrap().cache_scope, Some(CacheScope::System)));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::config::RuntimeConfig;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Largest `.bin` entry treated as a flattened symlink (older bundlers stored link targets as contents)
+const MAX_LINK_TARGET_LEN: u64 = 1024;
+
+/// Make every `node_modules/.bin` entry directly spawnable after extraction.
+///
+/// On Unix, flattened symlinks become real symlinks again and scripts get the executable bit.
+/// On Windows, a `<tool>.cmd` wrapper running the bundled node is generated next to each entry.
+pub fn prepare(app_dir: &Path, config: &RuntimeConfig) -> Result<()> {
+    let app_dir = &crate::long_path::extended(app_dir);
+    let app_root = app_dir.join("app");
+    let bin_dirs: Vec<PathBuf> = walkdir::WalkDir::new(&app_root)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_dir()
+                && entry.file_name() == ".bin"
+                && entry
+                    .path()
+                    .parent()
+                    .and_then(|parent| parent.file_name())
+                    .is_some_and(|name| name == "node_modules")
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+
+    for bin_dir in bin_dirs {
+        for entry in fs::read_dir(&bin_dir)
+            .with_context(|| format!("Failed to read {}", bin_dir.display()))?
+        {
+            let path = entry?.path();
+            if !is_shim_candidate(&path) {
+                continue;
+            }
+            let target = flattened_link_target(&bin_dir, &path);
+            if let Err(e) = port_entry(app_dir, config, &bin_dir, &path, target.as_deref()) {
+                verbose!("Could not prepare {}: {e:#}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generated Windows wrappers are recognised by verification as part of the extraction
+pub fn is_generated_wrapper(relative: &Path) -> bool {
+    cfg!(windows)
+        && relative.extension().is_some_and(|ext| ext == "cmd")
+        && relative
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .is_some_and(|name| name == ".bin")
+}
+
+fn is_shim_candidate(path: &Path) -> bool {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata.is_file() && path.extension().is_none(),
+        Err(_) => false,
+    }
+}
+
+/// The relative target if `path` holds a flattened symlink pointing at an existing file
+fn flattened_link_target(bin_dir: &Path, path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > MAX_LINK_TARGET_LEN {
+        return None;
+    }
+    let content = fs::read_to_string(path).ok()?;
+    if content.is_empty() || content.starts_with("#!") || content.contains('\n') {
+        return None;
+    }
+    bin_dir.join(&content).is_file().then_some(content)
+}
+
+#[cfg(unix)]
+fn port_entry(
+    _app_dir: &Path,
+    _config: &RuntimeConfig,
+    _bin_dir: &Path,
+    path: &Path,
+    target: Option<&str>,
+) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = match target {
+        Some(target) => {
+            fs::remove_file(path)?;
+            std::os::unix::fs::symlink(target, path)?;
+            path.parent().unwrap_or(path).join(target)
+        }
+        None => path.to_path_buf(),
+    };
+    let mut permissions = fs::metadata(&script)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    fs::set_permissions(&script, permissions)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn port_entry(
+    app_dir: &Path,
+    config: &RuntimeConfig,
+    bin_dir: &Path,
+    path: &Path,
+    target: Option<&str>,
+) -> Result<()> {
+    let wrapper = path.with_extension("cmd");
+    if wrapper.exists() {
+        return Ok(());
+    }
+    let script = match target {
+        Some(target) => target.replace('/', "\\"),
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    // Relative to %~dp0 so the wrapper keeps working if the extraction directory moves
+    let depth = bin_dir.strip_prefix(app_dir).map(|rel| rel.components().count()).unwrap_or(0);
+    let node = crate::get_runtime_executable_path(app_dir, config);
+    let node_relative = node.strip_prefix(app_dir).unwrap_or(&node);
+    let node_path = format!("{}{}", "..\\".repeat(depth), node_relative.display());
+    // Electron only behaves like node for tools when asked to
+    let run_as_node = if config.runtime == crate::config::Runtime::Electron {
+        "SET ELECTRON_RUN_AS_NODE=1\r\n"
+    } else {
+        ""
+    };
+    let runtime_args: String = config
+        .runtime
+        .script_args()
+        .iter()
+        .map(|arg| format!("{arg} "))
+        .collect();
+    fs::write(
+        &wrapper,
+        format!("@ECHO off\r\n{run_as_node}\"%~dp0\\{node_path}\" {runtime_args}\"%~dp0\\{script}\" %*\r\n"),
+    )
+    .with_context(|| format!("Failed to write {}", wrapper.display()))?;
+    Ok(())
+}
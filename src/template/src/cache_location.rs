@@ -0,0 +1,321 @@
+use anyhow::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Why a cache directory cannot be extracted into
+enum Unusable {
+    /// No home directory to derive the default location from
+    NoDefault,
+    /// Read-only filesystem, missing permissions, ...
+    NotWritable(PathBuf, std::io::Error),
+    /// The directory is shared, but this build's extraction or lock in it belongs to another user
+    /// (e.g. root ran the app first)
+    OtherUser(PathBuf, std::io::Error),
+    /// Mounted `noexec`: the extracted runtime would fail to start with a bare "Permission denied"
+    NoExec(PathBuf, PathBuf),
+}
+
+impl fmt::Display for Unusable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unusable::NoDefault => write!(f, "Cannot determine a cache directory: there is no home directory"),
+            Unusable::NotWritable(dir, e) => write!(f, "Cannot extract into {}: {e}", dir.display()),
+            Unusable::OtherUser(path, e) => write!(f, "Cannot use {}, which another user created: {e}", path.display()),
+            Unusable::NoExec(dir, mount) => write!(
+                f,
+                "Cannot run the bundled runtime from {}: its filesystem {} is mounted with the `noexec` flag",
+                dir.display(),
+                mount.display()
+            ),
+        }
+    }
+}
+
+/// The cache directory to extract into: `dir`, unless the bundle cannot be run from it.
+///
+/// A directory already holding this build's extraction only has to allow execution, so caches
+/// baked into read-only images keep working; otherwise it must also be writable. The default
+/// location (or `BANDEROLE_CACHE_DIR`) then falls back to a private directory under `/var/tmp`,
+/// the temp directory or, last, memory-backed ones; an explicit `--banderole-cache-dir` is an
+/// error naming the problem instead.
+///
+/// When the directory is shared between users and another user's copy of this build is in the
+/// way, a private `user-<uid>` subdirectory of it is used first, explicit or not.
+pub fn usable_cache_dir(dir: Option<PathBuf>, explicit: bool, extraction: &Path) -> Result<PathBuf> {
+    let problem = match dir {
+        Some(dir) => match check(&dir, extraction) {
+            Ok(()) => return Ok(dir),
+            Err(problem @ Unusable::OtherUser(..)) => {
+                let per_user = dir.join(per_user_name());
+                match private_dir(&per_user).map(|per_user| check(&per_user, extraction).map(|()| per_user)) {
+                    Some(Ok(per_user)) => {
+                        if per_user.join(extraction).join(".ready").exists() {
+                            verbose!("{problem}; using {} instead", per_user.display());
+                        } else {
+                            eprintln!("banderole: {problem}; extracting to {} instead", per_user.display());
+                        }
+                        return Ok(per_user);
+                    }
+                    _ => problem,
+                }
+            }
+            Err(problem) => problem,
+        },
+        None => Unusable::NoDefault,
+    };
+    if !explicit {
+        if let Some(fallback) = fallback_dirs().into_iter().find_map(|candidate| private_dir(&candidate)) {
+            verbose!("{problem}; extracting to {} instead", fallback.display());
+            return Ok(fallback);
+        }
+    }
+    anyhow::bail!(
+        "{problem}. Pass --banderole-cache-dir (or set BANDEROLE_CACHE_DIR) with a writable directory on a filesystem that allows execution."
+    )
+}
+
+fn check(dir: &Path, extraction: &Path) -> Result<(), Unusable> {
+    if !dir.join(extraction).join(".ready").exists() {
+        fs::create_dir_all(dir)
+            .and_then(|()| probe_writable(dir))
+            .map_err(|e| Unusable::NotWritable(dir.to_path_buf(), e))?;
+        // Left behind by another user's unfinished extraction, or its lock
+        let app_dir = dir.join(extraction);
+        if app_dir.is_dir() {
+            probe_writable(&app_dir).map_err(|e| Unusable::OtherUser(app_dir.clone(), e))?;
+        }
+        // The `<name>/<version>` directories of the named layout must take our extraction too
+        for parent in app_dir.ancestors().skip(1).take_while(|parent| *parent != dir) {
+            if parent.is_dir() {
+                probe_writable(parent).map_err(|e| Unusable::OtherUser(parent.to_path_buf(), e))?;
+            }
+        }
+        // Locking needs only read access, so another user's lock is in the way only when unreadable
+        let lock = crate::lock_path(&app_dir);
+        if lock.exists() {
+            fs::File::open(&lock).map_err(|e| Unusable::OtherUser(lock.clone(), e))?;
+        }
+    }
+    if is_noexec(dir) {
+        return Err(Unusable::NoExec(dir.to_path_buf(), mount_point(dir)));
+    }
+    Ok(())
+}
+
+/// Creating a directory succeeds on a read-only filesystem when it already exists
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+/// Whether `error` comes from lacking permissions, e.g. on a directory another user created in a
+/// shared cache
+pub fn is_permission_denied(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied))
+}
+
+/// Subdirectory of a shared cache directory private to the current user
+pub fn per_user_name() -> String {
+    #[cfg(windows)]
+    if let Some(user) = std::env::var_os("USERNAME").filter(|user| !user.is_empty()) {
+        return format!("user-{}", user.to_string_lossy());
+    }
+    format!("user-{}", uid())
+}
+
+/// Writable, executable-capable candidates, disk-backed before memory-backed ones
+fn fallback_dirs() -> Vec<PathBuf> {
+    let name = format!("banderole-{}", uid());
+    let mut dirs = Vec::new();
+    if cfg!(unix) {
+        dirs.push(Path::new("/var/tmp").join(&name));
+    }
+    dirs.push(std::env::temp_dir().join(&name));
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        dirs.push(PathBuf::from(runtime_dir).join("banderole"));
+    }
+    // Immutable containers often leave only shared memory writable
+    if cfg!(target_os = "linux") {
+        dirs.push(Path::new("/dev/shm").join(&name));
+    }
+    dirs.dedup();
+    dirs
+}
+
+/// `dir` when it can be used for extraction: created (or already present) as ours, closed to
+/// other users, writable and executable
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    if let Err(e) = std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return None;
+        }
+    }
+    // Shared temp directories could hold a directory planted by another user
+    let metadata = std::fs::symlink_metadata(dir).ok()?;
+    let private = metadata.is_dir() && metadata.uid() == uid() && metadata.mode() & 0o022 == 0;
+    (private && probe_writable(dir).is_ok() && !is_noexec(dir)).then(|| dir.to_path_buf())
+}
+
+/// The temp directory is per user on Windows
+#[cfg(not(unix))]
+fn private_dir(dir: &Path) -> Option<PathBuf> {
+    fs::create_dir_all(dir).and_then(|()| probe_writable(dir)).ok()?;
+    Some(dir.to_path_buf())
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn uid() -> u32 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn is_noexec(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        libc::statvfs(c_path.as_ptr(), &mut stat) == 0 && stat.f_flag & libc::ST_NOEXEC != 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_noexec(_path: &Path) -> bool {
+    false
+}
+
+/// The topmost ancestor of `path` on the same device, i.e. where its filesystem is mounted
+#[cfg(unix)]
+fn mount_point(path: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+    let path = &std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(device) = std::fs::metadata(path).map(|metadata| metadata.dev()) else {
+        return path.to_path_buf();
+    };
+    path.ancestors()
+        .take_while(|ancestor| std::fs::metadata(ancestor).is_ok_and(|metadata| metadata.dev() == device))
+        .last()
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+#[cfg(not(unix))]
+fn mount_point(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("banderole-cache-location-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_usable_cache_dir_keeps_a_writable_dir() {
+        let dir = scratch("writable");
+        let cache = dir.join("cache");
+        let chosen = usable_cache_dir(Some(cache.clone()), false, Path::new("app-1.0.0")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(chosen, cache);
+    }
+
+    #[test]
+    fn test_usable_cache_dir_falls_back_unless_explicit() {
+        let dir = scratch("blocked");
+        // Nothing, root included, can create a directory below a file
+        fs::write(dir.join("file"), "").unwrap();
+        let blocked = dir.join("file/cache");
+
+        let chosen = usable_cache_dir(Some(blocked.clone()), false, Path::new("app-1.0.0")).unwrap();
+        assert!(fallback_dirs().contains(&chosen), "{} is not a fallback", chosen.display());
+
+        let error = usable_cache_dir(Some(blocked), true, Path::new("app-1.0.0")).unwrap_err();
+        fs::remove_dir_all(&dir).ok();
+        assert!(error.to_string().contains("--banderole-cache-dir"), "{error}");
+    }
+
+    #[test]
+    fn test_check_needs_no_writes_for_a_finished_extraction() {
+        let dir = scratch("finished");
+        let extraction = Path::new("app/1.0.0/abc");
+        fs::create_dir_all(dir.join(extraction)).unwrap();
+        fs::write(dir.join(extraction).join(".ready"), "ready").unwrap();
+        let before: Vec<_> = walk(&dir);
+
+        assert!(check(&dir, extraction).is_ok());
+        // Neither a write probe nor a lock: a read-only image holding the extraction works as is
+        assert_eq!(walk(&dir), before);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_check_accepts_a_read_only_lock() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch("read-only-lock");
+        let extraction = Path::new("app/1.0.0/abc");
+        fs::create_dir_all(dir.join("app/1.0.0")).unwrap();
+        let lock = crate::lock_path(&dir.join(extraction));
+        fs::write(&lock, "").unwrap();
+        fs::set_permissions(&lock, fs::Permissions::from_mode(0o444)).unwrap();
+
+        assert!(check(&dir, extraction).is_ok());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    fn walk(dir: &Path) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path.clone());
+                }
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_private_dir_rejects_directories_others_can_write() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch("private");
+        let fresh = dir.join("fresh");
+        assert_eq!(private_dir(&fresh), Some(fresh.clone()));
+        assert_eq!(fs::metadata(&fresh).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let planted = dir.join("planted");
+        fs::create_dir(&planted).unwrap();
+        fs::set_permissions(&planted, fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(private_dir(&planted), None);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_fallback_dirs_prefer_disk() {
+        let dirs = fallback_dirs();
+        assert_eq!(dirs.first(), Some(&Path::new("/var/tmp").join(format!("banderole-{}", uid()))));
+        if cfg!(target_os = "linux") {
+            assert_eq!(dirs.last(), Some(&Path::new("/dev/shm").join(format!("banderole-{}", uid()))));
+        }
+    }
+}
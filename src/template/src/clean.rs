@@ -0,0 +1,149 @@
+//! `--banderole-clean` removes this bundle's extraction so the next launch unpacks it afresh;
+//! `--banderole-clean-all` also removes every other app's extraction and the shared runtimes,
+//! dependencies and ICU data in the same cache directory and in the user's private `user-<uid>`
+//! subdirectory of it. Only what banderole created is touched, so a custom cache directory holding
+//! other files is safe, and acknowledged notices are kept.
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use std::fs;
+use std::path::Path;
+
+/// Shared directories below the cache, each rebuilt on demand
+const SHARED_DIRS: &[&str] = &["runtimes", "deps", "icu"];
+
+/// Remove the extraction `app_dir` of the running bundle, waiting for an extraction in progress
+pub fn app(cache_dir: &Path, app_dir: &Path) -> Result<()> {
+    let lock_path = crate::lock_path(app_dir);
+    let lock = lock_path
+        .exists()
+        .then(|| fs::OpenOptions::new().write(true).open(&lock_path))
+        .transpose()
+        .with_context(|| format!("Failed to open lock file at {}", lock_path.display()))?;
+    if let Some(lock) = &lock {
+        lock.lock_exclusive().context("Failed to acquire extraction lock")?;
+    }
+    crate::remove_staging_leftovers(app_dir);
+    let removed = remove(app_dir)?;
+    drop(lock);
+    fs::remove_file(&lock_path).ok();
+    // Drop the `<name>/<version>` directories of the named layout once they are empty
+    for parent in app_dir.ancestors().skip(1).take_while(|parent| *parent != cache_dir) {
+        if fs::remove_dir(parent).is_err() {
+            break;
+        }
+    }
+    if removed {
+        eprintln!("banderole: removed {}", app_dir.display());
+    } else {
+        eprintln!("banderole: nothing to clean in {}", cache_dir.display());
+    }
+    Ok(())
+}
+
+/// Remove every extraction and shared directory in `cache_dir`, and in this user's private
+/// subdirectory of it
+pub fn all(cache_dir: &Path) -> Result<()> {
+    if !cache_dir.is_dir() {
+        eprintln!("banderole: nothing to clean in {}", cache_dir.display());
+        return Ok(());
+    }
+    let removed = clean_cache(cache_dir)?;
+    eprintln!("banderole: removed {removed} entries from {}", cache_dir.display());
+    Ok(())
+}
+
+fn clean_cache(cache_dir: &Path) -> Result<usize> {
+    // Extractions of the named layout sit two levels down, below `<name>/<version>`
+    let mut removed = clean_dir(cache_dir, 2)?;
+    let per_user = cache_dir.join(crate::cache_location::per_user_name());
+    if per_user.is_dir() {
+        removed += clean_cache(&per_user)?;
+        fs::remove_dir(&per_user).ok();
+    }
+    Ok(removed)
+}
+
+/// Remove the extractions in `dir` with their locks, looking `depth` directory levels further
+/// down; directories emptied that way are dropped too
+fn clean_dir(dir: &Path, depth: usize) -> Result<usize> {
+    let top_level = depth == 2;
+    let mut removed = 0;
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Files are left alone: locks go with their extraction. Other users' private
+        // subdirectories are theirs to clean.
+        if !path.is_dir() || (top_level && name.starts_with("user-")) {
+            continue;
+        }
+        let extraction = crate::health::is_extraction(&path);
+        let ours = (top_level && SHARED_DIRS.contains(&name.as_str()))
+            || name.contains(".tmp-")
+            || name.contains(".old-")
+            || extraction;
+        if ours {
+            verbose!("Removing {}", path.display());
+            removed += usize::from(remove(&path)?);
+            if extraction {
+                removed += usize::from(remove(&crate::lock_path(&path))?);
+            }
+        } else if depth > 0 {
+            removed += clean_dir(&path, depth - 1)?;
+            fs::remove_dir(&path).ok();
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove a file or directory, returning whether it existed
+fn remove(path: &Path) -> Result<bool> {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return Ok(false);
+    };
+    let extended = crate::long_path::extended(path);
+    if meta.is_dir() {
+        fs::remove_dir_all(&extended)
+    } else {
+        fs::remove_file(&extended)
+    }
+    .with_context(|| format!("Failed to remove {}; close any running instance and try again", path.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_removes_only_extractions_and_their_locks() {
+        let cache = std::env::temp_dir().join(format!("banderole-clean-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache);
+        let extraction = |dir: &Path| {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join(".ready"), "ready").unwrap();
+            fs::write(crate::lock_path(dir), "").unwrap();
+        };
+        extraction(&cache.join("server/1.0.0/abc"));
+        extraction(&cache.join("flat-build-id"));
+        extraction(&cache.join(crate::cache_location::per_user_name()).join("server/1.0.0/abc"));
+        fs::create_dir_all(cache.join("deps/node/1234")).unwrap();
+        // Not banderole's
+        fs::create_dir_all(cache.join("notes")).unwrap();
+        fs::write(cache.join("notes/todo.lock"), "").unwrap();
+        fs::write(cache.join("keep.lock"), "").unwrap();
+        fs::create_dir_all(cache.join("user-someone-else/server/1.0.0/abc")).unwrap();
+        fs::write(cache.join("user-someone-else/server/1.0.0/abc/.ready"), "").unwrap();
+
+        all(&cache).unwrap();
+        let mut left: Vec<String> = fs::read_dir(&cache)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["keep.lock", "notes", "user-someone-else"]);
+        assert!(cache.join("notes/todo.lock").exists());
+        assert!(cache.join("user-someone-else/server/1.0.0/abc/.ready").exists());
+        fs::remove_dir_all(&cache).ok();
+    }
+}
@@ -0,0 +1,88 @@
+//! Shell completions for the bundled app: scripts embedded at bundle time (`completionScripts`)
+//! or printed by the app's own generator (`completions`), and `--banderole-install-completions`,
+//! which writes them where the shell picks them up for the current user.
+
+use crate::config::RuntimeConfig;
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The embedded completion script for `shell`, if the bundle carries one
+pub fn embedded<'a>(config: &'a RuntimeConfig, shell: &str) -> Option<&'a str> {
+    config.completion_scripts.get(shell).map(String::as_str)
+}
+
+/// Install the completion script for `shell` into the user's completion directory
+pub fn install(config: &RuntimeConfig, shell: &str, cache_dir: Option<&Path>) -> Result<()> {
+    let executable = env::current_exe().context("Failed to locate the running executable")?;
+    let command = executable
+        .file_stem()
+        .context("The running executable has no file name")?
+        .to_string_lossy()
+        .into_owned();
+    let script = match embedded(config, shell) {
+        Some(script) => script.to_string(),
+        None => generate(&executable, shell, cache_dir)?,
+    };
+
+    let home = || -> Result<PathBuf> {
+        BaseDirs::new()
+            .map(|dirs| dirs.home_dir().to_path_buf())
+            .context("Cannot determine the home directory")
+    };
+    let xdg = |var: &str, fallback: &str| -> Result<PathBuf> {
+        match env::var_os(var).filter(|dir| !dir.is_empty()) {
+            Some(dir) => Ok(PathBuf::from(dir)),
+            None => Ok(home()?.join(fallback)),
+        }
+    };
+    let (path, hint) = match shell {
+        // Loaded on demand by bash-completion 2
+        "bash" => (
+            xdg("XDG_DATA_HOME", ".local/share")?.join("bash-completion").join("completions").join(&command),
+            None,
+        ),
+        "zsh" => (
+            home()?.join(".zfunc").join(format!("_{command}")),
+            Some("Make sure ~/.zshrc has `fpath+=~/.zfunc` before `autoload -Uz compinit && compinit`"),
+        ),
+        "fish" => (
+            xdg("XDG_CONFIG_HOME", ".config")?.join("fish").join("completions").join(format!("{command}.fish")),
+            None,
+        ),
+        _ => bail!(
+            "Installing {shell} completions is not supported; add the output of --banderole-completions {shell} to your shell profile instead"
+        ),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+    eprintln!("Installed {shell} completions for {command} to {}", path.display());
+    if let Some(hint) = hint {
+        eprintln!("{hint}");
+    }
+    eprintln!("Open a new shell to use them");
+    Ok(())
+}
+
+/// Run this executable with `--banderole-completions` and capture the script the app prints
+fn generate(executable: &Path, shell: &str, cache_dir: Option<&Path>) -> Result<String> {
+    let mut cmd = Command::new(executable);
+    cmd.arg("--banderole-completions").arg(shell);
+    if let Some(dir) = cache_dir {
+        cmd.arg("--banderole-cache-dir").arg(dir);
+    }
+    let output = cmd
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .context("Failed to run the app's completion command")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!("The app's completion command did not print a {shell} completion script ({})", output.status);
+    }
+    String::from_utf8(output.stdout).context("The app's completion script is not UTF-8")
+}
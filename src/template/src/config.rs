@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Bundle-time settings embedded by banderole (mirrors `RuntimeConfig` in the CLI crate)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    pub app_name: String,
+    pub app_version: String,
+    /// Platform the bundle was built for, e.g. `linux-x64`
+    pub platform: String,
+    pub runtime: Runtime,
+    /// ICU variant of the bundled node
+    pub node_build: NodeBuild,
+    /// Download full ICU data for the small-icu node once the app needs it
+    pub lazy_icu: bool,
+    /// Download URL template for the ICU data zip (`{version}`, `{major}`, `{minor}`)
+    pub icu_data_url: Option<String>,
+    /// Entry script resolved at bundle time, relative to the extracted app
+    pub main_script: Option<String>,
+    pub notice: Option<Notice>,
+    pub entrypoints: Vec<Entrypoint>,
+    pub default_entrypoint: Option<String>,
+    /// `--banderole-entry` was disabled at bundle time
+    pub entry_override_disabled: bool,
+    /// `--banderole-shell` was disabled at bundle time
+    pub shell_disabled: bool,
+    /// Preload a script that keeps `process.execPath` on the bundled node
+    pub exec_path_shim: bool,
+    /// Start node with Yarn's Plug'n'Play runtime from the app directory
+    pub pnp: bool,
+    /// Environment variables set for the app unless the caller already defines them
+    pub env: BTreeMap<String, String>,
+    /// npm's entry script relative to the runtime directory, for `--banderole-npm`
+    pub npm_cli: Option<String>,
+    /// Start the app without the caller's Node.js variables (`app_env::ISOLATED`)
+    pub isolate_env: bool,
+    /// Arguments passed to node before the entry script
+    pub node_args: Vec<String>,
+    /// Patterns (relative to the app) the app may modify without failing verification
+    pub mutable_paths: Vec<String>,
+    /// Hash the extraction against the manifest before every launch and refuse to run on changes
+    pub verify_on_start: bool,
+    /// App arguments that print its shell completion script, `{shell}` substituted
+    pub completion_args: Vec<String>,
+    /// Completion scripts embedded at bundle time, by shell
+    pub completion_scripts: BTreeMap<String, String>,
+    /// Content hash of the runtime directory, shared between apps embedding the same runtime
+    pub runtime_digest: Option<String>,
+    /// Content hash of the dependency layer, shared between apps embedding the same dependencies
+    pub deps_digest: Option<String>,
+    /// Runtime not embedded in the payload, downloaded into the shared runtimes on first use
+    pub external_runtime: Option<ExternalRuntime>,
+    /// JSON Lines file the startup timings of every launch are appended to (`~/` expanded)
+    pub metrics_file: Option<String>,
+    /// Shell command run on every launch with the timings in `BANDEROLE_METRICS`
+    pub metrics_command: Option<String>,
+    /// Shell command run from `app/` after extraction; the extraction is only ready once it succeeds
+    pub post_extract: Option<String>,
+    /// Cache the runner extracts into unless `--banderole-cache-scope` or `--banderole-cache-dir` say otherwise
+    pub cache_scope: CacheScope,
+    /// `<name>/<version>` the build ID directory is placed in below the cache, if any
+    pub extraction_dir: Option<String>,
+    /// Key file for an encrypted payload (`~/` expanded)
+    pub encryption_key_file: Option<String>,
+    /// Service registered by `--banderole-install-service`
+    pub service: Option<Service>,
+}
+
+/// Runtime bundled next to the app
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    /// `node/` holds a Node.js distribution
+    #[default]
+    Node,
+    /// `electron/` holds an Electron distribution; the app directory is passed to it
+    Electron,
+    /// `bun/` holds the Bun executable
+    Bun,
+    /// `deno/` holds the Deno executable; scripts run with all permissions granted
+    Deno,
+}
+
+impl Runtime {
+    /// Directory holding the runtime inside an extraction
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Runtime::Node => "node",
+            Runtime::Electron => "electron",
+            Runtime::Bun => "bun",
+            Runtime::Deno => "deno",
+        }
+    }
+
+    /// Arguments placed before the entry script
+    pub fn script_args(self) -> &'static [&'static str] {
+        match self {
+            Runtime::Deno => &["run", "-A"],
+            _ => &[],
+        }
+    }
+
+    /// Arguments that start the interactive REPL for `--banderole-shell`
+    pub fn repl_args(self) -> &'static [&'static str] {
+        match self {
+            Runtime::Bun => &["repl"],
+            Runtime::Deno => &["repl", "-A"],
+            _ => &[],
+        }
+    }
+}
+
+/// Whose cache the bundle is extracted into
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheScope {
+    /// The user's cache directory
+    #[default]
+    User,
+    /// A machine-wide directory, written by administrators and read by everyone
+    System,
+}
+
+/// ICU configuration or flavor the bundled node was built with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeBuild {
+    #[default]
+    Full,
+    SmallIcu,
+    WithoutIntl,
+    PointerCompression,
+    Debug,
+}
+
+/// Release archive of a runtime left out of the payload
+#[derive(Debug, Deserialize)]
+pub struct ExternalRuntime {
+    pub url: String,
+    /// Also the runtime digest the extraction is shared under
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Entrypoint {
+    pub name: String,
+    /// Script path relative to the extracted `app/` directory
+    pub script: String,
+    pub description: Option<String>,
+}
+
+/// Long-running service definition embedded at bundle time
+#[derive(Debug, Deserialize)]
+pub struct Service {
+    /// Unit, launchd label or scheduled task name
+    pub name: String,
+    pub description: String,
+    /// App arguments the service runs with
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Account the system service runs as
+    pub user: Option<String>,
+    #[serde(default)]
+    pub restart: ServiceRestart,
+}
+
+/// When the service manager restarts the app
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceRestart {
+    Always,
+    #[default]
+    OnFailure,
+    No,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Notice {
+    pub text: String,
+    #[serde(default)]
+    pub require_acceptance: bool,
+}
+
+impl RuntimeConfig {
+    /// Parse the configuration embedded at build time
+    pub fn embedded() -> Result<Self> {
+        serde_json::from_str(crate::RUNTIME_CONFIG).context("Failed to parse embedded runtime config")
+    }
+}
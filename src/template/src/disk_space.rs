@@ -0,0 +1,27 @@
+//! Free-space preflight, so a full disk is reported before extraction starts rather than as an
+//! I/O error halfway through it.
+
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// Fail with how much space extracting into `dir` needs and how much is left
+pub fn ensure_available(dir: &Path, required: u64) -> Result<()> {
+    // Unknown (unsupported filesystem, ...): let extraction find out
+    let Ok(available) = fs2::available_space(dir) else {
+        return Ok(());
+    };
+    verbose!("Extraction needs {}, {} available", megabytes(required), megabytes(available));
+    if available < required {
+        bail!(
+            "Not enough disk space to extract into {}: need {}, have {}. Free up space or pass --banderole-cache-dir with a directory on another disk.",
+            dir.display(),
+            megabytes(required),
+            megabytes(available)
+        );
+    }
+    Ok(())
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{} MB", bytes.div_ceil(1_000_000))
+}
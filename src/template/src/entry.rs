@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use crate::args::RunnerArgs;
+use crate::config::RuntimeConfig;
+use crate::exit_code;
+use crate::package_entry;
+
+/// Script to launch, chosen from the configured entrypoints or package.json
+pub enum EntrySelection {
+    /// Launch `script` (relative to the app directory) with the remaining arguments
+    Script(String),
+    /// Start the Node REPL (`--banderole-shell`); remaining arguments go to node itself
+    Shell,
+    /// Run the bundled npm (`--banderole-npm`) with the remaining arguments
+    Npm(String),
+    /// The generated command help was printed; exit with the given code
+    Help(i32),
+}
+
+/// Pick the entry script for this invocation, consuming the subcommand argument when the bundle
+/// declares multiple entrypoints. `--banderole-shell` and `--banderole-entry` override both.
+///
+/// `--banderole-completions` replaces the arguments with the app's configured completion command.
+pub fn select(
+    config: &RuntimeConfig,
+    app_path: &Path,
+    runner_args: &RunnerArgs,
+    args: &mut Vec<OsString>,
+) -> Result<EntrySelection> {
+    if let Some(shell) = &runner_args.completions {
+        anyhow::ensure!(
+            !config.completion_args.is_empty(),
+            "This executable has no {shell} completions (set \"completions\" or \"completionScripts\" in the banderole configuration)"
+        );
+        anyhow::ensure!(
+            !runner_args.shell,
+            "--banderole-completions cannot be combined with --banderole-shell"
+        );
+        *args = config
+            .completion_args
+            .iter()
+            .map(|arg| OsString::from(arg.replace("{shell}", shell)))
+            .collect();
+    }
+    if runner_args.npm {
+        let cli = config.npm_cli.as_ref().context(
+            "This executable was bundled without npm; rebundle it with --include-npm to use --banderole-npm",
+        )?;
+        return Ok(EntrySelection::Npm(cli.clone()));
+    }
+    if runner_args.shell {
+        anyhow::ensure!(!config.shell_disabled, "--banderole-shell is disabled for this executable");
+        if args.first().is_some_and(|a| a == "--") {
+            args.remove(0);
+        }
+        return Ok(EntrySelection::Shell);
+    }
+    if let Some(path) = &runner_args.entry {
+        return override_script(config, app_path, path).map(EntrySelection::Script);
+    }
+
+    if config.entrypoints.is_empty() {
+        if let Some(script) = &config.main_script {
+            return Ok(EntrySelection::Script(script.clone()));
+        }
+        return Ok(EntrySelection::Script(find_main_script(app_path)?));
+    }
+
+    let first = args.first().and_then(|a| a.to_str()).map(str::to_string);
+    if let Some(first) = &first {
+        if let Some(entry) = config.entrypoints.iter().find(|e| &e.name == first) {
+            args.remove(0);
+            return Ok(EntrySelection::Script(entry.script.clone()));
+        }
+    }
+
+    let asked_for_help = matches!(first.as_deref(), Some("--help" | "-h" | "help"));
+    if !asked_for_help {
+        if let Some(default) = &config.default_entrypoint {
+            if let Some(entry) = config.entrypoints.iter().find(|e| &e.name == default) {
+                return Ok(EntrySelection::Script(entry.script.clone()));
+            }
+        }
+    }
+
+    print_help(config, if asked_for_help { None } else { first.as_deref() });
+    Ok(EntrySelection::Help(if asked_for_help { exit_code::SUCCESS } else { exit_code::USAGE }))
+}
+
+/// Validate a `--banderole-entry` path: enabled for this bundle and inside the extracted app
+fn override_script(config: &RuntimeConfig, app_path: &Path, path: &Path) -> Result<String> {
+    anyhow::ensure!(
+        !config.entry_override_disabled,
+        "--banderole-entry is disabled for this executable"
+    );
+    let app_root = app_path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve app directory {}", app_path.display()))?;
+    let script = app_root
+        .join(path)
+        .canonicalize()
+        .with_context(|| format!("Entry script '{}' not found in the bundle", path.display()))?;
+    anyhow::ensure!(
+        script.starts_with(&app_root) && script.is_file(),
+        "Entry script '{}' must be a file inside the bundled app",
+        path.display()
+    );
+    Ok(script.to_string_lossy().to_string())
+}
+
+fn print_help(config: &RuntimeConfig, unknown_command: Option<&str>) {
+    let exe_name = std::env::args_os()
+        .next()
+        .and_then(|p| Path::new(&p).file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| config.app_name.clone());
+
+    if let Some(command) = unknown_command {
+        eprintln!("Unknown command '{command}'\n");
+    }
+    let mut out = format!("Usage: {exe_name} <command> [args...]\n\nCommands:\n");
+    let width = config.entrypoints.iter().map(|e| e.name.len()).max().unwrap_or(0);
+    for entry in &config.entrypoints {
+        let description = entry.description.as_deref().unwrap_or(&entry.script);
+        let marker = if config.default_entrypoint.as_deref() == Some(entry.name.as_str()) {
+            " (default)"
+        } else {
+            ""
+        };
+        out.push_str(&format!("  {:width$}  {description}{marker}\n", entry.name));
+    }
+    if unknown_command.is_some() {
+        eprint!("{out}");
+    } else {
+        print!("{out}");
+    }
+}
+
+/// Resolve the entry the way the bundler does; used for bundles without an embedded `main_script`
+fn find_main_script(app_path: &Path) -> Result<String> {
+    let package_json_path = app_path.join("package.json");
+    let package_json = if package_json_path.exists() {
+        let package_content = fs::read_to_string(&package_json_path)
+            .context("Failed to read package.json")?;
+        serde_json::from_str(&package_content).unwrap_or_default()
+    } else {
+        serde_json::Value::Null
+    };
+
+    // Default to index.js
+    Ok(package_entry::resolve(app_path, &package_json).unwrap_or_else(|| "index.js".to_string()))
+}
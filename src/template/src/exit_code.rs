@@ -0,0 +1,17 @@
+//! Exit codes of `banderole` and of the runner's own modes (`--banderole-verify`, ...), kept
+//! stable so wrapper scripts can branch on them without parsing output.
+//!
+//! Shared by the bundler (compiled via `#[path]`) and the runner. When the runner launches the
+//! app, the app's own exit code is passed through instead. Only `banderole` reports files that
+//! are not bundles (4), so that code lives with the bundler.
+
+/// Everything checked out
+pub const SUCCESS: i32 = 0;
+/// Unexpected failure: I/O errors, failed downloads, build errors
+pub const FAILURE: i32 = 1;
+/// Files differ from what was bundled, or a checksum did not match
+pub const INTEGRITY: i32 = 2;
+/// The host OS or CPU architecture is not supported
+pub const UNSUPPORTED_PLATFORM: i32 = 3;
+/// Invalid command-line arguments (`EX_USAGE`)
+pub const USAGE: i32 = 64;
@@ -0,0 +1,114 @@
+//! Glob matching for the gitignore-style patterns of the bundle configuration (`mutablePaths`)
+//! and of package files (`files`, `.npmignore`).
+//!
+//! Shared by the bundler (compiled via `#[path]`) and the runner, so both agree on what a
+//! pattern covers.
+
+/// Match `text` against a glob: `*` and `?` stop at `/`, `**` crosses directories and
+/// `[...]` is a character class
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_chars(&pattern, &text)
+}
+
+fn match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            if let ['/', after @ ..] = rest {
+                if match_chars(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| match_chars(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| match_chars(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != '/') && match_chars(rest, &text[1..])
+        }
+        ['[', class @ ..] => match class_end(class) {
+            Some(end) => match text.first() {
+                Some(&c) if c != '/' && class_matches(&class[..end], c) => {
+                    match_chars(&class[end + 1..], &text[1..])
+                }
+                _ => false,
+            },
+            None => text.first() == Some(&'[') && match_chars(class, &text[1..]),
+        },
+        ['\\', escaped, rest @ ..] => {
+            text.first() == Some(escaped) && match_chars(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && match_chars(rest, &text[1..]),
+    }
+}
+
+/// Index of the `]` closing a class whose contents start at `class[0]`
+fn class_end(class: &[char]) -> Option<usize> {
+    let start = match class.first() {
+        Some('!' | '^') => 1,
+        _ => 0,
+    };
+    // A `]` right after the opening bracket is a literal
+    (start + 1..class.len()).find(|&i| class[i] == ']')
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// Whether `pattern` matches the `/`-separated `path` or one of its parent directories, so
+/// `cache` covers everything below `cache/`
+pub fn covers(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_matches('/');
+    glob_match(pattern, path)
+        || path
+            .match_indices('/')
+            .any(|(end, _)| glob_match(pattern, &path[..end]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.map", "index.js.map"));
+        assert!(!glob_match("*.js", "lib/index.js"));
+        assert!(glob_match("**/*.js", "index.js"));
+        assert!(glob_match("**/*.js", "lib/deep/index.js"));
+        assert!(glob_match("lib/**", "lib/a/b.js"));
+        assert!(glob_match("a/**/b", "a/b"));
+        assert!(glob_match("a/**/b", "a/x/y/b"));
+        assert!(glob_match("file?.[jt]s", "file1.ts"));
+        assert!(!glob_match("file[!0-9].js", "file1.js"));
+    }
+
+    #[test]
+    fn test_covers() {
+        assert!(covers("cache", "cache/a/b.json"));
+        assert!(covers("cache/", "cache"));
+        assert!(covers("data/*.db", "data/app.db"));
+        assert!(covers("**/tmp", "a/b/tmp/x"));
+        assert!(!covers("cache", "caches/a"));
+        assert!(!covers("data/*.db", "data/sub/app.db"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Whether an extraction can be launched as is, judged against the embedded bundle manifest.
+//!
+//! Every launch compares the size of a bounded sample of bundled files with their extracted
+//! copies and hashes a few small ones among them (a different sample each time), so launches of
+//! large bundles stay fast; the app's package.json and the runtime executable are always checked. Damaged files are re-extracted on their own; an
+//! extraction that never finished, or was interrupted while its sentinel was present, is redone.
+//! Files the `postExtract` command changed are judged by what it left behind, and redo the
+//! whole extraction (and the command) when damaged, since the payload cannot restore them.
+
+use crate::config::RuntimeConfig;
+use crate::layers::Layer;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Present in the extraction directory while files are being written into it
+pub const SENTINEL: &str = ".extracting";
+const READY: &str = ".ready";
+/// Files compared by size per launch
+const SIZE_CHECKS: usize = 256;
+/// Files hashed per launch
+const SPOT_CHECKS: usize = 8;
+/// Larger files (the runtime executable, big assets) are only checked by size
+const SPOT_CHECK_MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+#[derive(Deserialize)]
+pub struct ManifestFile {
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Health {
+    Ready,
+    /// Ready, except for these payload entries, which are missing or corrupt
+    Damaged(Vec<String>),
+    /// Never extracted, or the extraction did not finish
+    Incomplete,
+}
+
+/// The bundle manifest embedded at build time, `None` for bundles built before manifests
+pub fn embedded_manifest() -> Option<Manifest> {
+    let json = crate::EMBEDDED_MANIFEST
+        .split(|&byte| byte == 0)
+        .find(|part| part.starts_with(b"{"))?;
+    serde_json::from_slice(json).ok()
+}
+
+/// Disk space the payload takes once extracted, leaving out entries `skip` selects: files
+/// sharing contents count once (they become hard links), each rounded up to whole blocks
+pub fn extracted_size(skip: impl Fn(&str) -> bool) -> Option<u64> {
+    const BLOCK: u64 = 4096;
+    let manifest = embedded_manifest()?;
+    let mut seen = HashSet::new();
+    let total = manifest
+        .files
+        .iter()
+        .filter(|(name, _)| !skip(name))
+        .filter(|(_, file)| seen.insert(file.sha256.as_str()))
+        .map(|(_, file)| file.size.div_ceil(BLOCK) * BLOCK)
+        .sum();
+    Some(total)
+}
+
+pub fn check(app_dir: &Path, config: &RuntimeConfig) -> Health {
+    let Ok(ready) = fs::read_to_string(app_dir.join(READY)) else {
+        return Health::Incomplete;
+    };
+    if app_dir.join(SENTINEL).exists() {
+        verbose!("Found an interrupted extraction in {}", app_dir.display());
+        return Health::Incomplete;
+    }
+    // Bundles built before manifests carried only the two-file check
+    let Some(manifest) = embedded_manifest() else {
+        return match crate::is_extraction_valid(app_dir, config) {
+            Ok(true) => Health::Ready,
+            _ => Health::Incomplete,
+        };
+    };
+    let skipped = skipped_entries(&ready);
+    let changes = crate::post_extract::changes(app_dir);
+
+    let root = crate::long_path::extended(app_dir);
+    let runtime = crate::get_runtime_executable_path(Path::new(""), config);
+    let always = |name: &str| name == "app/package.json" || Path::new(name) == runtime;
+    let candidates: Vec<(&str, &ManifestFile)> = crate::post_extract::expected_files(&manifest, &changes)
+        .into_iter()
+        .filter(|(name, _)| {
+            !skipped.contains(name) && !crate::integrity::is_mutable(&config.mutable_paths, Path::new(name))
+        })
+        .collect();
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos() as usize)
+        .unwrap_or(0);
+    let sampled = candidates.iter().filter(|(name, _)| always(name)).chain(
+        sample(&candidates, SIZE_CHECKS, seed).filter(|(name, _)| !always(name)),
+    );
+
+    let mut damaged = Vec::new();
+    let mut hashable = Vec::new();
+    for &(name, file) in sampled {
+        match fs::symlink_metadata(root.join(name)) {
+            // Restored links have the size of their target, not of the stored link text
+            Ok(metadata) if metadata.file_type().is_symlink() => {}
+            Ok(metadata) if metadata.is_file() && metadata.len() == file.size => {
+                if file.size <= SPOT_CHECK_MAX_SIZE {
+                    hashable.push((name, file));
+                }
+            }
+            _ => damaged.push(name.to_string()),
+        }
+    }
+
+    for &(name, file) in sample(&hashable, SPOT_CHECKS, seed) {
+        let matches = fs::read(root.join(name))
+            .is_ok_and(|data| format!("{:x}", Sha256::digest(&data)) == file.sha256);
+        if !matches && !damaged.iter().any(|damaged| damaged == name) {
+            damaged.push(name.to_string());
+        }
+    }
+
+    if damaged.iter().any(|name| changes.contains_key(name)) {
+        verbose!("Files written by the post-extract command are damaged in {}", app_dir.display());
+        return Health::Incomplete;
+    }
+    if damaged.is_empty() {
+        Health::Ready
+    } else {
+        Health::Damaged(damaged)
+    }
+}
+
+/// Up to `count` distinct `items`, spread evenly from an offset that `seed` picks
+fn sample<T>(items: &[T], count: usize, seed: usize) -> impl Iterator<Item = &T> {
+    let step = (items.len() / count.max(1)).max(1);
+    (0..count.min(items.len())).map(move |i| &items[(seed + i * step) % items.len()])
+}
+
+/// Entries the extraction deliberately left out, listed after the first line of the ready file
+fn skipped_entries(ready: &str) -> HashSet<&str> {
+    ready.lines().skip(1).collect()
+}
+
+/// [`skipped_entries`] of a ready extraction
+pub fn skipped(app_dir: &Path) -> HashSet<String> {
+    let ready = fs::read_to_string(app_dir.join(READY)).unwrap_or_default();
+    skipped_entries(&ready).into_iter().map(String::from).collect()
+}
+
+/// Whether `dir` is an app extraction, finished or not
+pub fn is_extraction(dir: &Path) -> bool {
+    dir.join(READY).exists() || dir.join(SENTINEL).exists()
+}
+
+/// Start writing into the extraction directory: until [`mark_ready`], it counts as interrupted
+pub fn mark_in_progress(app_dir: &Path) -> Result<()> {
+    fs::create_dir_all(app_dir).context("Failed to create app directory")?;
+    fs::write(app_dir.join(SENTINEL), "")
+        .with_context(|| format!("Failed to mark {} as being extracted", app_dir.display()))
+}
+
+/// Finish an extraction that left out the `skipped` payload entries
+pub fn mark_ready(app_dir: &Path, skipped: &[String]) -> Result<()> {
+    let ready_file = app_dir.join(READY);
+    let mut contents = String::from("ready\n");
+    for name in skipped {
+        contents.push_str(name);
+        contents.push('\n');
+    }
+    fs::write(&ready_file, contents)
+        .with_context(|| format!("Failed to create ready file at {}", ready_file.display()))?;
+    fs::remove_file(app_dir.join(SENTINEL)).context("Failed to remove the extraction sentinel")
+}
+
+/// Re-extract just the `damaged` entries of a ready extraction
+pub fn repair(app_dir: &Path, damaged: &[String], config: &RuntimeConfig) -> Result<()> {
+    fs::write(app_dir.join(SENTINEL), "")
+        .with_context(|| format!("Failed to mark {} as being repaired", app_dir.display()))?;
+    // Damaged dependencies reached through the link are other apps' too: the link goes and this
+    // extraction gets a private copy of the whole layer instead
+    let unshared = damaged.iter().any(|name| Layer::of(name, config) == Layer::Deps)
+        && crate::shared_deps::unshare(app_dir)?;
+    let root = crate::long_path::extended(app_dir);
+    for name in damaged {
+        if unshared && Layer::of(name, config) == Layer::Deps {
+            continue;
+        }
+        // Replaced rather than written through, in case the damage is a foreign link or directory
+        let path = root.join(name);
+        let _ = fs::remove_file(&path).or_else(|_| fs::remove_dir_all(&path));
+    }
+    let damaged: HashSet<&str> = damaged.iter().map(String::as_str).collect();
+    for layer in Layer::ALL {
+        if unshared && layer == Layer::Deps {
+            crate::extract_payload(app_dir, "dependencies", layer, |_| true)?;
+            crate::bin_shims::prepare(app_dir, config).context("Failed to prepare node_modules/.bin entries")?;
+        } else if damaged.iter().any(|name| Layer::of(name, config) == layer) {
+            crate::extract_payload(app_dir, "damaged files", layer, |name| damaged.contains(name))?;
+        }
+    }
+    fs::remove_file(app_dir.join(SENTINEL)).context("Failed to remove the extraction sentinel")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let items: Vec<usize> = (0..1000).collect();
+        for seed in [0, 7, 999, 123_456_789] {
+            let picked: HashSet<usize> = sample(&items, SIZE_CHECKS, seed).copied().collect();
+            assert_eq!(picked.len(), SIZE_CHECKS);
+        }
+        let few = [1, 2, 3];
+        let mut picked: Vec<i32> = sample(&few, SIZE_CHECKS, 5).copied().collect();
+        picked.sort();
+        assert_eq!(picked, few);
+        assert_eq!(sample(&[] as &[i32], SPOT_CHECKS, 5).count(), 0);
+    }
+}
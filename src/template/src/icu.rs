@@ -0,0 +1,146 @@
+use crate::config::RuntimeConfig;
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Written in the extraction directory by the preload once the app asked for missing locale data;
+/// holds the ICU version of the bundled node
+pub const MARKER_FILE: &str = "icu-needed";
+
+/// Tells the preload where to write [`MARKER_FILE`]
+pub const MARKER_ENV: &str = "BANDEROLE_ICU_MARKER";
+
+const DEFAULT_DATA_URL: &str = "https://github.com/unicode-org/icu/releases/download/release-{major}-{minor}/icu4c-{major}_{minor}-data-bin-l.zip";
+
+/// Run by the bundled node: the runner itself has no HTTP client
+const DOWNLOAD_SCRIPT: &str = r#"
+const fs = require('fs');
+const [url, dest] = process.argv.slice(1);
+const get = (target, redirects) => {
+  const client = target.startsWith('http:') ? require('http') : require('https');
+  client.get(target, { headers: { 'user-agent': 'banderole' } }, (res) => {
+    if (res.statusCode >= 300 && res.statusCode < 400 && res.headers.location && redirects < 10) {
+      res.resume();
+      return get(new URL(res.headers.location, target).toString(), redirects + 1);
+    }
+    if (res.statusCode !== 200) {
+      console.error(`HTTP ${res.statusCode} for ${target}`);
+      process.exit(1);
+    }
+    res.pipe(fs.createWriteStream(dest)).on('finish', () => process.exit(0));
+  }).on('error', (e) => {
+    console.error(e.message);
+    process.exit(1);
+  });
+};
+get(url, 0);
+"#;
+
+/// Directory to pass as `NODE_ICU_DATA`, fetching full ICU data first if the app turned out to need it.
+///
+/// The download happens on the start after the preload recorded the need (or right away with
+/// `BANDEROLE_FULL_ICU=1`); failures only cost internationalization, so they are reported and skipped.
+pub fn data_dir(app_dir: &Path, config: &RuntimeConfig, node: &Path) -> Option<PathBuf> {
+    if !config.lazy_icu {
+        return None;
+    }
+    let recorded = fs::read_to_string(app_dir.join(MARKER_FILE)).ok();
+    let forced = env::var_os("BANDEROLE_FULL_ICU").is_some_and(|value| value == "1");
+    if recorded.is_none() && !forced {
+        return None;
+    }
+    match ensure(app_dir, config, node, recorded.as_deref()) {
+        Ok(dir) => Some(dir),
+        Err(e) => {
+            eprintln!("banderole: could not fetch full ICU data, continuing without it: {e:#}");
+            None
+        }
+    }
+}
+
+fn ensure(app_dir: &Path, config: &RuntimeConfig, node: &Path, recorded: Option<&str>) -> Result<PathBuf> {
+    let version = match recorded.map(str::trim).filter(|version| !version.is_empty()) {
+        Some(version) => version.to_string(),
+        None => query_icu_version(node)?,
+    };
+    let (major, minor) = version
+        .split_once('.')
+        .with_context(|| format!("Unexpected ICU version '{version}'"))?;
+    // Shared by every bundle in the cache that uses the same ICU release
+    let cache_root = app_dir.parent().unwrap_or(app_dir);
+    let dir = cache_root.join("icu").join(&version);
+    let data_file = dir.join(format!("icudt{major}l.dat"));
+    if data_file.exists() {
+        return Ok(dir);
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let url = config
+        .icu_data_url
+        .as_deref()
+        .unwrap_or(DEFAULT_DATA_URL)
+        .replace("{version}", &version)
+        .replace("{major}", major)
+        .replace("{minor}", minor);
+    eprintln!("banderole: downloading ICU {version} data (once) from {url}");
+    let archive = dir.join("download.zip");
+    let status = Command::new(node)
+        .arg("-e")
+        .arg(DOWNLOAD_SCRIPT)
+        .arg(&url)
+        .arg(&archive)
+        .env_remove("NODE_OPTIONS")
+        .status()
+        .context("Failed to run node to download ICU data")?;
+    anyhow::ensure!(status.success(), "download of {url} failed");
+
+    let result = extract_data_file(&archive, &data_file);
+    let _ = fs::remove_file(&archive);
+    result?;
+
+    // node refuses to start with unusable ICU data, so never hand it a bad file
+    let usable = Command::new(node)
+        .args(["-e", ""])
+        .env("NODE_ICU_DATA", &dir)
+        .env_remove("NODE_OPTIONS")
+        .status()
+        .is_ok_and(|status| status.success());
+    if !usable {
+        let _ = fs::remove_file(&data_file);
+        anyhow::bail!("downloaded ICU data from {url} is not usable by the bundled node");
+    }
+    Ok(dir)
+}
+
+fn query_icu_version(node: &Path) -> Result<String> {
+    let output = Command::new(node)
+        .args(["-p", "process.versions.icu"])
+        .env_remove("NODE_OPTIONS")
+        .output()
+        .context("Failed to query the ICU version of node")?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    anyhow::ensure!(
+        output.status.success() && version != "undefined" && !version.is_empty(),
+        "the bundled node has no ICU support"
+    );
+    Ok(version)
+}
+
+fn extract_data_file(archive: &Path, data_file: &Path) -> Result<()> {
+    let file = fs::File::open(archive).context("Failed to open ICU archive")?;
+    let mut zip = zip::ZipArchive::new(file).context("Failed to read ICU archive")?;
+    let index = (0..zip.len())
+        .find(|&i| zip.name_for_index(i).is_some_and(|name| name.ends_with(".dat")))
+        .context("ICU archive contains no .dat file")?;
+    let mut entry = zip.by_index(index)?;
+    // Write next to the final name first so a partial file is never picked up
+    let partial = data_file.with_extension("part");
+    let mut out = fs::File::create(&partial)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    std::io::copy(&mut entry, &mut out).context("Failed to extract ICU data")?;
+    drop(out);
+    fs::rename(&partial, data_file).context("Failed to store ICU data")?;
+    Ok(())
+}
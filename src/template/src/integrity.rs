@@ -0,0 +1,301 @@
+use crate::config::RuntimeConfig;
+use crate::health::{Manifest, ManifestFile};
+use crate::layers::Layer;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Extensions that are always checked, even when they fall under a mutable path
+const CODE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "node", "wasm"];
+
+/// Result of comparing an extraction against the embedded payload
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub checked: usize,
+    pub exempt: usize,
+    pub problems: Vec<String>,
+}
+
+/// Compare every extracted file with its embedded copy and look for files that were added.
+///
+/// Files matching the bundle's `mutablePaths` are skipped unless they are code files. Files the
+/// `postExtract` command changed are compared with what it left behind.
+pub fn verify(app_dir: &Path, config: &RuntimeConfig) -> Result<VerifyReport> {
+    let root = crate::long_path::extended(app_dir);
+    let mut report = VerifyReport::default();
+    let mut expected: HashSet<PathBuf> = HashSet::new();
+    let changes = crate::post_extract::changes(app_dir);
+
+    for layer in Layer::ALL.into_iter().filter(|layer| layer.is_embedded()) {
+    let mut archive = layer.open()?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).context("Failed to read zip entry")?;
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        if entry.is_dir() {
+            expected.insert(relative);
+            continue;
+        }
+        if changes.contains_key(entry.name()) {
+            // Checked against the recorded state below
+            continue;
+        }
+        expected.insert(relative.clone());
+        if is_exempt(&config.mutable_paths, &relative) {
+            report.exempt += 1;
+            continue;
+        }
+
+        report.checked += 1;
+        let path = root.join(&relative);
+        let on_disk = match read_extracted(&path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                report.problems.push(format!("missing: {}", relative.display()));
+                continue;
+            }
+        };
+        let mut embedded = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut embedded)
+            .with_context(|| format!("Failed to read embedded {}", relative.display()))?;
+        if on_disk != embedded {
+            report.problems.push(format!("modified: {}", relative.display()));
+        }
+    }
+    }
+    for (name, file) in &changes {
+        if let Some(file) = file {
+            expected.insert(PathBuf::from(name));
+            check_digest(&root, name, file, &mut report);
+        }
+    }
+
+    find_unexpected(app_dir, config, &expected, &mut report)?;
+    Ok(report)
+}
+
+/// Hash every extracted file and compare it with the digest the embedded manifest recorded at
+/// build time, then look for added files, as [`verify`] does without decompressing the payload.
+/// Files the `postExtract` command changed are compared with what it left behind.
+///
+/// The expected digests live in the executable, so changing files in the cache cannot go
+/// unnoticed without changing the executable too. Bundles without a manifest fall back to
+/// [`verify`].
+pub fn verify_digests(app_dir: &Path, config: &RuntimeConfig) -> Result<VerifyReport> {
+    match crate::health::embedded_manifest() {
+        Some(manifest) => verify_against(app_dir, config, &manifest),
+        None => verify(app_dir, config),
+    }
+}
+
+fn verify_against(app_dir: &Path, config: &RuntimeConfig, manifest: &Manifest) -> Result<VerifyReport> {
+    let root = crate::long_path::extended(app_dir);
+    let skipped = crate::health::skipped(app_dir);
+    let mut report = VerifyReport::default();
+    let mut expected: HashSet<PathBuf> = HashSet::new();
+    let changes = crate::post_extract::changes(app_dir);
+
+    for (name, file) in crate::post_extract::expected_files(manifest, &changes) {
+        let relative = PathBuf::from(name);
+        expected.insert(relative.clone());
+        if skipped.contains(name) {
+            continue;
+        }
+        if is_exempt(&config.mutable_paths, &relative) {
+            report.exempt += 1;
+            continue;
+        }
+        check_digest(&root, name, file, &mut report);
+    }
+
+    find_unexpected(app_dir, config, &expected, &mut report)?;
+    Ok(report)
+}
+
+fn check_digest(root: &Path, name: &str, file: &ManifestFile, report: &mut VerifyReport) {
+    report.checked += 1;
+    match read_extracted(&root.join(name)) {
+        Ok(bytes) if format!("{:x}", Sha256::digest(&bytes)) == file.sha256 => {}
+        Ok(_) => report.problems.push(format!("modified: {name}")),
+        Err(_) => report.problems.push(format!("missing: {name}")),
+    }
+}
+
+/// Report files in the extraction that are neither in the bundle, written by the runner, nor
+/// covered by a mutable pattern. A shared runtime is walked through its link like a private
+/// copy; its files are compared by the callers, which read them through the link as well
+fn find_unexpected(
+    app_dir: &Path,
+    config: &RuntimeConfig,
+    expected: &HashSet<PathBuf>,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let root = crate::long_path::extended(app_dir);
+    for entry in walkdir::WalkDir::new(&root).min_depth(1) {
+        let entry = entry.context("Failed to walk extracted app")?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        if crate::shared_runtime::is_link(app_dir, relative, config) {
+            // A downloaded runtime is not in the bundle; its archive was checked when fetched
+            if config.external_runtime.is_none() {
+                for shared in walkdir::WalkDir::new(entry.path()).min_depth(1) {
+                    let shared = shared.context("Failed to walk the shared runtime")?;
+                    if shared.file_type().is_dir() {
+                        continue;
+                    }
+                    let relative = shared.path().strip_prefix(&root).unwrap_or(shared.path());
+                    if !expected.contains(relative) {
+                        report.problems.push(format!("unexpected: {}", relative.display()));
+                    }
+                }
+            }
+            continue;
+        }
+        if expected.contains(relative) || is_runner_file(app_dir, relative) {
+            continue;
+        }
+        if !is_exempt(&config.mutable_paths, relative) {
+            report.problems.push(format!("unexpected: {}", relative.display()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the runner rather than the payload put `relative` into the extraction
+pub fn is_runner_file(app_dir: &Path, relative: &Path) -> bool {
+    relative == Path::new(".ready")
+        || relative == Path::new(crate::health::SENTINEL)
+        || relative == Path::new(crate::post_extract::CHANGES_FILE)
+        || relative == Path::new(crate::preload::FILE_NAME)
+        || relative == Path::new(crate::icu::MARKER_FILE)
+        || crate::bin_shims::is_generated_wrapper(relative)
+        || crate::shared_deps::is_link(app_dir, relative)
+}
+
+/// File contents as the bundler stored them: symlinks restored after extraction read as their target
+pub fn read_extracted(path: &Path) -> std::io::Result<Vec<u8>> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Ok(fs::read_link(path)?.to_string_lossy().into_owned().into_bytes());
+    }
+    fs::read(path)
+}
+
+/// Whether `relative` (a path inside the extraction root) is covered by a mutable pattern.
+/// Patterns are relative to the bundled `app/` directory; the Node runtime and code files never are.
+pub fn is_exempt(patterns: &[String], relative: &Path) -> bool {
+    !is_code_file(relative) && is_mutable(patterns, relative)
+}
+
+/// Whether `relative` is covered by a mutable pattern, whatever kind of file it is
+pub fn is_mutable(patterns: &[String], relative: &Path) -> bool {
+    let Ok(inside_app) = relative.strip_prefix("app") else {
+        return false;
+    };
+    let path = inside_app
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    patterns.iter().any(|pattern| crate::glob::covers(pattern, &path))
+}
+
+fn is_code_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "package.json")
+        || path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| CODE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_added_to_the_shared_runtime_are_reported() {
+        let dir = std::env::temp_dir().join(format!("banderole-integrity-{}", std::process::id()));
+        let shared = dir.join("runtimes/abc");
+        let app_dir = dir.join("app-1.0.0");
+        fs::create_dir_all(shared.join("bin")).unwrap();
+        fs::create_dir_all(app_dir.join("app")).unwrap();
+        fs::write(shared.join("bin/node"), "node").unwrap();
+        fs::write(shared.join("bin/planted.js"), "evil").unwrap();
+        fs::write(app_dir.join("app/index.js"), "").unwrap();
+        std::os::unix::fs::symlink(&shared, app_dir.join("node")).unwrap();
+
+        let expected: HashSet<PathBuf> =
+            ["app/index.js", "node/bin/node"].into_iter().map(PathBuf::from).collect();
+        let mut report = VerifyReport::default();
+        find_unexpected(&app_dir, &RuntimeConfig::default(), &expected, &mut report).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(report.problems, ["unexpected: node/bin/planted.js"]);
+    }
+
+    fn manifest_file(contents: &str) -> ManifestFile {
+        ManifestFile {
+            size: contents.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(contents)),
+        }
+    }
+
+    #[test]
+    fn test_verify_digests() {
+        let app_dir = std::env::temp_dir().join(format!("banderole-digests-{}", std::process::id()));
+        fs::create_dir_all(app_dir.join("app/data")).unwrap();
+        fs::write(app_dir.join("app/index.js"), "index").unwrap();
+        fs::write(app_dir.join("app/config.json"), "configured").unwrap();
+        fs::write(app_dir.join("app/native.node"), "rebuilt").unwrap();
+        fs::write(app_dir.join("app/data/state.json"), "changed by the app").unwrap();
+        fs::write(app_dir.join(".ready"), "ready\n").unwrap();
+        // What the post-extract command left behind: a rewritten config, a rebuilt addon and a
+        // removed placeholder
+        fs::write(
+            app_dir.join(crate::post_extract::CHANGES_FILE),
+            format!(
+                "{} 10 app/config.json\n{} 7 app/native.node\n- - app/placeholder\n",
+                manifest_file("configured").sha256,
+                manifest_file("rebuilt").sha256
+            ),
+        )
+        .unwrap();
+        let manifest = Manifest {
+            files: [
+                ("app/index.js", "index"),
+                ("app/config.json", "{}"),
+                ("app/placeholder", ""),
+                ("app/data/state.json", "{}"),
+            ]
+            .into_iter()
+            .map(|(name, contents)| (name.to_string(), manifest_file(contents)))
+            .collect(),
+        };
+        let config = RuntimeConfig {
+            mutable_paths: vec!["data".to_string()],
+            ..RuntimeConfig::default()
+        };
+
+        let report = verify_against(&app_dir, &config, &manifest).unwrap();
+        assert_eq!((report.checked, report.exempt), (3, 1));
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+
+        fs::write(app_dir.join("app/index.js"), "tampered").unwrap();
+        fs::write(app_dir.join("app/native.node"), "tampered").unwrap();
+        fs::write(app_dir.join("app/planted.js"), "").unwrap();
+        let report = verify_against(&app_dir, &config, &manifest).unwrap();
+        fs::remove_dir_all(&app_dir).ok();
+        let mut problems = report.problems;
+        problems.sort();
+        assert_eq!(
+            problems,
+            ["modified: app/index.js", "modified: app/native.node", "unexpected: app/planted.js"]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! The payload is embedded as up to three xz-compressed zips: the app, its dependencies
+//! (`app/node_modules`) and the runtime. Each is only decompressed when something in it has to
+//! be extracted, so an update that reuses shared dependencies and runtime unpacks just the app.
+
+use crate::config::RuntimeConfig;
+use anyhow::{Context, Result};
+use std::io::Cursor;
+use zip::ZipArchive;
+
+/// Location of the dependency layer in the extraction
+pub const DEPS_DIR: &str = "app/node_modules";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    App,
+    Deps,
+    Runtime,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 3] = [Layer::App, Layer::Deps, Layer::Runtime];
+
+    fn data(self) -> &'static [u8] {
+        match self {
+            Layer::App => crate::XZ_DATA,
+            Layer::Deps => crate::DEPS_DATA,
+            Layer::Runtime => crate::RUNTIME_DATA,
+        }
+    }
+
+    /// Whether the bundle carries this layer; bundles without one keep its files in the app layer
+    pub fn is_embedded(self) -> bool {
+        self == Layer::App || !self.data().is_empty()
+    }
+
+    /// The layer holding the payload entry `name`
+    pub fn of(name: &str, config: &RuntimeConfig) -> Layer {
+        let name = name.trim_end_matches('/');
+        let in_dir = |dir: &str| name.strip_prefix(dir).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        if Layer::Runtime.is_embedded() && in_dir(config.runtime.dir_name()) {
+            Layer::Runtime
+        } else if Layer::Deps.is_embedded() && in_dir(DEPS_DIR) {
+            Layer::Deps
+        } else {
+            Layer::App
+        }
+    }
+
+    /// Decompress the layer (decrypting it first when encrypted) and open the inner ZIP
+    pub fn open(self) -> Result<ZipArchive<Cursor<Vec<u8>>>> {
+        let data = self.data();
+        let decrypted = if crate::payload_crypto::is_encrypted(data) {
+            Some(crate::payload_key::decrypt(data).context("Failed to decrypt the embedded payload")?)
+        } else {
+            None
+        };
+        let mut zip_buf: Vec<u8> = Vec::new();
+        let mut reader = Cursor::new(decrypted.as_deref().unwrap_or(data));
+        lzma_rs::xz_decompress(&mut reader, &mut zip_buf)
+            .context("Failed to decompress embedded xz data")?;
+        ZipArchive::new(Cursor::new(zip_buf)).context("Failed to open embedded zip archive")
+    }
+}
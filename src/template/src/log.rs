@@ -0,0 +1,48 @@
+//! Runner diagnostics behind `verbose!`: cache decisions, lock waits, extraction timing and the
+//! runtime that is launched. Off unless `--banderole-verbose` or `BANDEROLE_DEBUG=1` is given;
+//! lines go to stderr, or with `--banderole-log-file`/`BANDEROLE_LOG_FILE` are appended to a
+//! file, so they never mix with what the app prints.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FILE: Mutex<Option<fs::File>> = Mutex::new(None);
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Turn logging on for `--banderole-verbose`, `BANDEROLE_DEBUG` or a log file
+pub fn init(verbose: bool, log_file: Option<&Path>) {
+    START.get_or_init(Instant::now);
+    let env_file = std::env::var_os("BANDEROLE_LOG_FILE").filter(|file| !file.is_empty());
+    let log_file = log_file.or(env_file.as_deref().map(Path::new));
+    if let Some(path) = log_file {
+        match fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => *FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file),
+            Err(e) => eprintln!("banderole: cannot write the log file {}: {e}", path.display()),
+        }
+    }
+    let debug = std::env::var("BANDEROLE_DEBUG").is_ok_and(|value| !matches!(value.as_str(), "" | "0" | "false"));
+    ENABLED.store(verbose || debug || log_file.is_some(), Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// One line, prefixed with the milliseconds since the runner started
+pub fn write(message: std::fmt::Arguments) {
+    let elapsed = START.get_or_init(Instant::now).elapsed().as_millis();
+    let line = format!("[banderole +{elapsed}ms] {message}\n");
+    match FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        Some(file) => {
+            file.write_all(line.as_bytes()).ok();
+        }
+        None => {
+            std::io::stderr().write_all(line.as_bytes()).ok();
+        }
+    }
+}
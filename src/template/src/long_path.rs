@@ -0,0 +1,54 @@
+use std::path::{Path, PathBuf};
+
+/// `path` as an extended-length path (`\\?\C:\...` or `\\?\UNC\server\share\...`) on Windows,
+/// which lifts the 260 character `MAX_PATH` limit that deeply nested `node_modules` exceed.
+/// Other platforms have no such limit and get `path` back unchanged.
+///
+/// Meant for the runner's own file operations; node and the app keep seeing regular paths.
+#[cfg(windows)]
+pub fn extended(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => return path.to_path_buf(),
+        }
+    };
+    let mut components = absolute.components();
+    let mut extended = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(letter) => PathBuf::from(format!(r"\\?\{}:\", letter as char)),
+            Prefix::UNC(server, share) => {
+                let mut unc = OsString::from(r"\\?\UNC\");
+                unc.push(server);
+                unc.push(r"\");
+                unc.push(share);
+                unc.push(r"\");
+                PathBuf::from(unc)
+            }
+            // Already verbatim, or a device path
+            _ => return absolute,
+        },
+        _ => return absolute,
+    };
+    // Extended-length paths are taken literally, so `.`, `..` and `/` must be resolved here
+    for component in components {
+        match component {
+            Component::Normal(part) => extended.push(part),
+            Component::ParentDir => {
+                extended.pop();
+            }
+            _ => {}
+        }
+    }
+    extended
+}
+
+#[cfg(not(windows))]
+pub fn extended(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
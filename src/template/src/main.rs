@@ -1,11 +1,13 @@
+#![cfg_attr(banderole_gui, windows_subsystem = "windows")]
+
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::ffi::OsString;
-use zip::ZipArchive;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use directories::BaseDirs;
 use fs2::FileExt;
 
@@ -13,56 +15,326 @@ use fs2::FileExt;
 // The build script will generate a data.rs file with the actual data
 include!(concat!(env!("OUT_DIR"), "/data.rs"));
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+/// Log a runner diagnostic when debug logging is on (see `log`)
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled() {
+            $crate::log::write(format_args!($($arg)*));
+        }
+    };
+}
+
+mod app_env;
+mod arch_check;
+mod args;
+mod bin_shims;
+mod cache_location;
+mod clean;
+mod completions;
+mod config;
+mod disk_space;
+mod entry;
+mod exit_code;
+mod glob;
+mod health;
+mod icu;
+mod integrity;
+mod layers;
+mod log;
+mod long_path;
+mod metrics;
+mod notice;
+mod package_entry;
+mod payload_crypto;
+mod payload_key;
+mod pnp;
+mod post_extract;
+mod preload;
+mod progress;
+mod runtime_download;
+mod schema;
+mod service;
+mod shared_deps;
+mod shared_runtime;
+mod signals;
+mod system_cache;
+
+use args::RunnerArgs;
+use config::RuntimeConfig;
+use layers::Layer;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {e:?}");
+        std::process::exit(exit_code::FAILURE);
+    }
+}
+
+fn run() -> Result<()> {
+    let mut timings = metrics::Timings::start();
+    // Also read from outside (`banderole diff`); referenced so the linker keeps it
+    std::hint::black_box(EMBEDDED_MANIFEST.as_ptr());
+    let runner_args = match RunnerArgs::parse(env::args_os().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(exit_code::USAGE);
+        }
+    };
+    if runner_args.help {
+        args::print_runner_help();
+        return Ok(());
+    }
+    if runner_args.manifest {
+        return print_manifest();
+    }
+    log::init(runner_args.verbose, runner_args.log_file.as_deref());
+    verbose!("Runner schema {}", schema::RUNNER_SCHEMA);
+    let config = RuntimeConfig::embedded()?;
+    // Embedded scripts are printed without extracting the app
+    if let Some(script) = runner_args.completions.as_deref().and_then(|shell| completions::embedded(&config, shell)) {
+        print!("{script}");
+        return Ok(());
+    }
+    if let Some(shell) = &runner_args.install_completions {
+        return completions::install(&config, shell, runner_args.cache_dir.as_deref());
+    }
+    if runner_args.uninstall_service {
+        return service::uninstall(embedded_service(&config)?);
+    }
     
     // Get cache directory
-    let cache_dir = get_cache_dir().context("Failed to determine cache directory")?;
-    let app_dir = cache_dir.join(&BUILD_ID);
-    let ready_file = app_dir.join(".ready");
+    let extraction = extraction_path(&config);
+    let cache_dir = match &runner_args.cache_dir {
+        Some(dir) => Some(dir.clone()),
+        None => {
+            // An administrator installs the service from the system cache, which every account
+            // the service may run as can read
+            let scope = if runner_args.install_service && system_cache::is_admin() {
+                config::CacheScope::System
+            } else {
+                runner_args.cache_scope.unwrap_or(config.cache_scope)
+            };
+            (scope == config::CacheScope::System)
+                .then(|| system_cache::usable(&extraction, |app_dir| health::check(app_dir, &config) == health::Health::Ready))
+                .flatten()
+                .or_else(default_cache_dir)
+        }
+    };
+    let cache_dir = cache_location::usable_cache_dir(cache_dir, runner_args.cache_dir.is_some(), &extraction)?;
+    let shared_cache = runner_args.cache_dir.is_none() && cache_dir == system_cache::root();
+    let app_dir = cache_dir.join(&extraction);
+    verbose!("Cache directory: {}", cache_dir.display());
+    verbose!("App directory: {}", app_dir.display());
+    if runner_args.clean_all {
+        return clean::all(&cache_dir);
+    }
+    if runner_args.clean {
+        return clean::app(&cache_dir, &app_dir);
+    }
+    // Acknowledged notices stay per user, even when the extraction is shared
+    let state_dir = if shared_cache {
+        default_cache_dir().unwrap_or_else(|| cache_dir.clone())
+    } else {
+        cache_dir.clone()
+    };
+    
+    arch_check::check(&config);
     
     // Check if already extracted and ready
-    if ready_file.exists() && is_extraction_valid(&app_dir)? {
-        return run_app(&app_dir, &args[1..]);
+    if health::check(&app_dir, &config) == health::Health::Ready {
+        verbose!("Using existing extraction");
+        return launch(&app_dir, &state_dir, &config, &runner_args, &timings);
+    }
+    
+    if shared_cache && !system_cache::is_admin() {
+        anyhow::bail!(
+            "The extraction in the system cache {} was damaged while in use; ask an administrator to run the app once to repair it",
+            cache_dir.display()
+        );
     }
     
     // Use file locking to prevent concurrent extraction
-    let lock_file_path = cache_dir.join(format!("{}.lock", BUILD_ID));
+    let lock_file_path = lock_path(&app_dir);
+    if let Some(parent) = lock_file_path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
     let lock_file = fs::OpenOptions::new()
         .create(true)
         .write(true)
         .open(&lock_file_path)
+        // Another user's lock in a shared cache locks just as well opened for reading
+        .or_else(|e| fs::File::open(&lock_file_path).map_err(|_| e))
         .with_context(|| format!("Failed to create lock file at {}", lock_file_path.display()))?;
     
     // Acquire exclusive lock
+    verbose!("Waiting for extraction lock {}", lock_file_path.display());
+    let lock_started = std::time::Instant::now();
     lock_file.lock_exclusive().context("Failed to acquire extraction lock")?;
+    timings.lock_wait = Some(lock_started.elapsed());
+    verbose!("Acquired extraction lock after {:?}", lock_started.elapsed());
     
-    // Double-check if extraction completed while waiting for lock
-    if ready_file.exists() && is_extraction_valid(&app_dir)? {
-        // Release lock and run
-        lock_file.unlock().ok();
-        return run_app(&app_dir, &args[1..]);
+    // Double-check: another process may have extracted or repaired it while we waited
+    match health::check(&app_dir, &config) {
+        health::Health::Ready => {
+            // Release lock and run
+            lock_file.unlock().ok();
+            verbose!("Extraction completed by another process");
+            return launch(&app_dir, &state_dir, &config, &runner_args, &timings);
+        }
+        health::Health::Damaged(damaged) => {
+            verbose!("Repairing {} damaged files: {}", damaged.len(), damaged.join(", "));
+            health::repair(&app_dir, &damaged, &config)
+                .with_context(|| format!("Failed to repair the extraction in {}", app_dir.display()))?;
+            bin_shims::prepare(&app_dir, &config).context("Failed to prepare node_modules/.bin entries")?;
+            if shared_cache {
+                seal_shared(&app_dir)?;
+            }
+            lock_file.unlock().ok();
+            return launch(&app_dir, &state_dir, &config, &runner_args, &timings);
+        }
+        health::Health::Incomplete => {}
     }
     
     // Extract application if needed
-    extract_application(&app_dir)
+    verbose!("Extracting application");
+    let extraction_started = std::time::Instant::now();
+    let skipped = extract_application(&app_dir, &cache_dir, &config)
         .with_context(|| format!("Failed to extract application to {}", app_dir.display()))?;
+    timings.extraction = Some(extraction_started.elapsed());
+    verbose!("Extracted in {:?}", extraction_started.elapsed());
+    if let Some(command) = &config.post_extract {
+        post_extract::run(&app_dir, &config, command, &skipped)?;
+    }
     
     // Mark as ready
-    fs::write(&ready_file, "ready")
-        .with_context(|| format!("Failed to create ready file at {}", ready_file.display()))?;
+    health::mark_ready(&app_dir, &skipped)?;
+    if shared_cache {
+        seal_shared(&app_dir)?;
+    }
     
     // Release lock
     lock_file.unlock().context("Failed to release extraction lock")?;
     
     // Run the application
-    run_app(&app_dir, &args[1..])
+    launch(&app_dir, &state_dir, &config, &runner_args, &timings)
+}
+
+/// Run the app, or stop after extraction when `--banderole-extract-only` was given.
+/// `state_dir` is the user's own cache, holding what they acknowledged.
+fn launch(
+    app_dir: &Path,
+    state_dir: &Path,
+    config: &RuntimeConfig,
+    runner_args: &RunnerArgs,
+    timings: &metrics::Timings,
+) -> Result<()> {
+    if runner_args.extract_only {
+        println!("{}", app_dir.display());
+        return Ok(());
+    }
+    if runner_args.verify {
+        let report = integrity::verify(app_dir, config)?;
+        for problem in &report.problems {
+            println!("{problem}");
+        }
+        eprintln!(
+            "Verified {} files ({} mutable files skipped): {}",
+            report.checked,
+            report.exempt,
+            if report.problems.is_empty() { "ok" } else { "MODIFIED" }
+        );
+        std::process::exit(if report.problems.is_empty() { exit_code::SUCCESS } else { exit_code::INTEGRITY });
+    }
+    if runner_args.verify_on_start || config.verify_on_start {
+        let report = integrity::verify_digests(app_dir, config)?;
+        if !report.problems.is_empty() {
+            for problem in &report.problems {
+                eprintln!("{problem}");
+            }
+            eprintln!(
+                "Refusing to run: {} files in {} were modified, added or removed since extraction; delete the directory to extract it again",
+                report.problems.len(),
+                app_dir.display()
+            );
+            std::process::exit(exit_code::INTEGRITY);
+        }
+        verbose!("Verified {} extracted files", report.checked);
+    }
+    if let Some(notice) = &config.notice {
+        notice::ensure_acknowledged(notice, &config.app_name, state_dir, runner_args.accept_notice)?;
+    }
+    if runner_args.install_service {
+        return service::install(app_dir, embedded_service(config)?, &runner_args.app_args);
+    }
+    metrics::record(config, BUILD_ID, timings);
+    run_app(app_dir, config, runner_args)
+}
+
+fn embedded_service(config: &RuntimeConfig) -> Result<&config::Service> {
+    config.service.as_ref().context(
+        "This bundle defines no service; add \"service\" to its banderole configuration to use --banderole-install-service",
+    )
+}
+
+/// Pretty-print the manifest banderole embedded between its markers
+fn print_manifest() -> Result<()> {
+    let json = EMBEDDED_MANIFEST
+        .split(|&byte| byte == 0)
+        .find(|part| part.starts_with(b"{"))
+        .context("The bundle carries no manifest")?;
+    let manifest: serde_json::Value =
+        serde_json::from_slice(json).context("Failed to parse the embedded manifest")?;
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+    Ok(())
+}
+
+/// Where the build is extracted below the cache directory: `<name>/<version>/<build ID>`, or
+/// the build ID alone for bundles using the flat layout
+fn extraction_path(config: &RuntimeConfig) -> PathBuf {
+    match &config.extraction_dir {
+        Some(dir) => Path::new(dir).join(BUILD_ID),
+        None => PathBuf::from(BUILD_ID),
+    }
 }
 
-fn get_cache_dir() -> Result<PathBuf> {
-    let cache_dir = BaseDirs::new().unwrap().cache_dir().join("banderole");    
-    fs::create_dir_all(&cache_dir).context("Failed to create cache directory")?;
-    Ok(cache_dir)
+fn seal_shared(app_dir: &Path) -> Result<()> {
+    system_cache::seal(app_dir)
+        .with_context(|| format!("Failed to make {} readable by all users and writable by none", app_dir.display()))
+}
+
+/// Lock serializing extraction of `app_dir`, next to it
+fn lock_path(app_dir: &Path) -> PathBuf {
+    let mut name = app_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    app_dir.with_file_name(name)
+}
+
+/// `BANDEROLE_CACHE_DIR`, or `banderole` in the user's cache directory
+fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("BANDEROLE_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    BaseDirs::new().map(|dirs| dirs.cache_dir().join("banderole"))
+}
+
+/// The executable that runs the app: bundled node, or the Electron binary for Electron bundles
+fn get_runtime_executable_path(app_dir: &Path, config: &RuntimeConfig) -> PathBuf {
+    match config.runtime {
+        config::Runtime::Node => get_node_executable_path(app_dir),
+        config::Runtime::Electron => {
+            let electron_dir = app_dir.join("electron");
+            if cfg!(windows) {
+                electron_dir.join("electron.exe")
+            } else if cfg!(target_os = "macos") {
+                electron_dir.join("Electron.app").join("Contents").join("MacOS").join("Electron")
+            } else {
+                electron_dir.join("electron")
+            }
+        }
+        config::Runtime::Bun => app_dir.join("bun").join(if cfg!(windows) { "bun.exe" } else { "bun" }),
+        config::Runtime::Deno => app_dir.join("deno").join(if cfg!(windows) { "deno.exe" } else { "deno" }),
+    }
 }
 
 fn get_node_executable_path(app_dir: &Path) -> PathBuf {
@@ -122,9 +394,9 @@ fn get_node_executable_path(app_dir: &Path) -> PathBuf {
     }
 }
 
-fn is_extraction_valid(app_dir: &Path) -> Result<bool> {
+fn is_extraction_valid(app_dir: &Path, config: &RuntimeConfig) -> Result<bool> {
     let app_package_json = app_dir.join("app").join("package.json");
-    let node_executable = get_node_executable_path(app_dir);
+    let node_executable = get_runtime_executable_path(app_dir, config);
     #[cfg(windows)]
     let node_executable = node_executable
         .canonicalize()
@@ -134,54 +406,145 @@ fn is_extraction_valid(app_dir: &Path) -> Result<bool> {
     let node_exists = node_executable.exists();
     
     if !package_exists || !node_exists {
-        // Log debugging information for failed validation
-        eprintln!("Extraction validation failed:");
-        eprintln!("  App directory: {}", app_dir.display());
-        eprintln!("  Package.json exists: {} ({})", package_exists, app_package_json.display());
-        eprintln!("  Node executable exists: {} ({})", node_exists, node_executable.display());
-        
-        if let Ok(entries) = fs::read_dir(app_dir) {
-            eprintln!("  App directory contents:");
-            for entry in entries.flatten() {
-                eprintln!("    - {}", entry.file_name().to_string_lossy());
-            }
-        }
-        
-        if let Ok(entries) = fs::read_dir(app_dir.join("node")) {
-            eprintln!("  Node directory contents:");
-            for entry in entries.flatten() {
-                eprintln!("    - {}", entry.file_name().to_string_lossy());
-            }
-        }
+        // The extraction is redone, so this is only of interest when debugging
+        let listing = |dir: &Path| -> Vec<String> {
+            fs::read_dir(dir)
+                .map(|entries| entries.flatten().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect())
+                .unwrap_or_default()
+        };
+        verbose!("Extraction validation failed in {}", app_dir.display());
+        verbose!("  package.json exists: {package_exists} ({})", app_package_json.display());
+        verbose!("  runtime executable exists: {node_exists} ({})", node_executable.display());
+        verbose!("  app directory contents: {:?}", listing(app_dir));
+        verbose!("  node directory contents: {:?}", listing(&app_dir.join("node")));
     }
     
     Ok(package_exists && node_exists)
 }
 
-fn extract_application(app_dir: &Path) -> Result<()> {
-    // Remove existing directory if it exists to ensure clean extraction
-    if app_dir.exists() {
-        fs::remove_dir_all(app_dir).context("Failed to remove existing app directory")?;
+/// Whether a relative symlink at `link` pointing to `target` resolves inside `root` (lexically)
+#[cfg(unix)]
+fn symlink_stays_inside(root: &Path, link: &Path, target: &str) -> bool {
+    use std::path::Component;
+    let Some(Ok(parent)) = link.parent().map(|parent| parent.strip_prefix(root)) else {
+        return false;
+    };
+    let mut depth = parent.components().count();
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Extract the whole payload beside `app_dir` and move it into place, so a crash never leaves a
+/// half-written `app_dir`. Returns the entries deliberately left out.
+fn extract_application(app_dir: &Path, cache_dir: &Path, config: &RuntimeConfig) -> Result<Vec<String>> {
+    // Holding the lock, nobody else extracts this build: leftovers of crashed runs can go
+    remove_staging_leftovers(app_dir);
+    let runtime_shared = shared_runtime::is_extracted(cache_dir, config);
+    let deps_shared = shared_deps::is_extracted(cache_dir, config);
+    let already_shared = |name: &str| match Layer::of(name, config) {
+        Layer::App => false,
+        Layer::Deps => deps_shared,
+        Layer::Runtime => runtime_shared,
+    };
+    if let Some(required) = health::extracted_size(already_shared) {
+        disk_space::ensure_available(cache_dir, required)?;
     }
+    let staging = sibling_dir(app_dir, "tmp");
+    let extracted = extract_into(&staging, cache_dir, config);
+    let skipped = match extracted {
+        Ok(skipped) => skipped,
+        Err(e) => {
+            fs::remove_dir_all(long_path::extended(&staging)).ok();
+            return Err(e);
+        }
+    };
     
-    // Create app directory
+    // An unfinished earlier extraction is moved aside in one step rather than deleted in place
+    if app_dir.exists() {
+        let previous = sibling_dir(app_dir, "old");
+        fs::rename(app_dir, &previous).context("Failed to move the previous app directory aside")?;
+        fs::remove_dir_all(long_path::extended(&previous)).ok();
+    }
+    fs::rename(&staging, app_dir)
+        .with_context(|| format!("Failed to move {} into place", staging.display()))?;
+    Ok(skipped)
+}
+
+fn extract_into(dir: &Path, cache_dir: &Path, config: &RuntimeConfig) -> Result<Vec<String>> {
+    health::mark_in_progress(dir)?;
+    // Dependencies and runtime are linked from machine-wide copies when possible
+    let mut skipped = extract_payload(dir, &config.app_name, Layer::App, |_| true)?;
+    if !shared_deps::link(cache_dir, dir, config)? {
+        skipped.extend(extract_payload(dir, "dependencies", Layer::Deps, |_| true)?);
+    }
+    if !shared_runtime::link(cache_dir, dir, config)? {
+        skipped.extend(extract_payload(dir, config.runtime.dir_name(), Layer::Runtime, |_| true)?);
+    }
+    bin_shims::prepare(dir, config).context("Failed to prepare node_modules/.bin entries")?;
+    if preload::needed(config) {
+        preload::write(dir, config)?;
+    }
+    Ok(skipped)
+}
+
+/// `<app_dir>.<kind>-<pid>`, next to `app_dir` so renames stay on one filesystem
+fn sibling_dir(app_dir: &Path, kind: &str) -> PathBuf {
+    let mut name = app_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{kind}-{}", std::process::id()));
+    app_dir.with_file_name(name)
+}
+
+fn remove_staging_leftovers(app_dir: &Path) {
+    let (Some(parent), Some(name)) = (app_dir.parent(), app_dir.file_name()) else {
+        return;
+    };
+    let name = name.to_string_lossy();
+    let prefixes = [format!("{name}.tmp-"), format!("{name}.old-")];
+    for entry in fs::read_dir(parent).into_iter().flatten().flatten() {
+        let entry_name = entry.file_name();
+        let entry_name = entry_name.to_string_lossy();
+        if prefixes.iter().any(|prefix| entry_name.starts_with(prefix.as_str())) {
+            verbose!("Removing leftover {}", entry.path().display());
+            fs::remove_dir_all(long_path::extended(&entry.path())).ok();
+        }
+    }
+}
+
+/// Extract the payload entries whose name (without a trailing `/`) passes `select` into
+/// `app_dir`, returning the names of links skipped for pointing outside of it. `label` names
+/// what is extracted in the progress output.
+fn extract_payload(app_dir: &Path, label: &str, layer: Layer, select: impl Fn(&str) -> bool) -> Result<Vec<String>> {
+    if !layer.is_embedded() {
+        return Ok(Vec::new());
+    }
+    // Nested node_modules easily exceed MAX_PATH on Windows
+    let app_dir = &long_path::extended(app_dir);
     fs::create_dir_all(app_dir).context("Failed to create app directory")?;
     
-    // Decompress embedded XZ data to get inner ZIP, then extract
-    let mut tar_buf: Vec<u8> = Vec::new();
-    {
-        let mut reader = Cursor::new(XZ_DATA);
-        lzma_rs::xz_decompress(&mut reader, &mut tar_buf)
-            .context("Failed to decompress embedded xz data")?;
-    }
-    let cursor = Cursor::new(tar_buf);
-    let mut archive = ZipArchive::new(cursor).context("Failed to open embedded zip archive")?;
+    let mut archive = layer.open()?;
+    // Directory modes and times are applied last, once nothing is written into them anymore
+    let mut directories: Vec<(PathBuf, Option<u32>, Option<SystemTime>)> = Vec::new();
+    // Entries the bundler deduplicated share their data offset; later ones become hard links
+    let mut extracted_data: HashMap<u64, PathBuf> = HashMap::new();
+    let mut skipped = Vec::new();
+    let selected = archive
+        .file_names()
+        .filter(|name| select(name.trim_end_matches('/')))
+        .count();
+    let mut progress = progress::Progress::new(label, selected);
     
     for i in 0..archive.len() {
         let mut file = archive.by_index(i).context("Failed to read zip entry")?;
         
         // Get the file name from the zip entry
-        let file_name = file.name();
+        let file_name = file.name().to_owned();
         
         // Skip entries with invalid characters or paths
         if file_name.is_empty() || file_name.contains('\0') {
@@ -200,7 +563,7 @@ fn extract_application(app_dir: &Path) -> Result<()> {
         let clean_file_name = if is_directory {
             file_name.trim_end_matches('/')
         } else {
-            file_name
+            file_name.as_str()
         };
         
         // Skip if the cleaned name is empty (shouldn't happen but be safe)
@@ -213,9 +576,10 @@ fn extract_application(app_dir: &Path) -> Result<()> {
         let path_components: Vec<&str> = clean_file_name.split('/').filter(|s| !s.is_empty()).collect();
         
         // Skip if no valid path components
-        if path_components.is_empty() {
+        if path_components.is_empty() || !select(clean_file_name) {
             continue;
         }
+        progress.advance();
         
         let mut outpath = app_dir.to_path_buf();
         for component in path_components {
@@ -227,10 +591,32 @@ fn extract_application(app_dir: &Path) -> Result<()> {
             continue;
         }
         
+        #[cfg(unix)]
+        if file.is_symlink() {
+            // Symlink entries (node_modules links, Electron's macOS framework) hold their target as content
+            let mut target = String::new();
+            std::io::Read::read_to_string(&mut file, &mut target)
+                .with_context(|| format!("Failed to read symlink target for {}", file_name))?;
+            if !symlink_stays_inside(app_dir, &outpath, &target) {
+                verbose!("Skipping symlink {} -> {target} pointing outside the app", outpath.display());
+                skipped.push(clean_file_name.to_string());
+                continue;
+            }
+            if let Some(parent) = outpath.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create parent directory '{}'", parent.display()))?;
+            }
+            std::os::unix::fs::symlink(&target, &outpath)
+                .with_context(|| format!("Failed to create symlink {}", outpath.display()))?;
+            continue;
+        }
+        
+        let mtime = file.last_modified().and_then(zip_mtime);
         if is_directory {
             // Directory entry - create the directory
             fs::create_dir_all(&outpath)
                 .with_context(|| format!("Failed to create directory '{}' from zip entry '{}'", outpath.display(), file_name))?;
+            directories.push((outpath, file.unix_mode(), mtime));
         } else {
             // File entry - create parent directories first, then the file
             if let Some(parent) = outpath.parent() {
@@ -238,33 +624,85 @@ fn extract_application(app_dir: &Path) -> Result<()> {
                     .with_context(|| format!("Failed to create parent directory '{}' for file '{}'", parent.display(), outpath.display()))?;
             }
             
+            let data_start = file.data_start();
+            if let Some(original) = extracted_data.get(&data_start) {
+                // Without hard link support (e.g. FAT) the entry is extracted as a copy below
+                if fs::hard_link(original, &outpath).is_ok() {
+                    continue;
+                }
+            }
+            
             let mut outfile = fs::File::create(&outpath)
                 .with_context(|| format!("Failed to create output file '{}' from zip entry '{}'", outpath.display(), file_name))?;
             std::io::copy(&mut file, &mut outfile)
                 .with_context(|| format!("Failed to extract file to {}", outpath.display()))?;
+            // Keep the bundled modification time so timestamp-based caches behave like an install
+            if let Some(mtime) = mtime {
+                let _ = outfile.set_modified(mtime);
+            }
             
             // Ensure file is fully written before setting permissions
             outfile.sync_all().context("Failed to sync file to disk")?;
             drop(outfile); // Explicitly close the file
             
-            // Set executable permissions on Unix systems
+            // Restore the permission bits on Unix; setuid, setgid and sticky bits are never applied
             #[cfg(unix)]
             {
                 if let Some(mode) = file.unix_mode() {
                     use std::os::unix::fs::PermissionsExt;
-                    let permissions = std::fs::Permissions::from_mode(mode);
+                    let permissions = std::fs::Permissions::from_mode(mode & 0o777);
                     fs::set_permissions(&outpath, permissions).context("Failed to set permissions")?;
                 }
             }
+            extracted_data.entry(data_start).or_insert(outpath);
         }
     }
     
-    Ok(())
+    progress.finish();
+    
+    // Deepest first, so setting a parent's time is not undone by touching its children
+    for (dir, mode, mtime) in directories.iter().rev() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = mode {
+                // The owner keeps full access so the cache can be refreshed or removed later
+                let _ = fs::set_permissions(dir, fs::Permissions::from_mode((mode & 0o777) | 0o700));
+            }
+            if let (Some(mtime), Ok(handle)) = (mtime, fs::File::open(dir)) {
+                let _ = handle.set_modified(*mtime);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (dir, mode, mtime);
+    }
+    
+    Ok(skipped)
 }
 
-fn run_app(app_dir: &Path, args: &[String]) -> Result<()> {
+/// A zip timestamp as a point in time, read as UTC like the bundler writes it
+fn zip_mtime(time: zip::DateTime) -> Option<SystemTime> {
+    // Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's days_from_civil)
+    let month = i64::from(time.month());
+    let year = i64::from(time.year()) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + i64::from(time.day()) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = days * 86_400
+        + i64::from(time.hour()) * 3600
+        + i64::from(time.minute()) * 60
+        + i64::from(time.second());
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+fn run_app(app_dir: &Path, config: &RuntimeConfig, runner_args: &RunnerArgs) -> Result<()> {
+    let mut args = runner_args.app_args.clone();
+    // Node is started from the invoker's directory, so everything handed to it is absolute
+    let app_dir = &std::path::absolute(app_dir).unwrap_or_else(|_| app_dir.to_path_buf());
     let app_path = app_dir.join("app");
-    let node_executable = get_node_executable_path(app_dir);
+    let node_executable = get_runtime_executable_path(app_dir, config);
     
     // Verify Node.js executable exists and is accessible
     if !node_executable.exists() {
@@ -322,27 +760,141 @@ fn run_app(app_dir: &Path, args: &[String]) -> Result<()> {
         ));
     }
     
-    // Change to app directory
-    env::set_current_dir(&app_path)
-        .with_context(|| format!("Failed to change to app directory: {}", app_path.display()))?;
-    
-    // Find main script from package.json
-    let main_script = find_main_script(&app_path)?;
+    // Pick the entry script: a configured subcommand, or main from package.json
+    let selection = entry::select(config, &app_path, runner_args, &mut args)?;
+    let mut cmd_args: Vec<OsString> = Vec::new();
+    let electron = config.runtime == config::Runtime::Electron;
+    let mut runtime_args = config.runtime.script_args();
+    let main_script = match selection {
+        // Electron loads the app directory so package.json (name, main) is honoured
+        entry::EntrySelection::Script(script)
+            if electron && runner_args.entry.is_none() && config.entrypoints.is_empty() =>
+        {
+            cmd_args.push(app_path.clone().into_os_string());
+            script
+        }
+        entry::EntrySelection::Script(script) => {
+            cmd_args.push(app_path.join(&script).into_os_string());
+            script
+        }
+        entry::EntrySelection::Shell => {
+            eprintln!(
+                "banderole shell: {} {} in {}",
+                config.app_name,
+                config.app_version,
+                app_path.display()
+            );
+            runtime_args = config.runtime.repl_args();
+            "<repl>".to_string()
+        }
+        entry::EntrySelection::Npm(cli) => {
+            cmd_args.push(app_dir.join(config.runtime.dir_name()).join(cli).into_os_string());
+            "npm".to_string()
+        }
+        entry::EntrySelection::Help(code) => std::process::exit(code),
+    };
     
-    // Build command arguments
-    let mut cmd_args = vec![main_script.clone()];
+    // Build command arguments: runtime subcommand, bundle-time node args, the script, then the app's arguments
+    cmd_args.splice(
+        0..0,
+        runtime_args
+            .iter()
+            .map(OsString::from)
+            .chain(config.node_args.iter().map(OsString::from)),
+    );
     cmd_args.extend(args.iter().cloned());
     
+    // NODE_PATH as `app_env::path_list` defines it: bundled node_modules first, then inherited entries
+    let bundled_modules = app_path.join("node_modules");
+    let node_path = app_env::path_list("NODE_PATH", vec![bundled_modules.clone()], config);
+    // Bundled node and .bin tools first on PATH, so spawned `#!/usr/bin/env node` scripts work
+    let mut bin_dirs = vec![bundled_modules.join(".bin")];
+    bin_dirs.extend(node_executable.parent().map(Path::to_path_buf));
+    let path = app_env::path_list("PATH", bin_dirs, config);
+    let mut own_node_options = Vec::new();
+    if config.pnp && config.runtime == config::Runtime::Node {
+        own_node_options.push(pnp::node_options(&app_path));
+    }
+    if preload::needed(config) {
+        own_node_options.push(preload::require_option(app_dir, config)?);
+    }
+    let node_options = (!own_node_options.is_empty())
+        .then(|| {
+            let options = own_node_options.join(" ");
+            if config.isolate_env {
+                OsString::from(options)
+            } else {
+                preload::with_inherited_options(options)
+            }
+        });
+    // Full ICU data for a small-icu node, fetched once the app turned out to need it
+    let icu_data = if config.runtime == config::Runtime::Node {
+        icu::data_dir(app_dir, config, &node_executable)
+    } else {
+        None
+    };
+    verbose!("Launching {} {:?}", node_executable.display(), cmd_args);
+    
+    // Always an argv vector, never a command line for cmd.exe: on Windows std quotes each
+    // argument for CommandLineToArgvW, so quotes, `--` and non-ASCII text reach node unchanged
+    let node_command = || {
+        let mut cmd = Command::new(&node_executable);
+        app_env::isolate(&mut cmd, config);
+        cmd.args(&cmd_args)
+            .env("NODE_PATH", &node_path)
+            .env("PATH", &path)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        app_env::apply(&mut cmd, &app_path, &node_executable, config);
+        for (key, value) in &config.env {
+            if env::var_os(key).is_none() {
+                cmd.env(key, value);
+            }
+        }
+        if let Some(options) = &node_options {
+            cmd.env("NODE_OPTIONS", options);
+        }
+        if let Some(dir) = &icu_data {
+            cmd.env("NODE_ICU_DATA", dir);
+        } else if config.lazy_icu {
+            cmd.env(icu::MARKER_ENV, app_dir.join(icu::MARKER_FILE));
+        }
+        if runner_args.shell || runner_args.npm {
+            // The maintenance REPL and npm work inside the app, where relative requires resolve
+            cmd.current_dir(&app_path);
+        }
+        if electron && runner_args.shell {
+            cmd.env("ELECTRON_RUN_AS_NODE", "1");
+        }
+        // A GUI runner has no console; keep node (a console program) from opening its own.
+        // Redirected std handles are still inherited, so `app.exe > log.txt` keeps working.
+        #[cfg(all(windows, banderole_gui))]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+        cmd
+    };
+    
+    // Nothing has to happen after the app exits, so on Unix replace the runner with node.
+    // Signals then reach node directly and its exit status is reported verbatim.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = node_command().exec();
+        verbose!("exec of node failed ({err}), falling back to spawning it");
+    }
+    
     let mut last_err: Option<anyhow::Error> = None;
     let max_attempts: u32 = 8;
     let mut status: Option<std::process::ExitStatus> = None;
     for attempt in 1..=max_attempts {
-        let status_res = Command::new(&node_executable)
-            .args(&cmd_args)
-            .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status();
+        let status_res = node_command().spawn().and_then(|mut child| {
+            signals::forward_to(child.id());
+            child.wait()
+        });
         match status_res {
             Ok(s) => {
                 status = Some(s);
@@ -350,7 +902,7 @@ fn run_app(app_dir: &Path, args: &[String]) -> Result<()> {
             }
             Err(e) => {
                 last_err = Some(anyhow::anyhow!(e).context(format!(
-                    "Failed to execute Node.js application (attempt {attempt}/{max_attempts})\nExecutable: {}\nMain script: {}\nArgs: {:?}\nWorking directory: {}",
+                    "Failed to execute Node.js application (attempt {attempt}/{max_attempts})\nExecutable: {}\nMain script: {}\nArgs: {:?}\nApp directory: {}",
                     node_executable.display(),
                     main_script,
                     cmd_args,
@@ -358,7 +910,6 @@ fn run_app(app_dir: &Path, args: &[String]) -> Result<()> {
                 )));
                 #[cfg(windows)]
                 {
-                    use std::time::Duration;
                     std::thread::sleep(Duration::from_millis(50 * attempt as u64));
                 }
                 #[cfg(not(windows))]
@@ -375,23 +926,5 @@ fn run_app(app_dir: &Path, args: &[String]) -> Result<()> {
         max_attempts
     )))?;
     
-    std::process::exit(status.code().unwrap_or(1));
-}
-
-fn find_main_script(app_path: &Path) -> Result<String> {
-    let package_json_path = app_path.join("package.json");
-    
-    if package_json_path.exists() {
-        let package_content = fs::read_to_string(&package_json_path)
-            .context("Failed to read package.json")?;
-        
-        if let Ok(package_json) = serde_json::from_str::<serde_json::Value>(&package_content) {
-            if let Some(main) = package_json["main"].as_str() {
-                return Ok(main.to_string());
-            }
-        }
-    }
-    
-    // Default to index.js
-    Ok("index.js".to_string())
+    std::process::exit(signals::exit_code(&status));
 }
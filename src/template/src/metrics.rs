@@ -0,0 +1,157 @@
+//! Opt-in cold-start metrics.
+//!
+//! When a metrics file or command is configured (at bundle time, or for the file through
+//! `BANDEROLE_METRICS_FILE` on the machine), the runner appends one JSON line per launch with the
+//! time spent waiting for the extraction lock, extracting, and in total before the app starts.
+//! Recording never fails or delays the app. The command is only ever taken from the bundle, so
+//! the environment cannot make every bundled app run something.
+
+use crate::config::RuntimeConfig;
+use directories::BaseDirs;
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where the runner's own startup time goes
+pub struct Timings {
+    started: Instant,
+    /// Waiting for another process holding the extraction lock
+    pub lock_wait: Option<Duration>,
+    /// Extracting the payload (only on the run that extracted it)
+    pub extraction: Option<Duration>,
+}
+
+impl Timings {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            lock_wait: None,
+            extraction: None,
+        }
+    }
+}
+
+/// The machine's metrics file overrides the bundle's, so operators can collect metrics without
+/// rebundling
+fn metrics_file(bundled: &Option<String>) -> Option<String> {
+    std::env::var("BANDEROLE_METRICS_FILE")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(|| bundled.clone())
+}
+
+/// `~/` expanded to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn millis(duration: Option<Duration>) -> Option<u64> {
+    duration.map(|duration| duration.as_millis() as u64)
+}
+
+/// Append this launch's timings to the metrics file and hand them to the metrics command
+pub fn record(config: &RuntimeConfig, build_id: &str, timings: &Timings) {
+    let file = metrics_file(&config.metrics_file);
+    let command = config.metrics_command.as_deref();
+    if file.is_none() && command.is_none() {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    let line = json!({
+        "app": config.app_name,
+        "version": config.app_version,
+        "buildId": build_id,
+        "timestamp": timestamp,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "extracted": timings.extraction.is_some(),
+        "lockWaitMs": millis(timings.lock_wait),
+        "extractMs": millis(timings.extraction),
+        "startupMs": timings.started.elapsed().as_millis() as u64,
+    })
+    .to_string();
+
+    if let Some(file) = file {
+        let path = expand_home(&file);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+            })
+            .and_then(|mut out| writeln!(out, "{line}"));
+        if let Err(e) = written {
+            verbose!("Failed to write metrics to {}: {e}", path.display());
+        }
+    }
+    if let Some(command) = command {
+        if let Err(e) = start_detached(command, &line) {
+            verbose!("Failed to run metrics command {command}: {e}");
+        }
+    }
+}
+
+/// Start the metrics command alongside the app. On Unix the runner is replaced by node, which
+/// never reaps children it did not start, so an intermediate shell backgrounds the command and is
+/// waited for at once; the orphaned command is then reaped by init.
+fn start_detached(command: &str, line: &str) -> std::io::Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell
+            .arg("-c")
+            .arg(r#"sh -c "$1" &"#)
+            .arg("banderole-metrics")
+            .arg(command);
+        shell
+    };
+    shell
+        .env("BANDEROLE_METRICS", line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if cfg!(windows) {
+        // Windows has no zombies; dropping the handle leaves the command running
+        shell.spawn().map(drop)
+    } else {
+        shell.status().map(drop)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_detached_returns_before_the_command_finishes() {
+        let out = std::env::temp_dir().join(format!("banderole-metrics-{}", std::process::id()));
+        let _ = fs::remove_file(&out);
+        let command = format!("sleep 1; printf %s \"$BANDEROLE_METRICS\" > '{}'", out.display());
+
+        let started = Instant::now();
+        start_detached(&command, "{\"startupMs\":1}").unwrap();
+        assert!(started.elapsed() < Duration::from_millis(900));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while fs::read_to_string(&out).unwrap_or_default().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(fs::read_to_string(&out).unwrap(), "{\"startupMs\":1}");
+        fs::remove_file(&out).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::Notice;
+
+/// Environment variable that accepts the notice non-interactively (e.g. in provisioning scripts)
+const ACCEPT_ENV: &str = "BANDEROLE_ACCEPT_NOTICE";
+
+/// Show the embedded notice on the first run per machine, asking for acceptance if required.
+///
+/// A marker keyed by app name and notice text is recorded in the cache directory, so the notice
+/// is shown again only when its text changes.
+pub fn ensure_acknowledged(notice: &Notice, app_name: &str, cache_dir: &Path, accepted: bool) -> Result<()> {
+    let marker = marker_path(notice, app_name, cache_dir);
+    if marker.exists() {
+        return Ok(());
+    }
+
+    eprintln!("{}", notice.text.trim_end());
+    eprintln!();
+
+    if notice.require_acceptance {
+        let accepted = accepted || accepted_via_env() || prompt_acceptance()?;
+        anyhow::ensure!(
+            accepted,
+            "The notice above must be accepted before {app_name} can run. Re-run interactively, pass --banderole-accept-notice, or set {ACCEPT_ENV}=1."
+        );
+    }
+
+    if let Some(parent) = marker.parent() {
+        fs::create_dir_all(parent).context("Failed to create notice marker directory")?;
+    }
+    fs::write(&marker, if notice.require_acceptance { "accepted" } else { "shown" })
+        .with_context(|| format!("Failed to record notice marker at {}", marker.display()))?;
+    Ok(())
+}
+
+fn marker_path(notice: &Notice, app_name: &str, cache_dir: &Path) -> PathBuf {
+    let digest = Sha256::digest(notice.text.as_bytes());
+    let hash: String = digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+    cache_dir.join("notices").join(format!("{app_name}-{hash}"))
+}
+
+fn accepted_via_env() -> bool {
+    std::env::var(ACCEPT_ENV)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+fn prompt_acceptance() -> Result<bool> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(false);
+    }
+    eprint!("Do you accept these terms? [y/N] ");
+    std::io::stderr().flush().ok();
+    let mut answer = String::new();
+    stdin.lock().read_line(&mut answer).context("Failed to read answer")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
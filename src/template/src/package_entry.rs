@@ -0,0 +1,90 @@
+//! Entry resolution for a package directory, following Node's rules closely enough to launch it.
+//!
+//! Shared by the bundler (compiled via `#[path]`) and the runner so both agree on the entry.
+
+use serde_json::Value;
+use std::path::Path;
+
+/// Extensions probed for extensionless `main` values, in Node's order plus the ESM variants
+const SCRIPT_EXTENSIONS: &[&str] = &["js", "mjs", "cjs"];
+
+/// Resolve the script Node would run for this package: `exports["."]`, then `main`, then
+/// `index.{js,mjs,cjs}`. Returns a path relative to `dir` using '/' separators.
+pub fn resolve(dir: &Path, package_json: &Value) -> Option<String> {
+    let is_module = package_json["type"].as_str() == Some("module");
+
+    if let Some(target) = root_export(&package_json["exports"]) {
+        if let Some(script) = resolve_export_target(dir, target, is_module) {
+            return Some(script);
+        }
+    }
+
+    if let Some(main) = package_json["main"].as_str() {
+        if let Some(script) = probe(dir, main) {
+            return Some(script);
+        }
+    }
+
+    probe(dir, "index")
+}
+
+/// The `"."` subpath of an `exports` field (string, array, or condition object)
+fn root_export(exports: &Value) -> Option<&Value> {
+    match exports {
+        Value::Null => None,
+        Value::Object(map) if map.keys().any(|key| key.starts_with('.')) => map.get("."),
+        other => Some(other),
+    }
+}
+
+fn resolve_export_target(dir: &Path, target: &Value, is_module: bool) -> Option<String> {
+    match target {
+        Value::String(path) if path.starts_with("./") => {
+            let path = path.trim_start_matches("./");
+            dir.join(path).is_file().then(|| path.to_string())
+        }
+        Value::Array(targets) => targets
+            .iter()
+            .find_map(|target| resolve_export_target(dir, target, is_module)),
+        Value::Object(conditions) => {
+            // serde_json does not keep key order, so use a fixed priority for the conditions
+            // Node would match when launching the package as an app
+            let preferred: &[&str] = if is_module {
+                &["node", "import", "default", "require"]
+            } else {
+                &["node", "require", "default", "import"]
+            };
+            preferred
+                .iter()
+                .filter_map(|condition| conditions.get(*condition))
+                .find_map(|target| resolve_export_target(dir, target, is_module))
+        }
+        _ => None,
+    }
+}
+
+/// Find `path` as a file, with a script extension appended, or as a directory with an index file
+fn probe(dir: &Path, path: &str) -> Option<String> {
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    if path.is_empty() {
+        return probe(dir, "index");
+    }
+    if dir.join(path).is_file() {
+        return Some(path.to_string());
+    }
+    for ext in SCRIPT_EXTENSIONS {
+        let candidate = format!("{path}.{ext}");
+        if dir.join(&candidate).is_file() {
+            return Some(candidate);
+        }
+    }
+    if dir.join(path).is_dir() {
+        for ext in SCRIPT_EXTENSIONS {
+            let candidate = format!("{path}/index.{ext}");
+            if dir.join(&candidate).is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,70 @@
+//! Encryption of the embedded payload (`banderole bundle --encrypt`): AES-256-GCM under a key
+//! derived from the passphrase and a per-bundle salt with PBKDF2-HMAC-SHA256, with the header
+//! authenticated as associated data.
+//!
+//! Only the payload layers are encrypted. The bundle manifest embedded next to them stays
+//! readable, since the runner checks extractions against it without the key: the paths, sizes
+//! and SHA-256 digests of the bundled files are visible to anyone holding the executable.
+//!
+//! Shared by the bundler (compiled via `#[path]`) and the runner; the bundler encrypts with
+//! [`KEY_LEN`]-byte keys from [`derive_key`] and random salts and nonces.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{ensure, Result};
+use sha2::Sha256;
+
+/// Leads every encrypted payload; plain payloads start with the xz magic instead
+pub const MAGIC: &[u8; 8] = b"BDRLENC2";
+pub const SALT_LEN: usize = 16;
+pub const NONCE_LEN: usize = 12;
+pub const KEY_LEN: usize = 32;
+/// `MAGIC`, salt and nonce, followed by the ciphertext and its tag
+pub const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+pub fn is_encrypted(payload: &[u8]) -> bool {
+    payload.starts_with(MAGIC)
+}
+
+/// The plain payload, or an error when `passphrase` is wrong or the payload was modified
+pub fn decrypt(payload: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    ensure!(
+        is_encrypted(payload) && payload.len() >= HEADER_LEN + TAG_LEN,
+        "The payload is not encrypted by this banderole"
+    );
+    let (header, ciphertext) = payload.split_at(HEADER_LEN);
+    let salt = &header[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = &header[MAGIC.len() + SALT_LEN..];
+    let key = derive_key(passphrase, salt);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("Wrong encryption key, or the payload was modified"))
+}
+
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_rejects_plain_and_truncated_payloads() {
+        assert!(decrypt(b"\xfd7zXZ\0plain", "secret").is_err());
+        assert!(decrypt(MAGIC, "secret").is_err());
+        let mut payload = MAGIC.to_vec();
+        payload.resize(HEADER_LEN + TAG_LEN, 0);
+        assert!(decrypt(&payload, "secret").is_err());
+    }
+}
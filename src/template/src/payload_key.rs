@@ -0,0 +1,70 @@
+//! The passphrase of an encrypted payload, looked up only when the payload is read (extraction,
+//! repair, `--banderole-verify`); launching an existing extraction needs no key.
+//!
+//! Sources, first match wins: `BANDEROLE_ENCRYPTION_KEY`, the file named by
+//! `BANDEROLE_ENCRYPTION_KEY_FILE` or the bundle's `encryptionKeyFile`, then the OS keychain
+//! (service `banderole`, account = app name) on macOS and Linux.
+
+use crate::config::RuntimeConfig;
+use anyhow::{bail, Context, Result};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Looked up once per run; the keychain may prompt
+static KEY: OnceLock<String> = OnceLock::new();
+
+/// Decrypt the embedded payload
+pub fn decrypt(payload: &[u8]) -> Result<Vec<u8>> {
+    let key = match KEY.get() {
+        Some(key) => key,
+        None => {
+            let key = lookup()?;
+            KEY.get_or_init(|| key)
+        }
+    };
+    crate::payload_crypto::decrypt(payload, key)
+}
+
+fn lookup() -> Result<String> {
+    let config = RuntimeConfig::embedded()?;
+    if let Some(key) = std::env::var("BANDEROLE_ENCRYPTION_KEY").ok().filter(|key| !key.is_empty()) {
+        return Ok(key);
+    }
+    let key_file = std::env::var("BANDEROLE_ENCRYPTION_KEY_FILE")
+        .ok()
+        .filter(|file| !file.is_empty())
+        .or_else(|| config.encryption_key_file.clone());
+    if let Some(file) = key_file {
+        let path = crate::metrics::expand_home(&file);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read the encryption key from {}", path.display()))?;
+        return Ok(content.lines().next().unwrap_or_default().to_string());
+    }
+    if let Some(key) = keychain(&config.app_name) {
+        verbose!("Using the encryption key from the keychain");
+        return Ok(key);
+    }
+    bail!(
+        "{} is encrypted: provide its key in BANDEROLE_ENCRYPTION_KEY, in a file named by BANDEROLE_ENCRYPTION_KEY_FILE, or in the keychain (service `banderole`, account `{}`)",
+        config.app_name,
+        config.app_name
+    )
+}
+
+fn keychain(app_name: &str) -> Option<String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(["find-generic-password", "-s", "banderole", "-a", app_name, "-w"]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", "banderole", "account", app_name]);
+        command
+    } else {
+        return None;
+    };
+    let output = command.stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    let key = String::from_utf8(output.stdout).ok()?;
+    let key = key.lines().next()?.to_string();
+    (output.status.success() && !key.is_empty()).then_some(key)
+}
@@ -0,0 +1,35 @@
+use crate::preload::quote;
+use std::path::Path;
+
+/// Yarn's ESM loader, written next to `.pnp.cjs` by installs that support ESM
+const LOADER: &str = ".pnp.loader.mjs";
+
+/// `NODE_OPTIONS` words installing Yarn's Plug'n'Play runtime from the extracted app, like
+/// `yarn node` does: `.pnp.cjs` patches `require`, the loader resolves `import`
+pub fn node_options(app_path: &Path) -> String {
+    let mut options = format!(
+        "--require {}",
+        quote(&app_path.join(".pnp.cjs").to_string_lossy())
+    );
+    let loader = app_path.join(LOADER);
+    if loader.is_file() {
+        options.push_str(" --experimental-loader ");
+        options.push_str(&quote(&file_url(&loader)));
+    }
+    options
+}
+
+/// `file:` URL of an absolute path; loaders are resolved as URLs, so Windows paths need one
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b':' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
@@ -0,0 +1,236 @@
+//! The bundle's post-extract command (`postExtract`), run once per extraction before the app
+//! directory is marked ready.
+//!
+//! It runs through the shell from the app directory with the bundled runtime and
+//! `node_modules/.bin` first on `PATH`, so `node scripts/postextract.js` or `npm rebuild` use the
+//! bundled tools. A failing command leaves the extraction unfinished and the next launch retries.
+//!
+//! What the command wrote, replaced or deleted outside `mutablePaths` is recorded next to the
+//! ready file, so health checks and `--banderole-verify` compare against the state it left
+//! behind instead of reporting its work as damage.
+
+use crate::config::RuntimeConfig;
+use crate::health::{Manifest, ManifestFile};
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Lists the command's changes: `<sha256> <size> <path>` per written file, `- - <path>` per
+/// deleted one
+pub const CHANGES_FILE: &str = ".post-extract";
+
+/// Extracted files the command changed, keyed by path: what it left behind, or `None` when it
+/// deleted the file
+pub type Changes = BTreeMap<String, Option<ManifestFile>>;
+
+/// Run the command in a finished extraction that left out the `skipped` payload entries, then
+/// record what it changed
+pub fn run(app_dir: &Path, config: &RuntimeConfig, command: &str, skipped: &[String]) -> Result<()> {
+    let app_path = app_dir.join("app");
+    let runtime = crate::get_runtime_executable_path(app_dir, config);
+    let bundled_modules = app_path.join("node_modules");
+    let mut bin_dirs = vec![bundled_modules.join(".bin")];
+    bin_dirs.extend(runtime.parent().map(Path::to_path_buf));
+
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    crate::app_env::isolate(&mut shell, config);
+    shell
+        .arg(command)
+        .current_dir(&app_path)
+        .env("PATH", crate::app_env::path_list("PATH", bin_dirs, config))
+        .env("NODE_PATH", crate::app_env::path_list("NODE_PATH", vec![bundled_modules], config))
+        .stdin(Stdio::null())
+        // Keep stdout for the app: a piped first run must not start with the hook's output
+        .stdout(io::stderr())
+        .stderr(Stdio::inherit());
+    crate::app_env::apply(&mut shell, &app_path, &runtime, config);
+    for (key, value) in &config.env {
+        if env::var_os(key).is_none() {
+            shell.env(key, value);
+        }
+    }
+    if config.runtime == crate::config::Runtime::Electron {
+        shell.env("ELECTRON_RUN_AS_NODE", "1");
+    }
+
+    verbose!("Running post-extract command: {command}");
+    let status = shell
+        .status()
+        .with_context(|| format!("Failed to start post-extract command `{command}`"))?;
+    if !status.success() {
+        bail!("Post-extract command `{command}` failed ({status}); it runs again on the next launch");
+    }
+    match crate::health::embedded_manifest() {
+        Some(manifest) => record(app_dir, config, &manifest, skipped),
+        None => Ok(()),
+    }
+}
+
+/// Compare the extraction with the manifest and write the differences to [`CHANGES_FILE`]
+fn record(app_dir: &Path, config: &RuntimeConfig, manifest: &Manifest, skipped: &[String]) -> Result<()> {
+    let changes = changes_since_extraction(app_dir, config, manifest, skipped)?;
+    let mut contents = String::new();
+    for (name, file) in &changes {
+        match file {
+            Some(file) => writeln!(contents, "{} {} {name}", file.sha256, file.size),
+            None => writeln!(contents, "- - {name}"),
+        }
+        .expect("writing to a String cannot fail");
+    }
+    if !changes.is_empty() {
+        verbose!("Post-extract command changed {} files", changes.len());
+    }
+    let path = app_dir.join(CHANGES_FILE);
+    fs::write(&path, contents)
+        .with_context(|| format!("Failed to record the post-extract changes in {}", path.display()))
+}
+
+fn changes_since_extraction(
+    app_dir: &Path,
+    config: &RuntimeConfig,
+    manifest: &Manifest,
+    skipped: &[String],
+) -> Result<Changes> {
+    let root = crate::long_path::extended(app_dir);
+    let skipped: HashSet<&str> = skipped.iter().map(String::as_str).collect();
+    let mut changes = Changes::new();
+    for (name, file) in &manifest.files {
+        if skipped.contains(name.as_str()) || crate::integrity::is_exempt(&config.mutable_paths, Path::new(name)) {
+            continue;
+        }
+        match crate::integrity::read_extracted(&root.join(name)) {
+            Ok(bytes) => {
+                let written = digest(&bytes);
+                if written.size != file.size || written.sha256 != file.sha256 {
+                    changes.insert(name.clone(), Some(written));
+                }
+            }
+            Err(_) => {
+                changes.insert(name.clone(), None);
+            }
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(&root).min_depth(1) {
+        let entry = entry.context("Failed to walk extracted app")?;
+        let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        if entry.file_type().is_dir()
+            || crate::integrity::is_runner_file(app_dir, relative)
+            || crate::shared_runtime::is_link(app_dir, relative, config)
+        {
+            continue;
+        }
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if manifest.files.contains_key(&name) || crate::integrity::is_exempt(&config.mutable_paths, relative) {
+            continue;
+        }
+        let bytes = crate::integrity::read_extracted(entry.path())
+            .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+        changes.insert(name, Some(digest(&bytes)));
+    }
+    Ok(changes)
+}
+
+fn digest(bytes: &[u8]) -> ManifestFile {
+    ManifestFile {
+        size: bytes.len() as u64,
+        sha256: format!("{:x}", Sha256::digest(bytes)),
+    }
+}
+
+/// The changes recorded for an extraction, empty when it has no post-extract command
+pub fn changes(app_dir: &Path) -> Changes {
+    let contents = fs::read_to_string(app_dir.join(CHANGES_FILE)).unwrap_or_default();
+    contents.lines().filter_map(parse_change).collect()
+}
+
+fn parse_change(line: &str) -> Option<(String, Option<ManifestFile>)> {
+    let mut fields = line.splitn(3, ' ');
+    let (sha256, size, name) = (fields.next()?, fields.next()?, fields.next()?);
+    if sha256 == "-" {
+        return Some((name.to_string(), None));
+    }
+    let file = ManifestFile {
+        size: size.parse().ok()?,
+        sha256: sha256.to_string(),
+    };
+    Some((name.to_string(), Some(file)))
+}
+
+/// The files an extraction holds once the command ran: the manifest's, with the recorded
+/// changes applied
+pub fn expected_files<'a>(manifest: &'a Manifest, changes: &'a Changes) -> BTreeMap<&'a str, &'a ManifestFile> {
+    let mut files: BTreeMap<&str, &ManifestFile> =
+        manifest.files.iter().map(|(name, file)| (name.as_str(), file)).collect();
+    for (name, change) in changes {
+        match change {
+            Some(file) => files.insert(name, file),
+            None => files.remove(name.as_str()),
+        };
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_lists_what_the_command_changed() {
+        let app_dir = std::env::temp_dir().join(format!("banderole-post-extract-{}", std::process::id()));
+        fs::create_dir_all(app_dir.join("app/cache")).unwrap();
+        fs::write(app_dir.join("app/index.js"), "index").unwrap();
+        fs::write(app_dir.join("app/config.json"), "configured").unwrap();
+        fs::write(app_dir.join("app/generated.js"), "generated").unwrap();
+        fs::write(app_dir.join("app/cache/entry"), "cached").unwrap();
+        fs::write(app_dir.join(crate::health::SENTINEL), "").unwrap();
+        let manifest = Manifest {
+            files: [("app/index.js", "index"), ("app/config.json", "{}"), ("app/removed.txt", ""), ("app/skipped.txt", "")]
+                .into_iter()
+                .map(|(name, contents)| (name.to_string(), digest(contents.as_bytes())))
+                .collect(),
+        };
+        let config = RuntimeConfig {
+            mutable_paths: vec!["cache".to_string()],
+            ..RuntimeConfig::default()
+        };
+
+        record(&app_dir, &config, &manifest, &["app/skipped.txt".to_string()]).unwrap();
+        let changes = changes(&app_dir);
+        fs::remove_dir_all(&app_dir).ok();
+        let summary: Vec<(&str, Option<u64>)> = changes
+            .iter()
+            .map(|(name, file)| (name.as_str(), file.as_ref().map(|file| file.size)))
+            .collect();
+        assert_eq!(
+            summary,
+            [("app/config.json", Some(10)), ("app/generated.js", Some(9)), ("app/removed.txt", None)]
+        );
+        assert_eq!(changes["app/generated.js"].as_ref().unwrap().sha256, digest(b"generated").sha256);
+
+        let expected = expected_files(&manifest, &changes);
+        assert_eq!(
+            expected.keys().copied().collect::<Vec<_>>(),
+            ["app/config.json", "app/generated.js", "app/index.js", "app/skipped.txt"]
+        );
+        assert_eq!(expected["app/config.json"].size, 10);
+    }
+}
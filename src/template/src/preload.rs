@@ -0,0 +1,134 @@
+use crate::config::{NodeBuild, Runtime, RuntimeConfig};
+use anyhow::{Context, Result};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Written next to `.ready` in the extraction directory, outside the bundled app
+pub const FILE_NAME: &str = "banderole-preload.cjs";
+
+const HEADER: &str = "// Generated by banderole.\n'use strict';\n";
+
+/// Keeps process.execPath on the bundled node so that child_process.fork(),
+/// spawn(process.execPath) and worker pools launch node, not the runner.
+const EXEC_PATH_SHIM: &str = r#"
+const nodePath = process.env.BANDEROLE_NODE_PATH;
+if (nodePath && process.execPath !== nodePath) {
+  try {
+    Object.defineProperty(process, 'execPath', {
+      value: nodePath,
+      writable: true,
+      configurable: true,
+      enumerable: true,
+    });
+  } catch (_) {}
+}
+"#;
+
+/// Warns once when the app asks `Intl` for locales a small-icu node cannot serve
+const SMALL_ICU_CHECK: &str = r#"
+(() => {
+  let warned = false;
+  const warn = (locales) => {
+    if (warned) return;
+    warned = true;
+    const marker = process.env.BANDEROLE_ICU_MARKER;
+    if (marker) {
+      try { require('fs').writeFileSync(marker, process.versions.icu); } catch (_) {}
+      process.emitWarning(`Locale data for ${locales} is not available yet: full ICU data will be downloaded the next time the app starts.`, 'BanderoleWarning');
+      return;
+    }
+    process.emitWarning(`Locale data for ${locales} is not available: this bundle ships a small-icu Node.js build. Rebundle with --node-build full for complete internationalization.`, 'BanderoleWarning');
+  };
+  for (const name of ['Collator', 'DateTimeFormat', 'DisplayNames', 'ListFormat', 'NumberFormat', 'PluralRules', 'RelativeTimeFormat', 'Segmenter']) {
+    const original = Intl[name];
+    if (typeof original !== 'function') continue;
+    const check = (locales) => {
+      if (locales === undefined || warned) return;
+      try {
+        const requested = [].concat(locales);
+        if (original.supportedLocalesOf(requested).length < requested.length) warn(requested.join(', '));
+      } catch (_) {}
+    };
+    Intl[name] = new Proxy(original, {
+      construct(target, args, newTarget) { check(args[0]); return Reflect.construct(target, args, newTarget); },
+      apply(target, self, args) { check(args[0]); return Reflect.apply(target, self, args); },
+    });
+  }
+})();
+"#;
+
+/// Warns when the app touches `Intl` on a node built without it
+const WITHOUT_INTL_CHECK: &str = r#"
+if (typeof globalThis.Intl === 'undefined') {
+  let warned = false;
+  Object.defineProperty(globalThis, 'Intl', {
+    configurable: true,
+    get() {
+      if (!warned) {
+        warned = true;
+        process.emitWarning('Intl is not available: this bundle ships a Node.js build without ICU. Rebundle with --node-build full for internationalization support.', 'BanderoleWarning');
+      }
+      return undefined;
+    },
+  });
+}
+"#;
+
+/// Whether the runner has to preload a script for this bundle
+pub fn needed(config: &RuntimeConfig) -> bool {
+    config.exec_path_shim
+        || (config.runtime == Runtime::Node
+            && matches!(config.node_build, NodeBuild::SmallIcu | NodeBuild::WithoutIntl))
+}
+
+fn script(config: &RuntimeConfig) -> String {
+    let mut script = HEADER.to_string();
+    if config.exec_path_shim {
+        script.push_str(EXEC_PATH_SHIM);
+    }
+    match config.node_build {
+        _ if config.runtime != Runtime::Node => {}
+        NodeBuild::Full | NodeBuild::PointerCompression | NodeBuild::Debug => {}
+        NodeBuild::SmallIcu => script.push_str(SMALL_ICU_CHECK),
+        NodeBuild::WithoutIntl => script.push_str(WITHOUT_INTL_CHECK),
+    }
+    script
+}
+
+/// Write the preload script into the extraction unless it is already current.
+///
+/// Extraction writes it up front, so launches from a read-only (system-wide) cache find it there.
+pub fn write(app_dir: &Path, config: &RuntimeConfig) -> Result<PathBuf> {
+    let content = script(config);
+    let script = app_dir.join(FILE_NAME);
+    if fs::read_to_string(&script).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&script, &content)
+            .with_context(|| format!("Failed to write {}", script.display()))?;
+    }
+    Ok(script)
+}
+
+/// Write the preload script and return the `--require` option loading it.
+///
+/// Children inherit `NODE_OPTIONS`, so forks and workers keep the shim without touching execArgv.
+pub fn require_option(app_dir: &Path, config: &RuntimeConfig) -> Result<String> {
+    let script = write(app_dir, config)?;
+    Ok(format!("--require {}", quote(&script.to_string_lossy())))
+}
+
+/// `value` as a double-quoted `NODE_OPTIONS` word, which honours backslash escapes
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `options` followed by the caller's own `NODE_OPTIONS`
+pub fn with_inherited_options(options: String) -> OsString {
+    let mut options = OsString::from(options);
+    if let Some(existing) = env::var_os("NODE_OPTIONS").filter(|value| !value.is_empty()) {
+        options.push(" ");
+        options.push(existing);
+    }
+    options
+}
@@ -0,0 +1,84 @@
+//! Extraction progress on stderr, so a large bundle does not look frozen on its first launch.
+//!
+//! Shown on a terminal once extraction takes more than a moment. `BANDEROLE_PROGRESS=0` turns it
+//! off; `BANDEROLE_PROGRESS=1` prints a line every 10% even when stderr is not a terminal (CI logs).
+
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Quick extractions finish without any output
+const DELAY: Duration = Duration::from_millis(500);
+const BAR_WIDTH: usize = 30;
+
+#[derive(PartialEq)]
+enum Mode {
+    Off,
+    Bar,
+    Lines,
+}
+
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: usize,
+    mode: Mode,
+    started: Instant,
+    /// Last percentage printed
+    shown: Option<usize>,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: usize) -> Self {
+        let terminal = std::io::stderr().is_terminal();
+        let mode = match std::env::var("BANDEROLE_PROGRESS").as_deref() {
+            Ok("0") => Mode::Off,
+            Ok("1") if !terminal => Mode::Lines,
+            _ if terminal => Mode::Bar,
+            _ => Mode::Off,
+        };
+        Self {
+            label: label.to_string(),
+            total: total.max(1),
+            done: 0,
+            mode,
+            started: Instant::now(),
+            shown: None,
+        }
+    }
+
+    /// One more entry extracted
+    pub fn advance(&mut self) {
+        self.done += 1;
+        if self.mode == Mode::Off || self.started.elapsed() < DELAY {
+            return;
+        }
+        let percent = (self.done * 100 / self.total).min(100);
+        match self.mode {
+            Mode::Bar if self.shown != Some(percent) => {
+                let filled = percent * BAR_WIDTH / 100;
+                eprint!(
+                    "\rExtracting {} [{}{}] {percent:>3}%",
+                    self.label,
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled)
+                );
+                let _ = std::io::stderr().flush();
+            }
+            Mode::Lines if self.shown.is_none_or(|shown| percent / 10 > shown / 10) => {
+                eprintln!("Extracting {}... {percent}%", self.label);
+            }
+            _ => return,
+        }
+        self.shown = Some(percent);
+    }
+
+    /// Clear the bar, leaving the terminal to the app
+    pub fn finish(&mut self) {
+        if self.mode == Mode::Bar && self.shown.is_some() {
+            let width = "Extracting  [] 100%".len() + self.label.len() + BAR_WIDTH;
+            eprint!("\r{}\r", " ".repeat(width));
+            let _ = std::io::stderr().flush();
+        }
+        self.shown = None;
+    }
+}
@@ -0,0 +1,362 @@
+//! Runtime of an `--external-runtime` bundle: the release archive pinned at bundle time is
+//! downloaded on first use, checked against its SHA-256 and unpacked for `shared_runtime`.
+//!
+//! The runner has no HTTP client, so the download goes through `curl` (shipped with Windows 10
+//! and later), `wget` or PowerShell, whichever works first.
+
+use crate::config::ExternalRuntime;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Download `runtime` and unpack its contents into `dir`, without the archive's top-level folder
+pub fn fetch(runtime: &ExternalRuntime, dir: &Path) -> Result<()> {
+    let parent = dir.parent().unwrap_or(dir);
+    let archive = dir.with_extension("download");
+    eprintln!("banderole: downloading the runtime (once) from {}", runtime.url);
+    let result = download(&runtime.url, &archive)
+        .and_then(|()| verify(&archive, &runtime.sha256, &runtime.url))
+        .and_then(|()| {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            if runtime.url.ends_with(".zip") {
+                unpack_zip(&archive, dir)
+            } else if runtime.url.ends_with(".tar.xz") {
+                unpack_tar_xz(&archive, dir, parent)
+            } else {
+                bail!("Unsupported runtime archive {} (expected .tar.xz or .zip)", runtime.url)
+            }
+        });
+    fs::remove_file(&archive).ok();
+    result
+}
+
+fn download(url: &str, dest: &Path) -> Result<()> {
+    let mut attempts: Vec<Command> = Vec::new();
+    let mut curl = Command::new("curl");
+    curl.args(["-fsSL", "--retry", "3", "-o"]).arg(dest).arg(url);
+    attempts.push(curl);
+    let mut wget = Command::new("wget");
+    wget.args(["-q", "-O"]).arg(dest).arg(url);
+    attempts.push(wget);
+    if cfg!(windows) {
+        let mut powershell = Command::new("powershell");
+        powershell
+            .args(["-NoProfile", "-NonInteractive", "-Command"])
+            .arg("$ProgressPreference = 'SilentlyContinue'; Invoke-WebRequest -UseBasicParsing -Uri $env:BANDEROLE_URL -OutFile $env:BANDEROLE_DEST")
+            .env("BANDEROLE_URL", url)
+            .env("BANDEROLE_DEST", dest);
+        attempts.push(powershell);
+    }
+
+    let mut failures = Vec::new();
+    for mut command in attempts {
+        let program = command.get_program().to_string_lossy().into_owned();
+        match command.stdin(Stdio::null()).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => failures.push(format!("{program} {status}")),
+            // Not installed: try the next one
+            Err(e) => failures.push(format!("{program}: {e}")),
+        }
+    }
+    bail!("Failed to download {url} ({}); install curl or check the network connection", failures.join(", "))
+}
+
+fn verify(archive: &Path, expected: &str, url: &str) -> Result<()> {
+    let mut file = fs::File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).context("Failed to hash the downloaded runtime")?;
+    let actual = format!("{:x}", hasher.finalize());
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("The runtime downloaded from {url} has SHA-256 {actual}, but the bundle expects {expected}; the download is corrupted or was tampered with");
+    }
+    Ok(())
+}
+
+fn unpack_zip(archive: &Path, dir: &Path) -> Result<()> {
+    let file = fs::File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file)).context("Failed to open the runtime archive")?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).context("Failed to read the runtime archive")?;
+        let Some(path) = entry.enclosed_name().and_then(|name| strip_top_level(&name)) else {
+            continue;
+        };
+        let path = dir.join(path);
+        if entry.is_dir() {
+            fs::create_dir_all(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut out = BufWriter::new(fs::File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?);
+        io::copy(&mut entry, &mut out).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Decompress into a temporary tar next to the extraction (a Node.js tarball is ~200 MB
+/// uncompressed) and unpack that
+fn unpack_tar_xz(archive: &Path, dir: &Path, temp_dir: &Path) -> Result<()> {
+    let tar_path = temp_dir.join(format!("runtime-{}.tar", std::process::id()));
+    let decompressed = (|| -> Result<()> {
+        let mut input = BufReader::new(fs::File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?);
+        let mut output = BufWriter::new(fs::File::create(&tar_path).with_context(|| format!("Failed to create {}", tar_path.display()))?);
+        lzma_rs::xz_decompress(&mut input, &mut output).context("Failed to decompress the runtime archive")?;
+        Ok(())
+    })();
+    let result = decompressed.and_then(|()| {
+        let tar = fs::File::open(&tar_path).with_context(|| format!("Failed to open {}", tar_path.display()))?;
+        unpack_tar(BufReader::new(tar), dir)
+    });
+    fs::remove_file(&tar_path).ok();
+    result
+}
+
+/// Unpack a ustar/pax/GNU tar stream: regular files, directories, symlinks and hard links
+fn unpack_tar(mut tar: impl Read, dir: &Path) -> Result<()> {
+    let mut header = [0u8; 512];
+    // Set by a preceding pax or GNU long-name entry for the next header only
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+    loop {
+        tar.read_exact(&mut header).context("Truncated runtime archive")?;
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+        let size = octal(&header[124..136])?;
+        let kind = header[156];
+        let mut data = Vec::new();
+        if matches!(kind, b'x' | b'L' | b'K' | b'g') {
+            (&mut tar).take(size).read_to_end(&mut data).context("Truncated runtime archive")?;
+            skip_padding(&mut tar, size)?;
+            match kind {
+                b'L' => long_name = Some(c_string(&data)),
+                b'K' => long_link = Some(c_string(&data)),
+                b'x' => {
+                    for (key, value) in pax_records(&data) {
+                        match key {
+                            "path" => long_name = Some(value.to_string()),
+                            "linkpath" => long_link = Some(value.to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = c_string(&header[0..100]);
+            let prefix = if &header[257..262] == b"ustar" { c_string(&header[345..500]) } else { String::new() };
+            if prefix.is_empty() { name } else { format!("{prefix}/{name}") }
+        });
+        let link = long_link.take().unwrap_or_else(|| c_string(&header[157..257]));
+        let Some(relative) = strip_top_level(Path::new(&name)) else {
+            io::copy(&mut (&mut tar).take(size), &mut io::sink()).context("Truncated runtime archive")?;
+            skip_padding(&mut tar, size)?;
+            continue;
+        };
+        let path = dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        match kind {
+            b'5' => fs::create_dir_all(&path).with_context(|| format!("Failed to create {}", path.display()))?,
+            b'2' => symlink(&link, &path)?,
+            b'1' => {
+                let target = strip_top_level(Path::new(&link))
+                    .with_context(|| format!("Hard link {name} points outside the runtime"))?;
+                let target = dir.join(target);
+                if fs::hard_link(&target, &path).is_err() {
+                    fs::copy(&target, &path).with_context(|| format!("Failed to create {}", path.display()))?;
+                }
+            }
+            b'0' | b'\0' | b'7' => {
+                let mut out = BufWriter::new(fs::File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?);
+                io::copy(&mut (&mut tar).take(size), &mut out).with_context(|| format!("Failed to write {}", path.display()))?;
+                drop(out);
+                set_mode(&path, octal(&header[100..108])? as u32)?;
+                skip_padding(&mut tar, size)?;
+                continue;
+            }
+            // Devices and FIFOs have no place in a runtime
+            _ => {}
+        }
+        io::copy(&mut (&mut tar).take(size), &mut io::sink()).context("Truncated runtime archive")?;
+        skip_padding(&mut tar, size)?;
+    }
+}
+
+/// `path` without its first component, refusing anything that could leave the extraction
+fn strip_top_level(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    components.next()?;
+    let mut stripped = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => stripped.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!stripped.as_os_str().is_empty()).then_some(stripped)
+}
+
+fn skip_padding(tar: &mut impl Read, size: u64) -> Result<()> {
+    let padding = (512 - size % 512) % 512;
+    io::copy(&mut tar.take(padding), &mut io::sink()).context("Truncated runtime archive")?;
+    Ok(())
+}
+
+/// Numeric header field: NUL/space terminated octal, or big-endian binary when the high bit is set
+fn octal(field: &[u8]) -> Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..].iter().fold(0, |value, &b| (value << 8) | u64::from(b)));
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).with_context(|| format!("Invalid number '{text}' in the runtime archive"))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// `<length> <key>=<value>\n` records of a pax extended header
+fn pax_records(data: &[u8]) -> Vec<(&str, &str)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(length) = std::str::from_utf8(&rest[..space]).ok().and_then(|n| n.parse::<usize>().ok()) else {
+            break;
+        };
+        if length <= space || length > rest.len() {
+            break;
+        }
+        if let Ok(record) = std::str::from_utf8(&rest[space + 1..length]) {
+            if let Some((key, value)) = record.trim_end_matches('\n').split_once('=') {
+                records.push((key, value));
+            }
+        }
+        rest = &rest[length..];
+    }
+    records
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link).with_context(|| format!("Failed to create symlink {}", link.display()))
+}
+
+/// The Windows archives are zips; symlinks in a tarball are only needed on unix
+#[cfg(not(unix))]
+fn symlink(_target: &str, _link: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))
+        .with_context(|| format!("Failed to set permissions of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ustar header for `name`, with `size` bytes of data following it
+    fn header(name: &str, kind: u8, size: usize, link: &str, mode: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(format!("{mode:07o}\0").as_bytes());
+        header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header
+    }
+
+    fn entry(tar: &mut Vec<u8>, name: &str, kind: u8, data: &[u8], link: &str, mode: u32) {
+        tar.extend(header(name, kind, data.len(), link, mode));
+        tar.extend(data);
+        tar.resize(tar.len().next_multiple_of(512), 0);
+    }
+
+    #[test]
+    fn test_unpack_tar() {
+        let long = format!("node-v22/lib/{}/index.js", "deep".repeat(30));
+        let pax_path = format!("node-v22/include/{}.h", "header".repeat(20));
+        let pax_record = format!("path={pax_path}\n");
+        let pax = format!("{} {pax_record}", pax_record.len() + 4);
+        assert_eq!(pax.len(), pax_record.len() + 4);
+
+        let mut tar = Vec::new();
+        entry(&mut tar, "node-v22/", b'5', b"", "", 0o755);
+        entry(&mut tar, "node-v22/bin/node", b'0', b"#!node", "", 0o755);
+        entry(&mut tar, "node-v22/bin/npm", b'2', b"", "../lib/npm-cli.js", 0o777);
+        entry(&mut tar, "node-v22/bin/nodejs", b'1', b"", "node-v22/bin/node", 0o755);
+        entry(&mut tar, "././@LongLink", b'L', format!("{long}\0").as_bytes(), "", 0);
+        entry(&mut tar, "placeholder", b'0', b"long", "", 0o644);
+        entry(&mut tar, "PaxHeaders/x", b'x', pax.as_bytes(), "", 0);
+        entry(&mut tar, "placeholder", b'0', b"pax", "", 0o644);
+        entry(&mut tar, "node-v22/../../escaped", b'0', b"outside", "", 0o644);
+        tar.extend([0u8; 1024]);
+
+        let root = std::env::temp_dir().join(format!("banderole-unpack-tar-{}", std::process::id()));
+        let dir = root.join("runtime");
+        unpack_tar(tar.as_slice(), &dir).unwrap();
+        let read = |path: &str| fs::read_to_string(dir.join(path)).unwrap();
+        assert_eq!(read("bin/node"), "#!node");
+        assert_eq!(read("bin/nodejs"), "#!node");
+        assert_eq!(read(long.strip_prefix("node-v22/").unwrap()), "long");
+        assert_eq!(read(pax_path.strip_prefix("node-v22/").unwrap()), "pax");
+        assert!(!root.join("escaped").exists() && !dir.join("escaped").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::read_link(dir.join("bin/npm")).unwrap(), Path::new("../lib/npm-cli.js"));
+            assert_eq!(fs::metadata(dir.join("bin/node")).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+        fs::remove_dir_all(&root).unwrap();
+
+        // No end-of-archive blocks
+        let truncated = &tar[..512 + 100];
+        assert!(unpack_tar(truncated, &root.join("truncated")).is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_pax_records() {
+        assert_eq!(
+            pax_records(b"19 path=node/bin/x\n20 linkpath=../a.js\n"),
+            [("path", "node/bin/x"), ("linkpath", "../a.js")]
+        );
+        // Lengths that do not fit end the records
+        assert_eq!(pax_records(b"19 path=node/bin/x\n99 path=cut\n"), [("path", "node/bin/x")]);
+        assert!(pax_records(b"x path=a\n").is_empty());
+        assert!(pax_records(b"3 a\n").is_empty());
+    }
+
+    #[test]
+    fn test_octal() {
+        assert_eq!(octal(b"0000644\0").unwrap(), 0o644);
+        assert_eq!(octal(b"     12 \0").unwrap(), 0o12);
+        assert_eq!(octal(b"\0\0\0\0").unwrap(), 0);
+        assert_eq!(octal(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]).unwrap(), 256);
+        assert!(octal(b"0000958\0").is_err());
+    }
+}
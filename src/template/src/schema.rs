@@ -0,0 +1,43 @@
+//! Version of the contract between the bundler and the runner it compiles: the embedded runtime
+//! config, the payload layout and the runner flags.
+//!
+//! Shared by the bundler (compiled via `#[path]`) and the runner, and recorded in every bundle's
+//! manifest, so tools can tell when a bundle's runtime behavior differs from the current one.
+#![allow(dead_code)]
+
+/// Bump whenever a bundle built before the change behaves differently at runtime.
+///
+/// 1: manifests, shared runtime extraction, `--banderole-completions`
+/// 2: symlinks stored as symlink entries, recreated on extraction
+/// 3: entries sharing data extracted as hard links, except under `mutablePaths`
+/// 4: files changed by `postExtract` recorded and checked against what it left behind
+/// 5: launch-time health check samples a bounded number of files
+/// 6: system cache used only by administrators or when owned by them and writable by nobody else
+/// 7: payloads encrypted with AES-256-GCM (`BDRLENC2`)
+/// 8: shared dependencies kept at `deps/<runtime>/<digest>/node_modules`, not shared with `postExtract`, npm or mutable node_modules
+/// 9: reserved app names rejected for the named layout; `--banderole-clean-all` removes locks only with their extraction and cleans the `user-<uid>` subdirectory
+pub const RUNNER_SCHEMA: u32 = 9;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Changing the runner contract means bumping `RUNNER_SCHEMA`, saying why in its history and
+    /// moving this pin along
+    #[test]
+    fn test_runner_schema_is_pinned() {
+        assert_eq!(RUNNER_SCHEMA, 9);
+        let documented = include_str!("schema.rs").lines().rev().find_map(|line| {
+            line.strip_prefix("/// ")?
+                .split_once(':')?
+                .0
+                .parse::<u32>()
+                .ok()
+        });
+        assert_eq!(
+            documented,
+            Some(RUNNER_SCHEMA),
+            "RUNNER_SCHEMA has no history entry"
+        );
+    }
+}
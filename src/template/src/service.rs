@@ -0,0 +1,462 @@
+//! `--banderole-install-service` / `--banderole-uninstall-service`: register the bundle's
+//! embedded service definition with the platform's service manager.
+//!
+//! The service runs this executable against the cache it was installed from, so it starts from
+//! the existing extraction instead of unpacking another copy. Administrators install from the
+//! system cache, which the service's `user` can read but not modify, and the service uses it
+//! through `--banderole-cache-scope system`; other users' services run as themselves from their
+//! own cache, passed with `--banderole-cache-dir`.
+//! Linux gets a systemd unit (system-wide as root, a user unit otherwise), macOS a launchd plist
+//! (a daemon as root, an agent otherwise) and Windows a scheduled task started at boot that is
+//! restarted when it fails, since apps do not speak the Windows service control protocol.
+
+use crate::config::{Service, ServiceRestart};
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Register and start the service; `app_args` replace the definition's `args` when given
+pub fn install(app_dir: &Path, service: &Service, app_args: &[OsString]) -> Result<()> {
+    let executable = env::current_exe()
+        .and_then(|path| path.canonicalize())
+        .context("Failed to locate the running executable")?;
+    let system_root = crate::system_cache::root();
+    let mut args = if app_dir.starts_with(&system_root) {
+        vec!["--banderole-cache-scope".to_string(), "system".to_string()]
+    } else {
+        let cache_dir = app_dir.parent().context("The extraction has no cache directory")?;
+        vec!["--banderole-cache-dir".to_string(), cache_dir.display().to_string()]
+    };
+    // Installing is the interactive moment; the service itself cannot prompt
+    args.push("--banderole-accept-notice".to_string());
+    if app_args.is_empty() {
+        args.extend(service.args.iter().cloned());
+    } else {
+        args.extend(app_args.iter().map(|arg| arg.to_string_lossy().into_owned()));
+    }
+    let command = Launch {
+        executable: executable.display().to_string(),
+        args,
+        working_dir: app_dir.join("app").display().to_string(),
+    };
+
+    if cfg!(windows) {
+        install_scheduled_task(service, &command)
+    } else if cfg!(target_os = "macos") {
+        install_launchd(service, &command)
+    } else {
+        install_systemd(service, &command)
+    }
+}
+
+/// Stop the service and remove its registration
+pub fn uninstall(service: &Service) -> Result<()> {
+    if cfg!(windows) {
+        run("schtasks", &["/End", "/TN", &service.name], true);
+        if !run("schtasks", &["/Delete", "/TN", &service.name, "/F"], false) {
+            bail!("Failed to remove the scheduled task {}; run from an elevated prompt", service.name);
+        }
+    } else if cfg!(target_os = "macos") {
+        let plist = launchd_plist_path(service)?;
+        run("launchctl", &["unload", "-w", &plist.display().to_string()], true);
+        remove(&plist)?;
+    } else {
+        let unit = systemd_unit_path(service)?;
+        let user = user_flag();
+        run("systemctl", &[user, "disable", "--now", &unit_name(service)], true);
+        remove(&unit)?;
+        run("systemctl", &[user, "daemon-reload"], true);
+    }
+    eprintln!("Removed service {}", service.name);
+    Ok(())
+}
+
+/// What the service manager starts
+struct Launch {
+    executable: String,
+    args: Vec<String>,
+    working_dir: String,
+}
+
+fn install_systemd(service: &Service, command: &Launch) -> Result<()> {
+    let path = systemd_unit_path(service)?;
+    let unit = systemd_unit(service, command, is_root());
+    write(&path, unit.as_bytes())?;
+
+    let user = user_flag();
+    let name = unit_name(service);
+    // Restart rather than start, so reinstalling with other arguments takes effect
+    if !run("systemctl", &[user, "daemon-reload"], false)
+        || !run("systemctl", &[user, "enable", &name], false)
+        || !run("systemctl", &[user, "restart", &name], false)
+    {
+        bail!("Wrote {}, but systemctl could not enable it", path.display());
+    }
+    eprintln!(
+        "Installed and started {name} ({}); check it with `systemctl {}status {name}`",
+        path.display(),
+        if user.is_empty() { "" } else { "--user " }
+    );
+    Ok(())
+}
+
+/// The unit file: a system unit (running as the service's `user`) for root, a user unit otherwise
+fn systemd_unit(service: &Service, command: &Launch, root: bool) -> String {
+    let quote = |arg: &str| {
+        format!(
+            "\"{}\"",
+            arg.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$")
+        )
+    };
+    let exec_start: Vec<String> = std::iter::once(&command.executable)
+        .chain(&command.args)
+        .map(|arg| quote(arg))
+        .collect();
+    let mut unit = format!(
+        "[Unit]\nDescription={}\nAfter=network-online.target\nWants=network-online.target\n\n[Service]\nType=simple\nExecStart={}\nWorkingDirectory={}\n",
+        service.description,
+        exec_start.join(" "),
+        command.working_dir.replace('%', "%%")
+    );
+    match service.restart {
+        ServiceRestart::Always => unit.push_str("Restart=always\n"),
+        ServiceRestart::OnFailure => unit.push_str("Restart=on-failure\n"),
+        ServiceRestart::No => {}
+    }
+    if let Some(user) = service.user.as_deref().filter(|_| root) {
+        unit.push_str(&format!("User={user}\n"));
+    }
+    unit.push_str(&format!(
+        "\n[Install]\nWantedBy={}\n",
+        if root { "multi-user.target" } else { "default.target" }
+    ));
+    unit
+}
+
+fn install_launchd(service: &Service, command: &Launch) -> Result<()> {
+    let path = launchd_plist_path(service)?;
+    let log = if is_root() {
+        PathBuf::from("/var/log").join(format!("{}.log", service.name))
+    } else {
+        home_dir()?.join("Library").join("Logs").join(format!("{}.log", service.name))
+    };
+    let plist = launchd_plist(service, command, is_root(), &log);
+    write(&path, plist.as_bytes())?;
+
+    let path_arg = path.display().to_string();
+    // Reinstalling replaces a loaded definition
+    run("launchctl", &["unload", &path_arg], true);
+    if !run("launchctl", &["load", "-w", &path_arg], false) {
+        bail!("Wrote {}, but launchctl could not load it", path.display());
+    }
+    eprintln!("Installed and started {} ({}); logs go to {}", service.name, path.display(), log.display());
+    Ok(())
+}
+
+/// The property list of a daemon (running as the service's `user`) for root, an agent otherwise
+fn launchd_plist(service: &Service, command: &Launch, root: bool, log: &Path) -> String {
+    let string = |value: &str| format!("<string>{}</string>", xml_escape(value));
+    let program_arguments: String = std::iter::once(&command.executable)
+        .chain(&command.args)
+        .map(|arg| format!("\n    {}", string(arg)))
+        .collect();
+    let keep_alive = match service.restart {
+        ServiceRestart::Always => "<true/>".to_string(),
+        ServiceRestart::OnFailure => {
+            "<dict>\n    <key>SuccessfulExit</key>\n    <false/>\n  </dict>".to_string()
+        }
+        ServiceRestart::No => "<false/>".to_string(),
+    };
+    let user = match service.user.as_deref().filter(|_| root) {
+        Some(user) => format!("  <key>UserName</key>\n  {}\n", string(user)),
+        None => String::new(),
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  {}
+  <key>ProgramArguments</key>
+  <array>{program_arguments}
+  </array>
+  <key>WorkingDirectory</key>
+  {}
+  <key>RunAtLoad</key>
+  <true/>
+  <key>KeepAlive</key>
+  {keep_alive}
+  <key>StandardOutPath</key>
+  {}
+  <key>StandardErrorPath</key>
+  {}
+{user}</dict>
+</plist>
+"#,
+        string(&service.name),
+        string(&command.working_dir),
+        string(&log.display().to_string()),
+        string(&log.display().to_string()),
+    )
+}
+
+fn install_scheduled_task(service: &Service, command: &Launch) -> Result<()> {
+    let task = scheduled_task(service, command);
+    // schtasks reads task definitions as UTF-16
+    let encoded: Vec<u8> = std::iter::once(0xfeff)
+        .chain(task.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let definition = env::temp_dir().join(format!("{}-task.xml", service.name));
+    write(&definition, &encoded)?;
+    let created = run(
+        "schtasks",
+        &["/Create", "/TN", &service.name, "/XML", &definition.display().to_string(), "/F"],
+        false,
+    );
+    fs::remove_file(&definition).ok();
+    if !created {
+        bail!("Failed to register the scheduled task {}; run from an elevated prompt", service.name);
+    }
+    run("schtasks", &["/Run", "/TN", &service.name], false);
+    eprintln!("Installed and started {} as a scheduled task that runs at boot", service.name);
+    Ok(())
+}
+
+/// The Task Scheduler definition: started at boot as `LocalSystem` or the service's `user`
+fn scheduled_task(service: &Service, command: &Launch) -> String {
+    let principal = match &service.user {
+        Some(user) => format!("<UserId>{}</UserId><LogonType>S4U</LogonType>", xml_escape(user)),
+        // LocalSystem
+        None => "<UserId>S-1-5-18</UserId>".to_string(),
+    };
+    let restart = match service.restart {
+        ServiceRestart::No => "",
+        // Tasks are only restarted after failing
+        ServiceRestart::Always | ServiceRestart::OnFailure => {
+            "<RestartOnFailure><Interval>PT1M</Interval><Count>999</Count></RestartOnFailure>"
+        }
+    };
+    let arguments: Vec<String> = command.args.iter().map(|arg| windows_quote(arg)).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo><Description>{}</Description></RegistrationInfo>
+  <Triggers><BootTrigger><Enabled>true</Enabled></BootTrigger></Triggers>
+  <Principals><Principal id="Author">{principal}<RunLevel>HighestAvailable</RunLevel></Principal></Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    {restart}
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{}</Command>
+      <Arguments>{}</Arguments>
+      <WorkingDirectory>{}</WorkingDirectory>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        xml_escape(&service.description),
+        xml_escape(&command.executable),
+        xml_escape(&arguments.join(" ")),
+        xml_escape(&command.working_dir),
+    )
+}
+
+fn unit_name(service: &Service) -> String {
+    format!("{}.service", service.name)
+}
+
+/// `/etc/systemd/system` as root, the user's `systemd/user` config directory otherwise
+fn systemd_unit_path(service: &Service) -> Result<PathBuf> {
+    let dir = if is_root() {
+        PathBuf::from("/etc/systemd/system")
+    } else {
+        BaseDirs::new()
+            .context("Cannot determine the user's config directory")?
+            .config_dir()
+            .join("systemd")
+            .join("user")
+    };
+    Ok(dir.join(unit_name(service)))
+}
+
+/// `--user` for user units, nothing for system ones
+fn user_flag() -> &'static str {
+    if is_root() { "" } else { "--user" }
+}
+
+fn launchd_plist_path(service: &Service) -> Result<PathBuf> {
+    let dir = if is_root() {
+        PathBuf::from("/Library/LaunchDaemons")
+    } else {
+        home_dir()?.join("Library").join("LaunchAgents")
+    };
+    Ok(dir.join(format!("{}.plist", service.name)))
+}
+
+fn home_dir() -> Result<PathBuf> {
+    Ok(BaseDirs::new().context("Cannot determine the home directory")?.home_dir().to_path_buf())
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| {
+        format!("Failed to write {}; installing a system service needs administrator rights", path.display())
+    })
+}
+
+fn remove(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!("No service installed at {}", path.display()),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+    }
+}
+
+/// Run a service manager command, skipping empty arguments; returns whether it succeeded.
+/// With `quiet`, its output is discarded (used for steps that may fail harmlessly).
+fn run(program: &str, args: &[&str], quiet: bool) -> bool {
+    let mut cmd = Command::new(program);
+    cmd.args(args.iter().filter(|arg| !arg.is_empty()));
+    if quiet {
+        cmd.stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null());
+    }
+    verbose!("Running {program} {}", args.join(" "));
+    cmd.status().map(|status| status.success()).unwrap_or(false)
+}
+
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Quote an argument the way the Windows C runtime splits command lines
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        if c != '\\' {
+            quoted.push(c);
+        } else {
+            quoted.push('\\');
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(user: Option<&str>, restart: ServiceRestart) -> Service {
+        Service {
+            name: "acme-server".to_string(),
+            description: "Acme <server>".to_string(),
+            args: Vec::new(),
+            user: user.map(str::to_string),
+            restart,
+        }
+    }
+
+    fn launch() -> Launch {
+        Launch {
+            executable: "/opt/acme/server".to_string(),
+            args: vec!["--banderole-cache-scope".to_string(), "system".to_string(), "--name=a \"b\" 50%".to_string()],
+            working_dir: "/opt/banderole/acme/app".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = systemd_unit(&service(Some("acme"), ServiceRestart::Always), &launch(), true);
+        assert!(unit.contains(
+            "ExecStart=\"/opt/acme/server\" \"--banderole-cache-scope\" \"system\" \"--name=a \\\"b\\\" 50%%\"\n"
+        ));
+        assert!(unit.contains("WorkingDirectory=/opt/banderole/acme/app\n"));
+        assert!(unit.contains("Restart=always\n"));
+        assert!(unit.contains("User=acme\n"));
+        assert!(unit.ends_with("[Install]\nWantedBy=multi-user.target\n"));
+
+        // User units run as their owner
+        let unit = systemd_unit(&service(Some("acme"), ServiceRestart::No), &launch(), false);
+        assert!(!unit.contains("User="));
+        assert!(!unit.contains("Restart="));
+        assert!(unit.ends_with("WantedBy=default.target\n"));
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let log = Path::new("/var/log/acme-server.log");
+        let plist = launchd_plist(&service(Some("acme"), ServiceRestart::OnFailure), &launch(), true, log);
+        assert!(plist.contains("<key>Label</key>\n  <string>acme-server</string>"));
+        assert!(plist.contains(
+            "<array>\n    <string>/opt/acme/server</string>\n    <string>--banderole-cache-scope</string>\n    <string>system</string>\n    <string>--name=a &quot;b&quot; 50%</string>\n  </array>"
+        ));
+        assert!(plist.contains("<key>SuccessfulExit</key>\n    <false/>"));
+        assert!(plist.contains("<key>UserName</key>\n  <string>acme</string>"));
+        assert!(plist.contains("<key>StandardErrorPath</key>\n  <string>/var/log/acme-server.log</string>"));
+
+        let plist = launchd_plist(&service(Some("acme"), ServiceRestart::Always), &launch(), false, log);
+        assert!(plist.contains("<key>KeepAlive</key>\n  <true/>"));
+        assert!(!plist.contains("UserName"));
+    }
+
+    #[test]
+    fn test_scheduled_task() {
+        let task = scheduled_task(&service(None, ServiceRestart::OnFailure), &launch());
+        assert!(task.contains("<Description>Acme &lt;server&gt;</Description>"));
+        assert!(task.contains("<Principal id=\"Author\"><UserId>S-1-5-18</UserId><RunLevel>"));
+        assert!(task.contains("<RestartOnFailure>"));
+        assert!(task.contains("<Command>/opt/acme/server</Command>"));
+        assert!(task.contains(
+            "<Arguments>--banderole-cache-scope system &quot;--name=a \\&quot;b\\&quot; 50%&quot;</Arguments>"
+        ));
+
+        let task = scheduled_task(&service(Some("acme"), ServiceRestart::No), &launch());
+        assert!(task.contains("<UserId>acme</UserId><LogonType>S4U</LogonType>"));
+        assert!(!task.contains("<RestartOnFailure>"));
+    }
+
+    #[test]
+    fn test_windows_quote() {
+        assert_eq!(windows_quote("plain"), "plain");
+        assert_eq!(windows_quote(""), "\"\"");
+        assert_eq!(windows_quote("a b"), "\"a b\"");
+        assert_eq!(windows_quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(windows_quote("C:\\dir with space\\"), "\"C:\\dir with space\\\\\"");
+    }
+}
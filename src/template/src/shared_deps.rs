@@ -0,0 +1,147 @@
+//! Dependencies shared between extractions: bundles embedding the same dependency layer extract
+//! `app/node_modules` once into `deps/<runtime>/<digest>/node_modules` and link it into every app
+//! directory, so an update that only changes app code does not unpack node_modules again.
+//!
+//! The shared copy keeps the name `node_modules`: ESM resolution follows the link to its real
+//! path and still finds packages there. Bundles that may write below node_modules (`postExtract`,
+//! npm, `mutablePaths` reaching into it) keep a private copy.
+
+use crate::config::RuntimeConfig;
+use crate::layers::{Layer, DEPS_DIR};
+use crate::shared_runtime;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory below the cache holding one extraction per dependency digest
+const SHARED_DIR: &str = "deps";
+
+/// Link `app/node_modules` of `app_dir` to the machine-wide copy of the bundle's dependency
+/// layer, extracting that copy first when no other app has yet.
+///
+/// Returns `false` when the dependencies cannot be shared, in which case the caller extracts
+/// them into `app_dir`.
+pub fn link(cache_dir: &Path, app_dir: &Path, config: &RuntimeConfig) -> Result<bool> {
+    let Some(digest) = digest(config) else {
+        return Ok(false);
+    };
+    let shared = shared_runtime::ensure_shared(&root(cache_dir, config), digest, "dependencies", |staging| {
+        crate::extract_payload(staging, "dependencies", Layer::Deps, |_| true)?;
+        // `.bin` entries are prepared once here; links to the shared copy are not walked later
+        crate::bin_shims::prepare(staging, config).context("Failed to prepare node_modules/.bin entries")?;
+        Ok(staging.join("app"))
+    });
+    let shared = match shared {
+        Ok(shared) => shared,
+        Err(e) if crate::cache_location::is_permission_denied(&e) => {
+            verbose!("Cannot use the shared dependencies ({e:#}); extracting a private copy");
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let shared = shared.join("node_modules");
+    if !shared.is_dir() {
+        verbose!("Shared dependencies {} have an older layout; extracting a private copy", shared.display());
+        return Ok(false);
+    }
+    let link = app_dir.join(DEPS_DIR);
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    match shared_runtime::create_dir_link(&shared, &link) {
+        Ok(()) => {
+            verbose!("Linked {} to shared dependencies {}", link.display(), shared.display());
+            Ok(true)
+        }
+        Err(e) => {
+            verbose!("Cannot link shared dependencies ({e}); extracting a private copy");
+            Ok(false)
+        }
+    }
+}
+
+/// Whether the machine-wide copy of this bundle's dependencies is already extracted
+pub fn is_extracted(cache_dir: &Path, config: &RuntimeConfig) -> bool {
+    digest(config).is_some_and(|digest| shared_runtime::is_shared(&root(cache_dir, config), digest))
+}
+
+/// Whether `relative` (inside an extraction) is the link to shared dependencies
+pub fn is_link(app_dir: &Path, relative: &Path) -> bool {
+    relative == Path::new(DEPS_DIR)
+        && fs::symlink_metadata(app_dir.join(relative)).is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+/// Replace the link to shared dependencies in `app_dir` by an empty spot, so repairs extract a
+/// private copy instead of writing into the one other apps use. Returns whether there was a link.
+pub fn unshare(app_dir: &Path) -> Result<bool> {
+    if !is_link(app_dir, Path::new(DEPS_DIR)) {
+        return Ok(false);
+    }
+    let link = app_dir.join(DEPS_DIR);
+    // Junctions are directories to `remove_file`; neither call follows the link
+    fs::remove_file(&link)
+        .or_else(|_| fs::remove_dir(&link))
+        .with_context(|| format!("Failed to remove the link to shared dependencies {}", link.display()))?;
+    Ok(true)
+}
+
+fn digest(config: &RuntimeConfig) -> Option<&str> {
+    if !shareable(config) {
+        return None;
+    }
+    config
+        .deps_digest
+        .as_deref()
+        .filter(|digest| Layer::Deps.is_embedded() && !digest.is_empty() && digest.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Whether nothing the bundle runs may write below node_modules
+fn shareable(config: &RuntimeConfig) -> bool {
+    config.post_extract.is_none()
+        && config.npm_cli.is_none()
+        && !config.mutable_paths.iter().any(|pattern| reaches_node_modules(pattern))
+}
+
+/// Whether a `mutablePaths` pattern (relative to `app/`) can cover something below node_modules
+fn reaches_node_modules(pattern: &str) -> bool {
+    let first = pattern.trim_matches('/').split('/').next().unwrap_or_default();
+    crate::glob::glob_match(first, "node_modules")
+}
+
+/// Per runtime: Windows `.bin` wrappers name the runtime executable
+fn root(cache_dir: &Path, config: &RuntimeConfig) -> PathBuf {
+    cache_dir.join(SHARED_DIR).join(config.runtime.dir_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reaches_node_modules() {
+        for pattern in ["node_modules", "node_modules/pkg/cache", "/node_modules/", "**/*.log", "*", "node_*/x"] {
+            assert!(reaches_node_modules(pattern), "{pattern}");
+        }
+        for pattern in ["data", "logs/**", "*.log", "cache/node_modules"] {
+            assert!(!reaches_node_modules(pattern), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_unshare_removes_only_the_link() {
+        let dir = std::env::temp_dir().join(format!("banderole-unshare-{}", std::process::id()));
+        let shared = dir.join("shared").join("node_modules");
+        let app_dir = dir.join("app-dir");
+        fs::create_dir_all(shared.join("pkg")).unwrap();
+        fs::write(shared.join("pkg").join("index.js"), "").unwrap();
+        fs::create_dir_all(app_dir.join("app")).unwrap();
+        shared_runtime::create_dir_link(&shared, &app_dir.join(DEPS_DIR)).unwrap();
+
+        assert!(unshare(&app_dir).unwrap());
+        assert!(fs::symlink_metadata(app_dir.join(DEPS_DIR)).is_err());
+        assert!(shared.join("pkg").join("index.js").exists());
+        assert!(!unshare(&app_dir).unwrap());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::RuntimeConfig;
+use crate::layers::Layer;
+
+/// Directory below the cache holding one extraction per runtime digest
+const SHARED_DIR: &str = "runtimes";
+
+/// Link the runtime directory of `app_dir` to the machine-wide copy for the bundle's runtime
+/// digest, extracting that copy first when no other app has yet.
+///
+/// Returns `false` when the runtime cannot be shared (older bundle, no link support), in which
+/// case the caller extracts it into `app_dir` as usual. A runtime left out of the payload
+/// (`--external-runtime`) is downloaded instead of extracted, and failing to link it is an error.
+pub fn link(cache_dir: &Path, app_dir: &Path, config: &RuntimeConfig) -> Result<bool> {
+    let Some(digest) = config.runtime_digest.as_deref() else {
+        return Ok(false);
+    };
+    if digest.is_empty() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(false);
+    }
+    let dir_name = config.runtime.dir_name();
+    let shared = ensure_shared(&cache_dir.join(SHARED_DIR), digest, "runtime", |staging| {
+        match &config.external_runtime {
+            Some(external) => crate::runtime_download::fetch(external, &staging.join(dir_name))?,
+            None => {
+                crate::extract_payload(staging, dir_name, Layer::Runtime, |_| true)?;
+            }
+        }
+        Ok(staging.join(dir_name))
+    });
+    let shared = match shared {
+        Ok(shared) => shared,
+        // Another user owns the shared directory and has not extracted this runtime yet
+        Err(e) if config.external_runtime.is_none() && crate::cache_location::is_permission_denied(&e) => {
+            verbose!("Cannot use the shared runtime ({e:#}); extracting a private copy");
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let link = app_dir.join(dir_name);
+    match create_dir_link(&shared, &link) {
+        Ok(()) => {
+            verbose!("Linked {} to shared runtime {}", link.display(), shared.display());
+            Ok(true)
+        }
+        Err(e) if config.external_runtime.is_some() => {
+            Err(e).with_context(|| format!("Failed to link {} to the downloaded runtime {}", link.display(), shared.display()))
+        }
+        Err(e) => {
+            verbose!("Cannot link shared runtime ({e}); extracting a private copy");
+            Ok(false)
+        }
+    }
+}
+
+/// Whether the machine-wide copy of this bundle's runtime is already extracted, so extraction
+/// only has to link it
+pub fn is_extracted(cache_dir: &Path, config: &RuntimeConfig) -> bool {
+    config
+        .runtime_digest
+        .as_deref()
+        .is_some_and(|digest| is_shared(&cache_dir.join(SHARED_DIR), digest))
+}
+
+/// Whether `<root>/<digest>` is complete
+pub fn is_shared(root: &Path, digest: &str) -> bool {
+    root.join(format!("{digest}.ready")).exists() && root.join(digest).is_dir()
+}
+
+/// Whether `relative` (inside an extraction) is the link to a shared runtime
+pub fn is_link(app_dir: &Path, relative: &Path, config: &RuntimeConfig) -> bool {
+    relative == Path::new(config.runtime.dir_name())
+        && fs::symlink_metadata(app_dir.join(relative)).is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+/// `<root>/<digest>`, filled once per machine: `fill` writes into an empty staging directory
+/// and returns the path inside it that becomes the shared copy
+pub fn ensure_shared(root: &Path, digest: &str, what: &str, fill: impl FnOnce(&Path) -> Result<PathBuf>) -> Result<PathBuf> {
+    let shared = root.join(digest);
+    let ready = root.join(format!("{digest}.ready"));
+    if is_shared(root, digest) {
+        return Ok(shared);
+    }
+
+    fs::create_dir_all(root)
+        .with_context(|| format!("Failed to create shared {what} directory {}", root.display()))?;
+    let lock_path = root.join(format!("{digest}.lock"));
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to create lock file at {}", lock_path.display()))?;
+    lock.lock_exclusive().with_context(|| format!("Failed to acquire shared {what} lock"))?;
+    if is_shared(root, digest) {
+        lock.unlock().ok();
+        return Ok(shared);
+    }
+
+    verbose!("Extracting shared {what} {}", shared.display());
+    // Extract beside the final location and move it into place, so a crash never leaves a
+    // half-extracted copy that other apps would link to
+    let staging = root.join(format!("{digest}.tmp-{}", std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).ok();
+    }
+    let extracted = fill(&staging).and_then(|filled| {
+        if shared.exists() {
+            fs::remove_dir_all(&shared).with_context(|| format!("Failed to remove incomplete shared {what}"))?;
+        }
+        fs::rename(filled, &shared).with_context(|| format!("Failed to move shared {what} into place"))
+    });
+    fs::remove_dir_all(&staging).ok();
+    extracted?;
+    fs::write(&ready, "ready")
+        .with_context(|| format!("Failed to create ready file at {}", ready.display()))?;
+    lock.unlock().ok();
+    Ok(shared)
+}
+
+#[cfg(unix)]
+pub fn create_dir_link(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// A directory symlink when allowed (Developer Mode, elevated), else a junction, which needs
+/// no privileges
+#[cfg(windows)]
+pub fn create_dir_link(target: &Path, link: &Path) -> std::io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    if std::os::windows::fs::symlink_dir(target, link).is_ok() {
+        return Ok(());
+    }
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("mklink /J exited with {status}")))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn create_dir_link(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::other("directory links are not supported"))
+}
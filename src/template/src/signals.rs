@@ -0,0 +1,75 @@
+//! Signal forwarding from the runner to the spawned Node process.
+
+#[cfg(unix)]
+mod imp {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward(sig: libc::c_int, info: *mut libc::siginfo_t, _ctx: *mut libc::c_void) {
+        // Signals generated by the terminal (si_pid == 0) already reach the child through the
+        // shared process group; forwarding those again would deliver them twice.
+        let sender = if info.is_null() { 0 } else { unsafe { (*info).si_pid() } };
+        let pid = CHILD_PID.load(Ordering::SeqCst);
+        if pid > 0 && sender != 0 {
+            unsafe {
+                libc::kill(pid, sig);
+            }
+        }
+    }
+
+    pub fn forward_to(pid: u32) {
+        CHILD_PID.store(pid as i32, Ordering::SeqCst);
+        for sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = forward as *const () as libc::sighandler_t;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(sig, &action, std::ptr::null_mut());
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+
+    unsafe extern "system" fn swallow(_ctrl_type: u32) -> i32 {
+        // Ctrl+C / Ctrl+Break are delivered to every process on the console, node included.
+        // Report them as handled so the runner keeps waiting for node's own shutdown.
+        1
+    }
+
+    pub fn forward_to(_pid: u32) {
+        unsafe {
+            SetConsoleCtrlHandler(Some(swallow), 1);
+        }
+    }
+}
+
+/// Route SIGINT/SIGTERM/SIGHUP (Ctrl+C/Ctrl+Break on Windows) to the child with `pid` while the
+/// runner waits for it to exit.
+pub fn forward_to(pid: u32) {
+    imp::forward_to(pid);
+}
+
+/// Exit code for the runner mirroring the child's status, using the shell's 128+n for signals
+pub fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return 128 + sig;
+        }
+    }
+    1
+}
@@ -0,0 +1,275 @@
+//! The machine-wide cache (`cacheScope: "system"`), so every user of a server launches one
+//! extraction instead of unpacking the app into each home directory.
+//!
+//! Only administrators (root, or members of Administrators on Windows) extract into it or repair
+//! it. Everyone else launches an extraction found there only if the cache root, every directory
+//! down to the extraction and the shared runtime and dependencies it links to are owned by
+//! root (SYSTEM or Administrators on Windows) and writable by nobody else; otherwise they fall
+//! back to their own cache. Directories are checked, not each file: extractions are sealed
+//! ([`seal`]) before they are marked ready, so no file in them is writable by other users either.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `/opt/banderole`, or `%ProgramData%\banderole` on Windows
+pub fn root() -> PathBuf {
+    #[cfg(windows)]
+    let base = std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"));
+    #[cfg(not(windows))]
+    let base = PathBuf::from("/opt");
+    base.join("banderole")
+}
+
+/// The system cache, if this user may install `extraction` there, or if it is installed there,
+/// `ready` and trusted
+pub fn usable(extraction: &Path, ready: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    let root = root();
+    let app_dir = root.join(extraction);
+    if is_admin() {
+        return match prepare_root(&root) {
+            Ok(()) => Some(root),
+            Err(e) => {
+                verbose!("Not using the system cache {}: {e}; using the user cache", root.display());
+                None
+            }
+        };
+    }
+    if !is_trusted_extraction(&root, &app_dir) {
+        verbose!(
+            "{} is not installed in the system cache {}, or other users could have modified it; using the user cache",
+            extraction.display(),
+            root.display()
+        );
+        return None;
+    }
+    if !ready(&app_dir) {
+        verbose!(
+            "{} in the system cache {} is incomplete and only an administrator can repair it; using the user cache",
+            extraction.display(),
+            root.display()
+        );
+        return None;
+    }
+    Some(root)
+}
+
+/// Whether this process may install into and repair the system cache
+pub fn is_admin() -> bool {
+    #[cfg(unix)]
+    return unsafe { libc::geteuid() } == 0;
+    #[cfg(windows)]
+    return windows::is_admin();
+}
+
+/// Create the cache root, owned by administrators and writable by nobody else. An existing root
+/// that fails [`is_trusted`] is refused rather than fixed: anything in it may have been planted.
+fn prepare_root(root: &Path) -> io::Result<()> {
+    if root.exists() {
+        return if is_trusted(root) {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("{} is writable by users other than administrators", root.display())))
+        };
+    }
+    fs::create_dir_all(root)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(root, fs::Permissions::from_mode(0o755))?;
+    }
+    #[cfg(windows)]
+    windows::restrict(root)?;
+    Ok(())
+}
+
+/// Whether users other than administrators could have changed `app_dir` or what it links to
+fn is_trusted_extraction(root: &Path, app_dir: &Path) -> bool {
+    let (Ok(root), Ok(app_dir)) = (fs::canonicalize(root), fs::canonicalize(app_dir)) else {
+        return false;
+    };
+    let Some(linked) = linked_trees(&root, &app_dir) else {
+        return false;
+    };
+    is_trusted(&root)
+        && [app_dir].iter().chain(&linked).all(|tree| {
+            tree.starts_with(&root) && tree.ancestors().take_while(|dir| *dir != root).all(is_trusted)
+        })
+}
+
+/// The shared runtime and dependencies an extraction links to, canonical; `None` when a link
+/// leads out of the system cache
+fn linked_trees(root: &Path, app_dir: &Path) -> Option<Vec<PathBuf>> {
+    let links = walkdir::WalkDir::new(app_dir).min_depth(1).max_depth(2).into_iter().flatten();
+    let targets = links.filter(|entry| entry.path_is_symlink()).filter_map(|link| fs::canonicalize(link.path()).ok());
+    let outside: Vec<PathBuf> = targets.filter(|target| !target.starts_with(app_dir)).collect();
+    outside.iter().all(|target| target.starts_with(root)).then_some(outside)
+}
+
+/// Whether `path` is owned by an administrator and writable by nobody else
+fn is_trusted(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir() && meta.uid() == 0 && meta.mode() & 0o022 == 0)
+    }
+    #[cfg(windows)]
+    windows::is_trusted(path)
+}
+
+/// Make a finished extraction in the system cache readable, and nothing in it writable, by users
+/// other than administrators, whatever the umask of the installing user. Shared runtimes and
+/// dependencies it links to are sealed along with it.
+pub fn seal(app_dir: &Path) -> io::Result<()> {
+    let (root, app_dir) = (fs::canonicalize(root())?, fs::canonicalize(app_dir)?);
+    let linked = linked_trees(&root, &app_dir)
+        .ok_or_else(|| io::Error::other(format!("{} links out of the system cache", app_dir.display())))?;
+    for tree in [app_dir].into_iter().chain(linked) {
+        for entry in walkdir::WalkDir::new(&tree) {
+            let entry = entry.map_err(io::Error::other)?;
+            if entry.path_is_symlink() {
+                continue;
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = entry.metadata().map_err(io::Error::other)?.permissions().mode();
+                let sealed = if entry.file_type().is_dir() { 0o755 } else { (mode | 0o444) & !0o022 & 0o7777 };
+                if mode & 0o7777 != sealed {
+                    fs::set_permissions(entry.path(), fs::Permissions::from_mode(sealed))?;
+                }
+            }
+            // Files inherit the root's access list; directories must also be owned by administrators
+            #[cfg(windows)]
+            if entry.file_type().is_dir() {
+                windows::take_ownership(entry.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::null_mut;
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS, GENERIC_ALL, GENERIC_WRITE};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, GetNamedSecurityInfoW, SetNamedSecurityInfoW, SDDL_REVISION_1,
+        SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        CheckTokenMembership, CreateWellKnownSid, GetAce, GetSecurityDescriptorDacl, GetSecurityDescriptorOwner, IsWellKnownSid,
+        WinAuthenticatedUserSid, WinBuiltinAdministratorsSid, WinBuiltinUsersSid, WinLocalSystemSid, WinWorldSid, ACCESS_ALLOWED_ACE,
+        ACL, DACL_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+        PSID, SECURITY_MAX_SID_SIZE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        DELETE, FILE_APPEND_DATA, FILE_DELETE_CHILD, FILE_WRITE_DATA, WRITE_DAC, WRITE_OWNER,
+    };
+
+    /// Owned by Administrators; SYSTEM and Administrators have full control, users may read
+    const ROOT_SECURITY: &str = "O:BAD:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)(A;OICI;GRGX;;;BU)";
+    const ACCESS_ALLOWED_ACE_TYPE: u8 = 0;
+    /// Rights that let a user change a directory's contents or who may change them
+    const WRITE_RIGHTS: u32 =
+        FILE_WRITE_DATA | FILE_APPEND_DATA | FILE_DELETE_CHILD | DELETE | WRITE_DAC | WRITE_OWNER | GENERIC_WRITE | GENERIC_ALL;
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain(Some(0)).collect()
+    }
+
+    pub fn is_admin() -> bool {
+        let mut sid = [0u8; SECURITY_MAX_SID_SIZE as usize];
+        let mut size = sid.len() as u32;
+        let mut member = 0;
+        unsafe {
+            CreateWellKnownSid(WinBuiltinAdministratorsSid, null_mut(), sid.as_mut_ptr().cast(), &mut size) != 0
+                && CheckTokenMembership(null_mut(), sid.as_mut_ptr().cast(), &mut member) != 0
+                && member != 0
+        }
+    }
+
+    pub fn is_trusted(path: &Path) -> bool {
+        let path = wide(path);
+        let mut owner: PSID = null_mut();
+        let mut dacl: *mut ACL = null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+        let status = unsafe {
+            GetNamedSecurityInfoW(
+                path.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+                &mut owner,
+                null_mut(),
+                &mut dacl,
+                null_mut(),
+                &mut descriptor,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return false;
+        }
+        let trusted = unsafe {
+            (IsWellKnownSid(owner, WinLocalSystemSid) != 0 || IsWellKnownSid(owner, WinBuiltinAdministratorsSid) != 0)
+                // A missing access list grants everyone everything
+                && !dacl.is_null()
+                && !grants_users_write(dacl)
+        };
+        unsafe { LocalFree(descriptor) };
+        trusted
+    }
+
+    /// Whether an allow entry gives Users, Authenticated Users or Everyone a write right
+    unsafe fn grants_users_write(dacl: *const ACL) -> bool {
+        (0..(*dacl).AceCount as u32).any(|index| {
+            let mut ace = null_mut();
+            if GetAce(dacl, index, &mut ace) == 0 {
+                return true;
+            }
+            let ace = ace as *const ACCESS_ALLOWED_ACE;
+            if (*ace).Header.AceType != ACCESS_ALLOWED_ACE_TYPE || (*ace).Mask & WRITE_RIGHTS == 0 {
+                return false;
+            }
+            let sid = std::ptr::addr_of!((*ace).SidStart) as PSID;
+            [WinBuiltinUsersSid, WinAuthenticatedUserSid, WinWorldSid].into_iter().any(|group| IsWellKnownSid(sid, group) != 0)
+        })
+    }
+
+    /// Replace the access list of a new cache root with [`ROOT_SECURITY`], which everything
+    /// created below inherits
+    pub fn restrict(root: &Path) -> io::Result<()> {
+        set_security(root, OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION)
+    }
+
+    /// Make Administrators the owner of a directory the installing administrator created
+    pub fn take_ownership(dir: &Path) -> io::Result<()> {
+        set_security(dir, OWNER_SECURITY_INFORMATION)
+    }
+
+    fn set_security(path: &Path, parts: u32) -> io::Result<()> {
+        let sddl: Vec<u16> = ROOT_SECURITY.encode_utf16().chain(Some(0)).collect();
+        let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+        if unsafe { ConvertStringSecurityDescriptorToSecurityDescriptorW(sddl.as_ptr(), SDDL_REVISION_1, &mut descriptor, null_mut()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut owner: PSID = null_mut();
+        let mut dacl: *mut ACL = null_mut();
+        let (mut present, mut defaulted) = (0, 0);
+        let status = unsafe {
+            GetSecurityDescriptorOwner(descriptor, &mut owner, &mut defaulted);
+            GetSecurityDescriptorDacl(descriptor, &mut present, &mut dacl, &mut defaulted);
+            SetNamedSecurityInfoW(wide(path).as_ptr(), SE_FILE_OBJECT, parts, owner, null_mut(), dacl, null_mut())
+        };
+        unsafe { LocalFree(descriptor) };
+        if status == ERROR_SUCCESS {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(status as i32))
+        }
+    }
+}
@@ -0,0 +1,565 @@
+//! `banderole verify`: check a produced executable without running it, as a release gate.
+//!
+//! The embedded manifest must be readable, the embedded payload must match the size and SHA-256
+//! the manifest recorded for it, every file in the payload must match its manifest entry, and a
+//! code signature, if the executable carries one, must verify with the platform's tool.
+
+use crate::manifest::{BundleManifest, ManifestFile};
+use crate::payload_crypto;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::process::Command;
+
+/// Leads the plain (xz-compressed) payload
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\0";
+/// Leads a UPX-packed executable's compressed sections
+const UPX_MAGIC: &[u8] = b"UPX!";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    /// Could not be checked here; fails the verification only with `--strict`
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub checks: Vec<Check>,
+}
+
+/// The executable failed verification
+#[derive(Debug)]
+pub struct Inconsistent(pub usize);
+
+impl std::fmt::Display for Inconsistent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} verification checks failed", self.0)
+    }
+}
+
+impl std::error::Error for Inconsistent {}
+
+impl VerifyReport {
+    fn push(&mut self, name: &'static str, outcome: Outcome, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail: detail.into(),
+        });
+    }
+
+    /// Checks that fail the verification; skipped ones count too when `strict`
+    pub fn failures(&self, strict: bool) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| match check.outcome {
+                Outcome::Ok => false,
+                Outcome::Skipped => strict,
+                Outcome::Failed => true,
+            })
+            .count()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            let outcome = match check.outcome {
+                Outcome::Ok => "ok",
+                Outcome::Skipped => "skipped",
+                Outcome::Failed => "FAILED",
+            };
+            let _ = writeln!(out, "{outcome:<8} {:<10} {}", check.name, check.detail);
+        }
+        out
+    }
+}
+
+/// Run every check against the executable at `path`; fails only when it is not a bundle at all
+pub fn verify(path: &Path) -> Result<VerifyReport> {
+    let manifest = BundleManifest::read_from_executable(path)?;
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut report = VerifyReport::default();
+    report.push(
+        "manifest",
+        Outcome::Ok,
+        format!(
+            "{} {}, {} files",
+            manifest.app_name,
+            manifest.app_version,
+            manifest.files.len()
+        ),
+    );
+
+    match find_payload(&data, &manifest) {
+        Ok(payload) => {
+            report.push("payload", Outcome::Ok, payload_summary(&payload));
+            check_contents(&mut report, &payload, &manifest);
+        }
+        Err((outcome, detail)) => {
+            report.push("payload", outcome, detail);
+            report.push("contents", Outcome::Skipped, "payload not checked");
+        }
+    }
+    check_signature(&mut report, path, &data);
+    Ok(report)
+}
+
+/// The embedded payload archives (app, then the dependency and runtime layers) matching the
+/// manifest's records of them
+pub(crate) fn find_payload<'a>(
+    data: &'a [u8],
+    manifest: &BundleManifest,
+) -> Result<Vec<&'a [u8]>, (Outcome, String)> {
+    let Some(expected) = &manifest.payload else {
+        return Err((
+            Outcome::Skipped,
+            format!(
+                "built by banderole {}, which did not record the payload digest",
+                manifest.banderole_version
+            ),
+        ));
+    };
+    std::iter::once(expected)
+        .chain(&manifest.layers)
+        .map(|expected| find_archive(data, expected))
+        .collect()
+}
+
+fn find_archive<'a>(
+    data: &'a [u8],
+    expected: &ManifestFile,
+) -> Result<&'a [u8], (Outcome, String)> {
+    let size = expected.size as usize;
+    let found = [XZ_MAGIC, payload_crypto::MAGIC]
+        .iter()
+        .flat_map(|magic| positions(data, magic))
+        .filter_map(|start| data.get(start..start + size))
+        .find(|payload| format!("{:x}", Sha256::digest(payload)) == expected.sha256);
+    match found {
+        Some(payload) => Ok(payload),
+        None if positions(data, UPX_MAGIC).next().is_some() => Err((
+            Outcome::Skipped,
+            "the runner is UPX-compressed; the payload cannot be read without running it".into(),
+        )),
+        None => Err((
+            Outcome::Failed,
+            format!(
+                "no embedded payload of {} bytes with SHA-256 {}; the executable was modified after bundling",
+                expected.size, expected.sha256
+            ),
+        )),
+    }
+}
+
+fn payload_summary(payload: &[&[u8]]) -> String {
+    format!(
+        "{}{}{}, SHA-256 matches the manifest",
+        indicatif::HumanBytes(payload.iter().map(|part| part.len() as u64).sum()),
+        if payload.len() > 1 {
+            format!(" in {} layers", payload.len())
+        } else {
+            String::new()
+        },
+        if payload
+            .iter()
+            .any(|part| payload_crypto::is_encrypted(part))
+        {
+            " (encrypted)"
+        } else {
+            ""
+        }
+    )
+}
+
+/// Compare every file in the payload with its manifest entry. Encrypted payloads are only
+/// opened with `BANDEROLE_ENCRYPTION_KEY`.
+fn check_contents(report: &mut VerifyReport, payload: &[&[u8]], manifest: &BundleManifest) {
+    let mut xz = Vec::with_capacity(payload.len());
+    for part in payload {
+        if !payload_crypto::is_encrypted(part) {
+            xz.push(std::borrow::Cow::Borrowed(*part));
+            continue;
+        }
+        let Ok(key) = std::env::var("BANDEROLE_ENCRYPTION_KEY") else {
+            report.push(
+                "contents",
+                Outcome::Skipped,
+                "encrypted payload; set BANDEROLE_ENCRYPTION_KEY to check the files in it",
+            );
+            return;
+        };
+        match payload_crypto::decrypt(part, &key) {
+            Ok(plain) => xz.push(std::borrow::Cow::Owned(plain)),
+            Err(e) => {
+                report.push("contents", Outcome::Failed, e.to_string());
+                return;
+            }
+        }
+    }
+    let xz: Vec<&[u8]> = xz.iter().map(|part| part.as_ref()).collect();
+    let problems = match compare_contents(&xz, manifest) {
+        Ok(problems) => problems,
+        Err(e) => {
+            report.push("contents", Outcome::Failed, format!("{e:#}"));
+            return;
+        }
+    };
+    if problems.is_empty() {
+        report.push(
+            "contents",
+            Outcome::Ok,
+            format!("{} files match the manifest", manifest.files.len()),
+        );
+    } else {
+        let shown: Vec<&str> = problems.iter().take(5).map(String::as_str).collect();
+        let more = problems.len().saturating_sub(shown.len());
+        report.push(
+            "contents",
+            Outcome::Failed,
+            format!(
+                "{}{}",
+                shown.join(", "),
+                if more > 0 {
+                    format!(" and {more} more")
+                } else {
+                    String::new()
+                }
+            ),
+        );
+    }
+}
+
+/// Files of the xz-compressed payload zips that differ from, or are missing in, the manifest
+fn compare_contents(xz: &[&[u8]], manifest: &BundleManifest) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut buffer = Vec::new();
+    for part in xz {
+        let mut zip_data = Vec::new();
+        lzma_rs::xz_decompress(&mut Cursor::new(part), &mut zip_data)
+            .map_err(|e| anyhow::anyhow!("Failed to decompress the payload: {e}"))?;
+        let mut archive =
+            zip::ZipArchive::new(Cursor::new(zip_data)).context("Failed to read the payload")?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_string();
+            buffer.clear();
+            file.read_to_end(&mut buffer)
+                .with_context(|| format!("Failed to read {name} from the payload"))?;
+            match manifest.files.get(&name) {
+                Some(entry)
+                    if entry.size == buffer.len() as u64
+                        && entry.sha256 == format!("{:x}", Sha256::digest(&buffer)) => {}
+                Some(_) => problems.push(format!("modified: {name}")),
+                None => problems.push(format!("not in manifest: {name}")),
+            }
+            seen.insert(name);
+        }
+    }
+    for name in manifest.files.keys().filter(|name| !seen.contains(*name)) {
+        problems.push(format!("missing: {name}"));
+    }
+    Ok(problems)
+}
+
+/// Verify an Authenticode (PE) or code signature (Mach-O) if the executable has one
+fn check_signature(report: &mut VerifyReport, path: &Path, data: &[u8]) {
+    let (kind, signed) = if data.starts_with(b"MZ") {
+        ("Authenticode", pe_is_signed(data))
+    } else if let Some(signed) = macho_is_signed(data) {
+        ("code", signed)
+    } else {
+        report.push(
+            "signature",
+            Outcome::Skipped,
+            "ELF executables carry no signature",
+        );
+        return;
+    };
+    if !signed {
+        report.push(
+            "signature",
+            Outcome::Skipped,
+            format!("no {kind} signature"),
+        );
+        return;
+    }
+    match verify_signature_with_tool(path, data.starts_with(b"MZ")) {
+        Some(Ok(tool)) => report.push(
+            "signature",
+            Outcome::Ok,
+            format!("{kind} signature verified by {tool}"),
+        ),
+        Some(Err(reason)) => report.push("signature", Outcome::Failed, reason),
+        None => report.push(
+            "signature",
+            Outcome::Skipped,
+            format!(
+                "signed, but no tool to verify it here ({})",
+                if data.starts_with(b"MZ") {
+                    "needs Windows or osslsigncode"
+                } else {
+                    "needs codesign on macOS"
+                }
+            ),
+        ),
+    }
+}
+
+/// The name of the tool that accepted the signature, the reason it was rejected, or `None` when
+/// no verifying tool is available
+fn verify_signature_with_tool(path: &Path, pe: bool) -> Option<Result<&'static str, String>> {
+    let (tool, output) = if !pe {
+        let output = Command::new("codesign")
+            .args(["--verify", "--strict"])
+            .arg(path)
+            .output()
+            .ok()?;
+        ("codesign", output)
+    } else if cfg!(windows) {
+        let script = format!(
+            "$s = Get-AuthenticodeSignature -LiteralPath '{}'; if ($s.Status -ne 'Valid') {{ Write-Error $s.StatusMessage; exit 1 }}",
+            path.display().to_string().replace('\'', "''")
+        );
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .output()
+            .ok()?;
+        ("Get-AuthenticodeSignature", output)
+    } else {
+        let output = Command::new("osslsigncode")
+            .args(["verify", "-in"])
+            .arg(path)
+            .output()
+            .ok()?;
+        ("osslsigncode", output)
+    };
+    Some(if output.status.success() {
+        Ok(tool)
+    } else {
+        Err(format!(
+            "{tool} rejected the signature: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    })
+}
+
+/// Whether a PE image's certificate table (data directory 4) is non-empty
+pub(crate) fn pe_is_signed(data: &[u8]) -> bool {
+    let read_u16 = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let read_u32 = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let Some(pe) = read_u32(0x3c).map(|offset| offset as usize) else {
+        return false;
+    };
+    if data.get(pe..pe + 4) != Some(b"PE\0\0") {
+        return false;
+    }
+    let optional = pe + 24;
+    let directories = match read_u16(optional) {
+        Some(0x10b) => optional + 96,
+        Some(0x20b) => optional + 112,
+        _ => return false,
+    };
+    read_u32(directories + 4 * 8 + 4).is_some_and(|size| size > 0)
+}
+
+/// Whether a Mach-O image (or every slice of a universal one) has an `LC_CODE_SIGNATURE` load
+/// command; `None` when `data` is not Mach-O
+fn macho_is_signed(data: &[u8]) -> Option<bool> {
+    const LC_CODE_SIGNATURE: u32 = 0x1d;
+    let read = |at: usize, big_endian: bool| {
+        data.get(at..at + 4).map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        })
+    };
+    let thin = |start: usize| -> Option<bool> {
+        let header_len = match data.get(start..start + 4)? {
+            b"\xcf\xfa\xed\xfe" => 32,
+            b"\xce\xfa\xed\xfe" => 28,
+            _ => return None,
+        };
+        let commands = read(start + 16, false)?;
+        let mut at = start + header_len;
+        for _ in 0..commands {
+            if read(at, false)? == LC_CODE_SIGNATURE {
+                return Some(true);
+            }
+            at += read(at + 4, false)? as usize;
+        }
+        Some(false)
+    };
+    if data.starts_with(b"\xca\xfe\xba\xbe") {
+        let slices = read(4, true)? as usize;
+        let mut signed = slices > 0;
+        for i in 0..slices {
+            let offset = read(8 + i * 20 + 8, true)? as usize;
+            signed &= thin(offset)?;
+        }
+        return Some(signed);
+    }
+    thin(0)
+}
+
+fn positions<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime_config::RuntimeKind;
+    use std::io::Write;
+
+    fn zip(files: &[&(&str, &str)]) -> Vec<u8> {
+        let mut zip_data = Vec::new();
+        {
+            let mut zip = zip::ZipWriter::new(Cursor::new(&mut zip_data));
+            for (name, contents) in files {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        zip_data
+    }
+
+    /// A bundle with the runtime files (`node/`) in a layer of their own
+    fn bundle(files: &[(&str, &str)]) -> (Vec<u8>, BundleManifest) {
+        let (runtime, app): (Vec<_>, Vec<_>) = files
+            .iter()
+            .partition(|(name, _)| name.starts_with("node/"));
+        let mut manifest = BundleManifest::from_payload(
+            &zip(&files.iter().collect::<Vec<_>>()),
+            "demo",
+            "1.0.0",
+            RuntimeKind::Node,
+            "22.1.0",
+        )
+        .unwrap();
+        let mut executable = b"\x7fELF runner code".to_vec();
+        for layer in [app, runtime] {
+            if layer.is_empty() {
+                continue;
+            }
+            let mut xz = Vec::new();
+            lzma_rs::xz_compress(&mut Cursor::new(zip(&layer)), &mut xz).unwrap();
+            let file = ManifestFile {
+                size: xz.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(&xz)),
+            };
+            match manifest.payload {
+                None => manifest.payload = Some(file),
+                Some(_) => manifest.layers.push(file),
+            }
+            executable.extend(&xz);
+        }
+        executable.extend(manifest.to_embedded().unwrap());
+        (executable, manifest)
+    }
+
+    fn verify_bytes(executable: &[u8]) -> VerifyReport {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("demo");
+        std::fs::write(&path, executable).unwrap();
+        verify(&path).unwrap()
+    }
+
+    #[test]
+    fn test_untouched_bundle_verifies() {
+        let (executable, _) = bundle(&[("app/index.js", "console.log(1)"), ("node/bin/node", "")]);
+        let report = verify_bytes(&executable);
+        assert_eq!(report.failures(false), 0, "{}", report.render());
+        // ELF has no signature to check
+        assert_eq!(report.failures(true), 1);
+    }
+
+    #[test]
+    fn test_modified_payload_fails() {
+        let (mut executable, _) = bundle(&[("app/index.js", "console.log(1)")]);
+        let start = positions(&executable, XZ_MAGIC).next().unwrap();
+        executable[start + 40] ^= 1;
+        let report = verify_bytes(&executable);
+        assert!(matches!(report.checks[1].outcome, Outcome::Failed));
+        assert_eq!(report.failures(false), 1);
+    }
+
+    #[test]
+    fn test_payload_not_matching_manifest_fails() {
+        let (executable, mut manifest) =
+            bundle(&[("app/index.js", "console.log(1)"), ("node/bin/node", "ELF")]);
+        manifest.files.insert(
+            "app/extra.js".into(),
+            ManifestFile {
+                size: 1,
+                sha256: "00".into(),
+            },
+        );
+        let payload = find_payload(&executable, &manifest).unwrap();
+        assert_eq!(payload.len(), 2);
+        let problems = compare_contents(&payload, &manifest).unwrap();
+        assert_eq!(problems, vec!["missing: app/extra.js"]);
+        manifest.files.get_mut("app/index.js").unwrap().sha256 = "00".into();
+        manifest.files.get_mut("node/bin/node").unwrap().size = 4;
+        let problems = compare_contents(&payload, &manifest).unwrap();
+        assert!(problems.contains(&"modified: node/bin/node".to_string()));
+        assert!(compare_contents(&payload, &manifest)
+            .unwrap()
+            .contains(&"modified: app/index.js".to_string()));
+    }
+
+    #[test]
+    fn test_signature_detection() {
+        let mut pe = vec![0u8; 512];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x80;
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x98..0x9a].copy_from_slice(&0x20bu16.to_le_bytes());
+        assert!(!pe_is_signed(&pe));
+        // Certificate table size in data directory 4 (0x98 + 112 + 4 * 8 + 4)
+        pe[0x98 + 148] = 0x10;
+        assert!(pe_is_signed(&pe));
+
+        let mut macho = vec![0u8; 64];
+        macho[..4].copy_from_slice(b"\xcf\xfa\xed\xfe");
+        macho[16] = 1;
+        macho[32] = 0x19;
+        macho[36] = 16;
+        assert_eq!(macho_is_signed(&macho), Some(false));
+        macho[32] = 0x1d;
+        assert_eq!(macho_is_signed(&macho), Some(true));
+        assert_eq!(macho_is_signed(b"\x7fELF"), None);
+    }
+}
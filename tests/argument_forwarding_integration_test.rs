@@ -0,0 +1,68 @@
+mod common;
+
+use anyhow::{Context, Result};
+use common::{BundlerTestHelper, TestProject, TestProjectManager};
+use serial_test::serial;
+use std::path::Path;
+
+/// Run the bundled app and return the arguments it saw in `process.argv.slice(2)`
+fn app_argv(executable_path: &Path, args: &[&str]) -> Result<Vec<String>> {
+    let output = BundlerTestHelper::run_executable(executable_path, args, &[])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    anyhow::ensure!(
+        output.status.success(),
+        "Executable failed with {:?}\nStdout: {stdout}\nStderr: {}",
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Process args JSON: "))
+        .with_context(|| format!("No argument line in stdout:\n{stdout}"))?;
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Arguments with quotes, spaces, backslashes, `--` and non-ASCII text reach the app unchanged
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_tricky_arguments_are_forwarded_verbatim() -> Result<()> {
+    let manager = TestProjectManager::create(TestProject::new("argv-test-app"))?;
+    let executable_path = manager.bundle("argv-test")?;
+
+    let tricky = [
+        "plain",
+        "with space",
+        "",
+        r#"say "hi""#,
+        r#"trailing\"#,
+        r#"C:\Program Files\dir\"#,
+        r#"\\server\share"#,
+        "100% & | < > ^ !",
+        "--flag=value",
+        "-x",
+        "héllo wörld",
+        "日本語 ✓ 🚀",
+    ];
+    assert_eq!(app_argv(&executable_path, &tricky)?, tricky);
+
+    // `--` and everything after it is forwarded, runner flags included
+    let after_separator = [
+        "first",
+        "--",
+        "--banderole-verbose",
+        "--",
+        "ünïcode \"quoted\"",
+    ];
+    assert_eq!(
+        app_argv(&executable_path, &after_separator)?,
+        after_separator
+    );
+
+    // Runner flags before `--` are consumed and never reach the app
+    assert_eq!(
+        app_argv(&executable_path, &["--banderole-verbose", "a b", "ü"])?,
+        ["a b", "ü"]
+    );
+
+    Ok(())
+}
@@ -129,6 +129,35 @@ impl TestProjectManager {
         Ok(manager)
     }
 
+    /// Create a project `name` in a temporary directory from `(relative path, contents)` pairs,
+    /// for fixtures the project types do not cover. The directory is canonical, so fixtures can
+    /// hold paths beyond MAX_PATH on Windows.
+    pub fn from_files(name: &str, files: &[(&str, &str)]) -> Result<Self> {
+        let temp_dir = TempDir::new()?;
+        let project_path = temp_dir.path().canonicalize()?.join(name);
+        for (relative, contents) in files {
+            let path = project_path.join(relative);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(Self {
+            temp_dir,
+            project_path,
+            workspace_root: None,
+        })
+    }
+
+    /// Bundle the project uncompressed into the temporary directory as `output_name`
+    pub fn bundle(&self, output_name: &str) -> Result<PathBuf> {
+        BundlerTestHelper::bundle_project_with_compression(
+            &self.project_path,
+            self.temp_dir.path(),
+            Some(output_name),
+            false,
+        )
+    }
+
     /// Get the path to the project being tested
     pub fn project_path(&self) -> &Path {
         &self.project_path
@@ -219,6 +248,7 @@ console.log("Test env var:", process.env.TEST_VAR || 'not set');
 
 // Test process arguments
 console.log("Process args:", process.argv.slice(2));
+console.log("Process args JSON:", JSON.stringify(process.argv.slice(2)));
 
 // Test dependencies if any
 try {
@@ -762,49 +792,20 @@ impl BundlerTestHelper {
             cmd.current_dir(&work_dir).output()
         };
 
-        // If NotFound on Windows, retry using the copied executable directly with verbatim prefix; else cmd /C
+        // If the verbatim path is NotFound on Windows, retry with the plain path. Never go through
+        // `cmd /C`: re-quoting for cmd mangles quotes, `--`, `%` and non-ASCII arguments, while
+        // `Command::args` quotes each argument so the child's CommandLineToArgvW sees it unchanged.
         #[cfg(windows)]
         let output = match direct {
-            Ok(o) => Ok(o),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                use std::ffi::OsString;
-                let abs = exec_to_run
-                    .canonicalize()
-                    .unwrap_or_else(|_| exec_to_run.clone());
-                let mut verbatim: OsString = OsString::from(r"\\?\");
-                verbatim.push(&abs);
-                let mut cmd = Command::new(&verbatim);
+                let mut cmd = Command::new(&exec_to_run);
                 cmd.args(args).current_dir(&work_dir);
                 for (key, value) in env_vars {
                     cmd.env(key, value);
                 }
-                match cmd.output() {
-                    Ok(o2) => Ok(o2),
-                    Err(e2) if e2.kind() == std::io::ErrorKind::NotFound => {
-                        // Fallback to cmd /C with quoting
-                        fn quote(s: &str) -> String {
-                            let mut out = String::from("\"");
-                            out.push_str(&s.replace('"', "\\\""));
-                            out.push('"');
-                            out
-                        }
-                        let exe_str = exec_to_run.display().to_string();
-                        let mut cmdline = quote(&exe_str);
-                        for a in args {
-                            cmdline.push(' ');
-                            cmdline.push_str(&quote(a));
-                        }
-                        let mut c2 = Command::new("cmd");
-                        c2.args(["/C", &cmdline]).current_dir(&work_dir);
-                        for (key, value) in env_vars {
-                            c2.env(key, value);
-                        }
-                        c2.output()
-                    }
-                    Err(e2) => Err(e2),
-                }
+                cmd.output()
             }
-            Err(e) => Err(e),
+            other => other,
         };
 
         #[cfg(not(windows))]
@@ -891,9 +892,9 @@ impl TestCacheManager {
                     if path.is_dir() {
                         let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
-                        // Only remove directories that look like UUIDs (application cache)
-                        // Keep "node" directory (Node.js binaries cache)
-                        if dir_name != "node" && dir_name.len() > 10 {
+                        // Extractions are named `<app>/<version>/<build ID>` or by the build
+                        // ID alone; keep the "node" directory (Node.js binaries cache)
+                        if dir_name != "node" {
                             if let Err(e) = std::fs::remove_dir_all(&path) {
                                 println!(
                                     "Warning: Failed to remove cache directory {}: {}",
@@ -951,6 +952,21 @@ impl TestAssertions {
         Ok(())
     }
 
+    /// Assert that `--banderole-verify` finds the extraction unmodified
+    pub fn assert_verifies(executable_path: &Path) -> Result<()> {
+        let output =
+            BundlerTestHelper::run_executable(executable_path, &["--banderole-verify"], &[])?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Verification of the extraction failed with exit code {:?}.\nStdout: {}\nStderr: {}",
+                output.status.code(),
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
     /// Assert that dependency tests pass in the bundled executable
     pub fn assert_dependency_test_passes(executable_path: &Path, test_marker: &str) -> Result<()> {
         let output = BundlerTestHelper::run_executable(executable_path, &[], &[])?;
@@ -0,0 +1,48 @@
+mod common;
+
+use anyhow::Result;
+use common::{TestAssertions, TestProjectManager};
+use serial_test::serial;
+
+/// Identical files in different packages are stored once and extracted as hard links
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_identical_files_are_extracted_as_hard_links() -> Result<()> {
+    let shared = "// the same vendored helper in every package\n".repeat(2000);
+    let package_json = |package: &str| {
+        format!(r#"{{ "name": "{package}", "version": "1.0.0", "main": "helper.js" }}"#)
+    };
+    let (first, second) = (package_json("first"), package_json("second"));
+
+    let manager = TestProjectManager::from_files(
+        "dedup-app",
+        &[
+            (
+                "package.json",
+                r#"{
+  "name": "dedup-app",
+  "version": "1.0.0",
+  "main": "index.js",
+  "dependencies": { "first": "1.0.0", "second": "1.0.0" }
+}"#,
+            ),
+            (
+                "index.js",
+                r#"const fs = require('fs');
+const [a, b] = ['first', 'second'].map((p) => fs.statSync(require.resolve(p)));
+console.log('SAME_FILE:', a.ino === b.ino && a.dev === b.dev);
+"#,
+            ),
+            ("node_modules/first/package.json", &first),
+            ("node_modules/first/helper.js", &shared),
+            ("node_modules/second/package.json", &second),
+            ("node_modules/second/helper.js", &shared),
+        ],
+    )?;
+    let executable_path = manager.bundle("dedup-app")?;
+
+    TestAssertions::assert_executable_works(&executable_path, &["SAME_FILE: true"], &[], &[])?;
+    TestAssertions::assert_verifies(&executable_path)?;
+
+    Ok(())
+}
@@ -1238,6 +1238,21 @@ async fn test_bundle_simple_project() {
     // Test that the bundle exists
     let bundle_path = temp_dir.path().join("test-bundle");
     assert!(bundle_path.exists(), "Bundle file not created");
+
+    // The embedded manifest is printed without extracting or starting the app
+    let manifest_output = Command::new(&bundle_path)
+        .arg("--banderole-manifest")
+        .output()
+        .unwrap();
+    assert!(
+        manifest_output.status.success(),
+        "--banderole-manifest failed"
+    );
+    let manifest: serde_json::Value = serde_json::from_slice(&manifest_output.stdout).unwrap();
+    assert_eq!(manifest["appName"], "test-project");
+    assert!(manifest["packages"]["node_modules/commander"].is_string());
+    assert!(manifest["files"]["app/index.js"]["sha256"].is_string());
+    assert!(manifest["bundledAt"].is_string());
 }
 
 #[tokio::test(flavor = "multi_thread")]
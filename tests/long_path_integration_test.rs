@@ -0,0 +1,43 @@
+mod common;
+
+use anyhow::Result;
+use common::{TestAssertions, TestProjectManager};
+use serial_test::serial;
+use std::path::PathBuf;
+
+/// Relative path of a module nested deeper than Windows' 260 character MAX_PATH on its own
+fn deep_module_path() -> PathBuf {
+    let mut path = PathBuf::from("lib");
+    for level in 0..6 {
+        path.push(format!("nested-directory-level-{level}-{}", "x".repeat(20)));
+    }
+    path.join("deep-module.js")
+}
+
+/// A module whose extracted path exceeds MAX_PATH is extracted, loaded and verified
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_extraction_with_paths_beyond_max_path() -> Result<()> {
+    let deep_module = deep_module_path();
+    assert!(deep_module.as_os_str().len() > 260);
+    let require_path = deep_module.to_string_lossy().replace('\\', "/");
+    let index = format!("console.log(require('./{require_path}'));\n");
+
+    let manager = TestProjectManager::from_files(
+        "long-path-app",
+        &[
+            (
+                "package.json",
+                r#"{ "name": "long-path-app", "version": "1.0.0", "main": "index.js" }"#,
+            ),
+            ("index.js", &index),
+            (&require_path, r#"module.exports = "DEEP_MODULE_LOADED";"#),
+        ],
+    )?;
+    let executable_path = manager.bundle("long-path-app")?;
+
+    TestAssertions::assert_executable_works(&executable_path, &["DEEP_MODULE_LOADED"], &[], &[])?;
+    TestAssertions::assert_verifies(&executable_path)?;
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use anyhow::Result;
+use common::{BundlerTestHelper, TestAssertions, TestProject, TestProjectManager};
+use serial_test::serial;
+use std::process::Command;
+
+/// An `npm pack` tarball carries no node_modules; its dependencies still end up in the executable
+#[tokio::test]
+#[serial]
+async fn test_npm_pack_input_bundles_dependencies() -> Result<()> {
+    let project = TestProject::new("packed-app").with_dependency("adm-zip", "^0.5.10");
+    let manager = TestProjectManager::create(project)?;
+
+    let npm = if cfg!(windows) { "npm.cmd" } else { "npm" };
+    let output = Command::new(npm)
+        .arg("pack")
+        .arg("--pack-destination")
+        .arg(manager.temp_dir())
+        .current_dir(manager.project_path())
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "npm pack failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let tarball = manager.temp_dir().join("packed-app-1.0.0.tgz");
+    assert!(
+        tarball.exists(),
+        "npm pack did not create {}",
+        tarball.display()
+    );
+    assert!(!manager.project_path().join("node_modules").exists());
+
+    let executable_path =
+        BundlerTestHelper::bundle_project(&tarball, manager.temp_dir(), Some("packed-app"))?;
+    TestAssertions::assert_dependency_test_passes(&executable_path, "DEPENDENCY_TEST_PASSED")?;
+
+    Ok(())
+}
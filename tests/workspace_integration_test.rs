@@ -462,6 +462,73 @@ async fn test_workspace_collision_handling() -> Result<()> {
     println!("✅ workspace collision handling test passed!");
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_workspace_sibling_package_bundling() -> Result<()> {
+    println!("Testing bundling of a sibling workspace package...");
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let root = temp_dir.path().join("workspace");
+    let files = [
+        (
+            "package.json",
+            r#"{"name":"sibling-root","private":true,"workspaces":["packages/*"]}"#,
+        ),
+        (
+            "packages/shared/package.json",
+            r#"{"name":"@acme/shared","version":"1.0.0","main":"dist/index.js","dependencies":{"adm-zip":"^0.5.10"}}"#,
+        ),
+        (
+            "packages/shared/dist/index.js",
+            "const AdmZip = require('adm-zip');\nmodule.exports = () => typeof AdmZip;\n",
+        ),
+        (
+            "packages/app/package.json",
+            r#"{"name":"sibling-app","version":"1.0.0","main":"index.js","dependencies":{"@acme/shared":"*"}}"#,
+        ),
+        (
+            "packages/app/index.js",
+            "console.log('shared:', require('@acme/shared')());\nconsole.log('SIBLING_PACKAGE_TEST_PASSED');\n",
+        ),
+    ];
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, content)?;
+    }
+
+    let npm_install = std::process::Command::new("npm")
+        .arg("install")
+        .current_dir(&root)
+        .output()?;
+    assert!(
+        npm_install.status.success(),
+        "npm install failed: {}",
+        String::from_utf8_lossy(&npm_install.stderr)
+    );
+
+    let executable_path = BundlerTestHelper::bundle_project_with_compression(
+        &root.join("packages/app"),
+        temp_dir.path(),
+        Some("sibling-package-test"),
+        false,
+    )?;
+
+    // The bundle must not reach back into the workspace through links
+    std::fs::remove_dir_all(&root)?;
+
+    TestAssertions::assert_executable_works(
+        &executable_path,
+        &["shared: function", "SIBLING_PACKAGE_TEST_PASSED"],
+        &[],
+        &[],
+    )?;
+
+    println!("✅ sibling workspace package bundling test passed!");
+    Ok(())
+}
+
 /// Cleanup function to be called after all workspace tests
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
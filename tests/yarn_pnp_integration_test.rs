@@ -0,0 +1,69 @@
+mod common;
+
+use anyhow::Result;
+use common::{TestAssertions, TestProjectManager};
+use serial_test::serial;
+use std::process::Command;
+
+/// A Yarn Berry Plug'n'Play install (no node_modules) resolves its packages in the bundle,
+/// through both `require` and `import`
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_yarn_pnp_project() -> Result<()> {
+    let manager = TestProjectManager::from_files(
+        "pnp-app",
+        &[
+            (
+                "package.json",
+                r#"{
+  "name": "pnp-app",
+  "version": "1.0.0",
+  "main": "index.js",
+  "packageManager": "yarn@4.5.3",
+  "dependencies": { "is-odd": "3.0.1", "is-number": "7.0.0" }
+}"#,
+            ),
+            (".yarnrc.yml", "nodeLinker: pnp\nenableGlobalCache: false\n"),
+            (
+                "index.js",
+                r#"console.log('REQUIRE:', require('is-odd')(3));
+import('is-number').then((m) => console.log('IMPORT:', m.default(5)));
+"#,
+            ),
+        ],
+    )?;
+    let project_path = manager.project_path();
+
+    let install = Command::new(if cfg!(windows) {
+        "corepack.cmd"
+    } else {
+        "corepack"
+    })
+    .args(["yarn", "install"])
+    .env("COREPACK_ENABLE_DOWNLOAD_PROMPT", "0")
+    .current_dir(project_path)
+    .output();
+    match install {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => anyhow::bail!(
+            "yarn install failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(_) => {
+            println!("corepack not found, skipping the Yarn PnP test");
+            return Ok(());
+        }
+    }
+    assert!(project_path.join(".pnp.cjs").is_file());
+    assert!(!project_path.join("node_modules").exists());
+
+    let executable_path = manager.bundle("pnp-app")?;
+    TestAssertions::assert_executable_works(
+        &executable_path,
+        &["REQUIRE: true", "IMPORT: true"],
+        &[],
+        &[],
+    )?;
+
+    Ok(())
+}